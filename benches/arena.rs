@@ -0,0 +1,67 @@
+//! Parse a PUBLISH and serialize the EVENT delivering it, once through `Message` and once
+//! through a reused `ParseArena`.
+//!
+//! Run with `cargo bench --bench arena`.
+
+use std::hint::black_box;
+use std::time::Instant;
+use wamp_helpers::arena::ParseArena;
+use wamp_helpers::messages::{Event, Message, WampMessageTrait};
+
+const FRAME: &str = r#"[16, 239714735, {"acknowledge": true}, "com.myapp.mytopic1", ["Hello, world!", 42, {"nested": [1, 2, 3]}], {"color": "orange", "sizes": [23, 42, 7]}]"#;
+const ROUNDS: u32 = 200_000;
+
+fn default_path() -> String {
+    let Ok(Message::Publish(publish)) = Message::parse_message(FRAME) else {
+        unreachable!()
+    };
+    let event = Event {
+        subscription: 5512315355,
+        publication: 4429313566,
+        details: json::object! {},
+        args: publish.args,
+        kwargs: publish.kwargs,
+    };
+    event
+        .to_json()
+        .map(|event| event.dump())
+        .unwrap_or_default()
+}
+
+fn arena_path(arena: &mut ParseArena, out: &mut String) {
+    out.clear();
+    let Ok(publish) = arena.parse(FRAME) else {
+        unreachable!()
+    };
+    out.push_str("[36,5512315355,4429313566,{}");
+    for payload in publish.members().skip(4) {
+        out.push(',');
+        payload.write_to(out);
+    }
+    out.push(']');
+}
+
+fn main() {
+    let mut arena = ParseArena::new();
+    let mut out = String::new();
+    arena_path(&mut arena, &mut out);
+    assert_eq!(
+        json::parse(&out).unwrap(),
+        json::parse(&default_path()).unwrap()
+    );
+
+    let started = Instant::now();
+    for _ in 0..ROUNDS {
+        black_box(default_path());
+    }
+    let default = started.elapsed() / ROUNDS;
+
+    let started = Instant::now();
+    for _ in 0..ROUNDS {
+        arena_path(&mut arena, &mut out);
+        black_box(&out);
+    }
+    let arena = started.elapsed() / ROUNDS;
+
+    println!("message: {default:?}/frame, arena: {arena:?}/frame");
+}
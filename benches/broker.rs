@@ -0,0 +1,135 @@
+//! Route publications through the sharded `SubscriptionStore` and through a single-lock
+//! baseline, one map behind one mutex, first from one thread and then from a thread per
+//! core. One operation in ten subscribes and unsubscribes again, the rest route a
+//! publication.
+//!
+//! Run with `cargo bench --bench broker`.
+
+use std::collections::{BTreeSet, HashMap};
+use std::hint::black_box;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use wamp_helpers::broker::SubscriptionStore;
+
+const TOPICS: u64 = 1_000;
+const SUBSCRIBERS: u64 = 4;
+const ROUNDS: u64 = 200_000;
+
+/// Subscriptions of exact topics behind one lock, what a single-threaded broker keeps.
+#[derive(Default)]
+struct Baseline {
+    topics: Mutex<HashMap<String, BTreeSet<u64>>>,
+}
+
+trait Store: Send + Sync {
+    fn subscribe(&self, topic: &str, session: u64);
+    fn unsubscribe(&self, topic: &str, session: u64);
+    fn route(&self, topic: &str) -> usize;
+}
+
+impl Store for Baseline {
+    fn subscribe(&self, topic: &str, session: u64) {
+        let mut topics = self.topics.lock().unwrap();
+        topics.entry(topic.to_string()).or_default().insert(session);
+    }
+
+    fn unsubscribe(&self, topic: &str, session: u64) {
+        let mut topics = self.topics.lock().unwrap();
+        if let Some(subscribers) = topics.get_mut(topic) {
+            subscribers.remove(&session);
+        }
+    }
+
+    fn route(&self, topic: &str) -> usize {
+        let topics = self.topics.lock().unwrap();
+        topics.get(topic).map_or(0, BTreeSet::len)
+    }
+}
+
+impl Store for SubscriptionStore {
+    fn subscribe(&self, topic: &str, session: u64) {
+        SubscriptionStore::subscribe(self, topic, session);
+    }
+
+    fn unsubscribe(&self, topic: &str, session: u64) {
+        if let Some((subscription, _)) = self.lookup(topic) {
+            SubscriptionStore::unsubscribe(self, subscription, session);
+        }
+    }
+
+    fn route(&self, topic: &str) -> usize {
+        SubscriptionStore::route(self, topic)
+            .iter()
+            .map(|(_, subscribers)| subscribers.len())
+            .sum()
+    }
+}
+
+fn topic(index: u64) -> String {
+    format!("com.example.topic.{}", index % TOPICS)
+}
+
+fn populate(store: &dyn Store) {
+    for index in 0..TOPICS {
+        for session in 1..=SUBSCRIBERS {
+            store.subscribe(&topic(index), session);
+        }
+    }
+}
+
+/// Operations per second with `threads` threads doing `ROUNDS` operations each.
+fn measure(store: Arc<dyn Store>, threads: u64) -> f64 {
+    let started = Instant::now();
+    let workers: Vec<_> = (0..threads)
+        .map(|thread| {
+            let store = Arc::clone(&store);
+            thread::spawn(move || {
+                let session = SUBSCRIBERS + 1 + thread;
+                for round in 0..ROUNDS {
+                    let topic = topic(round * 7 + thread);
+                    if round % 10 == 0 {
+                        store.subscribe(&topic, session);
+                        store.unsubscribe(&topic, session);
+                    } else {
+                        black_box(store.route(black_box(&topic)));
+                    }
+                }
+            })
+        })
+        .collect();
+    for worker in workers {
+        worker.join().unwrap();
+    }
+    rate(threads * ROUNDS, started.elapsed())
+}
+
+fn rate(operations: u64, elapsed: Duration) -> f64 {
+    operations as f64 / elapsed.as_secs_f64()
+}
+
+fn main() {
+    let cores = thread::available_parallelism().map_or(4, |cores| cores.get() as u64);
+    let threads = cores.max(2);
+    let baseline = || {
+        let store = Arc::new(Baseline::default());
+        populate(store.as_ref());
+        store as Arc<dyn Store>
+    };
+    let sharded = || {
+        let store = Arc::new(SubscriptionStore::new(cores as usize * 4));
+        populate(store.as_ref());
+        store as Arc<dyn Store>
+    };
+
+    println!("baseline, 1 thread: {:.0} ops/s", measure(baseline(), 1));
+    println!("sharded, 1 thread: {:.0} ops/s", measure(sharded(), 1));
+    println!(
+        "baseline, {threads} threads: {:.0} ops/s",
+        measure(baseline(), threads)
+    );
+    println!(
+        "sharded, {threads} threads: {:.0} ops/s",
+        measure(sharded(), threads)
+    );
+}
@@ -0,0 +1,30 @@
+//! Validate a CALL once through `Message::parse_message` and once through `scan_frame`.
+//!
+//! Run with `cargo bench --bench scan`.
+
+use std::hint::black_box;
+use std::time::Instant;
+use wamp_helpers::messages::Message;
+use wamp_helpers::scan::scan_frame;
+
+const FRAME: &str = r#"[48, 7814135, {"disclose_me": true, "timeout": 1000}, "com.myapp.user.new", ["johnny", {"firstname": "John", "surname": "Doe"}], {"groups": ["admin", "users"], "details": {"age": 42, "tags": [1, 2, 3]}}]"#;
+const ROUNDS: u32 = 200_000;
+
+fn main() {
+    assert!(Message::parse_message(FRAME).is_ok());
+    assert!(scan_frame(FRAME).is_ok());
+
+    let started = Instant::now();
+    for _ in 0..ROUNDS {
+        black_box(Message::parse_message(black_box(FRAME)).is_ok());
+    }
+    let parse = started.elapsed() / ROUNDS;
+
+    let started = Instant::now();
+    for _ in 0..ROUNDS {
+        black_box(scan_frame(black_box(FRAME)).is_ok());
+    }
+    let scan = started.elapsed() / ROUNDS;
+
+    println!("parse: {parse:?}/frame, scan: {scan:?}/frame");
+}
@@ -0,0 +1,200 @@
+//! WAMP-CRA authentication: a router with salted secrets welcomes a client that knows its
+//! password and aborts the others.
+//!
+//! Both ends track the handshake with [`Session`]. Run with
+//! `cargo run --example auth --features cra`.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::task::{Context, Poll, Waker};
+use wamp_helpers::acceptor::Serializer;
+use wamp_helpers::bus::{AUTHENTICATION_FAILED, NOT_AUTHORIZED};
+use wamp_helpers::correlation::Direction;
+use wamp_helpers::cra::{derive_key, sign, CraSecret, DEFAULT_ITERATIONS, DEFAULT_KEYLEN};
+use wamp_helpers::framed::{decode_message, encode_message};
+use wamp_helpers::memory::MemoryTransport;
+use wamp_helpers::messages::{Abort, Authenticate, Challenge, Hello, Message, Roles, Welcome};
+use wamp_helpers::nonce::NonceGenerator;
+use wamp_helpers::session::{Session, SessionState, Side};
+use wamp_helpers::transport::Transport;
+
+const REALM: &str = "com.example.secure";
+
+fn now<F: Future>(future: F) -> F::Output {
+    let mut future = std::pin::pin!(future);
+    match future
+        .as_mut()
+        .poll(&mut Context::from_waker(Waker::noop()))
+    {
+        Poll::Ready(output) => output,
+        Poll::Pending => panic!("future is not ready"),
+    }
+}
+
+/// One end of the connection with its view of the session.
+struct End {
+    transport: MemoryTransport,
+    session: Session,
+}
+
+impl End {
+    fn send(&mut self, message: Message) {
+        self.session
+            .transition(Direction::Outbound, &message)
+            .unwrap();
+        let frame = encode_message(message, Serializer::Json).unwrap();
+        now(self.transport.send(frame)).unwrap();
+    }
+
+    fn receive(&mut self) -> Message {
+        let frame = self.transport.try_next().expect("a message is waiting");
+        let message = decode_message(&frame, Serializer::Json).unwrap();
+        self.session
+            .transition(Direction::Inbound, &message)
+            .unwrap();
+        message
+    }
+}
+
+fn abort(reason: &str) -> Message {
+    Message::Abort(Abort {
+        details: json::object! {},
+        reason: reason.to_string().into(),
+    })
+}
+
+/// The router's side of the handshake: challenge known principals and check the signature.
+struct Authenticator {
+    principals: HashMap<&'static str, CraSecret>,
+    /// Challenges sent, by authid.
+    challenges: HashMap<String, String>,
+    next_session: u64,
+}
+
+impl Authenticator {
+    fn on_hello(&mut self, hello: &Hello) -> Message {
+        let authid = hello.details["authid"].as_str().unwrap_or_default();
+        let offers_cra = hello.details["authmethods"]
+            .members()
+            .any(|method| method == "wampcra");
+        let Some(secret) = self.principals.get(authid).filter(|_| offers_cra) else {
+            return abort(NOT_AUTHORIZED);
+        };
+        let nonce = NonceGenerator::new().generate().unwrap();
+        let challenge = json::object! {
+            authid: authid,
+            authrole: "user",
+            authmethod: "wampcra",
+            nonce: nonce,
+        }
+        .dump();
+        let extra = secret.challenge_extra(&challenge);
+        self.challenges.insert(authid.to_string(), challenge);
+        Message::Challenge(Challenge {
+            authmethod: "wampcra".to_string(),
+            details: extra,
+        })
+    }
+
+    fn on_authenticate(&mut self, authid: &str, authenticate: &Authenticate) -> Message {
+        let challenge = self.challenges.remove(authid).unwrap_or_default();
+        if !self.principals[authid].verify(&challenge, &authenticate.signature) {
+            return abort(AUTHENTICATION_FAILED);
+        }
+        self.next_session += 1;
+        Message::Welcome(Welcome {
+            session: self.next_session,
+            details: json::object! {
+                authid: authid,
+                authrole: "user",
+                authmethod: "wampcra",
+                roles: { broker: {}, dealer: {} },
+            },
+        })
+    }
+}
+
+/// Run the handshake for `authid` with `password`, returns how the client's session ended up
+/// and the message that settled it.
+fn connect(
+    authenticator: &mut Authenticator,
+    authid: &str,
+    password: &str,
+) -> (SessionState, Message) {
+    let (client, router) = MemoryTransport::pair();
+    let mut client = End {
+        transport: client,
+        session: Session::new(Side::Client),
+    };
+    let mut router = End {
+        transport: router,
+        session: Session::new(Side::Router),
+    };
+
+    let mut hello = Hello::default(
+        REALM.to_string(),
+        vec![Roles::Caller, Roles::Subscriber],
+        Some(vec!["wampcra".to_string()]),
+    );
+    hello.details["authid"] = authid.into();
+    client.send(Message::Hello(hello));
+    let Message::Hello(hello) = router.receive() else {
+        unreachable!("the client starts with HELLO")
+    };
+    router.send(authenticator.on_hello(&hello));
+
+    let extra = match client.receive() {
+        Message::Challenge(challenge) => challenge.details,
+        other => return (client.session.state(), other),
+    };
+    let key = derive_key(
+        password,
+        extra["salt"].as_str().unwrap(),
+        extra["iterations"].as_u32().unwrap(),
+        extra["keylen"].as_usize().unwrap(),
+    );
+    let signature = sign(&key, extra["challenge"].as_str().unwrap());
+    client.send(Message::Authenticate(Authenticate {
+        signature,
+        details: json::object! {},
+    }));
+    let Message::Authenticate(authenticate) = router.receive() else {
+        unreachable!("the client answers the CHALLENGE")
+    };
+    router.send(authenticator.on_authenticate(authid, &authenticate));
+
+    let settled = client.receive();
+    (client.session.state(), settled)
+}
+
+pub fn main() {
+    let mut authenticator = Authenticator {
+        principals: HashMap::from([(
+            "alice",
+            CraSecret::derive("wonderland", "6d2f1c", DEFAULT_ITERATIONS, DEFAULT_KEYLEN),
+        )]),
+        challenges: HashMap::new(),
+        next_session: 0,
+    };
+
+    let (state, welcome) = connect(&mut authenticator, "alice", "wonderland");
+    assert_eq!(state, SessionState::Established);
+    let Message::Welcome(welcome) = welcome else {
+        panic!("alice was not welcomed: {welcome:?}");
+    };
+    println!("alice joined {REALM} as session {}", welcome.session);
+    assert_eq!(welcome.details["authrole"], "user");
+
+    for (authid, password, reason) in [
+        ("alice", "looking-glass", AUTHENTICATION_FAILED),
+        ("mallory", "wonderland", NOT_AUTHORIZED),
+    ] {
+        let (state, abort) = connect(&mut authenticator, authid, password);
+        assert_eq!(state, SessionState::Closed);
+        let Message::Abort(abort) = abort else {
+            panic!("{authid} was not aborted: {abort:?}");
+        };
+        println!("{authid} was refused with {}", abort.reason);
+        assert_eq!(abort.reason, reason);
+    }
+}
@@ -0,0 +1,203 @@
+//! RPC calculator: a callee registers `add` and `div`, a caller awaits typed results.
+//!
+//! The dealer routes CALLs to the callee and its answers back, the caller tracks its calls
+//! with [`PendingCalls`]. Run with `cargo run --example calculator --features serde`.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::task::{Context, Poll, Waker};
+use wamp_helpers::acceptor::Serializer;
+use wamp_helpers::dealer::invocation;
+use wamp_helpers::framed::{decode_message, encode_message};
+use wamp_helpers::memory::{InProcess, InProcessPeer};
+use wamp_helpers::messages::{
+    Call, ErrorMessage, Invocation, Message, Registered, WampId, WampMessageTrait, WampResult,
+    Yield,
+};
+use wamp_helpers::options::{guard_identity, SpoofPolicy};
+use wamp_helpers::rpc::{PendingCalls, RpcError};
+use wamp_helpers::sim::{IdGenerator, SequentialIdGenerator};
+use wamp_helpers::transport::Transport;
+use wamp_helpers::value::WampValue;
+
+const ADD: &str = "com.example.calculator.add";
+const DIV: &str = "com.example.calculator.div";
+const DIVISION_BY_ZERO: &str = "com.example.calculator.division_by_zero";
+
+fn now<F: Future>(future: F) -> F::Output {
+    let mut future = std::pin::pin!(future);
+    match future
+        .as_mut()
+        .poll(&mut Context::from_waker(Waker::noop()))
+    {
+        Poll::Ready(output) => output,
+        Poll::Pending => panic!("future is not ready"),
+    }
+}
+
+fn send(peer: &mut InProcessPeer, message: Message) {
+    let frame = encode_message(message, Serializer::Json).unwrap();
+    now(peer.transport.send(frame)).unwrap();
+}
+
+fn receive(peer: &mut InProcessPeer) -> Option<Message> {
+    let frame = peer.transport.try_next()?;
+    Some(decode_message(&frame, Serializer::Json).unwrap())
+}
+
+/// Registrations and running invocations of the dealer.
+#[derive(Default)]
+struct Dealer {
+    ids: SequentialIdGenerator,
+    /// Procedure to registration and the index of its callee.
+    procedures: HashMap<String, (WampId, usize)>,
+    /// Invocation request to the caller's index and CALL request.
+    invocations: HashMap<WampId, (usize, WampId)>,
+}
+
+impl Dealer {
+    fn route(&mut self, router: &mut [InProcessPeer]) {
+        for index in 0..router.len() {
+            while let Some(mut message) = receive(&mut router[index]) {
+                guard_identity(&mut message, SpoofPolicy::Strip).unwrap();
+                match message {
+                    Message::Register(register) => {
+                        let registration = self.ids.next_id();
+                        self.procedures
+                            .insert(register.procedure.to_string(), (registration, index));
+                        let registered = Registered {
+                            request: register.request,
+                            registration,
+                        };
+                        send(&mut router[index], Message::Registered(registered));
+                    }
+                    Message::Call(call) => self.call(router, index, call),
+                    Message::Yield(answer) => {
+                        let (caller, request) = self.invocations.remove(&answer.request).unwrap();
+                        let result = WampResult {
+                            request,
+                            details: json::object! {},
+                            args: answer.args,
+                            kwargs: answer.kwargs,
+                        };
+                        send(&mut router[caller], Message::MessageResult(result));
+                    }
+                    Message::ErrorMessage(mut error) if error.request_type == Invocation::ID => {
+                        let (caller, request) = self.invocations.remove(&error.request).unwrap();
+                        error.request_type = Call::ID;
+                        error.request = request;
+                        send(&mut router[caller], Message::ErrorMessage(error));
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    fn call(&mut self, router: &mut [InProcessPeer], caller: usize, call: Call) {
+        let Some(&(registration, callee)) = self.procedures.get(call.procedure.as_str()) else {
+            let error = format!(
+                r#"[8, 48, {}, {{}}, "wamp.error.no_such_procedure"]"#,
+                call.request
+            );
+            send(&mut router[caller], Message::parse_message(&error).unwrap());
+            return;
+        };
+        let request = self.ids.next_id();
+        self.invocations.insert(request, (caller, call.request));
+        let invocation = invocation(request, registration, &call);
+        send(&mut router[callee], Message::Invocation(invocation));
+    }
+}
+
+/// The callee: run an invocation of one of the two procedures.
+fn calculate(procedure: &str, invocation: Invocation) -> Message {
+    let args = invocation.args.unwrap_or_default();
+    let number = |index: usize| match args.get(index) {
+        Some(WampValue::Integer(number)) => *number as f64,
+        Some(WampValue::Float(number)) => *number,
+        _ => f64::NAN,
+    };
+    let (a, b) = (number(0), number(1));
+    let answer = match procedure {
+        ADD => a + b,
+        DIV if b == 0.0 => {
+            return Message::ErrorMessage(ErrorMessage {
+                request_type: Invocation::ID,
+                request: invocation.request,
+                details: json::object! {},
+                error: DIVISION_BY_ZERO.into(),
+                args: None,
+                kwargs: None,
+            })
+        }
+        _ => a / b,
+    };
+    Message::Yield(Yield {
+        request: invocation.request,
+        options: json::object! {},
+        args: Some(vec![answer.into()]),
+        kwargs: None,
+    })
+}
+
+pub fn main() {
+    let (mut router, mut peers) = InProcess::pair("com.example.math", 2).unwrap().into_parts();
+    let mut dealer = Dealer::default();
+    let (callee, caller) = (0, 1);
+
+    // The callee registers both procedures.
+    let mut registrations = HashMap::new();
+    for (request, procedure) in [ADD, DIV].into_iter().enumerate() {
+        let register = format!(r#"[64, {}, {{}}, "{procedure}"]"#, request + 1);
+        send(
+            &mut peers[callee],
+            Message::parse_message(&register).unwrap(),
+        );
+    }
+    dealer.route(&mut router);
+    while let Some(Message::Registered(registered)) = receive(&mut peers[callee]) {
+        let procedure = [ADD, DIV][registered.request as usize - 1];
+        registrations.insert(registered.registration, procedure);
+    }
+
+    // The caller sends three calls at once.
+    let mut calls = PendingCalls::new();
+    let mut ids = SequentialIdGenerator::default();
+    let mut call = |procedure: &str, a: i64, b: i64| {
+        let call = format!(
+            r#"[48, {}, {{}}, "{procedure}", [{a}, {b}]]"#,
+            ids.next_id()
+        );
+        let call: Call = call.parse().unwrap();
+        let future = calls.call::<f64>(&call);
+        send(&mut peers[caller], Message::Call(call));
+        future
+    };
+    let sum = call(ADD, 40, 2);
+    let quotient = call(DIV, 1, 4);
+    let undefined = call(DIV, 1, 0);
+
+    dealer.route(&mut router);
+    while let Some(Message::Invocation(invocation)) = receive(&mut peers[callee]) {
+        let procedure = registrations[&invocation.registration];
+        send(&mut peers[callee], calculate(procedure, invocation));
+    }
+    dealer.route(&mut router);
+    while let Some(answer) = receive(&mut peers[caller]) {
+        calls.on_message(&answer);
+    }
+
+    let sum = now(sum).unwrap();
+    let quotient = now(quotient).unwrap();
+    println!("40 + 2 = {sum}, 1 / 4 = {quotient}");
+    assert_eq!((sum, quotient), (42.0, 0.25));
+    match now(undefined) {
+        Err(RpcError::Error { error, .. }) => {
+            println!("1 / 0 failed with {error}");
+            assert_eq!(error, DIVISION_BY_ZERO);
+        }
+        other => panic!("1 / 0 gave {other:?}"),
+    }
+    assert!(calls.is_empty());
+}
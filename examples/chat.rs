@@ -0,0 +1,125 @@
+//! Pub/sub chat: three members join a room and each message reaches everyone else.
+//!
+//! The broker is a few lines on top of [`SubscriptionStore`], the sessions run over
+//! in-memory transports. Run with `cargo run --example chat`.
+
+use std::future::Future;
+use std::task::{Context, Poll, Waker};
+use wamp_helpers::acceptor::Serializer;
+use wamp_helpers::broker::SubscriptionStore;
+use wamp_helpers::framed::{decode_message, encode_message};
+use wamp_helpers::memory::{InProcess, InProcessPeer};
+use wamp_helpers::messages::{Event, Message, Subscribed};
+use wamp_helpers::options::{guard_identity, SpoofPolicy};
+use wamp_helpers::sim::{IdGenerator, RandomIdGenerator};
+use wamp_helpers::transport::Transport;
+use wamp_helpers::value::WampValue;
+
+const ROOM: &str = "com.example.chat.lobby";
+const MEMBERS: [&str; 3] = ["alice", "bob", "carol"];
+
+fn now<F: Future>(future: F) -> F::Output {
+    let mut future = std::pin::pin!(future);
+    match future
+        .as_mut()
+        .poll(&mut Context::from_waker(Waker::noop()))
+    {
+        Poll::Ready(output) => output,
+        Poll::Pending => panic!("future is not ready"),
+    }
+}
+
+fn send(peer: &mut InProcessPeer, message: Message) {
+    let frame = encode_message(message, Serializer::Json).unwrap();
+    now(peer.transport.send(frame)).unwrap();
+}
+
+fn receive(peer: &mut InProcessPeer) -> Option<Message> {
+    let frame = peer.transport.try_next()?;
+    Some(decode_message(&frame, Serializer::Json).unwrap())
+}
+
+/// Handle everything the members sent: answer SUBSCRIBEs and deliver publications to the
+/// other subscribers.
+fn broker(router: &mut [InProcessPeer], store: &SubscriptionStore, ids: &mut impl IdGenerator) {
+    for index in 0..router.len() {
+        let session = router[index].session_id().unwrap();
+        while let Some(mut message) = receive(&mut router[index]) {
+            // Members cannot pass themselves off as someone else.
+            guard_identity(&mut message, SpoofPolicy::Strip).unwrap();
+            match message {
+                Message::Subscribe(subscribe) => {
+                    let (subscription, _) = store.subscribe(&subscribe.topic, session);
+                    let subscribed = Subscribed {
+                        request: subscribe.request,
+                        subscription,
+                    };
+                    send(&mut router[index], Message::Subscribed(subscribed));
+                }
+                Message::Publish(publish) => {
+                    let publication = ids.next_id();
+                    for (subscription, subscribers) in store.route(&publish.topic) {
+                        for peer in router.iter_mut() {
+                            let receiver = peer.session_id().unwrap();
+                            // Publishers are excluded from their own events by default.
+                            if receiver == session || !subscribers.contains(&receiver) {
+                                continue;
+                            }
+                            let event = Event {
+                                subscription,
+                                publication,
+                                details: json::object! {},
+                                args: publish.args.clone(),
+                                kwargs: publish.kwargs.clone(),
+                            };
+                            send(peer, Message::Event(event));
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+pub fn main() {
+    let (mut router, mut members) = InProcess::pair("com.example.chat", MEMBERS.len())
+        .unwrap()
+        .into_parts();
+    let store = SubscriptionStore::default();
+    let mut ids = RandomIdGenerator::new(7);
+
+    for member in &mut members {
+        let subscribe = format!(r#"[32, 1, {{}}, "{ROOM}"]"#);
+        send(member, Message::parse_message(&subscribe).unwrap());
+    }
+    broker(&mut router, &store, &mut ids);
+    for member in &mut members {
+        assert!(matches!(receive(member), Some(Message::Subscribed(_))));
+    }
+
+    for (index, name) in MEMBERS.iter().enumerate() {
+        let publish = format!(r#"[16, 2, {{}}, "{ROOM}", ["{name}", "hi from {name}"]]"#);
+        send(
+            &mut members[index],
+            Message::parse_message(&publish).unwrap(),
+        );
+        broker(&mut router, &store, &mut ids);
+
+        for (other, member) in members.iter_mut().enumerate() {
+            let received = receive(member);
+            if other == index {
+                assert!(received.is_none(), "{name} got their own message");
+                continue;
+            }
+            let Some(Message::Event(event)) = received else {
+                panic!("{} missed a message from {name}", MEMBERS[other]);
+            };
+            let args = event.args.unwrap_or_default();
+            assert_eq!(args[0], WampValue::from(*name));
+            if let WampValue::String(text) = &args[1] {
+                println!("{} sees {name}: {text}", MEMBERS[other]);
+            }
+        }
+    }
+}
@@ -0,0 +1,536 @@
+use crate::error::Error;
+use crate::messages::Abort;
+use crate::proxy::parse_proxy_header;
+use crate::transport::{PeerInfo, UpgradeRequest};
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::net::{IpAddr, TcpListener, TcpStream};
+
+/// First byte of every RawSocket handshake.
+pub const RAWSOCKET_MAGIC: u8 = 0x7f;
+
+/// Serialization formats a connection can negotiate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Serializer {
+    Json,
+    MsgPack,
+    Cbor,
+}
+
+impl Serializer {
+    /// WebSocket subprotocol name, e.g. `wamp.2.json`.
+    pub fn subprotocol(self) -> &'static str {
+        match self {
+            Serializer::Json => "wamp.2.json",
+            Serializer::MsgPack => "wamp.2.msgpack",
+            Serializer::Cbor => "wamp.2.cbor",
+        }
+    }
+
+    pub fn from_subprotocol(subprotocol: &str) -> Option<Self> {
+        match subprotocol {
+            "wamp.2.json" => Some(Serializer::Json),
+            "wamp.2.msgpack" => Some(Serializer::MsgPack),
+            "wamp.2.cbor" => Some(Serializer::Cbor),
+            _ => None,
+        }
+    }
+
+    /// Subprotocol of the batched variant, see [`batch`](crate::batch).
+    pub fn batched_subprotocol(self) -> &'static str {
+        match self {
+            Serializer::Json => "wamp.2.json.batched",
+            Serializer::MsgPack => "wamp.2.msgpack.batched",
+            Serializer::Cbor => "wamp.2.cbor.batched",
+        }
+    }
+
+    pub fn from_batched_subprotocol(subprotocol: &str) -> Option<Self> {
+        Serializer::from_subprotocol(subprotocol.strip_suffix(".batched")?)
+    }
+
+    /// Serializer code of the RawSocket handshake.
+    pub fn rawsocket_code(self) -> u8 {
+        match self {
+            Serializer::Json => 1,
+            Serializer::MsgPack => 2,
+            Serializer::Cbor => 3,
+        }
+    }
+
+    pub fn from_rawsocket_code(code: u8) -> Option<Self> {
+        match code {
+            1 => Some(Serializer::Json),
+            2 => Some(Serializer::MsgPack),
+            3 => Some(Serializer::Cbor),
+            _ => None,
+        }
+    }
+}
+
+/// WebSocket subprotocols of WAMP, the name a connection negotiates in
+/// `Sec-WebSocket-Protocol`.
+/// # Examples
+/// ```
+/// use wamp_helpers::acceptor::SubProtocol;
+///
+/// // Client side, the header of the upgrade request.
+/// let offer = SubProtocol::offer(&[SubProtocol::CborBatched, SubProtocol::Json]);
+/// assert_eq!(offer, "wamp.2.cbor.batched, wamp.2.json");
+///
+/// // Server side, the first offer the router supports wins.
+/// let selected = SubProtocol::select(&offer, &[SubProtocol::Json, SubProtocol::MsgPack]);
+/// assert_eq!(selected, Some(SubProtocol::Json));
+///
+/// // Client side again, check what the 101 response picked.
+/// let accepted = SubProtocol::accepted(&offer, "wamp.2.json").unwrap();
+/// assert!(!accepted.is_batched());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SubProtocol {
+    Json,
+    MsgPack,
+    Cbor,
+    JsonBatched,
+    MsgPackBatched,
+    CborBatched,
+}
+
+impl SubProtocol {
+    pub const ALL: [SubProtocol; 6] = [
+        SubProtocol::Json,
+        SubProtocol::MsgPack,
+        SubProtocol::Cbor,
+        SubProtocol::JsonBatched,
+        SubProtocol::MsgPackBatched,
+        SubProtocol::CborBatched,
+    ];
+
+    pub fn new(serializer: Serializer, batched: bool) -> Self {
+        match (serializer, batched) {
+            (Serializer::Json, false) => SubProtocol::Json,
+            (Serializer::MsgPack, false) => SubProtocol::MsgPack,
+            (Serializer::Cbor, false) => SubProtocol::Cbor,
+            (Serializer::Json, true) => SubProtocol::JsonBatched,
+            (Serializer::MsgPack, true) => SubProtocol::MsgPackBatched,
+            (Serializer::Cbor, true) => SubProtocol::CborBatched,
+        }
+    }
+
+    pub fn serializer(self) -> Serializer {
+        match self {
+            SubProtocol::Json | SubProtocol::JsonBatched => Serializer::Json,
+            SubProtocol::MsgPack | SubProtocol::MsgPackBatched => Serializer::MsgPack,
+            SubProtocol::Cbor | SubProtocol::CborBatched => Serializer::Cbor,
+        }
+    }
+
+    /// Whether frames carry several messages, see [`batch`](crate::batch).
+    pub fn is_batched(self) -> bool {
+        matches!(
+            self,
+            SubProtocol::JsonBatched | SubProtocol::MsgPackBatched | SubProtocol::CborBatched
+        )
+    }
+
+    pub fn as_str(self) -> &'static str {
+        if self.is_batched() {
+            self.serializer().batched_subprotocol()
+        } else {
+            self.serializer().subprotocol()
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match Serializer::from_subprotocol(name) {
+            Some(serializer) => Some(SubProtocol::new(serializer, false)),
+            None => Some(SubProtocol::new(
+                Serializer::from_batched_subprotocol(name)?,
+                true,
+            )),
+        }
+    }
+
+    /// `Sec-WebSocket-Protocol` value offering `subprotocols`, in order of preference.
+    pub fn offer(subprotocols: &[SubProtocol]) -> String {
+        subprotocols
+            .iter()
+            .map(|subprotocol| subprotocol.as_str())
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// The known subprotocols of an offer header, in the client's order. Names this crate
+    /// does not know are skipped.
+    pub fn parse_offer(header: &str) -> Vec<SubProtocol> {
+        header
+            .split(',')
+            .filter_map(|name| SubProtocol::from_name(name.trim()))
+            .collect()
+    }
+
+    /// Pick the first offered subprotocol that is `supported`, what the server puts into the
+    /// 101 response.
+    pub fn select(header: &str, supported: &[SubProtocol]) -> Option<SubProtocol> {
+        SubProtocol::parse_offer(header)
+            .into_iter()
+            .find(|subprotocol| supported.contains(subprotocol))
+    }
+
+    /// The subprotocol the server answered with, `None` when it is missing or was never
+    /// offered, in which case the client has to fail the connection.
+    pub fn accepted(offer: &str, response: &str) -> Option<SubProtocol> {
+        let accepted = SubProtocol::from_name(response.trim())?;
+        SubProtocol::parse_offer(offer)
+            .contains(&accepted)
+            .then_some(accepted)
+    }
+}
+
+impl std::fmt::Display for SubProtocol {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Error codes a router answers a RawSocket handshake with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RawSocketError {
+    /// Never sent, a handshake that does not start with [`RAWSOCKET_MAGIC`] is not from a
+    /// WAMP client and gets no reply. It is the code of the returned
+    /// [`Error::Handshake`].
+    Illegal = 0,
+    SerializerUnsupported = 1,
+    MaxLengthUnacceptable = 2,
+    ReservedBitsUsed = 3,
+    MaxConnectionCountReached = 4,
+}
+
+impl RawSocketError {
+    /// The four byte reply carrying this error.
+    pub fn reply(self) -> [u8; 4] {
+        [RAWSOCKET_MAGIC, (self as u8) << 4, 0, 0]
+    }
+}
+
+/// Outcome of a successful negotiation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Negotiated {
+    pub serializer: Serializer,
+    /// Largest message the peer accepts, `None` where the transport has no limit.
+    pub max_send_len: Option<usize>,
+    pub peer: PeerInfo,
+}
+
+/// Server side of connection setup: optional PROXY protocol header, then RawSocket handshake
+/// or WebSocket subprotocol selection.
+///
+/// The acceptor only negotiates, the resulting stream and [`Negotiated`] are handed to
+/// whatever runs the sessions.
+/// # Examples
+/// ```
+/// use std::io::Cursor;
+/// use wamp_helpers::acceptor::{Acceptor, Serializer};
+///
+/// let acceptor = Acceptor::new(vec![Serializer::Json]).proxy_protocol(true);
+///
+/// // PROXY header, then a handshake asking for JSON with a 2^(9+15) byte limit.
+/// let mut input = b"PROXY TCP4 203.0.113.7 10.0.0.1 56324 8080\r\n".to_vec();
+/// input.extend([0x7f, 0xf1, 0, 0]);
+/// let mut stream = Cursor::new(input);
+/// let negotiated = acceptor.handshake(&mut stream, None).unwrap();
+/// assert_eq!(negotiated.serializer, Serializer::Json);
+/// assert_eq!(negotiated.max_send_len, Some(1 << 24));
+/// assert_eq!(negotiated.peer.proxied, Some("203.0.113.7:56324".parse().unwrap()));
+///
+/// // The reply was written after the consumed input.
+/// let written = stream.into_inner();
+/// assert_eq!(&written[written.len() - 4..], [0x7f, 0xf1, 0, 0]);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Acceptor {
+    serializers: Vec<Serializer>,
+    max_len_exponent: u8,
+    proxy_protocol: bool,
+    spec_strict: bool,
+}
+
+impl Acceptor {
+    /// Accept the given serializers, in order of preference.
+    pub fn new(serializers: Vec<Serializer>) -> Self {
+        Acceptor {
+            serializers,
+            max_len_exponent: 15,
+            proxy_protocol: false,
+            spec_strict: crate::parse::SPEC_STRICT,
+        }
+    }
+
+    /// Announce a receive limit of `2^(9 + exponent)` bytes on RawSocket, `exponent` is at most
+    /// 15.
+    pub fn max_len_exponent(mut self, exponent: u8) -> Self {
+        self.max_len_exponent = exponent.min(15);
+        self
+    }
+
+    /// Expect a PROXY protocol header before the handshake, only enable this behind a load
+    /// balancer that always sends one.
+    pub fn proxy_protocol(mut self, proxy_protocol: bool) -> Self {
+        self.proxy_protocol = proxy_protocol;
+        self
+    }
+
+    /// Refuse clients offering any subprotocol that is not a standard WAMP one, instead of
+    /// skipping it. On by default with the `spec_strict` feature.
+    pub fn spec_strict(mut self, spec_strict: bool) -> Self {
+        self.spec_strict = spec_strict;
+        self
+    }
+
+    /// Pick the first serializer of the client's `Sec-WebSocket-Protocol` list that is
+    /// accepted, the chosen subprotocol goes into the 101 response.
+    /// ```
+    /// use wamp_helpers::acceptor::{Acceptor, Serializer};
+    /// use wamp_helpers::transport::UpgradeRequest;
+    ///
+    /// let request = UpgradeRequest::parse(
+    ///     "GET /ws HTTP/1.1\r\nSec-WebSocket-Protocol: wamp.2.flatbuffers, wamp.2.json\r\n\r\n",
+    /// )
+    /// .unwrap();
+    /// let acceptor = Acceptor::new(vec![Serializer::Json]);
+    /// assert_eq!(acceptor.clone().spec_strict(false).select_subprotocol(&request), Some(Serializer::Json));
+    /// assert_eq!(acceptor.spec_strict(true).select_subprotocol(&request), None);
+    /// ```
+    pub fn select_subprotocol(&self, request: &UpgradeRequest) -> Option<Serializer> {
+        let supported: Vec<_> = self
+            .serializers
+            .iter()
+            .map(|serializer| SubProtocol::new(*serializer, false))
+            .collect();
+        self.negotiate(request, &supported)
+            .map(SubProtocol::serializer)
+    }
+
+    /// Like [`select_subprotocol`](Self::select_subprotocol), but the batched variants of the
+    /// accepted serializers are acceptable too.
+    pub fn select_batched_subprotocol(&self, request: &UpgradeRequest) -> Option<SubProtocol> {
+        let supported: Vec<_> = self
+            .serializers
+            .iter()
+            .flat_map(|serializer| {
+                [
+                    SubProtocol::new(*serializer, false),
+                    SubProtocol::new(*serializer, true),
+                ]
+            })
+            .collect();
+        self.negotiate(request, &supported)
+    }
+
+    fn negotiate(
+        &self,
+        request: &UpgradeRequest,
+        supported: &[SubProtocol],
+    ) -> Option<SubProtocol> {
+        let header = request.header("sec-websocket-protocol")?;
+        let offered = header.split(',').filter(|name| !name.trim().is_empty());
+        if self.spec_strict && offered.count() != SubProtocol::parse_offer(header).len() {
+            return None;
+        }
+        SubProtocol::select(header, supported)
+    }
+
+    /// Negotiate a WebSocket connection whose upgrade request was already read.
+    pub fn accept_websocket(
+        &self,
+        request: UpgradeRequest,
+        mut peer: PeerInfo,
+    ) -> Option<Negotiated> {
+        let serializer = self.select_subprotocol(&request)?;
+        peer.upgrade = Some(request);
+        Some(Negotiated {
+            serializer,
+            max_send_len: None,
+            peer,
+        })
+    }
+
+    /// Run the server side of the RawSocket handshake on `stream`, preceded by the PROXY
+    /// header when enabled. Refused handshakes are answered before the error is returned,
+    /// except those without the magic octet, the caller just drops the connection.
+    /// ```
+    /// use std::io::Cursor;
+    /// use wamp_helpers::acceptor::{Acceptor, Serializer};
+    /// use wamp_helpers::error::Error;
+    ///
+    /// let acceptor = Acceptor::new(vec![Serializer::Json]);
+    /// let mut stream = Cursor::new(b"GET / HTTP/1.1\r\n".to_vec());
+    /// assert!(matches!(acceptor.handshake(&mut stream, None), Err(Error::Handshake { code: 0 })));
+    /// assert_eq!(stream.into_inner(), b"GET / HTTP/1.1\r\n");
+    ///
+    /// let mut stream = Cursor::new(vec![0x7f, 0xf3, 0, 0]);
+    /// assert!(matches!(acceptor.handshake(&mut stream, None), Err(Error::Handshake { code: 1 })));
+    /// assert_eq!(&stream.into_inner()[4..], [0x7f, 0x10, 0, 0]);
+    /// ```
+    pub fn handshake<S: Read + Write>(
+        &self,
+        stream: &mut S,
+        address: Option<std::net::SocketAddr>,
+    ) -> Result<Negotiated, Error> {
+        let mut peer = PeerInfo {
+            address,
+            ..PeerInfo::default()
+        };
+        if self.proxy_protocol {
+            // Read byte by byte so nothing after the header is consumed.
+            let mut header = Vec::new();
+            loop {
+                let mut byte = [0u8];
+                stream.read_exact(&mut byte).map_err(Error::Io)?;
+                header.push(byte[0]);
+                if let Some((proxy, _)) = parse_proxy_header(&header)? {
+                    peer.proxied = proxy.source;
+                    break;
+                }
+            }
+        }
+
+        let mut request = [0u8; 4];
+        stream.read_exact(&mut request).map_err(Error::Io)?;
+        let refuse = |stream: &mut S, error: RawSocketError| {
+            stream.write_all(&error.reply()).map_err(Error::Io)?;
+            Err(Error::Handshake { code: error as u8 })
+        };
+        if request[0] != RAWSOCKET_MAGIC {
+            return Err(Error::Handshake {
+                code: RawSocketError::Illegal as u8,
+            });
+        }
+        if request[2] != 0 || request[3] != 0 {
+            return refuse(stream, RawSocketError::ReservedBitsUsed);
+        }
+        let serializer = match Serializer::from_rawsocket_code(request[1] & 0x0f) {
+            Some(serializer) if self.serializers.contains(&serializer) => serializer,
+            _ => return refuse(stream, RawSocketError::SerializerUnsupported),
+        };
+
+        let reply = [
+            RAWSOCKET_MAGIC,
+            self.max_len_exponent << 4 | serializer.rawsocket_code(),
+            0,
+            0,
+        ];
+        stream.write_all(&reply).map_err(Error::Io)?;
+        Ok(Negotiated {
+            serializer,
+            max_send_len: Some(1 << (9 + (request[1] >> 4))),
+            peer,
+        })
+    }
+
+    /// Accept the next TCP connection from `listener` and run [`handshake`](Self::handshake)
+    /// on it.
+    pub fn accept_rawsocket(
+        &self,
+        listener: &TcpListener,
+    ) -> Result<(TcpStream, Negotiated), Error> {
+        let (mut stream, address) = listener.accept().map_err(Error::Io)?;
+        let negotiated = self.handshake(&mut stream, Some(address))?;
+        Ok((stream, negotiated))
+    }
+}
+
+/// ABORT reason used when a limit of [`Admission`] is exceeded.
+pub const CONNECTION_LIMIT_REACHED: &str = "wamp.error.connection_limit_reached";
+
+/// Limits on concurrent sessions per realm and per client address.
+///
+/// Call [`admit`](Admission::admit) when a HELLO arrives and [`release`](Admission::release)
+/// when the session ends, for every admitted session.
+/// # Examples
+/// ```
+/// use std::net::IpAddr;
+/// use wamp_helpers::acceptor::Admission;
+///
+/// let mut admission = Admission::new().max_per_address(1);
+/// let client: IpAddr = "203.0.113.7".parse().unwrap();
+///
+/// admission.admit("realm1", client).unwrap();
+/// let abort = admission.admit("realm1", client).unwrap_err();
+/// assert_eq!(abort.reason, "wamp.error.connection_limit_reached");
+///
+/// admission.release("realm1", client);
+/// assert!(admission.admit("realm1", client).is_ok());
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Admission {
+    max_per_realm: Option<usize>,
+    max_per_address: Option<usize>,
+    realms: HashMap<String, usize>,
+    addresses: HashMap<IpAddr, usize>,
+}
+
+impl Admission {
+    /// No limits until configured.
+    pub fn new() -> Self {
+        Admission::default()
+    }
+
+    pub fn max_per_realm(mut self, max: usize) -> Self {
+        self.max_per_realm = Some(max);
+        self
+    }
+
+    pub fn max_per_address(mut self, max: usize) -> Self {
+        self.max_per_address = Some(max);
+        self
+    }
+
+    /// Whether another connection from `address` may even start the handshake, answer with
+    /// [`RawSocketError::MaxConnectionCountReached`] otherwise.
+    pub fn accepts_address(&self, address: IpAddr) -> bool {
+        self.max_per_address
+            .is_none_or(|max| self.addresses.get(&address).copied().unwrap_or(0) < max)
+    }
+
+    /// Count a new session on `realm` from `address`, or the ABORT refusing it.
+    pub fn admit(&mut self, realm: &str, address: IpAddr) -> Result<(), Abort> {
+        let realm_full = self
+            .max_per_realm
+            .is_some_and(|max| self.realms.get(realm).copied().unwrap_or(0) >= max);
+        if realm_full || !self.accepts_address(address) {
+            let message = if realm_full {
+                "too many sessions on this realm"
+            } else {
+                "too many sessions from this address"
+            };
+            return Err(Abort {
+                details: json::object! { message: message },
+                reason: CONNECTION_LIMIT_REACHED.into(),
+            });
+        }
+        *self.realms.entry(realm.to_string()).or_default() += 1;
+        *self.addresses.entry(address).or_default() += 1;
+        Ok(())
+    }
+
+    /// Forget a session admitted earlier.
+    pub fn release(&mut self, realm: &str, address: IpAddr) {
+        if let Some(count) = self.realms.get_mut(realm) {
+            *count -= 1;
+            if *count == 0 {
+                self.realms.remove(realm);
+            }
+        }
+        if let Some(count) = self.addresses.get_mut(&address) {
+            *count -= 1;
+            if *count == 0 {
+                self.addresses.remove(&address);
+            }
+        }
+    }
+
+    pub fn sessions_on(&self, realm: &str) -> usize {
+        self.realms.get(realm).copied().unwrap_or(0)
+    }
+}
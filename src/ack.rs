@@ -0,0 +1,133 @@
+//! Experimental acknowledged events for at-least-once delivery.
+//!
+//! The broker marks an EVENT with `acknowledge: true` in its Details, the subscriber answers
+//! with a CALL to [`ACKNOWLEDGE_PROCEDURE`] carrying the subscription and publication ids, and
+//! the broker redelivers events that stay unacknowledged for too long.
+
+use crate::messages::{Call, Event, WampId};
+use crate::value::WampValue;
+use std::collections::HashMap;
+use std::time::Instant;
+
+/// Procedure subscribers call to acknowledge an event, with `[subscription, publication]` as
+/// arguments.
+pub const ACKNOWLEDGE_PROCEDURE: &str = "wamp.event.acknowledge";
+
+/// Ask the subscriber to acknowledge `event`.
+pub fn request_acknowledgment(event: &mut Event) {
+    event.details["acknowledge"] = true.into();
+}
+
+/// Whether the broker asked for an acknowledgment of `event`.
+pub fn requires_acknowledgment(event: &Event) -> bool {
+    event.details["acknowledge"].as_bool().unwrap_or(false)
+}
+
+/// The CALL acknowledging `event`, `None` when no acknowledgment was asked for.
+pub fn acknowledgment(event: &Event, request: WampId) -> Option<Call> {
+    if !requires_acknowledgment(event) {
+        return None;
+    }
+    Some(Call {
+        request,
+        options: json::object! {},
+        procedure: ACKNOWLEDGE_PROCEDURE.into(),
+        args: Some(vec![
+            WampValue::Integer(event.subscription as i64),
+            WampValue::Integer(event.publication as i64),
+        ]),
+        kwargs: None,
+    })
+}
+
+/// Subscription and publication ids acknowledged by `call`, `None` for other calls.
+pub fn acknowledged(call: &Call) -> Option<(WampId, WampId)> {
+    if call.procedure != ACKNOWLEDGE_PROCEDURE {
+        return None;
+    }
+    match call.args.as_deref()? {
+        [WampValue::Integer(subscription), WampValue::Integer(publication)] => {
+            Some((*subscription as WampId, *publication as WampId))
+        }
+        _ => None,
+    }
+}
+
+/// Broker-side bookkeeping of deliveries still waiting for their acknowledgment.
+/// # Examples
+/// ```
+/// use std::time::{Duration, Instant};
+/// use wamp_helpers::ack::{acknowledgment, request_acknowledgment, PendingAcks};
+/// use wamp_helpers::messages::Event;
+///
+/// let mut pending = PendingAcks::new();
+/// let start = Instant::now();
+/// let mut event: Event = r#"[36, 5512315355, 4429313566, {}, ["Hello"]]"#.parse().unwrap();
+/// request_acknowledgment(&mut event);
+/// pending.deliver(71, &event, start);
+///
+/// // Not acknowledged in time, the event is handed back for redelivery.
+/// let overdue = pending.overdue(start + Duration::from_secs(1));
+/// assert_eq!(overdue[0].0, 71);
+/// assert_eq!(overdue[0].1.publication, 4429313566);
+///
+/// let call = acknowledgment(&event, 1).unwrap();
+/// assert!(pending.acknowledge(71, &call));
+/// assert_eq!(pending.len(), 0);
+/// ```
+#[derive(Debug, Default)]
+pub struct PendingAcks {
+    pending: HashMap<(WampId, WampId, WampId), (Event, Instant)>,
+}
+
+impl PendingAcks {
+    pub fn new() -> Self {
+        PendingAcks::default()
+    }
+
+    /// Record that `event` was sent to `session` at `at`, events without an acknowledgment
+    /// request are ignored.
+    pub fn deliver(&mut self, session: WampId, event: &Event, at: Instant) {
+        if requires_acknowledgment(event) {
+            self.pending.insert(
+                (session, event.subscription, event.publication),
+                (event.clone(), at),
+            );
+        }
+    }
+
+    /// Apply an acknowledgment CALL from `session`, returns whether it settled a delivery.
+    pub fn acknowledge(&mut self, session: WampId, call: &Call) -> bool {
+        acknowledged(call).is_some_and(|(subscription, publication)| {
+            self.pending
+                .remove(&(session, subscription, publication))
+                .is_some()
+        })
+    }
+
+    /// Forget every delivery to `session`, e.g. when it left.
+    pub fn forget_session(&mut self, session: WampId) {
+        self.pending.retain(|(target, _, _), _| *target != session);
+    }
+
+    /// Deliveries sent before `deadline`, their delivery time is reset to `deadline` so they
+    /// are reported again only after another timeout.
+    pub fn overdue(&mut self, deadline: Instant) -> Vec<(WampId, Event)> {
+        let mut overdue = Vec::new();
+        for ((session, _, _), (event, sent_at)) in self.pending.iter_mut() {
+            if *sent_at < deadline {
+                *sent_at = deadline;
+                overdue.push((*session, event.clone()));
+            }
+        }
+        overdue
+    }
+
+    pub fn len(&self) -> usize {
+        self.pending.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+}
@@ -0,0 +1,653 @@
+use crate::error::Error;
+use json::JsonValue;
+
+/// Nesting depth at which parsing gives up, like the `json` crate.
+pub const MAX_DEPTH: usize = 512;
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Node {
+    Null,
+    Bool(bool),
+    /// Number literal, as a span of the input.
+    Number(usize, usize),
+    /// String without escapes, as a span of the input between the quotes.
+    Raw(usize, usize),
+    /// String with escapes, decoded into the arena text.
+    Decoded(usize, usize),
+    /// `len` children follow, `end` is the index of the node after the last descendant.
+    Array {
+        len: usize,
+        end: usize,
+    },
+    /// `len` key and value pairs follow, keys are string nodes.
+    Object {
+        len: usize,
+        end: usize,
+    },
+}
+
+/// Reusable storage for parse trees of one message at a time.
+///
+/// For routers that parse a frame, route it and serialize it again right away. The tree is a
+/// flat list of nodes, strings and numbers point into the frame and only strings with escapes
+/// are copied. [`parse`](ParseArena::parse) drops the previous tree but keeps the memory, so
+/// after the first few messages parsing does not allocate at all.
+///
+/// Trees only borrow from the arena and the frame, [`ValueRef::to_json`] builds an owned
+/// `JsonValue` for messages that need the full [`Message`](crate::messages::Message) treatment.
+/// # Examples
+/// ```
+/// use wamp_helpers::arena::ParseArena;
+///
+/// let mut arena = ParseArena::new();
+/// let frame = r#"[16, 239714735, {}, "com.myapp.topic", ["café"], {"n": 1}]"#;
+/// let publish = arena.parse(frame).unwrap();
+/// assert_eq!(publish.get(0).and_then(|id| id.as_u64()), Some(16));
+/// assert_eq!(publish.get(3).and_then(|topic| topic.as_str()), Some("com.myapp.topic"));
+/// assert_eq!(publish.get(4).unwrap().get(0).unwrap().as_str(), Some("café"));
+///
+/// // Forward the payload as EVENT without building it again.
+/// let mut event = String::from("[36,5512315355,4429313566,{}");
+/// for payload in publish.members().skip(4) {
+///     event.push(',');
+///     payload.write_to(&mut event);
+/// }
+/// event.push(']');
+/// assert_eq!(event, r#"[36,5512315355,4429313566,{},["café"],{"n":1}]"#);
+///
+/// assert!(arena.parse("[16, 1,").is_err());
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ParseArena {
+    nodes: Vec<Node>,
+    text: String,
+}
+
+impl ParseArena {
+    pub fn new() -> Self {
+        ParseArena::default()
+    }
+
+    /// Parse `input`, replacing the tree of the previous message.
+    pub fn parse<'a>(&'a mut self, input: &'a str) -> Result<ValueRef<'a>, Error> {
+        self.nodes.clear();
+        self.text.clear();
+        let mut tokenizer = Tokenizer::new(input.as_bytes(), &mut *self);
+        tokenizer.value(0)?;
+        tokenizer.skip_whitespace();
+        if tokenizer.pos < tokenizer.input.len() {
+            return Err(tokenizer.unexpected());
+        }
+        Ok(ValueRef {
+            arena: self,
+            input,
+            index: 0,
+        })
+    }
+
+    /// Nodes of the current tree.
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+}
+
+/// The error the `json` crate reports for the byte at `pos`, or for the end of the input.
+pub(crate) fn unexpected(input: &[u8], pos: usize) -> Error {
+    let Some(rest) = std::str::from_utf8(input.get(pos..).unwrap_or_default())
+        .ok()
+        .and_then(|rest| rest.chars().next())
+    else {
+        return Error::JsonError(json::Error::UnexpectedEndOfJson);
+    };
+    let before = input.get(..pos).unwrap_or(input);
+    let line = before.iter().filter(|byte| **byte == b'\n').count() + 1;
+    let column = pos
+        - before
+            .iter()
+            .rposition(|byte| *byte == b'\n')
+            .map_or(0, |newline| newline + 1)
+        + 1;
+    Error::JsonError(json::Error::UnexpectedCharacter {
+        ch: rest,
+        line,
+        column,
+    })
+}
+
+/// What a [`Tokenizer`] reports while it walks a value.
+pub(crate) trait Visitor {
+    /// A null, boolean, number or string.
+    fn scalar(&mut self, node: Node);
+
+    /// An array or object starts, returns what [`close`](Visitor::close) is given back.
+    fn open(&mut self) -> usize;
+
+    /// The array or object opened at `open` ended, `node` says which with its length.
+    fn close(&mut self, open: usize, node: Node);
+
+    /// Where strings with escapes are decoded, `None` to only check them.
+    fn text(&mut self) -> Option<&mut String>;
+}
+
+impl Visitor for &mut ParseArena {
+    fn scalar(&mut self, node: Node) {
+        self.nodes.push(node);
+    }
+
+    fn open(&mut self) -> usize {
+        self.nodes.push(Node::Null);
+        self.nodes.len() - 1
+    }
+
+    fn close(&mut self, open: usize, node: Node) {
+        let node = match node {
+            Node::Array { len, .. } => Node::Array {
+                len,
+                end: self.nodes.len(),
+            },
+            Node::Object { len, .. } => Node::Object {
+                len,
+                end: self.nodes.len(),
+            },
+            other => other,
+        };
+        if let Some(slot) = self.nodes.get_mut(open) {
+            *slot = node;
+        }
+    }
+
+    fn text(&mut self) -> Option<&mut String> {
+        Some(&mut self.text)
+    }
+}
+
+/// JSON tokenizer shared by [`ParseArena`] and [`scan_frame`](crate::scan::scan_frame),
+/// accepting exactly what the `json` crate accepts and reporting the same errors.
+pub(crate) struct Tokenizer<'i, V> {
+    pub(crate) input: &'i [u8],
+    pub(crate) pos: usize,
+    visitor: V,
+}
+
+impl<'i, V: Visitor> Tokenizer<'i, V> {
+    pub(crate) fn new(input: &'i [u8], visitor: V) -> Self {
+        Tokenizer {
+            input,
+            pos: 0,
+            visitor,
+        }
+    }
+
+    pub(crate) fn unexpected(&self) -> Error {
+        unexpected(self.input, self.pos)
+    }
+
+    pub(crate) fn skip_whitespace(&mut self) {
+        while matches!(self.peek(), Some(b' ' | b'\t' | b'\n' | b'\r')) {
+            self.pos += 1;
+        }
+    }
+
+    pub(crate) fn peek(&self) -> Option<u8> {
+        self.input.get(self.pos).copied()
+    }
+
+    /// The input from the current position on.
+    fn rest(&self) -> &'i [u8] {
+        self.input.get(self.pos..).unwrap_or_default()
+    }
+
+    /// The input from `start` to the current position.
+    fn span(&self, start: usize) -> &'i [u8] {
+        self.input.get(start..self.pos).unwrap_or_default()
+    }
+
+    fn expect(&mut self, byte: u8) -> Result<(), Error> {
+        if self.peek() == Some(byte) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(self.unexpected())
+        }
+    }
+
+    fn literal(&mut self, literal: &[u8], node: Node) -> Result<(), Error> {
+        if self.rest().starts_with(literal) {
+            self.pos += literal.len();
+            self.visitor.scalar(node);
+            Ok(())
+        } else {
+            Err(self.unexpected())
+        }
+    }
+
+    /// Walk the value at the current position, nested `depth` containers deep.
+    pub(crate) fn value(&mut self, depth: usize) -> Result<(), Error> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some(b'[') => self.container(depth, b']'),
+            Some(b'{') => self.container(depth, b'}'),
+            Some(b'"') => {
+                let node = self.string()?;
+                self.visitor.scalar(node);
+                Ok(())
+            }
+            Some(b'n') => self.literal(b"null", Node::Null),
+            Some(b't') => self.literal(b"true", Node::Bool(true)),
+            Some(b'f') => self.literal(b"false", Node::Bool(false)),
+            Some(b'-' | b'0'..=b'9') => self.number(),
+            _ => Err(self.unexpected()),
+        }
+    }
+
+    fn container(&mut self, depth: usize, close: u8) -> Result<(), Error> {
+        if depth >= MAX_DEPTH {
+            return Err(Error::JsonError(json::Error::ExceededDepthLimit));
+        }
+        self.pos += 1;
+        let open = self.visitor.open();
+        let mut len = 0;
+        self.skip_whitespace();
+        if self.peek() == Some(close) {
+            self.pos += 1;
+        } else {
+            loop {
+                if close == b'}' {
+                    self.skip_whitespace();
+                    if self.peek() != Some(b'"') {
+                        return Err(self.unexpected());
+                    }
+                    let key = self.string()?;
+                    self.visitor.scalar(key);
+                    self.skip_whitespace();
+                    self.expect(b':')?;
+                }
+                self.value(depth + 1)?;
+                len += 1;
+                self.skip_whitespace();
+                match self.peek() {
+                    Some(b',') => self.pos += 1,
+                    Some(byte) if byte == close => {
+                        self.pos += 1;
+                        break;
+                    }
+                    _ => return Err(self.unexpected()),
+                }
+            }
+        }
+        let node = if close == b']' {
+            Node::Array { len, end: 0 }
+        } else {
+            Node::Object { len, end: 0 }
+        };
+        self.visitor.close(open, node);
+        Ok(())
+    }
+
+    fn digits(&mut self) -> Result<(), Error> {
+        if !matches!(self.peek(), Some(b'0'..=b'9')) {
+            return Err(self.unexpected());
+        }
+        while matches!(self.peek(), Some(b'0'..=b'9')) {
+            self.pos += 1;
+        }
+        Ok(())
+    }
+
+    fn number(&mut self) -> Result<(), Error> {
+        let start = self.pos;
+        if self.peek() == Some(b'-') {
+            self.pos += 1;
+        }
+        if self.peek() == Some(b'0') {
+            self.pos += 1;
+        } else {
+            self.digits()?;
+        }
+        if self.peek() == Some(b'.') {
+            self.pos += 1;
+            self.digits()?;
+        }
+        if matches!(self.peek(), Some(b'e' | b'E')) {
+            self.pos += 1;
+            if matches!(self.peek(), Some(b'+' | b'-')) {
+                self.pos += 1;
+            }
+            self.digits()?;
+        }
+        self.visitor.scalar(Node::Number(start, self.pos));
+        Ok(())
+    }
+
+    /// The four hex digits of a `\u` escape. Checked one by one, `from_str_radix` alone
+    /// would accept a sign.
+    fn hex(&mut self) -> Result<u32, Error> {
+        let digits = self
+            .input
+            .get(self.pos..self.pos + 4)
+            .filter(|digits| digits.iter().all(u8::is_ascii_hexdigit))
+            .and_then(|digits| std::str::from_utf8(digits).ok())
+            .and_then(|digits| u32::from_str_radix(digits, 16).ok());
+        match digits {
+            Some(code) => {
+                self.pos += 4;
+                Ok(code)
+            }
+            None => Err(self.unexpected()),
+        }
+    }
+
+    /// A string node: a span of the input when it has no escapes, otherwise decoded into the
+    /// visitor's text, or only checked when it has none.
+    fn string(&mut self) -> Result<Node, Error> {
+        self.pos += 1;
+        let start = self.pos;
+        loop {
+            match self.peek() {
+                Some(b'"') => {
+                    self.pos += 1;
+                    return Ok(Node::Raw(start, self.pos - 1));
+                }
+                Some(b'\\') => break,
+                Some(0..=0x1f) | None => return Err(self.unexpected()),
+                Some(_) => self.pos += 1,
+            }
+        }
+
+        // The input is a `str` and the span ends before an ASCII backslash.
+        let prefix = std::str::from_utf8(self.span(start)).unwrap_or_default();
+        let text_start = match self.visitor.text() {
+            Some(text) => {
+                let text_start = text.len();
+                text.push_str(prefix);
+                text_start
+            }
+            None => 0,
+        };
+        loop {
+            let run = self.pos;
+            while !matches!(self.peek(), Some(b'"' | b'\\' | 0..=0x1f) | None) {
+                self.pos += 1;
+            }
+            let run = std::str::from_utf8(self.span(run)).unwrap_or_default();
+            if let Some(text) = self.visitor.text() {
+                text.push_str(run);
+            }
+            match self.peek() {
+                Some(b'"') => {
+                    self.pos += 1;
+                    let text_end = self.visitor.text().map_or(0, |text| text.len());
+                    return Ok(Node::Decoded(text_start, text_end));
+                }
+                Some(b'\\') => {
+                    self.pos += 1;
+                    let escaped = match self.peek() {
+                        Some(b'u') => {
+                            self.pos += 1;
+                            self.unicode()?
+                        }
+                        letter => {
+                            let escaped = match letter {
+                                Some(b'"') => '"',
+                                Some(b'\\') => '\\',
+                                Some(b'/') => '/',
+                                Some(b'b') => '\u{8}',
+                                Some(b'f') => '\u{c}',
+                                Some(b'n') => '\n',
+                                Some(b'r') => '\r',
+                                Some(b't') => '\t',
+                                _ => return Err(self.unexpected()),
+                            };
+                            self.pos += 1;
+                            escaped
+                        }
+                    };
+                    if let Some(text) = self.visitor.text() {
+                        text.push(escaped);
+                    }
+                }
+                _ => return Err(self.unexpected()),
+            }
+        }
+    }
+
+    /// Decode the digits after `\u`. A surrogate has to be the high half of a pair written as
+    /// two escapes, as the `json` crate requires.
+    fn unicode(&mut self) -> Result<char, Error> {
+        let high = self.hex()?;
+        if let Some(code) = char::from_u32(high) {
+            return Ok(code);
+        }
+        self.expect(b'\\')?;
+        self.expect(b'u')?;
+        let low = self.hex()?;
+        // Both halves are four hex digits.
+        char::decode_utf16([high as u16, low as u16])
+            .next()
+            .and_then(Result::ok)
+            .ok_or(Error::JsonError(json::Error::FailedUtf8Parsing))
+    }
+}
+
+/// A value of the tree in a [`ParseArena`].
+#[derive(Debug, Clone, Copy)]
+pub struct ValueRef<'a> {
+    arena: &'a ParseArena,
+    input: &'a str,
+    index: usize,
+}
+
+impl<'a> ValueRef<'a> {
+    fn node(&self) -> Node {
+        // Indices come from the tree itself, `Null` is never reached.
+        self.arena.nodes.get(self.index).copied().unwrap_or(Node::Null)
+    }
+
+    /// A span of the frame, spans of the tree always lie within it.
+    fn input(&self, start: usize, end: usize) -> &'a str {
+        self.input.get(start..end).unwrap_or_default()
+    }
+
+    /// A span of the decoded strings.
+    fn text(&self, start: usize, end: usize) -> &'a str {
+        self.arena.text.get(start..end).unwrap_or_default()
+    }
+
+    fn at(&self, index: usize) -> ValueRef<'a> {
+        ValueRef { index, ..*self }
+    }
+
+    /// Index of the node after this value and its descendants.
+    fn end(&self) -> usize {
+        match self.node() {
+            Node::Array { end, .. } | Node::Object { end, .. } => end,
+            _ => self.index + 1,
+        }
+    }
+
+    pub fn is_null(&self) -> bool {
+        matches!(self.node(), Node::Null)
+    }
+
+    pub fn is_array(&self) -> bool {
+        matches!(self.node(), Node::Array { .. })
+    }
+
+    pub fn is_object(&self) -> bool {
+        matches!(self.node(), Node::Object { .. })
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self.node() {
+            Node::Bool(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    fn number(&self) -> Option<&'a str> {
+        match self.node() {
+            Node::Number(start, end) => Some(self.input(start, end)),
+            _ => None,
+        }
+    }
+
+    pub fn as_u64(&self) -> Option<u64> {
+        self.number()?.parse().ok()
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        self.number()?.parse().ok()
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        self.number()?.parse().ok()
+    }
+
+    pub fn as_str(&self) -> Option<&'a str> {
+        match self.node() {
+            Node::Raw(start, end) => Some(self.input(start, end)),
+            Node::Decoded(start, end) => Some(self.text(start, end)),
+            _ => None,
+        }
+    }
+
+    /// Elements of an array or entries of an object, 0 for anything else.
+    pub fn len(&self) -> usize {
+        match self.node() {
+            Node::Array { len, .. } | Node::Object { len, .. } => len,
+            _ => 0,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Elements of an array, nothing for other values.
+    pub fn members(&self) -> impl Iterator<Item = ValueRef<'a>> + 'a {
+        let this = *self;
+        let (len, mut next) = match self.node() {
+            Node::Array { len, .. } => (len, self.index + 1),
+            _ => (0, self.index + 1),
+        };
+        (0..len).map(move |_| {
+            let member = this.at(next);
+            next = member.end();
+            member
+        })
+    }
+
+    /// Entries of an object, nothing for other values.
+    pub fn entries(&self) -> impl Iterator<Item = (&'a str, ValueRef<'a>)> + 'a {
+        let this = *self;
+        let (len, mut next) = match self.node() {
+            Node::Object { len, .. } => (len, self.index + 1),
+            _ => (0, self.index + 1),
+        };
+        (0..len).map(move |_| {
+            let key = this.at(next);
+            let value = this.at(next + 1);
+            next = value.end();
+            (key.as_str().unwrap_or_default(), value)
+        })
+    }
+
+    /// Element `index` of an array.
+    pub fn get(&self, index: usize) -> Option<ValueRef<'a>> {
+        self.members().nth(index)
+    }
+
+    /// Value of `key` in an object, the last one when the key repeats.
+    pub fn get_key(&self, key: &str) -> Option<ValueRef<'a>> {
+        self.entries()
+            .filter(|(name, _)| *name == key)
+            .last()
+            .map(|(_, value)| value)
+    }
+
+    /// Append the value as compact JSON. Numbers and strings without escapes are copied from
+    /// the frame as they were.
+    pub fn write_to(&self, out: &mut String) {
+        match self.node() {
+            Node::Null => out.push_str("null"),
+            Node::Bool(value) => out.push_str(if value { "true" } else { "false" }),
+            Node::Number(start, end) => out.push_str(self.input(start, end)),
+            Node::Raw(start, end) => {
+                out.push('"');
+                out.push_str(self.input(start, end));
+                out.push('"');
+            }
+            Node::Decoded(start, end) => write_escaped(self.text(start, end), out),
+            Node::Array { .. } => {
+                out.push('[');
+                for (position, member) in self.members().enumerate() {
+                    if position > 0 {
+                        out.push(',');
+                    }
+                    member.write_to(out);
+                }
+                out.push(']');
+            }
+            Node::Object { .. } => {
+                out.push('{');
+                for (position, (key, value)) in self.entries().enumerate() {
+                    if position > 0 {
+                        out.push(',');
+                    }
+                    write_escaped(key, out);
+                    out.push(':');
+                    value.write_to(out);
+                }
+                out.push('}');
+            }
+        }
+    }
+
+    /// An owned copy of the value.
+    pub fn to_json(&self) -> JsonValue {
+        match self.node() {
+            Node::Null => JsonValue::Null,
+            Node::Bool(value) => value.into(),
+            Node::Number(..) => match (self.as_u64(), self.as_i64()) {
+                (Some(value), _) => value.into(),
+                (None, Some(value)) => value.into(),
+                _ => self.as_f64().unwrap_or(f64::NAN).into(),
+            },
+            Node::Raw(..) | Node::Decoded(..) => self.as_str().unwrap_or_default().into(),
+            Node::Array { .. } => {
+                JsonValue::Array(self.members().map(|member| member.to_json()).collect())
+            }
+            Node::Object { .. } => {
+                let mut object = JsonValue::new_object();
+                for (key, value) in self.entries() {
+                    object[key] = value.to_json();
+                }
+                object
+            }
+        }
+    }
+}
+
+fn write_escaped(text: &str, out: &mut String) {
+    out.push('"');
+    for character in text.chars() {
+        match character {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            '\u{8}' => out.push_str("\\b"),
+            '\u{c}' => out.push_str("\\f"),
+            control if (control as u32) < 0x20 => {
+                out.push_str(&format!("\\u{:04x}", control as u32));
+            }
+            other => out.push(other),
+        }
+    }
+    out.push('"');
+}
@@ -0,0 +1,321 @@
+use crate::error::Error;
+use json::JsonValue;
+use FieldKind::{Dict, Id, Str, Uri, U8};
+
+/// Type of one positional element of a message frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldKind {
+    /// The leading message code.
+    Code,
+    /// A WAMP ID in `[0, 2^53]`.
+    Id,
+    /// A message code referring to another message, e.g. `ERROR.REQUEST.Type`.
+    U8,
+    Uri,
+    Str,
+    Dict,
+    List,
+}
+
+/// One positional element of a message frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Field {
+    pub name: &'static str,
+    pub kind: FieldKind,
+    pub optional: bool,
+}
+
+const fn required(name: &'static str, kind: FieldKind) -> Field {
+    Field {
+        name,
+        kind,
+        optional: false,
+    }
+}
+
+const fn optional(name: &'static str, kind: FieldKind) -> Field {
+    Field {
+        name,
+        kind,
+        optional: true,
+    }
+}
+
+const CODE: Field = required("Code", FieldKind::Code);
+const ARGUMENTS: Field = optional("Arguments", FieldKind::List);
+const ARGUMENTS_KW: Field = optional("ArgumentsKw", FieldKind::Dict);
+
+/// Number of elements a message frame may have, counting the leading message code, and the
+/// layout of those elements.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Arity {
+    pub id: u8,
+    pub name: &'static str,
+    pub min: usize,
+    pub max: usize,
+    /// Elements in wire order, starting with the message code.
+    pub fields: &'static [Field],
+}
+
+impl Arity {
+    const fn new(id: u8, name: &'static str, fields: &'static [Field]) -> Self {
+        let mut min = 0;
+        while min < fields.len() && !fields[min].optional {
+            min += 1;
+        }
+        Arity {
+            id,
+            name,
+            min,
+            max: fields.len(),
+            fields,
+        }
+    }
+
+    pub fn accepts(&self, len: usize) -> bool {
+        self.min <= len && len <= self.max
+    }
+}
+
+/// Arity of every message defined by the spec, the optional trailing `Arguments|list` and
+/// `ArgumentsKw|dict` elements account for the difference between `min` and `max`.
+pub static ARITY_TABLE: [Arity; 24] = [
+    Arity::new(
+        1,
+        "HELLO",
+        &[CODE, required("Realm", Uri), required("Details", Dict)],
+    ),
+    Arity::new(
+        2,
+        "WELCOME",
+        &[CODE, required("Session", Id), required("Details", Dict)],
+    ),
+    Arity::new(
+        3,
+        "ABORT",
+        &[CODE, required("Details", Dict), required("Reason", Uri)],
+    ),
+    Arity::new(
+        4,
+        "CHALLENGE",
+        &[CODE, required("AuthMethod", Str), required("Extra", Dict)],
+    ),
+    Arity::new(
+        5,
+        "AUTHENTICATE",
+        &[CODE, required("Signature", Str), required("Extra", Dict)],
+    ),
+    Arity::new(
+        6,
+        "GOODBYE",
+        &[CODE, required("Details", Dict), required("Reason", Uri)],
+    ),
+    Arity::new(
+        8,
+        "ERROR",
+        &[
+            CODE,
+            required("REQUEST.Type", U8),
+            required("REQUEST.Request", Id),
+            required("Details", Dict),
+            required("Error", Uri),
+            ARGUMENTS,
+            ARGUMENTS_KW,
+        ],
+    ),
+    Arity::new(
+        16,
+        "PUBLISH",
+        &[
+            CODE,
+            required("Request", Id),
+            required("Options", Dict),
+            required("Topic", Uri),
+            ARGUMENTS,
+            ARGUMENTS_KW,
+        ],
+    ),
+    Arity::new(
+        17,
+        "PUBLISHED",
+        &[
+            CODE,
+            required("PUBLISH.Request", Id),
+            required("Publication", Id),
+        ],
+    ),
+    Arity::new(
+        32,
+        "SUBSCRIBE",
+        &[
+            CODE,
+            required("Request", Id),
+            required("Options", Dict),
+            required("Topic", Uri),
+        ],
+    ),
+    Arity::new(
+        33,
+        "SUBSCRIBED",
+        &[
+            CODE,
+            required("SUBSCRIBE.Request", Id),
+            required("Subscription", Id),
+        ],
+    ),
+    Arity::new(
+        34,
+        "UNSUBSCRIBE",
+        &[
+            CODE,
+            required("Request", Id),
+            required("SUBSCRIBED.Subscription", Id),
+        ],
+    ),
+    Arity::new(
+        35,
+        "UNSUBSCRIBED",
+        &[
+            CODE,
+            required("UNSUBSCRIBE.Request", Id),
+            optional("Details", Dict),
+        ],
+    ),
+    Arity::new(
+        36,
+        "EVENT",
+        &[
+            CODE,
+            required("SUBSCRIBED.Subscription", Id),
+            required("PUBLISHED.Publication", Id),
+            required("Details", Dict),
+            ARGUMENTS,
+            ARGUMENTS_KW,
+        ],
+    ),
+    Arity::new(
+        48,
+        "CALL",
+        &[
+            CODE,
+            required("Request", Id),
+            required("Options", Dict),
+            required("Procedure", Uri),
+            ARGUMENTS,
+            ARGUMENTS_KW,
+        ],
+    ),
+    Arity::new(
+        49,
+        "CANCEL",
+        &[
+            CODE,
+            required("CALL.Request", Id),
+            required("Options", Dict),
+        ],
+    ),
+    Arity::new(
+        50,
+        "RESULT",
+        &[
+            CODE,
+            required("CALL.Request", Id),
+            required("Details", Dict),
+            ARGUMENTS,
+            ARGUMENTS_KW,
+        ],
+    ),
+    Arity::new(
+        64,
+        "REGISTER",
+        &[
+            CODE,
+            required("Request", Id),
+            required("Options", Dict),
+            required("Procedure", Uri),
+        ],
+    ),
+    Arity::new(
+        65,
+        "REGISTERED",
+        &[
+            CODE,
+            required("REGISTER.Request", Id),
+            required("Registration", Id),
+        ],
+    ),
+    Arity::new(
+        66,
+        "UNREGISTER",
+        &[
+            CODE,
+            required("Request", Id),
+            required("REGISTERED.Registration", Id),
+        ],
+    ),
+    Arity::new(
+        67,
+        "UNREGISTERED",
+        &[
+            CODE,
+            required("UNREGISTER.Request", Id),
+            optional("Details", Dict),
+        ],
+    ),
+    Arity::new(
+        68,
+        "INVOCATION",
+        &[
+            CODE,
+            required("Request", Id),
+            required("REGISTERED.Registration", Id),
+            required("Details", Dict),
+            ARGUMENTS,
+            ARGUMENTS_KW,
+        ],
+    ),
+    Arity::new(
+        69,
+        "INTERRUPT",
+        &[
+            CODE,
+            required("INVOCATION.Request", Id),
+            required("Options", Dict),
+        ],
+    ),
+    Arity::new(
+        70,
+        "YIELD",
+        &[
+            CODE,
+            required("INVOCATION.Request", Id),
+            required("Options", Dict),
+            ARGUMENTS,
+            ARGUMENTS_KW,
+        ],
+    ),
+];
+
+/// Look up the arity of a message code, `None` for extension messages.
+/// # Examples
+/// ```
+/// use wamp_helpers::arity::arity;
+/// let call = arity(48).unwrap();
+/// assert_eq!((call.min, call.max), (4, 6));
+/// assert!(arity(200).is_none());
+/// ```
+pub fn arity(id: u8) -> Option<&'static Arity> {
+    ARITY_TABLE.iter().find(|arity| arity.id == id)
+}
+
+/// Reject frames carrying more elements than their message type allows. Frames that are too
+/// short are left to the field validation, which reports the missing element precisely.
+pub fn check_trailing_elements(data: &JsonValue) -> Result<(), Error> {
+    let len = data.len();
+    if let Some(arity) = data[0].as_u8().and_then(arity) {
+        if len > arity.max {
+            return Err(Error::TooManyElements { id: arity.id, len });
+        }
+    }
+    Ok(())
+}
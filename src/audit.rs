@@ -0,0 +1,231 @@
+use crate::arity::arity;
+use crate::correlation::Direction;
+use crate::error::Error;
+use crate::messages::WampId;
+use crate::nonce::to_hex;
+use crate::validator::{Violation, ViolationKind};
+use json::JsonValue;
+use sha2::{Digest, Sha256};
+
+/// Placeholder written in place of redacted elements.
+pub const REDACTED: &str = "<redacted>";
+
+/// How much of a rejected frame ends up in an [`AuditRecord`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Redaction {
+    /// The frame as received.
+    None,
+    /// Arguments, keyword arguments and AUTHENTICATE signatures are replaced by
+    /// [`REDACTED`], as is anything that is not valid JSON.
+    #[default]
+    Payload,
+    /// Only the hash and length of the frame are kept.
+    Full,
+}
+
+/// Evidence of one rejected frame.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuditRecord {
+    pub session: Option<WampId>,
+    pub direction: Direction,
+    /// Stable reason code, e.g. `too_many_elements` or `bad_sequencing`.
+    pub reason: &'static str,
+    /// Human readable explanation, never containing payload.
+    pub detail: String,
+    /// Lowercase hex SHA-256 of the frame bytes.
+    pub sha256: String,
+    pub len: usize,
+    /// The frame after redaction, `None` with [`Redaction::Full`].
+    pub frame: Option<String>,
+}
+
+/// Reason code of a parse error.
+pub fn reason_code(error: &Error) -> &'static str {
+    match error {
+        Error::DefaultImplementationError(_) => "default_implementation",
+        Error::JsonError(_) => "invalid_json",
+        Error::InvalidId => "invalid_message_code",
+        Error::ExtensionMessage => "extension_message",
+        Error::NonMatchingMessageId { .. } => "non_matching_message_code",
+        Error::InvalidRequestType { .. } => "invalid_request_type",
+        Error::InvalidJsonU8 { .. }
+        | Error::InvalidJsonDict { .. }
+        | Error::InvalidJsonArray { .. }
+        | Error::InvalidJsonU64 { .. }
+        | Error::InvalidJsonStr { .. } => "invalid_element_type",
+        Error::NonIntegerId { .. } | Error::NegativeId { .. } | Error::IdOutOfRange { .. } => {
+            "invalid_id"
+        }
+        Error::DuplicateKey { .. } => "duplicate_key",
+        Error::ReservedKey { .. } => "reserved_key",
+        Error::TooManyElements { .. } => "too_many_elements",
+        Error::InvalidChunk { .. } => "invalid_chunk",
+        Error::InvalidUriComponent { .. } | Error::UriCollision { .. } => "invalid_uri",
+        Error::InvalidErrorUri { .. } => "invalid_error_uri",
+        Error::DictionaryTooLarge { .. } | Error::ValueTooLarge { .. } => "dictionary_too_large",
+        Error::PayloadTooLarge { .. } => "payload_too_large",
+        Error::TransportClosed => "transport_closed",
+        Error::InvalidHandshake { .. } | Error::InvalidProxyHeader { .. } => "invalid_handshake",
+        Error::Handshake { .. } => "handshake_refused",
+        Error::UnsupportedSerializer { .. } => "unsupported_serializer",
+        Error::InvalidBatch { .. } => "invalid_batch",
+        Error::InvalidConfig { .. } => "invalid_config",
+        Error::MessageNotAllowed { .. } => "message_not_allowed",
+        Error::SpoofedIdentity { .. } => "spoofed_identity",
+        Error::ForwardingLoop { .. } => "forwarding_loop",
+        Error::FeatureNotNegotiated { .. } => "feature_not_negotiated",
+        Error::InvalidFrame { .. } => "invalid_frame",
+        Error::ReplayDiverged { .. } => "replay_diverged",
+        Error::Codec(_) => "codec",
+        Error::Transport(_) => "transport",
+        Error::Io(_) => "io",
+    }
+}
+
+/// Reason code of a protocol violation.
+pub fn violation_code(kind: ViolationKind) -> &'static str {
+    match kind {
+        ViolationKind::WrongDirection => "wrong_direction",
+        ViolationKind::BadSequencing => "bad_sequencing",
+        ViolationKind::InvalidId => "invalid_id",
+        ViolationKind::MalformedUri => "malformed_uri",
+        ViolationKind::DuplicateRequestId => "duplicate_request_id",
+    }
+}
+
+/// Apply `redaction` to a frame.
+/// ```
+/// use wamp_helpers::audit::{redact, Redaction};
+///
+/// let frame = br#"[48, 1, {}, "com.example.login", ["alice", "hunter2"]]"#;
+/// assert_eq!(
+///     redact(frame, Redaction::Payload).unwrap(),
+///     r#"[48,1,{},"com.example.login","<redacted>"]"#
+/// );
+/// assert_eq!(redact(b"[48, 1, {", Redaction::Payload).unwrap(), "<redacted>");
+/// assert_eq!(redact(frame, Redaction::Full), None);
+/// ```
+pub fn redact(frame: &[u8], redaction: Redaction) -> Option<String> {
+    match redaction {
+        Redaction::None => Some(String::from_utf8_lossy(frame).into_owned()),
+        Redaction::Full => None,
+        Redaction::Payload => {
+            let parsed = std::str::from_utf8(frame)
+                .ok()
+                .and_then(|text| json::parse(text).ok());
+            let Some(mut data) = parsed.filter(JsonValue::is_array) else {
+                return Some(REDACTED.to_string());
+            };
+            // Without a layout nothing is known about the elements, keep only the code.
+            let Some(layout) = data[0].as_u8().and_then(arity) else {
+                let code = data[0].take();
+                return Some(json::array![code, REDACTED].dump());
+            };
+            for (index, field) in layout.fields.iter().enumerate() {
+                let secret = matches!(field.name, "Arguments" | "ArgumentsKw" | "Signature");
+                if secret && index < data.len() {
+                    data[index] = REDACTED.into();
+                }
+            }
+            for index in layout.fields.len()..data.len() {
+                data[index] = REDACTED.into();
+            }
+            Some(data.dump())
+        }
+    }
+}
+
+/// Captures every rejected or violating frame for an audit trail, without leaking payloads.
+///
+/// Records go to the hook given to [`new`](Auditor::new), e.g. a logger or a channel.
+/// # Examples
+/// ```
+/// use std::sync::mpsc::channel;
+/// use wamp_helpers::audit::{Auditor, Redaction};
+/// use wamp_helpers::correlation::Direction;
+/// use wamp_helpers::messages::Message;
+/// use wamp_helpers::parse::ParseOptions;
+///
+/// let (sender, records) = channel();
+/// let mut auditor = Auditor::new(move |record| sender.send(record).unwrap());
+///
+/// let frame = r#"[16, 1, {}, "com.example.secret", ["token"], {}, "surplus"]"#;
+/// let error = Message::parse_message_with(frame, &ParseOptions::strict()).unwrap_err();
+/// auditor.reject(Some(9129137332), Direction::Inbound, frame.as_bytes(), &error);
+///
+/// let record = records.try_recv().unwrap();
+/// assert_eq!(record.reason, "too_many_elements");
+/// assert_eq!(record.sha256.len(), 64);
+/// assert!(!record.frame.unwrap().contains("token"));
+/// ```
+pub struct Auditor {
+    redaction: Redaction,
+    hook: Box<dyn FnMut(AuditRecord) + Send>,
+}
+
+impl std::fmt::Debug for Auditor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Auditor")
+            .field("redaction", &self.redaction)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Auditor {
+    /// Payloads are redacted unless configured otherwise.
+    pub fn new(hook: impl FnMut(AuditRecord) + Send + 'static) -> Self {
+        Auditor {
+            redaction: Redaction::default(),
+            hook: Box::new(hook),
+        }
+    }
+
+    pub fn redaction(mut self, redaction: Redaction) -> Self {
+        self.redaction = redaction;
+        self
+    }
+
+    fn record(
+        &mut self,
+        session: Option<WampId>,
+        direction: Direction,
+        frame: &[u8],
+        reason: &'static str,
+        detail: String,
+    ) {
+        let record = AuditRecord {
+            session,
+            direction,
+            reason,
+            detail,
+            sha256: to_hex(&Sha256::digest(frame)),
+            len: frame.len(),
+            frame: redact(frame, self.redaction),
+        };
+        (self.hook)(record);
+    }
+
+    /// A frame that failed to parse or was refused with `error`.
+    pub fn reject(
+        &mut self,
+        session: Option<WampId>,
+        direction: Direction,
+        frame: &[u8],
+        error: &Error,
+    ) {
+        // Error values may embed the offending JSON, only the reason code is kept.
+        let reason = reason_code(error);
+        self.record(session, direction, frame, reason, reason.replace('_', " "));
+    }
+
+    /// A frame the [`Validator`](crate::validator::Validator) reported.
+    pub fn violation(&mut self, session: Option<WampId>, frame: &[u8], violation: &Violation) {
+        self.record(
+            session,
+            violation.direction,
+            frame,
+            violation_code(violation.kind),
+            violation.detail.clone(),
+        );
+    }
+}
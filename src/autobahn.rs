@@ -0,0 +1,212 @@
+use crate::error::Error;
+use crate::messages::Message;
+use crate::parse::ParseOptions;
+use json::JsonValue;
+use std::time::{Duration, Instant};
+
+/// What a conforming parser does with the frame of a case.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Expectation {
+    /// The frame parses, into a message of the given type when one is listed.
+    Accept {
+        message_type: Option<u8>,
+    },
+    Reject,
+}
+
+/// One test case in the JSON layout of the autobahn|testsuite case files:
+/// `{"id": "1.2.3", "description": "...", "frame": [...], "expect": "accept", "type": 1}`.
+///
+/// `frame` is either the message itself or, for frames that are not valid JSON, a string with
+/// the raw text. `type` is optional and only meaningful for accepted frames.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TestCase {
+    pub id: String,
+    pub description: String,
+    pub frame: String,
+    pub expectation: Expectation,
+}
+
+fn invalid(reason: impl Into<String>) -> Error {
+    Error::InvalidConfig {
+        reason: reason.into(),
+    }
+}
+
+impl TestCase {
+    pub fn from_json(case: &JsonValue) -> Result<Self, Error> {
+        let Some(id) = case["id"].as_str() else {
+            return Err(invalid("case without id"));
+        };
+        let frame = match &case["frame"] {
+            JsonValue::Null => return Err(invalid(format!("case {id} has no frame"))),
+            frame => frame.as_str().map(str::to_string).unwrap_or(frame.dump()),
+        };
+        let expectation = match case["expect"].as_str() {
+            Some("accept") => Expectation::Accept {
+                message_type: case["type"].as_u8(),
+            },
+            Some("reject") => Expectation::Reject,
+            _ => {
+                return Err(invalid(format!(
+                    "case {id} expects neither accept nor reject"
+                )))
+            }
+        };
+        Ok(TestCase {
+            id: id.to_string(),
+            description: case["description"].as_str().unwrap_or_default().to_string(),
+            frame,
+            expectation,
+        })
+    }
+
+    /// The case in the layout [`from_json`](TestCase::from_json) reads, frames that are valid
+    /// JSON are embedded as such.
+    pub fn to_json(&self) -> JsonValue {
+        let mut case = json::object! {
+            id: self.id.as_str(),
+            description: self.description.as_str(),
+        };
+        case["frame"] = json::parse(&self.frame).unwrap_or_else(|_| self.frame.as_str().into());
+        match self.expectation {
+            Expectation::Accept { message_type } => {
+                case["expect"] = "accept".into();
+                if let Some(message_type) = message_type {
+                    case["type"] = message_type.into();
+                }
+            }
+            Expectation::Reject => case["expect"] = "reject".into(),
+        }
+        case
+    }
+
+    /// Parse the frame and compare the outcome with the expectation.
+    pub fn run(&self, options: &ParseOptions) -> CaseResult {
+        let started = Instant::now();
+        let parsed = Message::parse_message_with(&self.frame, options);
+        let duration = started.elapsed();
+        let (passed, observed) = match (&parsed, self.expectation) {
+            (Ok(message), Expectation::Accept { message_type }) => (
+                message_type.is_none_or(|expected| expected == message.message_id()),
+                format!("accepted as type {}", message.message_id()),
+            ),
+            (Ok(message), Expectation::Reject) => {
+                (false, format!("accepted as type {}", message.message_id()))
+            }
+            (Err(error), Expectation::Accept { .. }) => (false, format!("rejected: {error:?}")),
+            (Err(error), Expectation::Reject) => (true, format!("rejected: {error:?}")),
+        };
+        CaseResult {
+            id: self.id.clone(),
+            behavior: if passed {
+                Behavior::Ok
+            } else {
+                Behavior::Failed
+            },
+            duration,
+            observed,
+        }
+    }
+}
+
+/// Read a case file, a JSON list of cases.
+/// # Examples
+/// ```
+/// use wamp_helpers::autobahn::{load_cases, Behavior, Report};
+/// use wamp_helpers::parse::ParseOptions;
+///
+/// let cases = load_cases(r#"[
+///     {"id": "1.1.1", "description": "HELLO", "frame": [1, "realm1", {}], "expect": "accept", "type": 1},
+///     {"id": "1.1.2", "description": "truncated", "frame": "[1, \"realm1\"", "expect": "reject"}
+/// ]"#).unwrap();
+///
+/// let mut report = Report::new("wamp-helpers");
+/// for case in &cases {
+///     report.push(case.run(&ParseOptions::default()));
+/// }
+/// assert!(report.results().iter().all(|result| result.behavior == Behavior::Ok));
+/// assert_eq!(report.to_json()["wamp-helpers"]["1.1.2"]["behavior"], "OK");
+/// ```
+pub fn load_cases(text: &str) -> Result<Vec<TestCase>, Error> {
+    let cases = json::parse(text).map_err(Error::JsonError)?;
+    if !cases.is_array() {
+        return Err(Error::InvalidJsonArray { offense: cases });
+    }
+    cases.members().map(TestCase::from_json).collect()
+}
+
+/// Write cases in the layout [`load_cases`] reads.
+pub fn dump_cases(cases: &[TestCase]) -> String {
+    JsonValue::Array(cases.iter().map(TestCase::to_json).collect()).pretty(2)
+}
+
+/// Verdict on a case, named like in autobahn|testsuite reports.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Behavior {
+    Ok,
+    Failed,
+}
+
+impl Behavior {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Behavior::Ok => "OK",
+            Behavior::Failed => "FAILED",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CaseResult {
+    pub id: String,
+    pub behavior: Behavior,
+    pub duration: Duration,
+    /// What the parser did, for the report.
+    pub observed: String,
+}
+
+/// Results of one agent, exported like the `index.json` of autobahn|testsuite reports.
+#[derive(Debug, Clone)]
+pub struct Report {
+    agent: String,
+    results: Vec<CaseResult>,
+}
+
+impl Report {
+    pub fn new(agent: impl Into<String>) -> Self {
+        Report {
+            agent: agent.into(),
+            results: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, result: CaseResult) {
+        self.results.push(result);
+    }
+
+    pub fn results(&self) -> &[CaseResult] {
+        &self.results
+    }
+
+    pub fn failed(&self) -> impl Iterator<Item = &CaseResult> {
+        self.results
+            .iter()
+            .filter(|result| result.behavior == Behavior::Failed)
+    }
+
+    /// `{"<agent>": {"<case id>": {"behavior": "OK", "duration": <ms>, "result": "..."}}}`.
+    pub fn to_json(&self) -> JsonValue {
+        let mut cases = json::object! {};
+        for result in &self.results {
+            cases[result.id.as_str()] = json::object! {
+                behavior: result.behavior.as_str(),
+                duration: result.duration.as_millis() as u64,
+                result: result.observed.as_str(),
+            };
+        }
+        let mut report = json::object! {};
+        report[self.agent.as_str()] = cases;
+        report
+    }
+}
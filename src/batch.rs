@@ -0,0 +1,286 @@
+use crate::acceptor::Serializer;
+use crate::error::Error;
+use crate::pool::BufferPool;
+use crate::transport::{PeerInfo, Transport};
+use std::collections::VecDeque;
+
+/// Byte terminating every message of a batched JSON frame.
+pub const RECORD_SEPARATOR: u8 = 0x1e;
+
+/// Packs messages into frames following the batched subprotocol convention: JSON messages
+/// each end with [`RECORD_SEPARATOR`], binary messages are each prefixed with their length as
+/// a 32 bit big endian integer.
+/// # Examples
+/// ```
+/// use wamp_helpers::acceptor::Serializer;
+/// use wamp_helpers::batch::{split_batch, Batcher};
+///
+/// let mut batcher = Batcher::new(Serializer::Json, 16);
+/// assert!(batcher.push(b"[36, 1, 2, {}]").is_none());
+/// // The next message does not fit anymore, the previous batch is handed out.
+/// let frame = batcher.push(b"[36, 1, 3, {}]").unwrap();
+/// assert_eq!(frame, b"[36, 1, 2, {}]\x1e");
+/// let frame = batcher.flush().unwrap();
+///
+/// assert_eq!(split_batch(&frame, Serializer::Json).unwrap(), [b"[36, 1, 3, {}]"]);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Batcher {
+    serializer: Serializer,
+    max_frame_len: usize,
+    buffer: Vec<u8>,
+    messages: usize,
+    pool: Option<BufferPool>,
+}
+
+impl Batcher {
+    /// Batch messages of `serializer` into frames of at most `max_frame_len` bytes, a single
+    /// larger message still gets a frame of its own.
+    pub fn new(serializer: Serializer, max_frame_len: usize) -> Self {
+        Batcher {
+            serializer,
+            max_frame_len,
+            buffer: Vec::new(),
+            messages: 0,
+            pool: None,
+        }
+    }
+
+    /// Start every frame in a buffer from `pool`. The batcher cannot tell when a frame was
+    /// written, give it back with [`BufferPool::give_bytes`] then, or to whoever parses it
+    /// when it stays in the process.
+    pub fn pool(mut self, pool: BufferPool) -> Self {
+        self.buffer = pool.take_bytes();
+        self.pool = Some(pool);
+        self
+    }
+
+    /// Add a message, returns the frame built so far when the message does not fit into it.
+    pub fn push(&mut self, message: &[u8]) -> Option<Vec<u8>> {
+        let full = self.messages > 0 && self.buffer.len() + message.len() + 4 > self.max_frame_len;
+        let frame = if full { self.flush() } else { None };
+        match self.serializer {
+            Serializer::Json => {
+                self.buffer.extend_from_slice(message);
+                self.buffer.push(RECORD_SEPARATOR);
+            }
+            Serializer::MsgPack | Serializer::Cbor => {
+                self.buffer
+                    .extend_from_slice(&(message.len() as u32).to_be_bytes());
+                self.buffer.extend_from_slice(message);
+            }
+        }
+        self.messages += 1;
+        frame
+    }
+
+    /// Take the pending frame, `None` when no message is waiting.
+    pub fn flush(&mut self) -> Option<Vec<u8>> {
+        if self.messages == 0 {
+            return None;
+        }
+        self.messages = 0;
+        let next = match &self.pool {
+            Some(pool) => pool.take_bytes(),
+            None => Vec::new(),
+        };
+        Some(std::mem::replace(&mut self.buffer, next))
+    }
+
+    /// Messages waiting for the next frame.
+    pub fn len(&self) -> usize {
+        self.messages
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.messages == 0
+    }
+}
+
+/// Split a batched frame into its messages.
+pub fn split_batch(frame: &[u8], serializer: Serializer) -> Result<Vec<&[u8]>, Error> {
+    match serializer {
+        Serializer::Json => {
+            let Some(body) = frame.strip_suffix(&[RECORD_SEPARATOR]) else {
+                return Err(Error::InvalidBatch {
+                    offset: frame.len(),
+                });
+            };
+            Ok(body.split(|byte| *byte == RECORD_SEPARATOR).collect())
+        }
+        Serializer::MsgPack | Serializer::Cbor => {
+            let mut messages = Vec::new();
+            let mut offset = 0;
+            while offset < frame.len() {
+                let Some(&[a, b, c, d]) = frame.get(offset..offset + 4) else {
+                    return Err(Error::InvalidBatch { offset });
+                };
+                let len = u32::from_be_bytes([a, b, c, d]) as usize;
+                let Some(message) = frame.get(offset + 4..offset + 4 + len) else {
+                    return Err(Error::InvalidBatch { offset });
+                };
+                messages.push(message);
+                offset += 4 + len;
+            }
+            Ok(messages)
+        }
+    }
+}
+
+/// Wraps a transport negotiated on a batched subprotocol. Sent messages are coalesced until
+/// [`flush`](Batched::flush) or until a frame is full, received frames are split back into
+/// messages.
+/// # Examples
+/// ```
+/// use std::future::Future;
+/// use std::task::{Context, Poll, Waker};
+/// use wamp_helpers::acceptor::Serializer;
+/// use wamp_helpers::batch::Batched;
+/// use wamp_helpers::memory::MemoryTransport;
+/// use wamp_helpers::transport::Transport;
+///
+/// fn now<F: Future>(future: F) -> F::Output {
+///     let mut future = std::pin::pin!(future);
+///     match future.as_mut().poll(&mut Context::from_waker(Waker::noop())) {
+///         Poll::Ready(output) => output,
+///         Poll::Pending => panic!("future is not ready"),
+///     }
+/// }
+///
+/// let (client, router) = MemoryTransport::pair();
+/// let mut client = Batched::new(client, Serializer::Json, 1 << 16);
+/// let mut router = Batched::new(router, Serializer::Json, 1 << 16);
+/// now(client.send(b"[36, 1, 2, {}]".to_vec())).unwrap();
+/// now(client.send(b"[36, 1, 3, {}]".to_vec())).unwrap();
+/// now(client.flush()).unwrap();
+///
+/// assert_eq!(now(router.next()).unwrap().unwrap(), b"[36, 1, 2, {}]");
+/// assert_eq!(now(router.next()).unwrap().unwrap(), b"[36, 1, 3, {}]");
+/// ```
+///
+/// With a [`BufferPool`] shared by both ends, as with an in-process transport, the frames and
+/// messages keep cycling through the pool:
+/// ```
+/// # use std::future::Future;
+/// # use std::task::{Context, Poll, Waker};
+/// # fn now<F: Future>(future: F) -> F::Output {
+/// #     let mut future = std::pin::pin!(future);
+/// #     match future.as_mut().poll(&mut Context::from_waker(Waker::noop())) {
+/// #         Poll::Ready(output) => output,
+/// #         Poll::Pending => panic!("future is not ready"),
+/// #     }
+/// # }
+/// use wamp_helpers::acceptor::Serializer;
+/// use wamp_helpers::batch::Batched;
+/// use wamp_helpers::memory::MemoryTransport;
+/// use wamp_helpers::pool::BufferPool;
+/// use wamp_helpers::transport::Transport;
+///
+/// let pool = BufferPool::new();
+/// let (client, router) = MemoryTransport::pair();
+/// let mut client = Batched::new(client, Serializer::Json, 1 << 16).pool(pool.clone());
+/// let mut router = Batched::new(router, Serializer::Json, 1 << 16).pool(pool.clone());
+/// for _ in 0..3 {
+///     let mut message = pool.take_bytes();
+///     message.extend_from_slice(b"[36, 1, 2, {}]");
+///     now(client.send(message)).unwrap();
+///     now(client.flush()).unwrap();
+///     let received = now(router.next()).unwrap().unwrap();
+///     assert_eq!(received, b"[36, 1, 2, {}]");
+///     pool.give_bytes(received);
+/// }
+/// assert!(pool.stats().hits > pool.stats().misses);
+/// ```
+#[derive(Debug)]
+pub struct Batched<T> {
+    inner: T,
+    serializer: Serializer,
+    batcher: Batcher,
+    ready: VecDeque<Vec<u8>>,
+    pool: Option<BufferPool>,
+}
+
+impl<T: Transport + Send> Batched<T> {
+    pub fn new(inner: T, serializer: Serializer, max_frame_len: usize) -> Self {
+        Batched {
+            inner,
+            serializer,
+            batcher: Batcher::new(serializer, max_frame_len),
+            ready: VecDeque::new(),
+            pool: None,
+        }
+    }
+
+    /// Take frames and received messages from `pool`, and give it back the messages once
+    /// batched and the frames once split. Give received messages back once handled.
+    pub fn pool(mut self, pool: BufferPool) -> Self {
+        self.batcher = self.batcher.pool(pool.clone());
+        self.pool = Some(pool);
+        self
+    }
+
+    /// Send the messages batched so far.
+    pub async fn flush(&mut self) -> Result<(), Error> {
+        match self.batcher.flush() {
+            Some(frame) => self.inner.send(frame).await,
+            None => Ok(()),
+        }
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<T: Transport + Send> Transport for Batched<T> {
+    async fn send(&mut self, frame: Vec<u8>) -> Result<(), Error> {
+        let full = self.batcher.push(&frame);
+        if let Some(pool) = &self.pool {
+            pool.give_bytes(frame);
+        }
+        match full {
+            Some(full) => self.inner.send(full).await,
+            None => Ok(()),
+        }
+    }
+
+    async fn next(&mut self) -> Option<Result<Vec<u8>, Error>> {
+        loop {
+            if let Some(message) = self.ready.pop_front() {
+                return Some(Ok(message));
+            }
+            match self.inner.next().await? {
+                Ok(frame) => {
+                    let split = split_batch(&frame, self.serializer).map(|messages| {
+                        let copy = |message: &[u8]| match &self.pool {
+                            Some(pool) => {
+                                let mut buffer = pool.take_bytes();
+                                buffer.extend_from_slice(message);
+                                buffer
+                            }
+                            None => message.to_vec(),
+                        };
+                        self.ready.extend(messages.into_iter().map(copy));
+                    });
+                    if let Some(pool) = &self.pool {
+                        pool.give_bytes(frame);
+                    }
+                    if let Err(error) = split {
+                        return Some(Err(error));
+                    }
+                }
+                Err(error) => return Some(Err(error)),
+            }
+        }
+    }
+
+    /// Flushes pending messages before closing.
+    async fn close(&mut self, reason: &str) -> Result<(), Error> {
+        self.flush().await?;
+        self.inner.close(reason).await
+    }
+
+    fn peer(&self) -> Option<&PeerInfo> {
+        self.inner.peer()
+    }
+}
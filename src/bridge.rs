@@ -0,0 +1,4 @@
+/// Caller and publisher webhooks, exposing procedures and topics over HTTP.
+pub mod http;
+/// Mapping between MQTT and WAMP publish/subscribe.
+pub mod mqtt;
@@ -0,0 +1,190 @@
+use crate::bus::NOT_AUTHORIZED;
+use crate::client::{CANCELED, TIMEOUT, UNAVAILABLE};
+use crate::dealer::NO_SUCH_PROCEDURE;
+use crate::messages::{
+    validate_args, validate_kwargs, Args, Call, ErrorMessage, Kwargs, Publish, Published, Uri,
+    WampId, WampResult,
+};
+use crate::meta::INVALID_ARGUMENT;
+use crate::uri::is_valid_uri;
+use crate::value::WampValue;
+use json::JsonValue;
+
+/// Media type of every request and response body.
+pub const CONTENT_TYPE: &str = "application/json";
+
+/// Status, content type and body of the HTTP response to send.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HttpResponse {
+    pub status: u16,
+    pub content_type: &'static str,
+    pub body: String,
+}
+
+impl HttpResponse {
+    fn json(status: u16, body: JsonValue) -> Self {
+        HttpResponse {
+            status,
+            content_type: CONTENT_TYPE,
+            body: body.dump(),
+        }
+    }
+
+    /// 400 response refusing a request body, in the shape of a WAMP error.
+    pub fn bad_request(message: &str) -> Self {
+        HttpResponse::json(
+            400,
+            json::object! { error: INVALID_ARGUMENT, args: [message] },
+        )
+    }
+}
+
+fn payload(body: &mut JsonValue) -> Result<(Option<Args>, Option<Kwargs>), HttpResponse> {
+    let args = validate_args(body.remove("args"))
+        .map_err(|_| HttpResponse::bad_request("args must be a list"))?;
+    let kwargs = validate_kwargs(body.remove("kwargs"))
+        .map_err(|_| HttpResponse::bad_request("kwargs must be a dict"))?;
+    Ok((args, kwargs))
+}
+
+fn request_body(body: &str, uri_key: &str) -> Result<(JsonValue, Uri, JsonValue), HttpResponse> {
+    let mut body =
+        json::parse(body).map_err(|_| HttpResponse::bad_request("body is not valid JSON"))?;
+    if !body.is_object() {
+        return Err(HttpResponse::bad_request("body must be an object"));
+    }
+    let uri = match body[uri_key].as_str() {
+        Some(uri) if is_valid_uri(uri, false) => uri.to_string(),
+        _ => {
+            return Err(HttpResponse::bad_request(&format!(
+                "{uri_key} must be a valid URI"
+            )))
+        }
+    };
+    let options = match body.remove("options") {
+        JsonValue::Null => json::object! {},
+        options if options.is_object() => options,
+        _ => return Err(HttpResponse::bad_request("options must be a dict")),
+    };
+    Ok((body, uri.into(), options))
+}
+
+/// CALL for the body of a caller webhook request,
+/// `{"procedure": "...", "args": [...], "kwargs": {...}, "options": {...}}`.
+///
+/// Only `procedure` is required. A body that is not such an object is answered with the
+/// returned 400 response instead.
+/// # Examples
+/// ```
+/// use wamp_helpers::bridge::http;
+/// use wamp_helpers::messages::WampResult;
+///
+/// let call = http::call(r#"{"procedure": "com.example.add", "args": [1, 2]}"#, 7).unwrap();
+/// assert_eq!(call.procedure, "com.example.add");
+/// assert_eq!(call.args.unwrap().len(), 2);
+///
+/// let result: WampResult = "[50, 7, {}, [3]]".parse().unwrap();
+/// let response = http::result_response(&result);
+/// assert_eq!(response.status, 200);
+/// assert_eq!(response.body, r#"{"args":[3]}"#);
+///
+/// assert_eq!(http::call(r#"{"args": []}"#, 8).unwrap_err().status, 400);
+/// ```
+pub fn call(body: &str, request: WampId) -> Result<Call, HttpResponse> {
+    let (mut body, procedure, options) = request_body(body, "procedure")?;
+    let (args, kwargs) = payload(&mut body)?;
+    Ok(Call {
+        request,
+        options,
+        procedure,
+        args,
+        kwargs,
+    })
+}
+
+/// PUBLISH for the body of a publisher webhook request,
+/// `{"topic": "...", "args": [...], "kwargs": {...}, "options": {...}}`. Acknowledgment is
+/// always requested so [`published_response`] can report the publication id.
+/// # Examples
+/// ```
+/// use wamp_helpers::bridge::http;
+/// use wamp_helpers::messages::Published;
+///
+/// let publish = http::publish(r#"{"topic": "com.example.news", "args": ["hi"]}"#, 3).unwrap();
+/// assert_eq!(publish.options["acknowledge"], true);
+///
+/// let published = Published { request: 3, publication: 4711 };
+/// assert_eq!(http::published_response(&published).body, r#"{"id":4711}"#);
+/// ```
+pub fn publish(body: &str, request: WampId) -> Result<Publish, HttpResponse> {
+    let (mut body, topic, mut options) = request_body(body, "topic")?;
+    let (args, kwargs) = payload(&mut body)?;
+    options["acknowledge"] = true.into();
+    Ok(Publish {
+        request,
+        options,
+        topic,
+        args,
+        kwargs,
+    })
+}
+
+fn with_payload(mut body: JsonValue, args: &Option<Args>, kwargs: &Option<Kwargs>) -> JsonValue {
+    if let Some(args) = args {
+        body["args"] = WampValue::List(args.clone()).into();
+    }
+    if let Some(kwargs) = kwargs {
+        body["kwargs"] = WampValue::Dict(kwargs.clone()).into();
+    }
+    body
+}
+
+/// 200 response carrying the positional and keyword results of a call.
+pub fn result_response(result: &WampResult) -> HttpResponse {
+    HttpResponse::json(
+        200,
+        with_payload(json::object! {}, &result.args, &result.kwargs),
+    )
+}
+
+/// 200 response reporting the id of a publication.
+pub fn published_response(published: &Published) -> HttpResponse {
+    HttpResponse::json(200, json::object! { id: published.publication })
+}
+
+/// Status code for a WAMP error URI: 400 for invalid arguments, 403 when not authorized, 404
+/// for unknown procedures, 503 when no callee is available, 504 for timeouts and cancellation,
+/// 500 otherwise.
+pub fn status(error: &str) -> u16 {
+    match error {
+        INVALID_ARGUMENT | "wamp.error.invalid_uri" => 400,
+        NOT_AUTHORIZED | "wamp.error.authorization_failed" => 403,
+        NO_SUCH_PROCEDURE => 404,
+        UNAVAILABLE | "wamp.error.no_available_callee" => 503,
+        TIMEOUT | CANCELED => 504,
+        _ => 500,
+    }
+}
+
+/// Response to an ERROR answering the CALL or PUBLISH of a webhook request, the body is
+/// `{"error": "...", "args": [...], "kwargs": {...}}`.
+/// # Examples
+/// ```
+/// use wamp_helpers::bridge::http;
+/// use wamp_helpers::messages::ErrorMessage;
+///
+/// let error: ErrorMessage = r#"[8, 48, 7, {}, "wamp.error.no_such_procedure"]"#.parse().unwrap();
+/// let response = http::error_response(&error);
+/// assert_eq!(response.status, 404);
+/// assert_eq!(response.body, r#"{"error":"wamp.error.no_such_procedure"}"#);
+/// ```
+pub fn error_response(error: &ErrorMessage) -> HttpResponse {
+    HttpResponse::json(
+        status(&error.error),
+        with_payload(
+            json::object! { error: error.error.as_str() },
+            &error.args,
+            &error.kwargs,
+        ),
+    )
+}
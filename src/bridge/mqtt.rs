@@ -0,0 +1,258 @@
+use crate::error::Error;
+use crate::messages::{Event, Publish, Subscribe, Uri, WampId};
+use crate::uri::{is_valid_component, MatchPolicy};
+use crate::value::WampValue;
+
+/// MQTT delivery guarantee.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum QoS {
+    #[default]
+    AtMostOnce,
+    AtLeastOnce,
+    ExactlyOnce,
+}
+
+impl QoS {
+    pub fn from_u8(qos: u8) -> Option<Self> {
+        match qos {
+            0 => Some(QoS::AtMostOnce),
+            1 => Some(QoS::AtLeastOnce),
+            2 => Some(QoS::ExactlyOnce),
+            _ => None,
+        }
+    }
+
+    pub fn as_u8(self) -> u8 {
+        self as u8
+    }
+}
+
+/// An MQTT PUBLISH, as received from or sent to a device.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MqttPublish {
+    pub topic: String,
+    pub payload: Vec<u8>,
+    pub qos: QoS,
+    pub retain: bool,
+}
+
+/// One topic filter of an MQTT SUBSCRIBE.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MqttSubscribe {
+    pub filter: String,
+    pub qos: QoS,
+}
+
+/// How MQTT payloads travel as the single positional argument of WAMP messages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PayloadFormat {
+    /// Parsed as JSON, payloads that are not valid JSON are carried as bytes.
+    #[default]
+    Json,
+    /// UTF-8 text as a string, anything else as bytes.
+    Text,
+    /// Always bytes.
+    Binary,
+}
+
+/// Translates MQTT traffic into WAMP messages under a URI prefix and back.
+///
+/// Topic levels become URI components: `sensors/kitchen/temp` is published to
+/// `<prefix>.sensors.kitchen.temp`. Levels must be valid loose URI components, so MQTT topics
+/// with empty levels or levels containing `.`, `#` or whitespace are refused, as are `$` system
+/// topics. QoS 1 and 2 ask the broker for acknowledgment, the bridge answers the device's
+/// PUBACK or PUBREC once the PUBLISHED arrived. Retained messages set the `retain` option.
+/// # Examples
+/// ```
+/// use wamp_helpers::bridge::mqtt::{MqttBridge, MqttPublish, MqttSubscribe, QoS};
+/// use wamp_helpers::messages::Event;
+/// use wamp_helpers::value::WampValue;
+///
+/// let bridge = MqttBridge::new("com.example.devices");
+/// let publish = MqttPublish {
+///     topic: "sensors/kitchen/temp".to_string(),
+///     payload: br#"{"celsius": 21.5}"#.to_vec(),
+///     qos: QoS::AtLeastOnce,
+///     retain: false,
+/// };
+/// let publish = bridge.to_wamp_publish(&publish, 1).unwrap();
+/// assert_eq!(publish.topic, "com.example.devices.sensors.kitchen.temp");
+/// assert_eq!(publish.options["acknowledge"], true);
+/// assert!(matches!(
+///     &publish.args.unwrap()[0],
+///     WampValue::Dict(reading) if reading["celsius"] == WampValue::Float(21.5)
+/// ));
+///
+/// let filter = MqttSubscribe { filter: "sensors/+/temp".to_string(), qos: QoS::AtMostOnce };
+/// let subscribe = bridge.to_wamp_subscribe(&filter, 2).unwrap();
+/// assert_eq!(subscribe.topic, "com.example.devices.sensors..temp");
+/// assert_eq!(subscribe.options["match"], "wildcard");
+///
+/// let event: Event = r#"[36, 5, 6, {"topic": "com.example.devices.sensors.hall.temp"}, [18]]"#
+///     .parse()
+///     .unwrap();
+/// let delivered = bridge.to_mqtt_publish(&subscribe.topic, &event, QoS::AtMostOnce).unwrap();
+/// assert_eq!(delivered.topic, "sensors/hall/temp");
+/// assert_eq!(delivered.payload, b"18");
+/// ```
+#[derive(Debug, Clone)]
+pub struct MqttBridge {
+    prefix: Uri,
+    payload: PayloadFormat,
+}
+
+impl MqttBridge {
+    /// Bridge MQTT topics into the URI namespace below `prefix`, with JSON payloads.
+    pub fn new(prefix: impl Into<Uri>) -> Self {
+        MqttBridge {
+            prefix: prefix.into(),
+            payload: PayloadFormat::default(),
+        }
+    }
+
+    pub fn payload(mut self, payload: PayloadFormat) -> Self {
+        self.payload = payload;
+        self
+    }
+
+    /// The URI an MQTT topic is published to.
+    pub fn topic_to_uri(&self, topic: &str) -> Result<Uri, Error> {
+        let mut uri = self.prefix.clone();
+        for level in topic.split('/') {
+            if !is_valid_component(level, false) || level.starts_with('$') || level == "+" {
+                return Err(Error::InvalidUriComponent {
+                    component: level.to_string(),
+                });
+            }
+            uri.push('.');
+            uri.push_str(level);
+        }
+        Ok(uri)
+    }
+
+    /// The MQTT topic of a URI below the prefix.
+    pub fn uri_to_topic(&self, uri: &str) -> Option<String> {
+        let path = uri.strip_prefix(self.prefix.as_str())?.strip_prefix('.')?;
+        Some(path.replace('.', "/"))
+    }
+
+    /// The WAMP subscription pattern and match policy of an MQTT topic filter. `+` levels
+    /// become empty wildcard components, a trailing `#` a prefix match. Filters using both
+    /// cannot be expressed and are refused.
+    pub fn filter_to_pattern(&self, filter: &str) -> Result<(Uri, MatchPolicy), Error> {
+        let (filter, multi_level) = match filter.strip_suffix('#') {
+            Some("") => ("", true),
+            Some(rest) => match rest.strip_suffix('/') {
+                Some(rest) => (rest, true),
+                None => {
+                    return Err(Error::InvalidUriComponent {
+                        component: filter.to_string(),
+                    })
+                }
+            },
+            None => (filter, false),
+        };
+        let mut pattern = self.prefix.clone();
+        let mut single_level = false;
+        for level in filter.split('/').filter(|_| !filter.is_empty()) {
+            pattern.push('.');
+            if level == "+" {
+                single_level = true;
+            } else if is_valid_component(level, false) && !level.starts_with('$') {
+                pattern.push_str(level);
+            } else {
+                return Err(Error::InvalidUriComponent {
+                    component: level.to_string(),
+                });
+            }
+        }
+        match (single_level, multi_level) {
+            (true, true) => Err(Error::InvalidUriComponent {
+                component: "#".to_string(),
+            }),
+            (true, false) => Ok((pattern, MatchPolicy::Wildcard)),
+            (false, true) => {
+                pattern.push('.');
+                Ok((pattern, MatchPolicy::Prefix))
+            }
+            (false, false) => Ok((pattern, MatchPolicy::Exact)),
+        }
+    }
+
+    /// SUBSCRIBE for an MQTT topic filter, see [`filter_to_pattern`](Self::filter_to_pattern).
+    pub fn to_wamp_subscribe(
+        &self,
+        subscribe: &MqttSubscribe,
+        request: WampId,
+    ) -> Result<Subscribe, Error> {
+        let (topic, policy) = self.filter_to_pattern(&subscribe.filter)?;
+        let mut options = json::object! {};
+        if policy != MatchPolicy::Exact {
+            options["match"] = policy.as_str().into();
+        }
+        Ok(Subscribe {
+            request,
+            options,
+            topic,
+        })
+    }
+
+    /// PUBLISH carrying a device's MQTT PUBLISH into the realm.
+    pub fn to_wamp_publish(
+        &self,
+        publish: &MqttPublish,
+        request: WampId,
+    ) -> Result<Publish, Error> {
+        let mut options = json::object! {};
+        if publish.qos != QoS::AtMostOnce {
+            options["acknowledge"] = true.into();
+        }
+        if publish.retain {
+            options["retain"] = true.into();
+        }
+        Ok(Publish {
+            request,
+            options,
+            topic: self.topic_to_uri(&publish.topic)?,
+            args: Some(vec![self.decode(&publish.payload)]),
+            kwargs: None,
+        })
+    }
+
+    /// MQTT PUBLISH delivering an EVENT of a subscription to `topic` to a device, which was
+    /// granted `qos`. Pattern subscriptions take the concrete topic from the `topic` detail.
+    /// `None` when the topic lies outside the prefix.
+    pub fn to_mqtt_publish(&self, topic: &str, event: &Event, qos: QoS) -> Option<MqttPublish> {
+        let topic = event.details["topic"].as_str().unwrap_or(topic);
+        Some(MqttPublish {
+            topic: self.uri_to_topic(topic)?,
+            payload: self.encode(event.args.as_ref().and_then(|args| args.first())),
+            qos,
+            retain: event.details["retained"].as_bool().unwrap_or(false),
+        })
+    }
+
+    fn decode(&self, payload: &[u8]) -> WampValue {
+        let text = std::str::from_utf8(payload).ok();
+        match (self.payload, text) {
+            (PayloadFormat::Json, Some(text)) => match json::parse(text) {
+                Ok(value) => WampValue::from(value),
+                Err(_) => WampValue::Bytes(payload.to_vec()),
+            },
+            (PayloadFormat::Text, Some(text)) => WampValue::String(text.to_string()),
+            _ => WampValue::Bytes(payload.to_vec()),
+        }
+    }
+
+    fn encode(&self, value: Option<&WampValue>) -> Vec<u8> {
+        match (self.payload, value) {
+            (_, None) => Vec::new(),
+            (_, Some(WampValue::Bytes(bytes))) => bytes.clone(),
+            (PayloadFormat::Json, Some(value)) => {
+                json::JsonValue::from(value.clone()).dump().into_bytes()
+            }
+            (_, Some(WampValue::String(text))) => text.clone().into_bytes(),
+            (_, Some(value)) => json::JsonValue::from(value.clone()).dump().into_bytes(),
+        }
+    }
+}
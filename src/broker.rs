@@ -0,0 +1,374 @@
+use crate::messages::{Uri, WampId, MAX_ID};
+use crate::options::PublishOptions;
+use crate::uri::MatchPolicy;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeSet, HashMap};
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::RwLock;
+
+/// Subscriptions of one topic.
+#[derive(Debug, Clone, Default)]
+struct Topic {
+    subscription: WampId,
+    subscribers: BTreeSet<WampId>,
+}
+
+#[derive(Debug, Default)]
+struct Shard {
+    topics: HashMap<Uri, Topic>,
+    prefixes: HashMap<Uri, Topic>,
+    wildcards: HashMap<Uri, Topic>,
+    /// Reverse index from subscription id to topic or pattern.
+    subscriptions: HashMap<WampId, (MatchPolicy, Uri)>,
+}
+
+impl Shard {
+    fn topics(&self, policy: MatchPolicy) -> &HashMap<Uri, Topic> {
+        match policy {
+            MatchPolicy::Exact => &self.topics,
+            MatchPolicy::Prefix => &self.prefixes,
+            MatchPolicy::Wildcard => &self.wildcards,
+        }
+    }
+
+    fn topics_mut(&mut self, policy: MatchPolicy) -> &mut HashMap<Uri, Topic> {
+        match policy {
+            MatchPolicy::Exact => &mut self.topics,
+            MatchPolicy::Prefix => &mut self.prefixes,
+            MatchPolicy::Wildcard => &mut self.wildcards,
+        }
+    }
+}
+
+const POLICIES: [MatchPolicy; 3] = [
+    MatchPolicy::Exact,
+    MatchPolicy::Prefix,
+    MatchPolicy::Wildcard,
+];
+
+/// One subscription a publication would be delivered on, see
+/// [`SubscriptionStore::would_receive`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SubscriptionMatch {
+    pub subscription: WampId,
+    pub match_policy: MatchPolicy,
+    /// The subscribed topic or pattern.
+    pub topic: Uri,
+    /// Subscribers that would get the EVENT, in ascending order.
+    pub receivers: Vec<WampId>,
+    /// Subscribers left out by `exclude`, `eligible` or `exclude_me`, in ascending order.
+    pub excluded: Vec<WampId>,
+}
+
+/// Subscription store shared between threads, for exact topics and prefix and wildcard
+/// patterns.
+///
+/// Topics are spread over independently locked shards by hash, so publishes to different
+/// topics never contend and a publish only takes read locks. Routing a publication reads
+/// the other shards too, for their prefix and wildcard patterns.
+///
+/// Consistency: every operation on one topic is atomic and sees all earlier operations on
+/// that topic. There is no snapshot across topics, [`len`](SubscriptionStore::len) may
+/// observe some shards before and others after a concurrent change.
+///
+/// Subscription ids encode the shard of their topic, so
+/// [`unsubscribe`](SubscriptionStore::unsubscribe) finds it without a global lookup.
+/// # Examples
+/// ```
+/// use std::sync::Arc;
+/// use wamp_helpers::broker::SubscriptionStore;
+///
+/// let store = Arc::new(SubscriptionStore::new(8));
+/// let (subscription, created) = store.subscribe("com.example.topic", 7);
+/// assert!(created);
+/// assert_eq!(store.subscribe("com.example.topic", 9), (subscription, false));
+///
+/// let worker = Arc::clone(&store);
+/// std::thread::spawn(move || assert_eq!(worker.subscribers("com.example.topic"), [7, 9]))
+///     .join()
+///     .unwrap();
+///
+/// assert_eq!(store.unsubscribe(subscription, 7), Some(false));
+/// assert_eq!(store.unsubscribe(subscription, 9), Some(true));
+/// assert!(store.is_empty());
+/// ```
+#[derive(Debug)]
+pub struct SubscriptionStore {
+    shards: Vec<RwLock<Shard>>,
+    next_id: AtomicU64,
+}
+
+impl SubscriptionStore {
+    /// A store with `shards` shards, about the number of cores publishing concurrently.
+    pub fn new(shards: usize) -> Self {
+        SubscriptionStore {
+            shards: (0..shards.max(1)).map(|_| RwLock::default()).collect(),
+            next_id: AtomicU64::new(0),
+        }
+    }
+
+    fn shard_of(&self, topic: &str) -> usize {
+        let mut hasher = DefaultHasher::new();
+        topic.hash(&mut hasher);
+        (hasher.finish() % self.shards.len() as u64) as usize
+    }
+
+    fn shard(&self, index: usize) -> std::sync::RwLockReadGuard<'_, Shard> {
+        // A panic while holding the lock leaves the maps consistent, keep going.
+        self.shards[index]
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn shard_mut(&self, index: usize) -> std::sync::RwLockWriteGuard<'_, Shard> {
+        self.shards[index]
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Add `session` to the subscription of `topic`, returns the subscription id and whether
+    /// the subscription was created.
+    pub fn subscribe(&self, topic: &str, session: WampId) -> (WampId, bool) {
+        self.subscribe_with(topic, MatchPolicy::Exact, session)
+    }
+
+    /// Same as [`subscribe`](Self::subscribe) for a prefix or wildcard pattern, which is a
+    /// different subscription than the exact topic with the same URI.
+    pub fn subscribe_with(
+        &self,
+        topic: &str,
+        match_policy: MatchPolicy,
+        session: WampId,
+    ) -> (WampId, bool) {
+        let index = self.shard_of(topic);
+        let mut shard = self.shard_mut(index);
+        if let Some(existing) = shard.topics_mut(match_policy).get_mut(topic) {
+            existing.subscribers.insert(session);
+            return (existing.subscription, false);
+        }
+
+        let shards = self.shards.len() as u64;
+        let sequence = self.next_id.fetch_add(1, Ordering::Relaxed) % (MAX_ID / shards);
+        let subscription = sequence * shards + index as u64 + 1;
+        shard.topics_mut(match_policy).insert(
+            topic.to_string().into(),
+            Topic {
+                subscription,
+                subscribers: BTreeSet::from([session]),
+            },
+        );
+        shard
+            .subscriptions
+            .insert(subscription, (match_policy, topic.to_string().into()));
+        (subscription, true)
+    }
+
+    /// Remove `session` from `subscription`, returns whether the subscription is gone now, or
+    /// `None` when the session was not subscribed.
+    pub fn unsubscribe(&self, subscription: WampId, session: WampId) -> Option<bool> {
+        let index = ((subscription.checked_sub(1)?) % self.shards.len() as u64) as usize;
+        let mut shard = self.shard_mut(index);
+        let (match_policy, topic) = shard.subscriptions.get(&subscription)?.clone();
+        let entry = shard.topics_mut(match_policy).get_mut(&topic)?;
+        if !entry.subscribers.remove(&session) {
+            return None;
+        }
+        let deleted = entry.subscribers.is_empty();
+        if deleted {
+            shard.topics_mut(match_policy).remove(&topic);
+            shard.subscriptions.remove(&subscription);
+        }
+        Some(deleted)
+    }
+
+    /// Drop every subscription of a session that left, returns the subscriptions deleted.
+    pub fn remove_session(&self, session: WampId) -> Vec<WampId> {
+        let mut deleted = Vec::new();
+        for index in 0..self.shards.len() {
+            let mut shard = self.shard_mut(index);
+            let mut emptied = Vec::new();
+            for match_policy in POLICIES {
+                for (topic, entry) in shard.topics_mut(match_policy).iter_mut() {
+                    if entry.subscribers.remove(&session) && entry.subscribers.is_empty() {
+                        emptied.push((match_policy, topic.clone(), entry.subscription));
+                    }
+                }
+            }
+            for (match_policy, topic, subscription) in emptied {
+                shard.topics_mut(match_policy).remove(&topic);
+                shard.subscriptions.remove(&subscription);
+                deleted.push(subscription);
+            }
+        }
+        deleted
+    }
+
+    /// The exact subscription of `topic` and its subscribers. Prefix and wildcard
+    /// subscriptions matching `topic` are left out, see [`route`](Self::route).
+    pub fn lookup(&self, topic: &str) -> Option<(WampId, Vec<WampId>)> {
+        let shard = self.shard(self.shard_of(topic));
+        let entry = shard.topics.get(topic)?;
+        Some((
+            entry.subscription,
+            entry.subscribers.iter().copied().collect(),
+        ))
+    }
+
+    /// Sessions subscribed to `topic` exactly or through a pattern, in ascending order.
+    pub fn subscribers(&self, topic: &str) -> Vec<WampId> {
+        let sessions: BTreeSet<WampId> = self
+            .route(topic)
+            .into_iter()
+            .flat_map(|(_, subscribers)| subscribers)
+            .collect();
+        sessions.into_iter().collect()
+    }
+
+    /// Every subscription a PUBLISH to `topic` is delivered on, with its subscribers in
+    /// ascending order: the exact subscription first, then prefix and wildcard ones matching
+    /// `topic`. A session subscribed several ways gets one EVENT per subscription.
+    /// ```
+    /// use wamp_helpers::broker::SubscriptionStore;
+    /// use wamp_helpers::uri::MatchPolicy;
+    ///
+    /// let store = SubscriptionStore::new(4);
+    /// let (exact, _) = store.subscribe("com.example.orders.created", 7);
+    /// let (prefix, _) = store.subscribe_with("com.example.orders", MatchPolicy::Prefix, 9);
+    /// store.subscribe_with("com.example..deleted", MatchPolicy::Wildcard, 11);
+    ///
+    /// let routed = store.route("com.example.orders.created");
+    /// assert_eq!(routed, [(exact, vec![7]), (prefix, vec![9])]);
+    /// assert_eq!(store.subscribers("com.example.orders.deleted"), [9, 11]);
+    /// ```
+    pub fn route(&self, topic: &str) -> Vec<(WampId, Vec<WampId>)> {
+        self.matching(topic)
+            .into_iter()
+            .map(|(_, _, entry)| (entry.subscription, entry.subscribers.into_iter().collect()))
+            .collect()
+    }
+
+    /// Subscriptions matching `topic` in [`route`](Self::route) order, with their policy
+    /// and topic or pattern.
+    fn matching(&self, topic: &str) -> Vec<(MatchPolicy, Uri, Topic)> {
+        let mut matches = Vec::new();
+        for match_policy in POLICIES {
+            let mut found = Vec::new();
+            for index in 0..self.shards.len() {
+                // Exact subscriptions only live in the shard of their topic.
+                if match_policy == MatchPolicy::Exact && index != self.shard_of(topic) {
+                    continue;
+                }
+                let shard = self.shard(index);
+                for (pattern, entry) in shard.topics(match_policy) {
+                    if match_policy.matches(pattern, topic) {
+                        found.push((match_policy, pattern.clone(), entry.clone()));
+                    }
+                }
+            }
+            found.sort_by(|(_, a, _), (_, b, _)| a.as_str().cmp(b.as_str()));
+            matches.extend(found);
+        }
+        matches
+    }
+
+    /// Every subscription a PUBLISH to `topic` by `publisher` with `options` would be
+    /// delivered on, and to whom, without delivering anything. The subscriptions are those of
+    /// [`route`](Self::route), in the same order.
+    ///
+    /// The `exclude` and `eligible` lists and `exclude_me`, which defaults to `true`, are
+    /// applied like the broker does, so operators and tests can check black- and
+    /// whitelisting and pattern rules against hypothetical publications.
+    /// # Examples
+    /// ```
+    /// use wamp_helpers::broker::SubscriptionStore;
+    /// use wamp_helpers::options::PublishOptions;
+    /// use wamp_helpers::uri::MatchPolicy;
+    ///
+    /// let store = SubscriptionStore::new(4);
+    /// let (exact, _) = store.subscribe("com.example.orders.created", 7);
+    /// store.subscribe("com.example.orders.created", 9);
+    /// let (prefix, _) = store.subscribe_with("com.example.orders", MatchPolicy::Prefix, 11);
+    /// store.subscribe_with("com.example..deleted", MatchPolicy::Wildcard, 13);
+    ///
+    /// let options = PublishOptions {
+    ///     exclude: vec![9],
+    ///     ..PublishOptions::default()
+    /// };
+    /// let matches = store.would_receive("com.example.orders.created", 7, &options);
+    /// assert_eq!(matches.len(), 2);
+    /// assert_eq!((matches[0].subscription, matches[1].subscription), (exact, prefix));
+    /// // The publisher is excluded by default, 9 by the blacklist.
+    /// assert!(matches[0].receivers.is_empty());
+    /// assert_eq!(matches[0].excluded, [7, 9]);
+    /// assert_eq!(matches[1].receivers, [11]);
+    ///
+    /// let whitelisted = PublishOptions {
+    ///     eligible: vec![13],
+    ///     ..PublishOptions::default()
+    /// };
+    /// let matches = store.would_receive("com.example.orders.deleted", 1, &whitelisted);
+    /// assert_eq!(matches[0].excluded, [11]);
+    /// assert_eq!(matches[1].receivers, [13]);
+    ///
+    /// assert_eq!(store.len(), 3);
+    /// assert_eq!(store.unsubscribe(prefix, 11), Some(true));
+    /// assert_eq!(store.remove_session(13).len(), 1);
+    /// assert_eq!(store.len(), 1);
+    /// ```
+    pub fn would_receive(
+        &self,
+        topic: &str,
+        publisher: WampId,
+        options: &PublishOptions,
+    ) -> Vec<SubscriptionMatch> {
+        let exclude_me = options.exclude_me.unwrap_or(true);
+        let receives = |session: WampId| {
+            let excluded = options.exclude.contains(&session) || exclude_me && session == publisher;
+            let eligible = options.eligible.is_empty() || options.eligible.contains(&session);
+            eligible && !excluded
+        };
+
+        self.matching(topic)
+            .into_iter()
+            .map(|(match_policy, pattern, entry)| {
+                let (receivers, excluded) = entry
+                    .subscribers
+                    .iter()
+                    .partition(|session| receives(**session));
+                SubscriptionMatch {
+                    subscription: entry.subscription,
+                    match_policy,
+                    topic: pattern,
+                    receivers,
+                    excluded,
+                }
+            })
+            .collect()
+    }
+
+    /// Number of subscriptions.
+    pub fn len(&self) -> usize {
+        (0..self.shards.len())
+            .map(|index| {
+                let shard = self.shard(index);
+                POLICIES
+                    .iter()
+                    .map(|match_policy| shard.topics(*match_policy).len())
+                    .sum::<usize>()
+            })
+            .sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Default for SubscriptionStore {
+    /// One shard per available core.
+    fn default() -> Self {
+        let cores = std::thread::available_parallelism().map_or(1, |cores| cores.get());
+        SubscriptionStore::new(cores)
+    }
+}
@@ -0,0 +1,138 @@
+use crate::correlation::Direction;
+use crate::messages::{Message, Uri, WampId};
+use crate::session::{Session, SessionState};
+use crate::validator::Violation;
+use std::sync::mpsc::{channel, Receiver, Sender};
+
+/// ABORT reasons reported as [`LifecycleEvent::AuthFailure`].
+pub const AUTHENTICATION_FAILED: &str = "wamp.error.authentication_failed";
+pub const NOT_AUTHORIZED: &str = "wamp.error.not_authorized";
+
+/// Something an embedder may want to log or alert on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LifecycleEvent {
+    SessionOpened {
+        session: WampId,
+    },
+    /// The session ended with GOODBYE, or was aborted before it was established.
+    SessionClosed {
+        session: Option<WampId>,
+        reason: Uri,
+    },
+    ProtocolViolation(Violation),
+    AuthFailure {
+        reason: Uri,
+        message: Option<String>,
+    },
+    /// An ERROR answering a request, e.g. a CALL to a procedure without callee.
+    RoutingError {
+        session: Option<WampId>,
+        request_type: u8,
+        request: WampId,
+        error: Uri,
+    },
+}
+
+/// Fan-out of [`LifecycleEvent`]s to any number of subscribers.
+///
+/// Feed it every message through [`observe`](EventBus::observe) before the message is
+/// applied to the [`Session`], and report violations found by the
+/// [`Validator`](crate::validator::Validator) with [`emit`](EventBus::emit). Subscribers
+/// whose receiver was dropped are forgotten on the next event.
+/// # Examples
+/// ```
+/// use wamp_helpers::bus::{EventBus, LifecycleEvent};
+/// use wamp_helpers::correlation::Direction;
+/// use wamp_helpers::messages::Message;
+/// use wamp_helpers::session::{Session, Side};
+///
+/// let mut bus = EventBus::new();
+/// let audit = bus.subscribe();
+/// let mut session = Session::new(Side::Router);
+/// for (direction, raw) in [
+///     (Direction::Inbound, r#"[1, "realm1", {"roles": {"caller": {}}}]"#),
+///     (Direction::Outbound, r#"[2, 9129137332, {"roles": {"dealer": {}}}]"#),
+///     (Direction::Outbound, r#"[8, 48, 7, {}, "wamp.error.no_such_procedure"]"#),
+/// ] {
+///     let message = Message::parse_message(raw).unwrap();
+///     bus.observe(&session, direction, &message);
+///     session.transition(direction, &message).unwrap();
+/// }
+///
+/// assert_eq!(audit.try_recv().unwrap(), LifecycleEvent::SessionOpened { session: 9129137332 });
+/// assert!(matches!(audit.try_recv().unwrap(), LifecycleEvent::RoutingError { request: 7, .. }));
+/// ```
+#[derive(Debug, Default)]
+pub struct EventBus {
+    subscribers: Vec<Sender<LifecycleEvent>>,
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        EventBus::default()
+    }
+
+    pub fn subscribe(&mut self) -> Receiver<LifecycleEvent> {
+        let (sender, receiver) = channel();
+        self.subscribers.push(sender);
+        receiver
+    }
+
+    pub fn emit(&mut self, event: LifecycleEvent) {
+        self.subscribers
+            .retain(|subscriber| subscriber.send(event.clone()).is_ok());
+    }
+
+    /// Derive the events a message causes, `session` is the state before the message.
+    pub fn observe(&mut self, session: &Session, direction: Direction, message: &Message) {
+        for event in lifecycle_events(session, direction, message) {
+            self.emit(event);
+        }
+    }
+}
+
+/// The events [`EventBus::observe`] emits for `message`.
+pub fn lifecycle_events(
+    session: &Session,
+    direction: Direction,
+    message: &Message,
+) -> Vec<LifecycleEvent> {
+    match (session.state(), message) {
+        (SessionState::Establishing, Message::Welcome(welcome)) => {
+            vec![LifecycleEvent::SessionOpened {
+                session: welcome.session,
+            }]
+        }
+        (SessionState::Establishing | SessionState::Challenged, Message::Abort(abort)) => {
+            let mut events = Vec::new();
+            if abort.reason == AUTHENTICATION_FAILED || abort.reason == NOT_AUTHORIZED {
+                events.push(LifecycleEvent::AuthFailure {
+                    reason: abort.reason.clone(),
+                    message: abort.details["message"].as_str().map(str::to_string),
+                });
+            }
+            events.push(LifecycleEvent::SessionClosed {
+                session: None,
+                reason: abort.reason.clone(),
+            });
+            events
+        }
+        // The reason of the GOODBYE that started closing is the one worth reporting, the reply
+        // usually just says goodbye_and_out.
+        (SessionState::Established, Message::Goodbye(goodbye)) => {
+            vec![LifecycleEvent::SessionClosed {
+                session: session.session_id(),
+                reason: goodbye.reason.clone(),
+            }]
+        }
+        (_, Message::ErrorMessage(error)) if !session.from_client(direction) => {
+            vec![LifecycleEvent::RoutingError {
+                session: session.session_id(),
+                request_type: error.request_type,
+                request: error.request,
+                error: error.error.clone(),
+            }]
+        }
+        _ => Vec::new(),
+    }
+}
@@ -0,0 +1,242 @@
+use crate::messages::{Args, Kwargs, WampId};
+use crate::value::{encode_binary, WampValue};
+use json::number::Number;
+use json::JsonValue;
+use std::fmt::Write;
+
+/// Serialize a `JsonValue` deterministically: object keys are sorted by their UTF-16 code
+/// units (as in RFC 8785), no whitespace is emitted, integral numbers are written without a
+/// fraction or exponent and strings only escape what JSON requires.
+///
+/// Two values that compare equal always produce the same string, which makes the output
+/// suitable for hashing, signing and golden files.
+/// # Examples
+/// ```
+/// use wamp_helpers::canonical::to_canonical_string;
+/// let value = json::parse(r#"{"b": 1.0, "a": [1e2, "xé"]}"#).unwrap();
+/// assert_eq!(to_canonical_string(&value), r#"{"a":[100,"xé"],"b":1}"#);
+/// ```
+pub fn to_canonical_string(value: &JsonValue) -> String {
+    let mut out = String::new();
+    write_value(&mut out, value);
+    out
+}
+
+/// Writes the canonical form of a message frame element by element, borrowing the fields
+/// instead of building the frame's `JsonValue`. Elements are written in wire order with
+/// one method per field kind, the ones [`wamp_message!`](crate::wamp_message) knows.
+/// # Examples
+/// ```
+/// use wamp_helpers::canonical::{to_canonical_string, FrameWriter};
+/// use wamp_helpers::messages::{Call, WampMessageTrait};
+///
+/// let call: Call = r#"[48, 7, {"b": 1.0, "a": 2}, "com.example.add", [1, 2]]"#.parse().unwrap();
+/// let mut frame = FrameWriter::new(48);
+/// frame.id(&call.request);
+/// frame.dict(&call.options);
+/// frame.uri(&call.procedure);
+/// frame.payload(&call.args, &call.kwargs);
+/// assert_eq!(frame.finish(), to_canonical_string(&call.to_json().unwrap()));
+/// ```
+#[derive(Debug, Clone)]
+pub struct FrameWriter {
+    out: String,
+}
+
+impl FrameWriter {
+    pub fn new(message_id: u8) -> Self {
+        let mut out = String::from("[");
+        let _ = write!(out, "{}", message_id);
+        FrameWriter { out }
+    }
+
+    pub fn id(&mut self, value: &WampId) {
+        let _ = write!(self.out, ",{}", value);
+    }
+
+    pub fn u8(&mut self, value: &u8) {
+        let _ = write!(self.out, ",{}", value);
+    }
+
+    pub fn uri(&mut self, value: &str) {
+        self.str(value);
+    }
+
+    pub fn str(&mut self, value: &str) {
+        self.out.push(',');
+        write_string(&mut self.out, value);
+    }
+
+    pub fn dict(&mut self, value: &JsonValue) {
+        self.out.push(',');
+        write_value(&mut self.out, value);
+    }
+
+    /// A trailing Details element that may be left out, as in UNSUBSCRIBED.
+    pub fn optional_dict(&mut self, value: &Option<JsonValue>) {
+        if let Some(value) = value {
+            self.dict(value);
+        }
+    }
+
+    /// The optional `args`/`kwargs` pair, with an empty `args` list when only `kwargs` is
+    /// present.
+    pub fn payload(&mut self, args: &Option<Args>, kwargs: &Option<Kwargs>) {
+        match (args, kwargs) {
+            (Some(args), _) => {
+                self.out.push(',');
+                write_list(&mut self.out, args);
+            }
+            (None, Some(_)) => self.out.push_str(",[]"),
+            (None, None) => {}
+        }
+        if let Some(kwargs) = kwargs {
+            self.out.push(',');
+            write_dict(&mut self.out, kwargs);
+        }
+    }
+
+    pub fn finish(mut self) -> String {
+        self.out.push(']');
+        self.out
+    }
+}
+
+/// Same output as writing the `JsonValue` a [`WampValue`] converts to.
+fn write_wamp_value(out: &mut String, value: &WampValue) {
+    match value {
+        WampValue::Null => out.push_str("null"),
+        WampValue::Bool(true) => out.push_str("true"),
+        WampValue::Bool(false) => out.push_str("false"),
+        WampValue::Integer(value) => write_number(out, Number::from(*value)),
+        WampValue::Float(value) => write_number(out, Number::from(*value)),
+        WampValue::String(value) => write_string(out, value),
+        WampValue::Bytes(value) => write_string(out, &encode_binary(value)),
+        WampValue::List(items) => write_list(out, items),
+        WampValue::Dict(entries) => write_dict(out, entries),
+    }
+}
+
+fn write_list(out: &mut String, items: &[WampValue]) {
+    out.push('[');
+    for (index, item) in items.iter().enumerate() {
+        if index > 0 {
+            out.push(',');
+        }
+        write_wamp_value(out, item);
+    }
+    out.push(']');
+}
+
+fn write_dict(out: &mut String, entries: &Kwargs) {
+    let mut entries: Vec<(&str, &WampValue)> = entries
+        .iter()
+        .map(|(key, item)| (key.as_str(), item))
+        .collect();
+    entries.sort_by(|(a, _), (b, _)| a.encode_utf16().cmp(b.encode_utf16()));
+    out.push('{');
+    for (index, (key, item)) in entries.into_iter().enumerate() {
+        if index > 0 {
+            out.push(',');
+        }
+        write_string(out, key);
+        out.push(':');
+        write_wamp_value(out, item);
+    }
+    out.push('}');
+}
+
+fn write_value(out: &mut String, value: &JsonValue) {
+    match value {
+        JsonValue::Null => out.push_str("null"),
+        JsonValue::Boolean(true) => out.push_str("true"),
+        JsonValue::Boolean(false) => out.push_str("false"),
+        JsonValue::Short(short) => write_string(out, short.as_str()),
+        JsonValue::String(string) => write_string(out, string),
+        JsonValue::Number(number) => write_number(out, *number),
+        JsonValue::Array(items) => {
+            out.push('[');
+            for (index, item) in items.iter().enumerate() {
+                if index > 0 {
+                    out.push(',');
+                }
+                write_value(out, item);
+            }
+            out.push(']');
+        }
+        JsonValue::Object(object) => {
+            let mut entries: Vec<(&str, &JsonValue)> = object.iter().collect();
+            entries.sort_by(|(a, _), (b, _)| a.encode_utf16().cmp(b.encode_utf16()));
+            out.push('{');
+            for (index, (key, item)) in entries.into_iter().enumerate() {
+                if index > 0 {
+                    out.push(',');
+                }
+                write_string(out, key);
+                out.push(':');
+                write_value(out, item);
+            }
+            out.push('}');
+        }
+    }
+}
+
+fn write_number(out: &mut String, number: Number) {
+    if number.is_nan() {
+        out.push_str("null");
+        return;
+    }
+
+    if let Some((positive, magnitude)) = integral_parts(number) {
+        if !positive && magnitude != 0 {
+            out.push('-');
+        }
+        let _ = write!(out, "{}", magnitude);
+        return;
+    }
+
+    let float = f64::from(number);
+    if float.is_finite() {
+        let _ = write!(out, "{}", float);
+    } else {
+        out.push_str("null");
+    }
+}
+
+/// Returns the sign and magnitude when the number is an integer that fits in a `u64`.
+fn integral_parts(number: Number) -> Option<(bool, u64)> {
+    let (positive, mut mantissa, exponent) = number.as_parts();
+    if exponent >= 0 {
+        for _ in 0..exponent {
+            mantissa = mantissa.checked_mul(10)?;
+        }
+    } else {
+        for _ in 0..exponent.unsigned_abs() {
+            if mantissa % 10 != 0 {
+                return None;
+            }
+            mantissa /= 10;
+        }
+    }
+    Some((positive, mantissa))
+}
+
+fn write_string(out: &mut String, value: &str) {
+    out.push('"');
+    for character in value.chars() {
+        match character {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\u{08}' => out.push_str("\\b"),
+            '\u{0c}' => out.push_str("\\f"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            control if (control as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", control as u32);
+            }
+            other => out.push(other),
+        }
+    }
+    out.push('"');
+}
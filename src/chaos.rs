@@ -0,0 +1,129 @@
+use crate::error::Error;
+use crate::rng::SeededRng;
+use crate::transport::{PeerInfo, Transport};
+use std::collections::VecDeque;
+use std::future::Future;
+
+/// Probabilities of each fault, applied independently to every frame in both directions.
+#[derive(Debug, Clone, Default)]
+pub struct ChaosConfig {
+    pub seed: u64,
+    pub drop: f64,
+    /// Probability a frame is held back and delivered after the following one.
+    pub reorder: f64,
+    /// Probability a single bit of the frame is flipped.
+    pub corrupt: f64,
+}
+
+#[derive(Debug, Default)]
+struct Mangler {
+    held: Option<Vec<u8>>,
+}
+
+impl Mangler {
+    fn apply(&mut self, frame: Vec<u8>, config: &ChaosConfig, rng: &mut SeededRng) -> Vec<Vec<u8>> {
+        if rng.chance(config.drop) {
+            return Vec::new();
+        }
+
+        let mut frame = frame;
+        if !frame.is_empty() && rng.chance(config.corrupt) {
+            let index = rng.below(frame.len() as u64) as usize;
+            frame[index] ^= 1 << rng.below(8);
+        }
+
+        if self.held.is_none() && rng.chance(config.reorder) {
+            self.held = Some(frame);
+            return Vec::new();
+        }
+
+        let mut frames = vec![frame];
+        frames.extend(self.held.take());
+        frames
+    }
+}
+
+/// Wraps a transport and randomly drops, reorders and corrupts the frames passing through it.
+///
+/// Put it in front of the router side of a connection to check that clients built on this
+/// crate survive a misbehaving network, the same seed always produces the same faults.
+/// # Examples
+/// ```
+/// use std::future::Future;
+/// use std::task::{Context, Poll, Waker};
+/// use wamp_helpers::chaos::{Chaos, ChaosConfig};
+/// use wamp_helpers::memory::MemoryTransport;
+/// use wamp_helpers::transport::Transport;
+///
+/// fn now<F: Future>(future: F) -> F::Output {
+///     let mut future = std::pin::pin!(future);
+///     match future.as_mut().poll(&mut Context::from_waker(Waker::noop())) {
+///         Poll::Ready(output) => output,
+///         Poll::Pending => panic!("future is not ready"),
+///     }
+/// }
+///
+/// let (client, mut router) = MemoryTransport::pair();
+/// let config = ChaosConfig { seed: 42, drop: 1.0, ..ChaosConfig::default() };
+/// let mut client = Chaos::new(client, config);
+/// now(client.send(b"[1]".to_vec())).unwrap();
+/// assert!(router.try_next().is_none());
+/// ```
+#[derive(Debug)]
+pub struct Chaos<T> {
+    inner: T,
+    config: ChaosConfig,
+    rng: SeededRng,
+    outbound: Mangler,
+    inbound: Mangler,
+    ready: VecDeque<Vec<u8>>,
+}
+
+impl<T: Transport + Send> Chaos<T> {
+    pub fn new(inner: T, config: ChaosConfig) -> Self {
+        Chaos {
+            inner,
+            rng: SeededRng::new(config.seed),
+            config,
+            outbound: Mangler::default(),
+            inbound: Mangler::default(),
+            ready: VecDeque::new(),
+        }
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<T: Transport + Send> Transport for Chaos<T> {
+    async fn send(&mut self, frame: Vec<u8>) -> Result<(), Error> {
+        for frame in self.outbound.apply(frame, &self.config, &mut self.rng) {
+            self.inner.send(frame).await?;
+        }
+        Ok(())
+    }
+
+    async fn next(&mut self) -> Option<Result<Vec<u8>, Error>> {
+        loop {
+            if let Some(frame) = self.ready.pop_front() {
+                return Some(Ok(frame));
+            }
+            match self.inner.next().await {
+                Some(Ok(frame)) => {
+                    let frames = self.inbound.apply(frame, &self.config, &mut self.rng);
+                    self.ready.extend(frames);
+                }
+                other => return other,
+            }
+        }
+    }
+
+    fn close(&mut self, reason: &str) -> impl Future<Output = Result<(), Error>> + Send {
+        self.inner.close(reason)
+    }
+
+    fn peer(&self) -> Option<&PeerInfo> {
+        self.inner.peer()
+    }
+}
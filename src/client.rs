@@ -0,0 +1,1494 @@
+use crate::canonical::to_canonical_string;
+use crate::messages::{
+    Call, ErrorMessage, Event, Invocation, Message, Options, Publish, Published, Registered,
+    Subscribe, Subscribed, Unregister, Unsubscribe, Uri, WampId, WampMessageTrait,
+};
+use crate::options::{PublishOptions, DEDUP_KEY};
+use crate::sim::{IdGenerator, SequentialIdGenerator};
+use crate::value::WampValue;
+use json::JsonValue;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Error URI the dealer answers with when no callee could take the call.
+pub const UNAVAILABLE: &str = "wamp.error.unavailable";
+/// Error URI for calls that ran out of time.
+pub const TIMEOUT: &str = "wamp.error.timeout";
+/// Error URI for calls that were canceled, by the caller or because the callee left.
+pub const CANCELED: &str = "wamp.error.canceled";
+
+/// When and how often a CALL is sent again.
+///
+/// `wamp.error.unavailable` means no callee ran the call, so it is always retried. A timeout
+/// leaves open whether the callee already ran it, which is only retried for calls marked
+/// idempotent.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CallRetry {
+    /// Attempts including the first one.
+    pub max_attempts: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    pub idempotent: bool,
+}
+
+impl Default for CallRetry {
+    fn default() -> Self {
+        CallRetry {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(5),
+            idempotent: false,
+        }
+    }
+}
+
+impl CallRetry {
+    pub fn new(max_attempts: u32) -> Self {
+        CallRetry {
+            max_attempts,
+            ..CallRetry::default()
+        }
+    }
+
+    /// Wait `initial` before the second attempt, doubling for every further one up to `max`.
+    pub fn backoff(mut self, initial: Duration, max: Duration) -> Self {
+        self.initial_backoff = initial;
+        self.max_backoff = max;
+        self
+    }
+
+    /// Mark the procedure as safe to run more than once.
+    pub fn idempotent(mut self, idempotent: bool) -> Self {
+        self.idempotent = idempotent;
+        self
+    }
+
+    /// Delay before attempt `attempt + 1`, given `attempt` attempts were made.
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let factor = 1u32
+            .checked_shl(attempt.saturating_sub(1))
+            .unwrap_or(u32::MAX);
+        self.initial_backoff
+            .saturating_mul(factor)
+            .min(self.max_backoff)
+    }
+}
+
+/// What to do after a response or a timeout of a [`RetryingCall`].
+#[derive(Debug, Clone)]
+pub enum CallStep {
+    /// The call got its RESULT, or an ERROR that is not retried.
+    Complete { response: Message, attempts: u32 },
+    /// Send `call` after waiting `after`, it carries a fresh request id.
+    Retry { after: Duration, call: Call },
+    /// Retrying is not allowed any more, `response` is `None` after a timeout.
+    Failed {
+        response: Option<Message>,
+        attempts: u32,
+    },
+}
+
+/// A CALL together with its [`CallRetry`] policy and the attempts made so far.
+/// # Examples
+/// ```
+/// use std::time::Duration;
+/// use wamp_helpers::client::{CallRetry, CallStep, RetryingCall};
+/// use wamp_helpers::messages::{Call, Message};
+/// use wamp_helpers::sim::SequentialIdGenerator;
+///
+/// let mut ids = SequentialIdGenerator::default();
+/// let call: Call = r#"[48, 1, {}, "com.example.add", [1, 2]]"#.parse().unwrap();
+/// let mut retrying = RetryingCall::new(call, CallRetry::new(3));
+///
+/// let unavailable = Message::parse_message(r#"[8, 48, 1, {}, "wamp.error.unavailable"]"#).unwrap();
+/// let retry = match retrying.on_response(&unavailable, &mut ids) {
+///     Some(CallStep::Retry { after, call }) => {
+///         assert_eq!(after, Duration::from_millis(100));
+///         call
+///     }
+///     other => panic!("{:?}", other),
+/// };
+///
+/// let result = Message::parse_message(&format!("[50, {}, {{}}, [3]]", retry.request)).unwrap();
+/// assert!(matches!(
+///     retrying.on_response(&result, &mut ids),
+///     Some(CallStep::Complete { attempts: 2, .. })
+/// ));
+/// ```
+#[derive(Debug, Clone)]
+pub struct RetryingCall {
+    call: Call,
+    policy: CallRetry,
+    attempts: u32,
+}
+
+impl RetryingCall {
+    /// Track `call`, which counts as the first attempt.
+    pub fn new(call: Call, policy: CallRetry) -> Self {
+        RetryingCall {
+            call,
+            policy,
+            attempts: 1,
+        }
+    }
+
+    /// The CALL of the current attempt.
+    pub fn call(&self) -> &Call {
+        &self.call
+    }
+
+    pub fn attempts(&self) -> u32 {
+        self.attempts
+    }
+
+    /// Feed a message received from the dealer, `None` when it does not answer the current
+    /// attempt.
+    pub fn on_response(
+        &mut self,
+        response: &Message,
+        ids: &mut impl IdGenerator,
+    ) -> Option<CallStep> {
+        let retryable = match response {
+            Message::MessageResult(result) if result.request == self.call.request => false,
+            Message::ErrorMessage(error)
+                if error.request_type == Call::ID && error.request == self.call.request =>
+            {
+                error.error == UNAVAILABLE || (error.error == TIMEOUT && self.policy.idempotent)
+            }
+            _ => return None,
+        };
+
+        if retryable {
+            Some(self.retry(Some(response), ids))
+        } else {
+            Some(CallStep::Complete {
+                response: response.clone(),
+                attempts: self.attempts,
+            })
+        }
+    }
+
+    /// The caller gave up waiting for the current attempt.
+    pub fn on_timeout(&mut self, ids: &mut impl IdGenerator) -> CallStep {
+        if self.policy.idempotent {
+            self.retry(None, ids)
+        } else {
+            CallStep::Failed {
+                response: None,
+                attempts: self.attempts,
+            }
+        }
+    }
+
+    fn retry(&mut self, response: Option<&Message>, ids: &mut impl IdGenerator) -> CallStep {
+        if self.attempts >= self.policy.max_attempts {
+            return CallStep::Failed {
+                response: response.cloned(),
+                attempts: self.attempts,
+            };
+        }
+        let after = self.policy.delay(self.attempts);
+        self.attempts += 1;
+        self.call.request = ids.next_id();
+        CallStep::Retry {
+            after,
+            call: self.call.clone(),
+        }
+    }
+}
+
+/// Per-session outbox giving publications at-least-once delivery.
+///
+/// Every publication is sent with `acknowledge: true` and a deduplication key, and kept until
+/// its PUBLISHED or ERROR arrives. After a reconnect [`resend`](Outbox::resend) hands back the
+/// unanswered ones with fresh request ids but their original keys, so subscribers can drop
+/// the copies they already processed.
+/// # Examples
+/// ```
+/// use wamp_helpers::client::Outbox;
+/// use wamp_helpers::messages::{Publish, Published};
+/// use wamp_helpers::options::PublishOptions;
+/// use wamp_helpers::sim::SequentialIdGenerator;
+///
+/// let mut outbox = Outbox::new("device-7");
+/// let publish: Publish = r#"[16, 1, {}, "com.example.reading", [21]]"#.parse().unwrap();
+/// let sent = outbox.publish(publish);
+/// assert_eq!(PublishOptions::from(&sent.options).dedup_key.as_deref(), Some("device-7-1"));
+///
+/// // The connection dropped before the PUBLISHED arrived.
+/// let mut ids = SequentialIdGenerator::default();
+/// let resent = outbox.resend(&mut ids);
+/// assert_eq!(resent[0].options["x_dedup_key"], "device-7-1");
+///
+/// let published = Published { request: resent[0].request, publication: 99 };
+/// assert!(outbox.on_published(&published));
+/// assert!(outbox.is_empty());
+/// ```
+#[derive(Debug, Clone)]
+pub struct Outbox {
+    key_prefix: String,
+    next_key: u64,
+    pending: BTreeMap<WampId, Publish>,
+    /// Publications of a previous run, whose request ids mean nothing in this session.
+    restored: Vec<Publish>,
+}
+
+impl Outbox {
+    /// `key_prefix` makes generated deduplication keys unique across publishers, e.g. a device
+    /// or installation id.
+    pub fn new(key_prefix: impl Into<String>) -> Self {
+        Outbox {
+            key_prefix: key_prefix.into(),
+            next_key: 1,
+            pending: BTreeMap::new(),
+            restored: Vec::new(),
+        }
+    }
+
+    /// Track `publish` and return it ready to send, with acknowledgment requested and a
+    /// deduplication key unless it already carries one.
+    pub fn publish(&mut self, mut publish: Publish) -> Publish {
+        let mut options = PublishOptions::from(&publish.options);
+        options.acknowledge = true;
+        if options.dedup_key.is_none() {
+            options.dedup_key = Some(format!("{}-{}", self.key_prefix, self.next_key));
+            self.next_key += 1;
+        }
+        publish.options["acknowledge"] = true.into();
+        publish.options[DEDUP_KEY] = options.dedup_key.into();
+        self.pending.insert(publish.request, publish.clone());
+        publish
+    }
+
+    /// Settle a publication, returns whether it was pending.
+    pub fn on_published(&mut self, published: &Published) -> bool {
+        self.pending.remove(&published.request).is_some()
+    }
+
+    /// Settle a publication the broker refused, returns it when it was pending. Refused
+    /// publications are not resent.
+    pub fn on_error(&mut self, error: &ErrorMessage) -> Option<Publish> {
+        if error.request_type != Publish::ID {
+            return None;
+        }
+        self.pending.remove(&error.request)
+    }
+
+    /// Take back publications that were pending when the client stopped, e.g. loaded from an
+    /// [`OutboxStore`](crate::persist::OutboxStore). They are sent with the next
+    /// [`resend`](Self::resend) with fresh request ids, and new keys continue after the
+    /// restored ones.
+    /// ```
+    /// use wamp_helpers::client::Outbox;
+    /// use wamp_helpers::messages::{Publish, Published};
+    /// use wamp_helpers::sim::SequentialIdGenerator;
+    ///
+    /// let mut previous = Outbox::new("device-7");
+    /// let saved: Vec<Publish> = (0..2)
+    ///     .map(|_| previous.publish(r#"[16, 1, {}, "com.example.reading"]"#.parse().unwrap()))
+    ///     .collect();
+    ///
+    /// // Both were sent with request 1 by the previous run, and the new session starts at 1.
+    /// let mut outbox = Outbox::new("device-7");
+    /// outbox.restore(saved);
+    /// let mut ids = SequentialIdGenerator::default();
+    /// let fresh = outbox.publish(r#"[16, 1, {}, "com.example.reading"]"#.parse().unwrap());
+    /// let resent = outbox.resend(&mut ids);
+    /// assert_eq!(resent.len(), 3);
+    /// assert_eq!(resent[0].options["x_dedup_key"], "device-7-1");
+    /// assert_eq!(fresh.options["x_dedup_key"], "device-7-3");
+    /// ```
+    pub fn restore(&mut self, publishes: impl IntoIterator<Item = Publish>) {
+        let prefix = format!("{}-", self.key_prefix);
+        for publish in publishes {
+            let restored = publish.options[DEDUP_KEY]
+                .as_str()
+                .and_then(|key| key.strip_prefix(&prefix))
+                .and_then(|number| number.parse::<u64>().ok());
+            if let Some(number) = restored {
+                self.next_key = self.next_key.max(number + 1);
+            }
+            self.restored.push(publish);
+        }
+    }
+
+    /// Publications still waiting for an answer, restored ones first, renumbered with
+    /// request ids of the new session.
+    pub fn resend(&mut self, ids: &mut impl IdGenerator) -> Vec<Publish> {
+        let restored = std::mem::take(&mut self.restored);
+        let pending = std::mem::take(&mut self.pending);
+        restored
+            .into_iter()
+            .chain(pending.into_values())
+            .map(|mut publish| {
+                publish.request = ids.next_id();
+                self.pending.insert(publish.request, publish.clone());
+                publish
+            })
+            .collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.pending.len() + self.restored.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum DedupKey {
+    /// Subscription and publication id, both only valid within one session.
+    Publication(WampId, WampId),
+    Key(String),
+}
+
+/// Subscriber-side window of recently processed events, dropping repeats caused by
+/// reconnects or replays.
+///
+/// Events are identified by their `x_dedup_key` Details entry when the publisher set one (see
+/// [`Outbox`]), whatever subscription delivered them since subscription ids change with a
+/// reconnect. Events without one are identified by subscription and publication id. Only the
+/// last `capacity` events are remembered, the oldest is evicted first.
+/// # Examples
+/// ```
+/// use wamp_helpers::client::DedupWindow;
+/// use wamp_helpers::messages::Event;
+///
+/// let mut window = DedupWindow::new(2);
+/// let first: Event = r#"[36, 1, 100, {"x_dedup_key": "order-42"}]"#.parse().unwrap();
+/// let republished: Event = r#"[36, 1, 101, {"x_dedup_key": "order-42"}]"#.parse().unwrap();
+/// let other: Event = r#"[36, 1, 102, {}]"#.parse().unwrap();
+///
+/// assert!(!window.is_duplicate(&first));
+/// assert!(window.is_duplicate(&republished));
+/// assert!(!window.is_duplicate(&other));
+/// assert!(window.is_duplicate(&other));
+///
+/// // Redelivered after a reconnect, on the new subscription id.
+/// let redelivered: Event = r#"[36, 2, 200, {"x_dedup_key": "order-42"}]"#.parse().unwrap();
+/// let mut window = DedupWindow::new(2);
+/// assert!(!window.is_duplicate(&first));
+/// assert!(window.is_duplicate(&redelivered));
+/// ```
+#[derive(Debug, Clone)]
+pub struct DedupWindow {
+    capacity: usize,
+    seen: HashSet<DedupKey>,
+    /// Keys in the order they were seen, with the subscription that delivered them.
+    order: VecDeque<(WampId, DedupKey)>,
+}
+
+impl DedupWindow {
+    pub fn new(capacity: usize) -> Self {
+        DedupWindow {
+            capacity: capacity.max(1),
+            seen: HashSet::new(),
+            order: VecDeque::new(),
+        }
+    }
+
+    /// Whether `event` was seen within the window, records it when it was not.
+    pub fn is_duplicate(&mut self, event: &Event) -> bool {
+        let key = match event.details[DEDUP_KEY].as_str() {
+            Some(key) => DedupKey::Key(key.to_string()),
+            None => DedupKey::Publication(event.subscription, event.publication),
+        };
+        if self.seen.contains(&key) {
+            return true;
+        }
+
+        if self.order.len() == self.capacity {
+            if let Some((_, evicted)) = self.order.pop_front() {
+                self.seen.remove(&evicted);
+            }
+        }
+        self.seen.insert(key.clone());
+        self.order.push_back((event.subscription, key));
+        false
+    }
+
+    /// Forget everything seen on `subscription`, e.g. after unsubscribing.
+    pub fn forget_subscription(&mut self, subscription: WampId) {
+        let seen = &mut self.seen;
+        self.order.retain(|(delivered_by, key)| {
+            let forget = *delivered_by == subscription;
+            if forget {
+                seen.remove(key);
+            }
+            !forget
+        });
+    }
+
+    pub fn len(&self) -> usize {
+        self.order.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.order.is_empty()
+    }
+}
+
+/// Guard of one subscription, handed out by [`Subscriptions::track`].
+///
+/// Dropping or [`close`](Subscription::close)-ing it queues an UNSUBSCRIBE with the
+/// [`Subscriptions`] it came from, so a forgotten handle cannot leak the subscription.
+#[derive(Debug)]
+pub struct Subscription {
+    subscription: WampId,
+    closed: Option<Sender<WampId>>,
+}
+
+impl Subscription {
+    pub fn id(&self) -> WampId {
+        self.subscription
+    }
+
+    /// Unsubscribe now, same as dropping the handle.
+    pub fn close(self) {}
+
+    /// Keep the subscription for the rest of the session, dropping the handle no longer
+    /// unsubscribes.
+    pub fn detach(mut self) -> WampId {
+        self.closed = None;
+        self.subscription
+    }
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        if let Some(closed) = self.closed.take() {
+            // The tracker is gone with its session, nothing is left to unsubscribe from.
+            let _ = closed.send(self.subscription);
+        }
+    }
+}
+
+/// Client-side bookkeeping of subscriptions with [`Subscription`] handles.
+///
+/// Send what [`unsubscribes`](Subscriptions::unsubscribes) returns whenever handles may have
+/// been dropped, and feed UNSUBSCRIBED and ERROR replies to
+/// [`on_message`](Subscriptions::on_message), which reports the subscriptions that are gone.
+/// # Examples
+/// ```
+/// use wamp_helpers::client::Subscriptions;
+/// use wamp_helpers::messages::{Message, Subscribed};
+/// use wamp_helpers::sim::SequentialIdGenerator;
+///
+/// let mut ids = SequentialIdGenerator::default();
+/// let mut subscriptions = Subscriptions::new();
+/// let subscribed: Subscribed = "[33, 1, 5512315355]".parse().unwrap();
+/// let handle = subscriptions.track(&subscribed);
+/// assert!(subscriptions.is_active(5512315355));
+///
+/// drop(handle);
+/// let unsubscribe = subscriptions.unsubscribes(&mut ids).remove(0);
+/// assert_eq!(unsubscribe.subscription, 5512315355);
+/// assert!(subscriptions.is_unsubscribing(5512315355));
+///
+/// let unsubscribed = Message::parse_message(&format!("[35, {}]", unsubscribe.request)).unwrap();
+/// assert_eq!(subscriptions.on_message(&unsubscribed), Some(5512315355));
+/// assert!(subscriptions.is_empty());
+/// ```
+#[derive(Debug)]
+pub struct Subscriptions {
+    closed: Sender<WampId>,
+    dropped: Receiver<WampId>,
+    /// Live handles per subscription. Brokers hand out the same id when a topic is
+    /// subscribed again, so several handles may share one.
+    active: HashMap<WampId, usize>,
+    /// UNSUBSCRIBE request ids awaiting their reply, with the subscription.
+    unsubscribing: HashMap<WampId, WampId>,
+}
+
+impl Default for Subscriptions {
+    fn default() -> Self {
+        let (closed, dropped) = channel();
+        Subscriptions {
+            closed,
+            dropped,
+            active: HashMap::new(),
+            unsubscribing: HashMap::new(),
+        }
+    }
+}
+
+impl Subscriptions {
+    pub fn new() -> Self {
+        Subscriptions::default()
+    }
+
+    /// Handle of the subscription a SUBSCRIBED confirmed.
+    ///
+    /// The subscription is unsubscribed once every handle tracked for it is dropped.
+    /// ```
+    /// use wamp_helpers::client::Subscriptions;
+    /// use wamp_helpers::messages::Subscribed;
+    /// use wamp_helpers::sim::SequentialIdGenerator;
+    ///
+    /// let mut ids = SequentialIdGenerator::default();
+    /// let mut subscriptions = Subscriptions::new();
+    /// // The broker answers a second SUBSCRIBE to the same topic with the same id.
+    /// let first = subscriptions.track(&"[33, 1, 5512315355]".parse::<Subscribed>().unwrap());
+    /// let second = subscriptions.track(&"[33, 2, 5512315355]".parse::<Subscribed>().unwrap());
+    ///
+    /// drop(first);
+    /// assert!(subscriptions.unsubscribes(&mut ids).is_empty());
+    /// assert!(subscriptions.is_active(second.id()));
+    /// drop(second);
+    /// assert_eq!(subscriptions.unsubscribes(&mut ids).len(), 1);
+    /// ```
+    pub fn track(&mut self, subscribed: &Subscribed) -> Subscription {
+        *self.active.entry(subscribed.subscription).or_default() += 1;
+        Subscription {
+            subscription: subscribed.subscription,
+            closed: Some(self.closed.clone()),
+        }
+    }
+
+    /// UNSUBSCRIBE for every handle dropped or closed since the last call.
+    pub fn unsubscribes(&mut self, ids: &mut impl IdGenerator) -> Vec<Unsubscribe> {
+        let mut unsubscribes = Vec::new();
+        while let Ok(subscription) = self.dropped.try_recv() {
+            // Revoked by the router in the meantime.
+            let Some(handles) = self.active.get_mut(&subscription) else {
+                continue;
+            };
+            *handles -= 1;
+            if *handles > 0 {
+                continue;
+            }
+            self.active.remove(&subscription);
+            let request = ids.next_id();
+            self.unsubscribing.insert(request, subscription);
+            unsubscribes.push(Unsubscribe {
+                request,
+                subscription,
+            });
+        }
+        unsubscribes
+    }
+
+    /// The subscription that ended with `message`: an UNSUBSCRIBED or ERROR answering one of
+    /// our UNSUBSCRIBEs, or an UNSUBSCRIBED revoking a subscription.
+    pub fn on_message(&mut self, message: &Message) -> Option<WampId> {
+        match message {
+            Message::Unsubscribed(unsubscribed) => match &unsubscribed.details {
+                Some(details) => {
+                    let subscription = details["subscription"].as_u64()?;
+                    self.active.remove(&subscription).map(|_| subscription)
+                }
+                None => self.unsubscribing.remove(&unsubscribed.request),
+            },
+            // The router does not know the subscription, it is gone either way.
+            Message::ErrorMessage(error) if error.request_type == Unsubscribe::ID => {
+                self.unsubscribing.remove(&error.request)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn is_active(&self, subscription: WampId) -> bool {
+        self.active.contains_key(&subscription)
+    }
+
+    /// Whether an UNSUBSCRIBE for `subscription` was sent and not answered yet.
+    pub fn is_unsubscribing(&self, subscription: WampId) -> bool {
+        self.unsubscribing
+            .values()
+            .any(|pending| *pending == subscription)
+    }
+
+    /// Number of subscriptions that are active or being unsubscribed.
+    pub fn len(&self) -> usize {
+        self.active.len() + self.unsubscribing.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// WELCOME details key carrying the broker's state epoch. A broker that lost its
+/// subscriptions, e.g. after a restart, announces a different epoch.
+pub const EPOCH: &str = "x_epoch";
+/// Revocation reason of a broker that dropped all subscriptions.
+pub const BROKER_RESET: &str = "wamp.subscription.broker_reset";
+
+/// Subscriptions a broker dropped, with the SUBSCRIBEs restoring them.
+#[derive(Debug, Clone)]
+pub struct SubscriptionLoss {
+    /// Ids of the subscriptions that no longer exist.
+    pub lost: Vec<WampId>,
+    /// SUBSCRIBEs to send, with fresh request ids.
+    pub resubscribes: Vec<Subscribe>,
+}
+
+/// Keeps the subscriptions a client wants alive across broker resets.
+///
+/// Every SUBSCRIBE goes through [`subscribe`](Resubscriber::subscribe), every message from the
+/// router through [`on_message`](Resubscriber::on_message). A subscription is considered lost
+/// when the broker revokes it with [`BROKER_RESET`], and all of them when a WELCOME announces
+/// an [`EPOCH`] other than the last one seen, or none at all after one was seen. Lost
+/// subscriptions are subscribed again with their original topic and options.
+/// # Examples
+/// ```
+/// use wamp_helpers::client::Resubscriber;
+/// use wamp_helpers::messages::{Message, Subscribe};
+/// use wamp_helpers::sim::SequentialIdGenerator;
+///
+/// let mut ids = SequentialIdGenerator::default();
+/// let mut resubscriber = Resubscriber::new();
+/// let welcome = Message::parse_message(r#"[2, 1, {"x_epoch": 1}]"#).unwrap();
+/// assert!(resubscriber.on_message(&welcome, &mut ids).is_none());
+///
+/// let subscribe: Subscribe = r#"[32, 7, {"match": "prefix"}, "com.example"]"#.parse().unwrap();
+/// resubscriber.subscribe(subscribe);
+/// let subscribed = Message::parse_message("[33, 7, 5512315355]").unwrap();
+/// assert!(resubscriber.on_message(&subscribed, &mut ids).is_none());
+/// assert_eq!(resubscriber.topic(5512315355), Some("com.example"));
+///
+/// // The session was resumed on a broker that restarted in the meantime.
+/// let welcome = Message::parse_message(r#"[2, 1, {"x_epoch": 2}]"#).unwrap();
+/// let loss = resubscriber.on_message(&welcome, &mut ids).unwrap();
+/// assert_eq!(loss.lost, [5512315355]);
+/// assert_eq!(loss.resubscribes[0].topic, "com.example");
+/// assert_eq!(loss.resubscribes[0].options["match"], "prefix");
+/// assert!(resubscriber.topic(5512315355).is_none());
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Resubscriber {
+    epoch: Option<JsonValue>,
+    /// SUBSCRIBEs awaiting their reply, by request id.
+    pending: HashMap<WampId, Subscribe>,
+    active: BTreeMap<WampId, Subscribe>,
+}
+
+impl Resubscriber {
+    pub fn new() -> Self {
+        Resubscriber::default()
+    }
+
+    /// Remember a SUBSCRIBE being sent.
+    pub fn subscribe(&mut self, subscribe: Subscribe) {
+        self.pending.insert(subscribe.request, subscribe);
+    }
+
+    /// Stop restoring a subscription the client unsubscribed from.
+    pub fn forget(&mut self, subscription: WampId) -> Option<Subscribe> {
+        self.active.remove(&subscription)
+    }
+
+    /// Track `message` from the router, returns the subscriptions it revealed as lost.
+    pub fn on_message(
+        &mut self,
+        message: &Message,
+        ids: &mut impl IdGenerator,
+    ) -> Option<SubscriptionLoss> {
+        match message {
+            Message::Subscribed(subscribed) => {
+                let subscribe = self.pending.remove(&subscribed.request)?;
+                self.active.insert(subscribed.subscription, subscribe);
+                None
+            }
+            Message::ErrorMessage(error) if error.request_type == Subscribe::ID => {
+                self.pending.remove(&error.request);
+                None
+            }
+            Message::Unsubscribed(unsubscribed) => {
+                let details = unsubscribed.details.as_ref()?;
+                let subscription = details["subscription"].as_u64()?;
+                if details["reason"].as_str() == Some(BROKER_RESET) {
+                    self.resubscribe(vec![subscription], ids)
+                } else {
+                    self.active.remove(&subscription);
+                    None
+                }
+            }
+            Message::Welcome(welcome) => {
+                let epoch = welcome.details[EPOCH].clone();
+                let previous = self.epoch.replace(epoch.clone());
+                match previous {
+                    Some(previous) if previous != epoch => {
+                        let lost = self.active.keys().copied().collect();
+                        self.resubscribe(lost, ids)
+                    }
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
+    fn resubscribe(
+        &mut self,
+        lost: Vec<WampId>,
+        ids: &mut impl IdGenerator,
+    ) -> Option<SubscriptionLoss> {
+        let mut loss = SubscriptionLoss {
+            lost: Vec::new(),
+            resubscribes: Vec::new(),
+        };
+        for subscription in lost {
+            let Some(mut subscribe) = self.active.remove(&subscription) else {
+                continue;
+            };
+            subscribe.request = ids.next_id();
+            self.pending.insert(subscribe.request, subscribe.clone());
+            loss.lost.push(subscription);
+            loss.resubscribes.push(subscribe);
+        }
+        (!loss.lost.is_empty()).then_some(loss)
+    }
+
+    /// Topic of an active subscription.
+    pub fn topic(&self, subscription: WampId) -> Option<&str> {
+        self.active
+            .get(&subscription)
+            .map(|subscribe| subscribe.topic.as_str())
+    }
+
+    /// Number of subscriptions that are active or being (re)subscribed.
+    pub fn len(&self) -> usize {
+        self.active.len() + self.pending.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Liveness of a registration as seen by its callee.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RegistrationState {
+    Active,
+    /// The handle was dropped, the UNREGISTER is pending.
+    Unregistering,
+    Unregistered,
+    /// The router removed the registration on its own.
+    Revoked,
+    /// The session to the router ended while the registration was active.
+    RouterLost,
+}
+
+#[derive(Debug)]
+struct RegistrationShared {
+    state: RegistrationState,
+    watchers: Vec<Sender<RegistrationState>>,
+}
+
+impl RegistrationShared {
+    fn set(shared: &Mutex<RegistrationShared>, state: RegistrationState) {
+        // Watchers never panic while holding the lock, a poisoned one is still consistent.
+        let mut shared = shared
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        if shared.state != state {
+            shared.state = state;
+            shared
+                .watchers
+                .retain(|watcher| watcher.send(state).is_ok());
+        }
+    }
+}
+
+/// Handle of one registration, handed out by [`Registrations::track`].
+///
+/// Dropping it or calling [`unregister`](Registration::unregister) queues an UNREGISTER with
+/// the [`Registrations`] it came from. The state is shared with the tracker, so the callee
+/// learns about revocation and router loss through [`state`](Registration::state) or a
+/// [`watch`](Registration::watch) receiver, which outlive the handle.
+#[derive(Debug)]
+pub struct Registration {
+    registration: WampId,
+    shared: Arc<Mutex<RegistrationShared>>,
+    closed: Option<Sender<WampId>>,
+}
+
+impl Registration {
+    pub fn id(&self) -> WampId {
+        self.registration
+    }
+
+    pub fn state(&self) -> RegistrationState {
+        self.shared
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .state
+    }
+
+    /// Receiver of every later state change.
+    pub fn watch(&self) -> Receiver<RegistrationState> {
+        let (sender, receiver) = channel();
+        self.shared
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .watchers
+            .push(sender);
+        receiver
+    }
+
+    /// Unregister now, same as dropping the handle.
+    pub fn unregister(self) {}
+
+    /// Keep the registration for the rest of the session, dropping the handle no longer
+    /// unregisters.
+    pub fn detach(mut self) -> WampId {
+        self.closed = None;
+        self.registration
+    }
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        if let Some(closed) = self.closed.take() {
+            let _ = closed.send(self.registration);
+        }
+    }
+}
+
+/// Callee-side bookkeeping of registrations with [`Registration`] handles, the counterpart
+/// of [`Subscriptions`].
+/// # Examples
+/// ```
+/// use wamp_helpers::client::{RegistrationState, Registrations};
+/// use wamp_helpers::messages::{Message, Registered};
+/// use wamp_helpers::sim::SequentialIdGenerator;
+///
+/// let mut ids = SequentialIdGenerator::default();
+/// let mut registrations = Registrations::new();
+/// let registered: Registered = "[65, 1, 2103333224]".parse().unwrap();
+/// let handle = registrations.track(&registered);
+/// let changes = handle.watch();
+///
+/// let revoked = Message::parse_message(
+///     r#"[67, 0, {"registration": 2103333224, "reason": "wamp.error.unavailable"}]"#,
+/// )
+/// .unwrap();
+/// assert_eq!(registrations.on_message(&revoked), Some(2103333224));
+/// assert_eq!(handle.state(), RegistrationState::Revoked);
+/// assert_eq!(changes.try_recv(), Ok(RegistrationState::Revoked));
+///
+/// // Nothing left to unregister.
+/// handle.unregister();
+/// assert!(registrations.unregisters(&mut ids).is_empty());
+/// ```
+#[derive(Debug)]
+pub struct Registrations {
+    closed: Sender<WampId>,
+    dropped: Receiver<WampId>,
+    active: HashMap<WampId, Arc<Mutex<RegistrationShared>>>,
+    /// UNREGISTER request ids awaiting their reply.
+    unregistering: HashMap<WampId, (WampId, Arc<Mutex<RegistrationShared>>)>,
+}
+
+impl Default for Registrations {
+    fn default() -> Self {
+        let (closed, dropped) = channel();
+        Registrations {
+            closed,
+            dropped,
+            active: HashMap::new(),
+            unregistering: HashMap::new(),
+        }
+    }
+}
+
+impl Registrations {
+    pub fn new() -> Self {
+        Registrations::default()
+    }
+
+    /// Handle of the registration a REGISTERED confirmed.
+    pub fn track(&mut self, registered: &Registered) -> Registration {
+        let shared = Arc::new(Mutex::new(RegistrationShared {
+            state: RegistrationState::Active,
+            watchers: Vec::new(),
+        }));
+        self.active
+            .insert(registered.registration, Arc::clone(&shared));
+        Registration {
+            registration: registered.registration,
+            shared,
+            closed: Some(self.closed.clone()),
+        }
+    }
+
+    /// UNREGISTER for every handle dropped or unregistered since the last call.
+    pub fn unregisters(&mut self, ids: &mut impl IdGenerator) -> Vec<Unregister> {
+        let mut unregisters = Vec::new();
+        while let Ok(registration) = self.dropped.try_recv() {
+            let Some(shared) = self.active.remove(&registration) else {
+                continue;
+            };
+            RegistrationShared::set(&shared, RegistrationState::Unregistering);
+            let request = ids.next_id();
+            self.unregistering.insert(request, (registration, shared));
+            unregisters.push(Unregister {
+                request,
+                registration,
+            });
+        }
+        unregisters
+    }
+
+    /// The registration that ended with `message`: an UNREGISTERED or ERROR answering one of
+    /// our UNREGISTERs, or an UNREGISTERED revoking a registration.
+    pub fn on_message(&mut self, message: &Message) -> Option<WampId> {
+        let (registration, shared, state) = match message {
+            Message::Unregistered(unregistered) => match &unregistered.details {
+                Some(details) => {
+                    let registration = details["registration"].as_u64()?;
+                    let shared = self.active.remove(&registration)?;
+                    (registration, shared, RegistrationState::Revoked)
+                }
+                None => {
+                    let (registration, shared) =
+                        self.unregistering.remove(&unregistered.request)?;
+                    (registration, shared, RegistrationState::Unregistered)
+                }
+            },
+            Message::ErrorMessage(error) if error.request_type == Unregister::ID => {
+                let (registration, shared) = self.unregistering.remove(&error.request)?;
+                (registration, shared, RegistrationState::Unregistered)
+            }
+            _ => return None,
+        };
+        RegistrationShared::set(&shared, state);
+        Some(registration)
+    }
+
+    /// The session ended: every registration still known is lost, returns their ids.
+    pub fn router_lost(&mut self) -> Vec<WampId> {
+        let active = self.active.drain();
+        let unregistering = self.unregistering.drain().map(|(_, pending)| pending);
+        let mut lost = Vec::new();
+        for (registration, shared) in active.chain(unregistering) {
+            RegistrationShared::set(&shared, RegistrationState::RouterLost);
+            lost.push(registration);
+        }
+        lost
+    }
+
+    pub fn state(&self, registration: WampId) -> Option<RegistrationState> {
+        let shared = match self.active.get(&registration) {
+            Some(shared) => shared,
+            None => {
+                &self
+                    .unregistering
+                    .values()
+                    .find(|(pending, _)| *pending == registration)?
+                    .1
+            }
+        };
+        Some(
+            shared
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+                .state,
+        )
+    }
+
+    /// Number of registrations that are active or being unregistered.
+    pub fn len(&self) -> usize {
+        self.active.len() + self.unregistering.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Callee-side pool running at most `limit` invocations at once, the counterpart of the
+/// `concurrency` option the callee registered with.
+///
+/// Invocations beyond the limit wait in arrival order, each completed one lets the next
+/// waiting invocation run.
+/// # Examples
+/// ```
+/// use wamp_helpers::client::ExecutionPool;
+/// use wamp_helpers::messages::Invocation;
+///
+/// let mut pool = ExecutionPool::new(1);
+/// let first: Invocation = "[68, 1, 9, {}]".parse().unwrap();
+/// let second: Invocation = "[68, 2, 9, {}]".parse().unwrap();
+///
+/// assert_eq!(pool.submit(first).map(|run| run.request), Some(1));
+/// assert!(pool.submit(second).is_none());
+/// assert_eq!(pool.complete(1).map(|run| run.request), Some(2));
+/// assert!(pool.complete(2).is_none());
+/// assert!(pool.is_idle());
+/// ```
+#[derive(Debug, Clone)]
+pub struct ExecutionPool {
+    limit: usize,
+    running: HashSet<WampId>,
+    waiting: VecDeque<Invocation>,
+}
+
+impl ExecutionPool {
+    pub fn new(limit: usize) -> Self {
+        ExecutionPool {
+            limit: limit.max(1),
+            running: HashSet::new(),
+            waiting: VecDeque::new(),
+        }
+    }
+
+    /// The invocation back when it may run now, otherwise it waits.
+    pub fn submit(&mut self, invocation: Invocation) -> Option<Invocation> {
+        if self.running.len() < self.limit {
+            self.running.insert(invocation.request);
+            Some(invocation)
+        } else {
+            self.waiting.push_back(invocation);
+            None
+        }
+    }
+
+    /// `request` finished, returns the invocation to run next.
+    pub fn complete(&mut self, request: WampId) -> Option<Invocation> {
+        if !self.running.remove(&request) {
+            // Interrupted before it ran.
+            self.waiting.retain(|waiting| waiting.request != request);
+            return None;
+        }
+        let next = self.waiting.pop_front()?;
+        self.running.insert(next.request);
+        Some(next)
+    }
+
+    pub fn running(&self) -> usize {
+        self.running.len()
+    }
+
+    pub fn waiting(&self) -> usize {
+        self.waiting.len()
+    }
+
+    pub fn is_idle(&self) -> bool {
+        self.running.is_empty() && self.waiting.is_empty()
+    }
+}
+
+/// Index of a session in a [`SessionPool`].
+pub type SessionSlot = usize;
+
+#[derive(Debug, Clone, Default)]
+struct PooledSession {
+    ready: bool,
+    ids: SequentialIdGenerator,
+    /// Routed messages awaiting their answer, by request id.
+    in_flight: HashMap<WampId, Message>,
+}
+
+/// Spreads CALLs and PUBLISHes over several sessions to the same router and realm.
+///
+/// Each message passed to [`route`](SessionPool::route) goes to the ready session with the
+/// fewest unanswered requests, and gets its request id from that session. Feed every
+/// message a session receives to [`on_message`](SessionPool::on_message). When a session
+/// fails, [`on_failure`](SessionPool::on_failure) takes it out of rotation and hands back
+/// what it left unanswered, to be routed again: the callee may already have run those calls,
+/// so only resend the ones that are safe to repeat. Other requests on a pooled session take
+/// their ids from [`next_request`](SessionPool::next_request) so they cannot collide.
+///
+/// Only CALLs and PUBLISHes with `acknowledge` expect an answer, other publications are
+/// routed but not tracked.
+/// # Examples
+/// ```
+/// use wamp_helpers::client::SessionPool;
+/// use wamp_helpers::messages::Message;
+///
+/// let mut pool = SessionPool::new(2);
+/// pool.on_ready(0);
+/// pool.on_ready(1);
+///
+/// let mut call = Message::parse_message(r#"[48, 1, {}, "com.example.add", [1, 2]]"#).unwrap();
+/// let first = pool.route(&mut call).unwrap();
+/// let second = pool.route(&mut call.clone()).unwrap();
+/// assert_ne!(first, second);
+///
+/// // The first session drops, its call moves to the other one.
+/// let mut unanswered = pool.on_failure(first);
+/// assert_eq!(unanswered, [call]);
+/// assert_eq!(pool.route(&mut unanswered[0]), Some(second));
+/// assert_eq!(unanswered[0].request_id(), Some(2));
+///
+/// let result = Message::parse_message("[50, 2, {}, [3]]").unwrap();
+/// assert!(pool.on_message(second, &result));
+/// assert_eq!(pool.in_flight(second), 1);
+/// ```
+#[derive(Debug, Clone)]
+pub struct SessionPool {
+    sessions: Vec<PooledSession>,
+    /// Session preferred among equally loaded ones, rotating.
+    next: SessionSlot,
+}
+
+impl SessionPool {
+    /// A pool of `size` sessions, none of them ready yet.
+    pub fn new(size: usize) -> Self {
+        SessionPool {
+            sessions: vec![PooledSession::default(); size.max(1)],
+            next: 0,
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.sessions.len()
+    }
+
+    /// The session in `slot` was welcomed, after a first connect or a reconnect.
+    pub fn on_ready(&mut self, slot: SessionSlot) {
+        if let Some(session) = self.sessions.get_mut(slot) {
+            session.ready = true;
+        }
+    }
+
+    pub fn is_ready(&self, slot: SessionSlot) -> bool {
+        self.sessions.get(slot).is_some_and(|session| session.ready)
+    }
+
+    /// Number of sessions taking messages.
+    pub fn ready(&self) -> usize {
+        self.sessions.iter().filter(|session| session.ready).count()
+    }
+
+    /// Unanswered requests routed to the session in `slot`.
+    pub fn in_flight(&self, slot: SessionSlot) -> usize {
+        self.sessions
+            .get(slot)
+            .map_or(0, |session| session.in_flight.len())
+    }
+
+    /// A request id for a message sent on the session in `slot` outside the pool.
+    pub fn next_request(&mut self, slot: SessionSlot) -> Option<WampId> {
+        self.sessions
+            .get_mut(slot)
+            .map(|session| session.ids.next_id())
+    }
+
+    /// Pick a session for a CALL or PUBLISH and give the message a request id of that
+    /// session. `None` when no session is ready or the message is of another type.
+    pub fn route(&mut self, message: &mut Message) -> Option<SessionSlot> {
+        let tracked = match message {
+            Message::Call(_) => true,
+            Message::Publish(publish) => publish.options["acknowledge"].as_bool() == Some(true),
+            _ => return None,
+        };
+        let size = self.sessions.len();
+        let slot = (0..size)
+            .map(|offset| (self.next + offset) % size)
+            .filter(|&slot| self.sessions[slot].ready)
+            .min_by_key(|&slot| self.sessions[slot].in_flight.len())?;
+        self.next = (slot + 1) % size;
+
+        let session = &mut self.sessions[slot];
+        let request = session.ids.next_id();
+        match message {
+            Message::Call(call) => call.request = request,
+            Message::Publish(publish) => publish.request = request,
+            _ => unreachable!("only CALL and PUBLISH are routed"),
+        }
+        if tracked {
+            session.in_flight.insert(request, message.clone());
+        }
+        Some(slot)
+    }
+
+    /// Track a message received on the session in `slot`, returns whether it answered a
+    /// routed request.
+    pub fn on_message(&mut self, slot: SessionSlot, message: &Message) -> bool {
+        let Some(session) = self.sessions.get_mut(slot) else {
+            return false;
+        };
+        let request = match message {
+            Message::MessageResult(result) => {
+                if result.details["progress"].as_bool() == Some(true) {
+                    return false;
+                }
+                result.request
+            }
+            Message::Published(published) => published.request,
+            Message::ErrorMessage(error)
+                if error.request_type == Call::ID || error.request_type == Publish::ID =>
+            {
+                error.request
+            }
+            _ => return false,
+        };
+        session.in_flight.remove(&request).is_some()
+    }
+
+    /// The session in `slot` failed, it takes no messages until it is ready again. Returns
+    /// its unanswered CALLs and PUBLISHes in the order they were routed.
+    pub fn on_failure(&mut self, slot: SessionSlot) -> Vec<Message> {
+        let Some(session) = self.sessions.get_mut(slot) else {
+            return Vec::new();
+        };
+        session.ready = false;
+        let mut unanswered: Vec<_> = session.in_flight.drain().collect();
+        unanswered.sort_by_key(|(request, _)| *request);
+        unanswered.into_iter().map(|(_, message)| message).collect()
+    }
+}
+
+/// FNV-1a over `parts`, stable across processes and releases unlike `DefaultHasher`, which
+/// matters when publishers and subscribers have to agree on a shard.
+fn stable_hash(parts: &[&[u8]]) -> u64 {
+    let mut hash = 0xcbf2_9ce4_8422_2325_u64;
+    for byte in parts.iter().flat_map(|part| part.iter()) {
+        hash ^= u64::from(*byte);
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    hash
+}
+
+/// Splits the events of a topic over a fixed number of shard topics, by the value of one
+/// keyword argument.
+///
+/// Publishers send to the shard topic of each event's key, so all events with the same key
+/// land on the same shard, and a group of subscriber instances divides the shards among
+/// themselves with [`ShardedSubscriber`]. The shard of a key only depends on the key and the
+/// number of shards, every instance computes the same one.
+/// # Examples
+/// ```
+/// use wamp_helpers::client::TopicSharding;
+/// use wamp_helpers::messages::Publish;
+///
+/// let sharding = TopicSharding::new("com.example.order", 8, "customer");
+/// let mut publish: Publish = r#"[16, 1, {}, "com.example.order", [], {"customer": "c-17"}]"#.parse().unwrap();
+/// let shard = sharding.route(&mut publish).unwrap();
+/// assert!(shard < 8);
+/// assert_eq!(publish.topic, sharding.shard_topic(shard));
+///
+/// // Without the key the publication is left alone.
+/// let mut publish: Publish = r#"[16, 2, {}, "com.example.order", ["c-17"]]"#.parse().unwrap();
+/// assert_eq!(sharding.route(&mut publish), None);
+/// assert_eq!(publish.topic, "com.example.order");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TopicSharding {
+    topic: String,
+    shards: u32,
+    key: String,
+}
+
+impl TopicSharding {
+    /// Shard `topic` over `shards` shard topics by the keyword argument `key`.
+    pub fn new(topic: impl Into<String>, shards: u32, key: impl Into<String>) -> Self {
+        TopicSharding {
+            topic: topic.into(),
+            shards: shards.max(1),
+            key: key.into(),
+        }
+    }
+
+    pub fn topic(&self) -> &str {
+        &self.topic
+    }
+
+    pub fn shards(&self) -> u32 {
+        self.shards
+    }
+
+    /// Topic of `shard`, the sharded topic followed by the shard number.
+    pub fn shard_topic(&self, shard: u32) -> Uri {
+        Uri::from(format!("{}.{shard}", self.topic))
+    }
+
+    /// Shard the events with the key `value` go to.
+    pub fn shard_of(&self, value: &WampValue) -> u32 {
+        let key = to_canonical_string(&JsonValue::from(value.clone()));
+        (stable_hash(&[key.as_bytes()]) % u64::from(self.shards)) as u32
+    }
+
+    /// Send `publish` to the shard topic of its key, `None` and untouched when its
+    /// keyword arguments have no key.
+    pub fn route(&self, publish: &mut Publish) -> Option<u32> {
+        let value = publish.kwargs.as_ref()?.get(&self.key)?;
+        let shard = self.shard_of(value);
+        publish.topic = self.shard_topic(shard);
+        Some(shard)
+    }
+
+    /// Whether `member` of the subscriber group `members` handles `shard`. Shards are
+    /// assigned by rendezvous hashing, so a member joining or leaving only moves the shards
+    /// it takes or gives up.
+    pub fn owns<S: AsRef<str>>(&self, member: &str, members: &[S], shard: u32) -> bool {
+        let shard = shard.to_be_bytes();
+        let weight = |member: &str| stable_hash(&[member.as_bytes(), &[0], &shard]);
+        members
+            .iter()
+            .map(AsRef::as_ref)
+            .max_by_key(|member| (weight(member), *member))
+            == Some(member)
+    }
+}
+
+/// Subscriptions a change of group membership calls for.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ShardChange {
+    pub subscribes: Vec<Subscribe>,
+    pub unsubscribes: Vec<Unsubscribe>,
+}
+
+/// One member of a group of subscriber instances sharing a [`TopicSharding`].
+///
+/// Pass the current members of the group, this one included, to
+/// [`on_membership`](ShardedSubscriber::on_membership) at startup and whenever the group
+/// changes, and send the SUBSCRIBEs and UNSUBSCRIBEs it returns. SUBSCRIBED and ERROR replies
+/// go to [`on_message`](ShardedSubscriber::on_message). How members learn about each other,
+/// e.g. through the session meta API or a registry topic, is up to the application.
+/// # Examples
+/// ```
+/// use wamp_helpers::client::{ShardedSubscriber, TopicSharding};
+/// use wamp_helpers::messages::Message;
+/// use wamp_helpers::sim::SequentialIdGenerator;
+///
+/// let sharding = TopicSharding::new("com.example.order", 4, "customer");
+/// let mut ids = SequentialIdGenerator::default();
+/// let mut subscriber = ShardedSubscriber::new(sharding, "worker-a");
+///
+/// // Alone in the group, the member takes every shard.
+/// let change = subscriber.on_membership(&["worker-a"], &mut ids);
+/// assert_eq!(change.subscribes.len(), 4);
+/// for subscribe in &change.subscribes {
+///     let subscribed = format!("[33, {}, {}]", subscribe.request, 100 + subscribe.request);
+///     subscriber.on_message(&Message::parse_message(&subscribed).unwrap(), &mut ids);
+/// }
+///
+/// // A second worker joins and takes over some shards.
+/// let change = subscriber.on_membership(&["worker-a", "worker-b"], &mut ids);
+/// assert!(change.subscribes.is_empty());
+/// assert_eq!(change.unsubscribes.len(), 4 - subscriber.owned().count());
+///
+/// // A shard the router refused is not handled, and asked for again on the next change.
+/// let sharding = TopicSharding::new("com.example.order", 1, "customer");
+/// let mut subscriber = ShardedSubscriber::new(sharding, "worker-a");
+/// let change = subscriber.on_membership(&["worker-a"], &mut ids);
+/// let error = format!(r#"[8, 32, {}, {{}}, "wamp.error.not_authorized"]"#, change.subscribes[0].request);
+/// subscriber.on_message(&Message::parse_message(&error).unwrap(), &mut ids);
+/// assert_eq!(subscriber.owned().count(), 0);
+/// assert_eq!(subscriber.on_membership(&["worker-a"], &mut ids).subscribes.len(), 1);
+/// ```
+#[derive(Debug, Clone)]
+pub struct ShardedSubscriber {
+    sharding: TopicSharding,
+    member: String,
+    options: Options,
+    owned: BTreeSet<u32>,
+    /// SUBSCRIBE request ids awaiting their reply, with the shard.
+    pending: HashMap<WampId, u32>,
+    /// Subscriptions by shard.
+    active: BTreeMap<u32, WampId>,
+}
+
+impl ShardedSubscriber {
+    pub fn new(sharding: TopicSharding, member: impl Into<String>) -> Self {
+        ShardedSubscriber {
+            sharding,
+            member: member.into(),
+            options: JsonValue::new_object(),
+            owned: BTreeSet::new(),
+            pending: HashMap::new(),
+            active: BTreeMap::new(),
+        }
+    }
+
+    /// SUBSCRIBE options of the shard subscriptions.
+    pub fn options(mut self, options: Options) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub fn sharding(&self) -> &TopicSharding {
+        &self.sharding
+    }
+
+    /// Shards this member handles.
+    pub fn owned(&self) -> impl Iterator<Item = u32> + '_ {
+        self.owned.iter().copied()
+    }
+
+    /// Shard of an active subscription.
+    pub fn shard(&self, subscription: WampId) -> Option<u32> {
+        self.active
+            .iter()
+            .find(|(_, active)| **active == subscription)
+            .map(|(shard, _)| *shard)
+    }
+
+    /// The group now consists of `members`, subscribe to the shards this member gained and
+    /// unsubscribe from those it lost.
+    pub fn on_membership<S: AsRef<str>>(
+        &mut self,
+        members: &[S],
+        ids: &mut impl IdGenerator,
+    ) -> ShardChange {
+        let owned: BTreeSet<u32> = (0..self.sharding.shards())
+            .filter(|&shard| self.sharding.owns(&self.member, members, shard))
+            .collect();
+        let mut change = ShardChange::default();
+        for &shard in owned.difference(&self.owned) {
+            // Still subscribed from an earlier membership, or about to be.
+            if self.active.contains_key(&shard) || self.pending.values().any(|s| *s == shard) {
+                continue;
+            }
+            let request = ids.next_id();
+            self.pending.insert(request, shard);
+            change.subscribes.push(Subscribe {
+                request,
+                options: self.options.clone(),
+                topic: self.sharding.shard_topic(shard),
+            });
+        }
+        self.owned = owned;
+        let lost: Vec<u32> = self
+            .active
+            .keys()
+            .filter(|shard| !self.owned.contains(shard))
+            .copied()
+            .collect();
+        for shard in lost {
+            change.unsubscribes.push(self.unsubscribe(shard, ids));
+        }
+        change
+    }
+
+    fn unsubscribe(&mut self, shard: u32, ids: &mut impl IdGenerator) -> Unsubscribe {
+        let subscription = self.active.remove(&shard).unwrap_or_default();
+        Unsubscribe {
+            request: ids.next_id(),
+            subscription,
+        }
+    }
+
+    /// Track a reply to a shard SUBSCRIBE. Returns the UNSUBSCRIBE to send when the shard
+    /// moved to another member while the SUBSCRIBE was on its way. A refused SUBSCRIBE
+    /// leaves the shard unowned, the next [`on_membership`](Self::on_membership) tries it
+    /// again.
+    pub fn on_message(
+        &mut self,
+        message: &Message,
+        ids: &mut impl IdGenerator,
+    ) -> Option<Unsubscribe> {
+        match message {
+            Message::Subscribed(subscribed) => {
+                let shard = self.pending.remove(&subscribed.request)?;
+                self.active.insert(shard, subscribed.subscription);
+                (!self.owned.contains(&shard)).then(|| self.unsubscribe(shard, ids))
+            }
+            Message::ErrorMessage(error) if error.request_type == Subscribe::ID => {
+                if let Some(shard) = self.pending.remove(&error.request) {
+                    self.owned.remove(&shard);
+                }
+                None
+            }
+            _ => None,
+        }
+    }
+}
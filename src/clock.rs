@@ -0,0 +1,111 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Source of the current time, injected wherever timeouts are computed so tests and
+/// simulations can control time.
+///
+/// The helpers of this crate take the current time as an argument (`at`, `now`,
+/// `deadline`), callers read it from a `Clock` once per event.
+pub trait Clock {
+    fn now(&self) -> Instant;
+}
+
+impl<C: Clock + ?Sized> Clock for &C {
+    fn now(&self) -> Instant {
+        (**self).now()
+    }
+}
+
+impl<C: Clock + ?Sized> Clock for Arc<C> {
+    fn now(&self) -> Instant {
+        (**self).now()
+    }
+}
+
+/// The real monotonic clock.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// A clock that only moves when told to, for tests of timeouts, backoff and heartbeats
+/// without sleeping. Clones share the same time.
+/// # Examples
+/// ```
+/// use std::time::Duration;
+/// use wamp_helpers::client::CallRetry;
+/// use wamp_helpers::clock::{Clock, MockClock};
+/// use wamp_helpers::correlation::{Correlator, Direction};
+/// use wamp_helpers::messages::Message;
+///
+/// let clock = MockClock::new();
+/// let mut correlator = Correlator::new();
+/// let call = Message::parse_message(r#"[48, 1, {}, "com.example.slow"]"#).unwrap();
+/// correlator.observe(Direction::Outbound, &call, clock.now());
+///
+/// let timeout = Duration::from_secs(30);
+/// let start = clock.now();
+/// clock.advance(Duration::from_secs(29));
+/// assert!(correlator.expire(clock.now() - timeout + Duration::from_nanos(1)).is_empty());
+///
+/// // Time out, then wait for the backoff before the second attempt.
+/// clock.advance(Duration::from_secs(2));
+/// assert_eq!(correlator.expire(clock.now() - timeout).len(), 1);
+/// clock.advance(CallRetry::new(3).delay(1));
+/// assert_eq!(clock.elapsed(start), Duration::from_millis(31_100));
+/// ```
+#[derive(Debug, Clone)]
+pub struct MockClock {
+    now: Arc<Mutex<Instant>>,
+}
+
+impl Default for MockClock {
+    fn default() -> Self {
+        MockClock::at(Instant::now())
+    }
+}
+
+impl MockClock {
+    /// A clock standing at the moment it was created.
+    pub fn new() -> Self {
+        MockClock::default()
+    }
+
+    pub fn at(now: Instant) -> Self {
+        MockClock {
+            now: Arc::new(Mutex::new(now)),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Instant> {
+        // Holding the lock never panics, a poisoned one still holds a valid time.
+        self.now
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    pub fn advance(&self, by: Duration) {
+        *self.lock() += by;
+    }
+
+    /// Move the clock to `now`, which must not be earlier than the current time.
+    pub fn set(&self, now: Instant) {
+        let mut current = self.lock();
+        *current = now.max(*current);
+    }
+
+    /// Time passed since `earlier` on this clock.
+    pub fn elapsed(&self, earlier: Instant) -> Duration {
+        self.lock().saturating_duration_since(earlier)
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> Instant {
+        *self.lock()
+    }
+}
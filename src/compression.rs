@@ -0,0 +1,147 @@
+use crate::error::Error;
+use crate::messages::{push_payload, validate_args, validate_kwargs, Message};
+use crate::value::WampValue;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use json::JsonValue;
+use std::io::{Read, Write};
+
+/// `ppt_scheme` marking a payload compressed by [`Compressor`].
+pub const GZIP_SCHEME: &str = "x_gzip";
+/// `ppt_serializer` of the compressed payload, the `[args, kwargs]` pair as JSON.
+pub const SERIALIZER: &str = "json";
+/// Default limit of the decompressed payload size in bytes.
+pub const DEFAULT_MAX_DECOMPRESSED: usize = 16 * 1024 * 1024;
+
+/// Opt-in gzip compression of large payloads, transported with payload passthru mode.
+///
+/// A compressed message carries `ppt_scheme: "x_gzip"` and `ppt_serializer: "json"` in its
+/// Details/Options and a single binary argument holding the gzipped `[args, kwargs]` pair.
+/// Routers forward such payloads untouched, only the receiving peer decompresses.
+/// # Examples
+/// ```
+/// use wamp_helpers::compression::Compressor;
+/// use wamp_helpers::messages::Message;
+///
+/// let body = "x".repeat(4096);
+/// let publish = Message::parse_message(&format!(r#"[16, 1, {{}}, "com.example.topic", ["{}"]]"#, body)).unwrap();
+///
+/// let compressor = Compressor::new(1024);
+/// let compressed = compressor.compress(publish).unwrap();
+/// assert_eq!(compressed.details().unwrap()["ppt_scheme"], "x_gzip");
+/// assert!(compressed.clone().to_json().unwrap().dump().len() < 1024);
+///
+/// let restored = Compressor::decompress(compressed).unwrap();
+/// assert!(restored.details().unwrap()["ppt_scheme"].is_null());
+/// assert_eq!(restored.args().unwrap()[0], body.as_str().into());
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct Compressor {
+    threshold: usize,
+    level: u32,
+}
+
+impl Compressor {
+    /// Compress payloads whose JSON encoding is at least `threshold` bytes.
+    pub fn new(threshold: usize) -> Self {
+        Compressor {
+            threshold,
+            level: flate2::Compression::default().level(),
+        }
+    }
+
+    /// gzip level from 0 (none) to 9 (best).
+    pub fn level(mut self, level: u32) -> Self {
+        self.level = level.min(9);
+        self
+    }
+
+    /// Compress the payload of `message` if it is large enough and does not use payload
+    /// passthru mode already.
+    pub fn compress(&self, mut message: Message) -> Result<Message, Error> {
+        let Some((details, args, kwargs)) = message.payload_mut() else {
+            return Ok(message);
+        };
+        if !details["ppt_scheme"].is_null() || (args.is_none() && kwargs.is_none()) {
+            return Ok(message);
+        }
+
+        let mut payload = JsonValue::new_array();
+        push_payload(&mut payload, args.clone(), kwargs.clone())?;
+        let raw = payload.dump();
+        if raw.len() < self.threshold {
+            return Ok(message);
+        }
+
+        let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::new(self.level));
+        encoder.write_all(raw.as_bytes()).map_err(Error::Io)?;
+        let compressed = encoder.finish().map_err(Error::Io)?;
+
+        details["ppt_scheme"] = GZIP_SCHEME.into();
+        details["ppt_serializer"] = SERIALIZER.into();
+        *args = Some(vec![WampValue::Bytes(compressed)]);
+        *kwargs = None;
+        Ok(message)
+    }
+
+    /// Restore the payload of a message compressed by [`compress`](Compressor::compress),
+    /// other messages are returned unchanged. Payloads decompressing to more than
+    /// [`DEFAULT_MAX_DECOMPRESSED`] bytes are refused.
+    pub fn decompress(message: Message) -> Result<Message, Error> {
+        Compressor::decompress_limited(message, DEFAULT_MAX_DECOMPRESSED)
+    }
+
+    /// [`decompress`](Compressor::decompress) refusing payloads that decompress to more than
+    /// `limit` bytes with [`Error::PayloadTooLarge`], a few compressed kilobytes can otherwise
+    /// expand to gigabytes.
+    /// # Examples
+    /// ```
+    /// use wamp_helpers::compression::Compressor;
+    /// use wamp_helpers::error::Error;
+    /// use wamp_helpers::messages::Message;
+    ///
+    /// let body = "x".repeat(64 * 1024);
+    /// let publish = Message::parse_message(&format!(r#"[16, 1, {{}}, "com.example.topic", ["{}"]]"#, body)).unwrap();
+    /// let compressed = Compressor::new(0).compress(publish).unwrap();
+    ///
+    /// let refused = Compressor::decompress_limited(compressed.clone(), 1024);
+    /// assert!(matches!(refused, Err(Error::PayloadTooLarge { limit: 1024 })));
+    /// assert!(Compressor::decompress_limited(compressed, 128 * 1024).is_ok());
+    /// ```
+    pub fn decompress_limited(mut message: Message, limit: usize) -> Result<Message, Error> {
+        let Some((details, args, kwargs)) = message.payload_mut() else {
+            return Ok(message);
+        };
+        if details["ppt_scheme"] != GZIP_SCHEME {
+            return Ok(message);
+        }
+
+        let compressed = match args.as_deref() {
+            Some([WampValue::Bytes(compressed)]) if kwargs.is_none() => compressed,
+            _ => {
+                return Err(Error::InvalidChunk {
+                    offense: JsonValue::from(args.clone().map(WampValue::List)),
+                })
+            }
+        };
+        let mut raw = Vec::new();
+        GzDecoder::new(compressed.as_slice())
+            .take(limit as u64 + 1)
+            .read_to_end(&mut raw)
+            .map_err(Error::Io)?;
+        if raw.len() > limit {
+            return Err(Error::PayloadTooLarge { limit });
+        }
+        let raw = String::from_utf8(raw).map_err(|error| {
+            Error::Io(std::io::Error::new(std::io::ErrorKind::InvalidData, error))
+        })?;
+        let mut payload = json::parse(&raw).map_err(Error::JsonError)?;
+
+        let kwargs_json = payload.array_remove(1);
+        *args = validate_args(payload.array_remove(0))?;
+        *kwargs = validate_kwargs(kwargs_json)?;
+        details.remove("ppt_scheme");
+        details.remove("ppt_serializer");
+        Ok(message)
+    }
+}
@@ -0,0 +1,754 @@
+use crate::arity::{arity, ARITY_TABLE};
+use crate::bus::NOT_AUTHORIZED;
+use crate::error::Error;
+use crate::messages::{
+    ErrorMessage, Message, RequestType, Unregistered, Unsubscribed, Uri, WampId,
+};
+use crate::uri::{is_valid_pattern, MatchPolicy};
+use crate::validator::ViolationPolicy;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+/// In-process router configuration modeled after Crossbar's: realms, the roles of each realm
+/// with their URI permissions, and the principals that authenticate into those roles.
+///
+/// Deserialize it with any serde format crate, e.g. `serde_json`, `toml` or `serde_yaml`,
+/// then [`validate`](RouterConfig::validate) it before handing it to the router.
+/// # Examples
+/// ```
+/// use wamp_helpers::config::{Action, Permission, RealmConfig, RoleConfig, RouterConfig};
+/// use wamp_helpers::uri::MatchPolicy;
+///
+/// let config = RouterConfig {
+///     realms: vec![RealmConfig {
+///         name: "realm1".into(),
+///         roles: vec![RoleConfig {
+///             name: "frontend".to_string(),
+///             permissions: vec![Permission {
+///                 uri: "com.example.".into(),
+///                 match_policy: MatchPolicy::Prefix,
+///                 allow: [Action::Call, Action::Subscribe].into(),
+///             }],
+///             ..RoleConfig::default()
+///         }],
+///         ..RealmConfig::default()
+///     }],
+/// };
+/// config.validate().unwrap();
+///
+/// let realm = config.realm("realm1").unwrap();
+/// assert!(realm.authorize("frontend", "com.example.add", Action::Call));
+/// assert!(!realm.authorize("frontend", "com.example.add", Action::Register));
+/// assert!(!realm.authorize("backend", "com.example.add", Action::Call));
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RouterConfig {
+    #[serde(default)]
+    pub realms: Vec<RealmConfig>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RealmConfig {
+    pub name: Uri,
+    #[serde(default)]
+    pub roles: Vec<RoleConfig>,
+    #[serde(default)]
+    pub principals: Vec<Principal>,
+    /// How sessions on the realm handle protocol violations.
+    #[serde(default)]
+    pub violations: ViolationPolicy,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RoleConfig {
+    pub name: String,
+    #[serde(default)]
+    pub permissions: Vec<Permission>,
+    /// Names of the messages the role may send, e.g. `["CALL", "SUBSCRIBE"]`, any message
+    /// when absent. See [`RealmConfig::check_message`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub allow_messages: Option<Vec<String>>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Permission {
+    pub uri: Uri,
+    #[serde(rename = "match", default)]
+    pub match_policy: MatchPolicy,
+    #[serde(default)]
+    pub allow: Allow,
+}
+
+/// The actions a [`Permission`] grants, all denied by default.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Allow {
+    pub call: bool,
+    pub register: bool,
+    pub publish: bool,
+    pub subscribe: bool,
+}
+
+/// A static principal, authenticating with a ticket or a WAMP-CRA secret. Salted secrets
+/// carry `salt`, `iterations` and `keylen`, see [`cra`](crate::cra).
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Principal {
+    pub authid: String,
+    pub role: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ticket: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub secret: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub salt: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub iterations: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub keylen: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Action {
+    Call,
+    Register,
+    Publish,
+    Subscribe,
+}
+
+impl Action {
+    /// The action a CALL, REGISTER, PUBLISH or SUBSCRIBE performs on its URI.
+    pub fn of(message: &Message) -> Option<Action> {
+        match message {
+            Message::Call(_) => Some(Action::Call),
+            Message::Register(_) => Some(Action::Register),
+            Message::Publish(_) => Some(Action::Publish),
+            Message::Subscribe(_) => Some(Action::Subscribe),
+            _ => None,
+        }
+    }
+
+    /// Lowercase name, as passed to a [dynamic authorizer](crate::dynamic::DynamicAuthorizer).
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Action::Call => "call",
+            Action::Register => "register",
+            Action::Publish => "publish",
+            Action::Subscribe => "subscribe",
+        }
+    }
+}
+
+impl Allow {
+    pub fn allows(&self, action: Action) -> bool {
+        match action {
+            Action::Call => self.call,
+            Action::Register => self.register,
+            Action::Publish => self.publish,
+            Action::Subscribe => self.subscribe,
+        }
+    }
+}
+
+impl<const N: usize> From<[Action; N]> for Allow {
+    fn from(actions: [Action; N]) -> Self {
+        let mut allow = Allow::default();
+        for action in actions {
+            match action {
+                Action::Call => allow.call = true,
+                Action::Register => allow.register = true,
+                Action::Publish => allow.publish = true,
+                Action::Subscribe => allow.subscribe = true,
+            }
+        }
+        allow
+    }
+}
+
+impl RouterConfig {
+    pub fn realm(&self, name: &str) -> Option<&RealmConfig> {
+        self.realms.iter().find(|realm| realm.name == name)
+    }
+
+    /// Check the configuration is consistent: unique realm and role names, valid permission
+    /// URIs and principals referring to existing roles.
+    pub fn validate(&self) -> Result<(), Error> {
+        let invalid = |reason: String| Err(Error::InvalidConfig { reason });
+        for (index, realm) in self.realms.iter().enumerate() {
+            if self.realms[..index]
+                .iter()
+                .any(|other| other.name == realm.name)
+            {
+                return invalid(format!("realm {} is configured twice", realm.name));
+            }
+            for (index, role) in realm.roles.iter().enumerate() {
+                if realm.roles[..index]
+                    .iter()
+                    .any(|other| other.name == role.name)
+                {
+                    return invalid(format!("role {} is configured twice", role.name));
+                }
+                for name in role.allow_messages.iter().flatten() {
+                    if !ARITY_TABLE.iter().any(|arity| arity.name == name) {
+                        return invalid(format!("unknown message {name} allowed to {}", role.name));
+                    }
+                }
+                for permission in &role.permissions {
+                    // An empty prefix grants everything, Crossbar's catch-all.
+                    let catch_all =
+                        permission.uri.is_empty() && permission.match_policy == MatchPolicy::Prefix;
+                    if !catch_all && !is_valid_pattern(&permission.uri, false) {
+                        return invalid(format!("invalid permission URI {:?}", permission.uri));
+                    }
+                }
+            }
+            for principal in &realm.principals {
+                if realm.role(&principal.role).is_none() {
+                    return invalid(format!(
+                        "principal {} refers to unknown role {}",
+                        principal.authid, principal.role
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl RealmConfig {
+    pub fn role(&self, name: &str) -> Option<&RoleConfig> {
+        self.roles.iter().find(|role| role.name == name)
+    }
+
+    pub fn principal(&self, authid: &str) -> Option<&Principal> {
+        self.principals
+            .iter()
+            .find(|principal| principal.authid == authid)
+    }
+
+    /// Check that `authrole` may send `message` at all, before looking at its URI. HELLO,
+    /// AUTHENTICATE, GOODBYE and ABORT are always allowed, so a session can be opened and
+    /// closed whatever the role; unknown roles may send nothing else.
+    ///
+    /// Answer a refused request with [`denial`], and publish
+    /// [`message_denied_publication`](crate::meta::message_denied_publication) for the
+    /// audit trail.
+    /// # Examples
+    /// ```
+    /// use wamp_helpers::config::{denial, RealmConfig, RoleConfig};
+    /// use wamp_helpers::error::Error;
+    /// use wamp_helpers::messages::Message;
+    ///
+    /// let realm = RealmConfig {
+    ///     name: "realm1".into(),
+    ///     roles: vec![RoleConfig {
+    ///         name: "frontend".to_string(),
+    ///         allow_messages: Some(vec!["CALL".to_string(), "SUBSCRIBE".to_string()]),
+    ///         ..RoleConfig::default()
+    ///     }],
+    ///     ..RealmConfig::default()
+    /// };
+    ///
+    /// let call = Message::parse_message(r#"[48, 1, {}, "com.example.add"]"#).unwrap();
+    /// assert!(realm.check_message("frontend", &call).is_ok());
+    ///
+    /// let register = Message::parse_message(r#"[64, 2, {}, "com.example.add"]"#).unwrap();
+    /// let error = realm.check_message("frontend", &register).unwrap_err();
+    /// assert!(matches!(error, Error::MessageNotAllowed { message_type: 64, .. }));
+    /// let reply = denial(&register).unwrap();
+    /// assert_eq!((reply.request, reply.error.as_str()), (2, "wamp.error.not_authorized"));
+    ///
+    /// let goodbye = Message::parse_message(r#"[6, {}, "wamp.close.normal"]"#).unwrap();
+    /// assert!(realm.check_message("frontend", &goodbye).is_ok());
+    /// ```
+    pub fn check_message(&self, authrole: &str, message: &Message) -> Result<(), Error> {
+        let allowed = match message {
+            Message::Hello(_)
+            | Message::Authenticate(_)
+            | Message::Goodbye(_)
+            | Message::Abort(_) => true,
+            _ => self.role(authrole).is_some_and(|role| {
+                role.allow_messages.as_ref().is_none_or(|names| {
+                    let name = arity(message.message_id()).map(|arity| arity.name);
+                    names.iter().any(|allowed| Some(allowed.as_str()) == name)
+                })
+            }),
+        };
+        if allowed {
+            Ok(())
+        } else {
+            Err(Error::MessageNotAllowed {
+                authrole: authrole.to_string(),
+                message_type: message.message_id(),
+            })
+        }
+    }
+
+    /// Whether `authrole` may perform `action` on `uri`, unknown roles may do nothing.
+    pub fn authorize(&self, authrole: &str, uri: &str, action: Action) -> bool {
+        self.role(authrole)
+            .and_then(|role| role.permission_for(uri))
+            .is_some_and(|permission| permission.allow.allows(action))
+    }
+}
+
+impl RoleConfig {
+    /// The permission deciding about `uri`: an exact match wins over the longest prefix
+    /// match, which wins over the longest wildcard match.
+    pub fn permission_for(&self, uri: &str) -> Option<&Permission> {
+        let best = |policy: MatchPolicy| {
+            self.permissions
+                .iter()
+                .filter(|permission| permission.match_policy == policy)
+                .filter(|permission| policy.matches(&permission.uri, uri))
+                .max_by_key(|permission| permission.uri.len())
+        };
+        best(MatchPolicy::Exact)
+            .or_else(|| best(MatchPolicy::Prefix))
+            .or_else(|| best(MatchPolicy::Wildcard))
+    }
+}
+
+/// Decision about each [`Action`] and when it was made.
+type Decisions = [Option<(bool, Instant)>; 4];
+/// Decisions by realm, authrole and URI.
+type DecisionTree = HashMap<String, HashMap<String, HashMap<String, Decisions>>>;
+
+/// Default number of (realm, authrole, URI) entries an [`AuthorizationCache`] keeps.
+pub const DEFAULT_MAX_ENTRIES: usize = 10_000;
+
+/// Authorization decisions by realm, authrole, URI and action, each kept for a time to live
+/// so the permissions are not searched again for every PUBLISH and CALL.
+///
+/// At most [`max_entries`](AuthorizationCache::with_max_entries) realm, authrole and URI
+/// combinations are kept, the oldest one is dropped to make room for a new one, so peers
+/// making up URIs cannot grow the cache without bound.
+///
+/// Cached decisions outlive changes to the configuration: drop them with
+/// [`invalidate_realm`](AuthorizationCache::invalidate_realm) when a realm shows up in
+/// [`ConfigDiff::changed`], or more selectively with
+/// [`invalidate`](AuthorizationCache::invalidate) and
+/// [`invalidate_role`](AuthorizationCache::invalidate_role).
+/// # Examples
+/// ```
+/// use std::time::Duration;
+/// use wamp_helpers::clock::{Clock, MockClock};
+/// use wamp_helpers::config::{Action, AuthorizationCache, Permission, RealmConfig, RoleConfig};
+/// use wamp_helpers::messages::Message;
+/// use wamp_helpers::uri::MatchPolicy;
+///
+/// let realm = RealmConfig {
+///     name: "realm1".into(),
+///     roles: vec![RoleConfig {
+///         name: "sensor".to_string(),
+///         permissions: vec![Permission {
+///             uri: "com.example.readings.".into(),
+///             match_policy: MatchPolicy::Prefix,
+///             allow: [Action::Publish].into(),
+///         }],
+///         ..RoleConfig::default()
+///     }],
+///     ..RealmConfig::default()
+/// };
+/// let clock = MockClock::new();
+/// let mut cache = AuthorizationCache::new(Duration::from_secs(60));
+/// let publish = Message::parse_message(r#"[16, 1, {}, "com.example.readings.temp", [21]]"#).unwrap();
+///
+/// for _ in 0..4 {
+///     assert!(cache.authorize_message(&realm, "sensor", &publish, clock.now()));
+/// }
+/// assert_eq!((cache.hits(), cache.misses()), (3, 1));
+/// assert_eq!(cache.hit_rate(), 0.75);
+///
+/// // Stale decisions are decided again.
+/// clock.advance(Duration::from_secs(61));
+/// cache.authorize_message(&realm, "sensor", &publish, clock.now());
+/// assert_eq!(cache.misses(), 2);
+///
+/// // A role of the same name on another realm is decided on its own.
+/// let other = RealmConfig { name: "realm2".into(), ..RealmConfig::default() };
+/// assert!(!cache.authorize_message(&other, "sensor", &publish, clock.now()));
+/// assert!(cache.authorize_message(&realm, "sensor", &publish, clock.now()));
+///
+/// cache.invalidate_role("realm1", "sensor");
+/// cache.invalidate_realm("realm2");
+/// assert!(cache.is_empty());
+///
+/// // The oldest entry makes room for a new one.
+/// let mut cache = AuthorizationCache::with_max_entries(Duration::from_secs(60), 2);
+/// for uri in ["com.example.a", "com.example.b", "com.example.c"] {
+///     cache.insert("realm1", "sensor", uri, Action::Call, true, clock.now());
+/// }
+/// assert_eq!(cache.len(), 2);
+/// assert!(!cache.authorize("realm1", "sensor", "com.example.a", Action::Call, clock.now(), || false));
+/// ```
+#[derive(Debug, Clone)]
+pub struct AuthorizationCache {
+    ttl: Duration,
+    max_entries: usize,
+    /// Decisions and when they were made, by realm, authrole, URI and action.
+    decisions: DecisionTree,
+    /// The (realm, authrole, URI) entries of `decisions`, oldest first.
+    order: VecDeque<(String, String, String)>,
+    hits: u64,
+    misses: u64,
+}
+
+impl AuthorizationCache {
+    pub fn new(ttl: Duration) -> Self {
+        AuthorizationCache::with_max_entries(ttl, DEFAULT_MAX_ENTRIES)
+    }
+
+    /// Cache keeping decisions about at most `max_entries` realm, authrole and URI
+    /// combinations.
+    pub fn with_max_entries(ttl: Duration, max_entries: usize) -> Self {
+        AuthorizationCache {
+            ttl,
+            max_entries,
+            decisions: HashMap::new(),
+            order: VecDeque::new(),
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    /// The cached decision whether `authrole` on `realm` may perform `action` on `uri`,
+    /// `decide` is called and its answer cached when there is none younger than the time to
+    /// live.
+    pub fn authorize(
+        &mut self,
+        realm: &str,
+        authrole: &str,
+        uri: &str,
+        action: Action,
+        now: Instant,
+        decide: impl FnOnce() -> bool,
+    ) -> bool {
+        let cached = self
+            .decisions
+            .get(realm)
+            .and_then(|roles| roles.get(authrole))
+            .and_then(|uris| uris.get(uri))
+            .and_then(|actions| actions[action as usize])
+            .filter(|(_, at)| now.saturating_duration_since(*at) < self.ttl);
+        if let Some((allowed, _)) = cached {
+            self.hits += 1;
+            return allowed;
+        }
+        self.misses += 1;
+        let allowed = decide();
+        self.insert(realm, authrole, uri, action, allowed, now);
+        allowed
+    }
+
+    /// Whether `authrole` may send `message` according to `realm`: the
+    /// [`check_message`](RealmConfig::check_message) allowlist, then for a CALL, REGISTER,
+    /// PUBLISH or SUBSCRIBE the cached [`authorize`](RealmConfig::authorize) decision about
+    /// its URI. Answer a refused request with [`denial`].
+    pub fn authorize_message(
+        &mut self,
+        realm: &RealmConfig,
+        authrole: &str,
+        message: &Message,
+        now: Instant,
+    ) -> bool {
+        if realm.check_message(authrole, message).is_err() {
+            return false;
+        }
+        match (Action::of(message), message.uri()) {
+            (Some(action), Some(uri)) => {
+                self.authorize(&realm.name, authrole, uri, action, now, || {
+                    realm.authorize(authrole, uri, action)
+                })
+            }
+            _ => true,
+        }
+    }
+
+    /// Cache a decision made elsewhere, e.g. by a dynamic authorizer.
+    pub fn insert(
+        &mut self,
+        realm: &str,
+        authrole: &str,
+        uri: &str,
+        action: Action,
+        allowed: bool,
+        now: Instant,
+    ) {
+        let uris = self
+            .decisions
+            .entry(realm.to_string())
+            .or_default()
+            .entry(authrole.to_string())
+            .or_default();
+        if let Some(actions) = uris.get_mut(uri) {
+            actions[action as usize] = Some((allowed, now));
+            return;
+        }
+        let mut actions = Decisions::default();
+        actions[action as usize] = Some((allowed, now));
+        uris.insert(uri.to_string(), actions);
+        self.order
+            .push_back((realm.to_string(), authrole.to_string(), uri.to_string()));
+        while self.order.len() > self.max_entries {
+            if let Some((realm, authrole, uri)) = self.order.pop_front() {
+                self.remove_entry(&realm, &authrole, &uri);
+            }
+        }
+    }
+
+    /// Drop one entry from `decisions` and the realms and roles left empty, not from `order`.
+    fn remove_entry(&mut self, realm: &str, authrole: &str, uri: &str) {
+        let Some(roles) = self.decisions.get_mut(realm) else {
+            return;
+        };
+        if let Some(uris) = roles.get_mut(authrole) {
+            uris.remove(uri);
+            if uris.is_empty() {
+                roles.remove(authrole);
+            }
+        }
+        if roles.is_empty() {
+            self.decisions.remove(realm);
+        }
+    }
+
+    /// Forget the entries of `order` no longer in `decisions`.
+    fn sync_order(&mut self) {
+        let decisions = &self.decisions;
+        self.order.retain(|(realm, authrole, uri)| {
+            decisions
+                .get(realm)
+                .and_then(|roles| roles.get(authrole))
+                .is_some_and(|uris| uris.contains_key(uri))
+        });
+    }
+
+    /// Forget the decision about one action on one URI.
+    pub fn invalidate(&mut self, realm: &str, authrole: &str, uri: &str, action: Action) {
+        let Some(actions) = self
+            .decisions
+            .get_mut(realm)
+            .and_then(|roles| roles.get_mut(authrole))
+            .and_then(|uris| uris.get_mut(uri))
+        else {
+            return;
+        };
+        actions[action as usize] = None;
+        if actions.iter().all(Option::is_none) {
+            self.remove_entry(realm, authrole, uri);
+            self.sync_order();
+        }
+    }
+
+    /// Forget every decision about `authrole` on `realm`, after its permissions changed.
+    pub fn invalidate_role(&mut self, realm: &str, authrole: &str) {
+        let Some(roles) = self.decisions.get_mut(realm) else {
+            return;
+        };
+        roles.remove(authrole);
+        if roles.is_empty() {
+            self.decisions.remove(realm);
+        }
+        self.sync_order();
+    }
+
+    /// Forget every decision about `realm`, after its configuration changed.
+    pub fn invalidate_realm(&mut self, realm: &str) {
+        self.decisions.remove(realm);
+        self.sync_order();
+    }
+
+    /// Forget every decision, after the whole configuration changed.
+    pub fn clear(&mut self) {
+        self.decisions.clear();
+        self.order.clear();
+    }
+
+    /// Drop the decisions older than the time to live, returns how many were dropped.
+    pub fn expire(&mut self, now: Instant) -> usize {
+        let ttl = self.ttl;
+        let mut expired = 0;
+        for roles in self.decisions.values_mut() {
+            for uris in roles.values_mut() {
+                for actions in uris.values_mut() {
+                    for decision in actions.iter_mut() {
+                        if decision.is_some_and(|(_, at)| now.saturating_duration_since(at) >= ttl)
+                        {
+                            *decision = None;
+                            expired += 1;
+                        }
+                    }
+                }
+                uris.retain(|_, actions| actions.iter().any(Option::is_some));
+            }
+            roles.retain(|_, uris| !uris.is_empty());
+        }
+        self.decisions.retain(|_, roles| !roles.is_empty());
+        self.sync_order();
+        expired
+    }
+
+    /// Number of cached decisions.
+    pub fn len(&self) -> usize {
+        self.decisions
+            .values()
+            .flat_map(HashMap::values)
+            .flat_map(HashMap::values)
+            .map(|actions| actions.iter().flatten().count())
+            .sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.decisions.is_empty()
+    }
+
+    /// Lookups answered from the cache.
+    pub fn hits(&self) -> u64 {
+        self.hits
+    }
+
+    /// Lookups that had to decide.
+    pub fn misses(&self) -> u64 {
+        self.misses
+    }
+
+    /// Share of lookups answered from the cache, 0 before the first one.
+    pub fn hit_rate(&self) -> f64 {
+        let lookups = self.hits + self.misses;
+        if lookups == 0 {
+            0.0
+        } else {
+            self.hits as f64 / lookups as f64
+        }
+    }
+}
+
+/// ERROR answering a request refused by [`RealmConfig::check_message`], `None` when the
+/// message is not a request and is dropped silently.
+pub fn denial(message: &Message) -> Option<ErrorMessage> {
+    let request_type = RequestType::try_from(message.message_id()).ok()?;
+    Some(ErrorMessage::for_request(
+        request_type,
+        message.request_id()?,
+        NOT_AUTHORIZED.into(),
+    ))
+}
+
+/// Reason given when a subscription or registration is revoked because the configuration no
+/// longer permits it.
+pub const AUTHORIZATION_LOST: &str = "wamp.authorization.lost";
+/// GOODBYE reason for sessions on a realm that was removed.
+pub const CLOSE_REALM: &str = "wamp.close.close_realm";
+
+/// A subscription or registration a session holds, as tracked by the router.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Grant {
+    pub session: WampId,
+    pub authrole: String,
+    /// [`Action::Subscribe`] or [`Action::Register`].
+    pub action: Action,
+    pub uri: Uri,
+    /// The subscription or registration id.
+    pub id: WampId,
+}
+
+/// Realms added, removed and changed between two configurations.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConfigDiff {
+    pub added: Vec<Uri>,
+    /// Sessions on these realms are closed with [`CLOSE_REALM`].
+    pub removed: Vec<Uri>,
+    /// Grants on these realms must be checked with [`revocations`].
+    pub changed: Vec<Uri>,
+}
+
+impl RouterConfig {
+    /// Compare with the configuration about to replace this one.
+    /// # Examples
+    /// ```
+    /// use wamp_helpers::config::{
+    ///     revocations, Action, Grant, Permission, RealmConfig, RoleConfig, RouterConfig,
+    /// };
+    /// use wamp_helpers::messages::Message;
+    /// use wamp_helpers::uri::MatchPolicy;
+    ///
+    /// let realm = |allow: &[Action]| RealmConfig {
+    ///     name: "realm1".into(),
+    ///     roles: vec![RoleConfig {
+    ///         name: "backend".to_string(),
+    ///         permissions: vec![Permission {
+    ///             uri: "com.example.".into(),
+    ///             match_policy: MatchPolicy::Prefix,
+    ///             allow: match allow {
+    ///                 [] => [].into(),
+    ///                 _ => [Action::Register].into(),
+    ///             },
+    ///         }],
+    ///         ..RoleConfig::default()
+    ///     }],
+    ///     ..RealmConfig::default()
+    /// };
+    /// let old = RouterConfig { realms: vec![realm(&[Action::Register])] };
+    /// let new = RouterConfig { realms: vec![realm(&[])] };
+    /// let diff = old.diff(&new);
+    /// assert_eq!(diff.changed, ["realm1"]);
+    ///
+    /// let grants = [Grant {
+    ///     session: 7,
+    ///     authrole: "backend".to_string(),
+    ///     action: Action::Register,
+    ///     uri: "com.example.add".into(),
+    ///     id: 42,
+    /// }];
+    /// let revoked = revocations(new.realm("realm1").unwrap(), &grants);
+    /// let (session, Message::Unregistered(unregistered)) = &revoked[0] else { panic!() };
+    /// assert_eq!(*session, 7);
+    /// assert_eq!(unregistered.details.as_ref().unwrap()["registration"], 42);
+    /// ```
+    pub fn diff(&self, new: &RouterConfig) -> ConfigDiff {
+        let mut diff = ConfigDiff::default();
+        for realm in &new.realms {
+            match self.realm(&realm.name) {
+                None => diff.added.push(realm.name.clone()),
+                Some(old) if old != realm => diff.changed.push(realm.name.clone()),
+                Some(_) => {}
+            }
+        }
+        for realm in &self.realms {
+            if new.realm(&realm.name).is_none() {
+                diff.removed.push(realm.name.clone());
+            }
+        }
+        diff
+    }
+}
+
+/// The revocation messages for grants `realm` no longer permits, with the session to send
+/// each to. Everything still permitted is left alone.
+pub fn revocations(realm: &RealmConfig, grants: &[Grant]) -> Vec<(WampId, Message)> {
+    grants
+        .iter()
+        .filter(|grant| !realm.authorize(&grant.authrole, &grant.uri, grant.action))
+        .filter_map(|grant| {
+            let message = match grant.action {
+                Action::Subscribe => Message::Unsubscribed(Unsubscribed {
+                    request: 0,
+                    details: Some(json::object! {
+                        subscription: grant.id,
+                        reason: AUTHORIZATION_LOST,
+                    }),
+                }),
+                Action::Register => Message::Unregistered(Unregistered {
+                    request: 0,
+                    details: Some(json::object! {
+                        registration: grant.id,
+                        reason: AUTHORIZATION_LOST,
+                    }),
+                }),
+                Action::Call | Action::Publish => return None,
+            };
+            Some((grant.session, message))
+        })
+        .collect()
+}
@@ -0,0 +1,153 @@
+use crate::messages::{
+    Call, Invocation, Message, Publish, Register, Subscribe, Unregister, Unsubscribe, WampId,
+    WampMessageTrait,
+};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// Which way a message travelled, relative to the peer doing the observing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum Direction {
+    Inbound,
+    Outbound,
+}
+
+/// A request paired with the message answering it.
+#[derive(Debug, Clone)]
+pub struct Exchange {
+    pub request: Message,
+    pub request_direction: Direction,
+    pub response: Message,
+    pub latency: Duration,
+    /// `true` for progressive RESULT/YIELD messages, the request stays pending until the
+    /// final response arrives.
+    pub progress: bool,
+}
+
+/// Pairs requests with their responses: CALL with RESULT/ERROR, PUBLISH with PUBLISHED,
+/// SUBSCRIBE with SUBSCRIBED, INVOCATION with YIELD and so on.
+///
+/// Requests are keyed by their message type and request id, which keeps the id spaces of the
+/// client (CALL, PUBLISH, ...) and the router (INVOCATION) apart.
+/// # Examples
+/// ```
+/// use std::time::{Duration, Instant};
+/// use wamp_helpers::correlation::{Correlator, Direction};
+/// use wamp_helpers::messages::Message;
+///
+/// let mut correlator = Correlator::new();
+/// let start = Instant::now();
+/// let call = Message::parse_message(r#"[48, 1, {}, "com.example.add", [1, 2]]"#).unwrap();
+/// let result = Message::parse_message(r#"[50, 1, {}, [3]]"#).unwrap();
+///
+/// assert!(correlator.observe(Direction::Outbound, &call, start).is_none());
+/// let exchange = correlator
+///     .observe(Direction::Inbound, &result, start + Duration::from_millis(5))
+///     .unwrap();
+/// assert_eq!(exchange.latency, Duration::from_millis(5));
+/// assert_eq!(correlator.pending_len(), 0);
+/// ```
+#[derive(Debug, Default)]
+pub struct Correlator {
+    pending: HashMap<(u8, WampId), (Message, Direction, Instant)>,
+}
+
+impl Correlator {
+    pub fn new() -> Self {
+        Correlator::default()
+    }
+
+    /// Feed a message seen at `at`, returns the completed exchange when it answers a pending
+    /// request.
+    pub fn observe(
+        &mut self,
+        direction: Direction,
+        message: &Message,
+        at: Instant,
+    ) -> Option<Exchange> {
+        if let Some(key) = request_key(message) {
+            self.pending.insert(key, (message.clone(), direction, at));
+            return None;
+        }
+
+        let (key, progress) = response_key(message)?;
+        let (request, request_direction, sent_at) = if progress {
+            self.pending.get(&key).cloned()?
+        } else {
+            self.pending.remove(&key)?
+        };
+
+        Some(Exchange {
+            request,
+            request_direction,
+            response: message.clone(),
+            latency: at.saturating_duration_since(sent_at),
+            progress,
+        })
+    }
+
+    /// Number of requests still waiting for a response.
+    pub fn pending_len(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Drop and return the requests sent before `deadline`, e.g. to report timeouts.
+    pub fn expire(&mut self, deadline: Instant) -> Vec<Message> {
+        let expired: Vec<(u8, WampId)> = self
+            .pending
+            .iter()
+            .filter(|(_, (_, _, sent_at))| *sent_at < deadline)
+            .map(|(key, _)| *key)
+            .collect();
+
+        expired
+            .into_iter()
+            .filter_map(|key| self.pending.remove(&key))
+            .map(|(request, _, _)| request)
+            .collect()
+    }
+}
+
+pub(crate) fn request_key(message: &Message) -> Option<(u8, WampId)> {
+    match message {
+        Message::Publish(publish) if acknowledged(publish) => Some((Publish::ID, publish.request)),
+        Message::Subscribe(subscribe) => Some((Subscribe::ID, subscribe.request)),
+        Message::Unsubscribe(unsubscribe) => Some((Unsubscribe::ID, unsubscribe.request)),
+        Message::Call(call) => Some((Call::ID, call.request)),
+        Message::Register(register) => Some((Register::ID, register.request)),
+        Message::Unregister(unregister) => Some((Unregister::ID, unregister.request)),
+        Message::Invocation(invocation) => Some((Invocation::ID, invocation.request)),
+        _ => None,
+    }
+}
+
+pub(crate) fn response_key(message: &Message) -> Option<((u8, WampId), bool)> {
+    match message {
+        Message::ErrorMessage(error) => Some(((error.request_type, error.request), false)),
+        Message::Published(published) => Some(((Publish::ID, published.request), false)),
+        Message::Subscribed(subscribed) => Some(((Subscribe::ID, subscribed.request), false)),
+        Message::Unsubscribed(unsubscribed) => {
+            Some(((Unsubscribe::ID, unsubscribed.request), false))
+        }
+        Message::MessageResult(result) => Some((
+            (Call::ID, result.request),
+            result.details["progress"].as_bool().unwrap_or(false),
+        )),
+        Message::Registered(registered) => Some(((Register::ID, registered.request), false)),
+        Message::Unregistered(unregistered) => {
+            Some(((Unregister::ID, unregistered.request), false))
+        }
+        Message::Yield(yield_message) => Some((
+            (Invocation::ID, yield_message.request),
+            yield_message.options["progress"].as_bool().unwrap_or(false),
+        )),
+        _ => None,
+    }
+}
+
+/// Only publications with `acknowledge: true` receive a PUBLISHED.
+fn acknowledged(publish: &Publish) -> bool {
+    publish.options["acknowledge"].as_bool().unwrap_or(false)
+}
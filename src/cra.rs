@@ -0,0 +1,137 @@
+use crate::error::Error;
+use crate::messages::Details;
+use crate::nonce::{NonceEncoding, NonceGenerator};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use json::JsonValue;
+use sha2::Sha256;
+
+/// Iterations Crossbar uses when a principal does not specify them.
+pub const DEFAULT_ITERATIONS: u32 = 1000;
+/// Derived key length in bytes Crossbar uses when a principal does not specify it.
+pub const DEFAULT_KEYLEN: usize = 32;
+
+/// Derive a salted WAMP-CRA key with PBKDF2-HMAC-SHA256, base64 encoded like Autobahn's
+/// `derive_key`.
+/// ```
+/// use wamp_helpers::cra::derive_key;
+///
+/// assert_eq!(derive_key("password", "salt", 1, 32), "Eg+2z/z4syxD5yJSVsT4N6hlSMkszDVICAWYfLcL4Xs=");
+/// ```
+pub fn derive_key(secret: &str, salt: &str, iterations: u32, keylen: usize) -> String {
+    let mut key = vec![0u8; keylen];
+    pbkdf2::pbkdf2_hmac::<Sha256>(secret.as_bytes(), salt.as_bytes(), iterations, &mut key);
+    STANDARD.encode(key)
+}
+
+/// The WAMP-CRA signature of `challenge`: HMAC-SHA256 keyed with the secret, or the derived
+/// key for salted secrets, base64 encoded.
+pub fn sign(key: &str, challenge: &str) -> String {
+    STANDARD.encode(mac(key, challenge).finalize().into_bytes())
+}
+
+fn mac(key: &str, challenge: &str) -> Hmac<Sha256> {
+    // HMAC accepts keys of any length.
+    let mut mac =
+        <Hmac<Sha256> as Mac>::new_from_slice(key.as_bytes()).unwrap_or_else(|_| unreachable!());
+    mac.update(challenge.as_bytes());
+    mac
+}
+
+/// A salted CRA secret as stored in Crossbar's static principal database: the derived key
+/// instead of the plaintext secret, plus the parameters the client needs to derive it too.
+/// # Examples
+/// ```
+/// use wamp_helpers::cra::{sign, CraSecret};
+///
+/// let stored = CraSecret::derive("secret123", "salt123", 1000, 32);
+/// let principal = stored.to_principal();
+/// assert_eq!(principal["secret"], "Eu7CQLfR+/Ffb+275A4s9/6H/RGKYxM4s6IMrsNKzC8=");
+/// assert_eq!(CraSecret::from_principal(&principal), Some(stored.clone()));
+///
+/// // The client derives the same key from the CHALLENGE extra and signs with it.
+/// let challenge = r#"{"nonce":"abc"}"#;
+/// let extra = stored.challenge_extra(challenge);
+/// let key = wamp_helpers::cra::derive_key(
+///     "secret123",
+///     extra["salt"].as_str().unwrap(),
+///     extra["iterations"].as_u32().unwrap(),
+///     extra["keylen"].as_usize().unwrap(),
+/// );
+/// assert!(stored.verify(challenge, &sign(&key, challenge)));
+/// assert!(!stored.verify(challenge, &sign("wrong", challenge)));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CraSecret {
+    /// The derived key, base64 encoded.
+    pub key: String,
+    pub salt: String,
+    pub iterations: u32,
+    pub keylen: usize,
+}
+
+impl CraSecret {
+    pub fn derive(secret: &str, salt: &str, iterations: u32, keylen: usize) -> Self {
+        CraSecret {
+            key: derive_key(secret, salt, iterations, keylen),
+            salt: salt.to_string(),
+            iterations,
+            keylen,
+        }
+    }
+
+    /// Derive with a fresh random salt and Crossbar's default parameters.
+    pub fn generate(secret: &str) -> Result<Self, Error> {
+        let salt = NonceGenerator::new()
+            .len(16)
+            .encoding(NonceEncoding::Hex)
+            .generate()?;
+        Ok(CraSecret::derive(
+            secret,
+            &salt,
+            DEFAULT_ITERATIONS,
+            DEFAULT_KEYLEN,
+        ))
+    }
+
+    /// Read a principal entry, `{"secret": .., "salt": .., "iterations": .., "keylen": ..}`.
+    /// Entries without salt hold a plaintext secret and are not accepted.
+    pub fn from_principal(principal: &JsonValue) -> Option<Self> {
+        Some(CraSecret {
+            key: principal["secret"].as_str()?.to_string(),
+            salt: principal["salt"].as_str()?.to_string(),
+            iterations: principal["iterations"]
+                .as_u32()
+                .unwrap_or(DEFAULT_ITERATIONS),
+            keylen: principal["keylen"].as_usize().unwrap_or(DEFAULT_KEYLEN),
+        })
+    }
+
+    pub fn to_principal(&self) -> JsonValue {
+        json::object! {
+            secret: self.key.as_str(),
+            salt: self.salt.as_str(),
+            iterations: self.iterations,
+            keylen: self.keylen,
+        }
+    }
+
+    /// The Extra of the CHALLENGE for `challenge`, carrying the salting parameters.
+    pub fn challenge_extra(&self, challenge: &str) -> Details {
+        json::object! {
+            challenge: challenge,
+            salt: self.salt.as_str(),
+            iterations: self.iterations,
+            keylen: self.keylen,
+        }
+    }
+
+    /// Check the signature of an AUTHENTICATE in constant time.
+    pub fn verify(&self, challenge: &str, signature: &str) -> bool {
+        match STANDARD.decode(signature) {
+            Ok(signature) => mac(&self.key, challenge).verify_slice(&signature).is_ok(),
+            Err(_) => false,
+        }
+    }
+}
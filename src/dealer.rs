@@ -0,0 +1,550 @@
+use crate::client::{CANCELED, UNAVAILABLE};
+use crate::messages::{Call, ErrorMessage, Invocation, Options, WampId};
+use crate::options::{InvocationDetails, RegisterOptions};
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+/// Error URI for calls to procedures nobody registered.
+pub const NO_SUCH_PROCEDURE: &str = "wamp.error.no_such_procedure";
+
+/// What the dealer does with a CALL when the callee the invocation policy picked cannot take
+/// it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BusyPolicy {
+    /// Answer with `wamp.error.unavailable` at once.
+    Reject,
+    /// Try the other callees of a shared registration, answer with `wamp.error.unavailable`
+    /// when all are busy.
+    #[default]
+    Spillover,
+    /// Like `Spillover`, but then wait for the first callee to become free. At most `max`
+    /// calls wait per registration, each up to `timeout`.
+    Queue { max: usize, timeout: Duration },
+}
+
+/// The INVOCATION handing `call` to a callee of `registration`, with the Details
+/// [`InvocationDetails::for_call`] builds from the CALL's Options.
+/// ```
+/// use wamp_helpers::dealer::invocation;
+/// use wamp_helpers::messages::Call;
+///
+/// let call: Call = r#"[48, 7, {"timeout": 500, "x_trace": 1, "forward_for": [{"session": 1}]}, "com.example.add", [1, 2]]"#
+///     .parse()
+///     .unwrap();
+/// let invocation = invocation(5001, 9, &call);
+/// assert_eq!((invocation.request, invocation.registration), (5001, 9));
+/// assert_eq!(invocation.details.dump(), r#"{"timeout":500}"#);
+/// assert_eq!(invocation.args, call.args);
+/// ```
+pub fn invocation(request: WampId, registration: WampId, call: &Call) -> Invocation {
+    Invocation {
+        request,
+        registration,
+        details: InvocationDetails::for_call(&call.options).into(),
+        args: call.args.clone(),
+        kwargs: call.kwargs.clone(),
+    }
+}
+
+/// One callee of a registration, shared registrations have several.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Endpoint {
+    pub registration: WampId,
+    pub callee: WampId,
+}
+
+/// A CALL waiting for a callee of its registration to become free.
+#[derive(Debug, Clone)]
+pub struct QueuedCall {
+    pub caller: WampId,
+    pub call: Call,
+    pub queued_at: Instant,
+}
+
+impl QueuedCall {
+    /// The ERROR refusing the call.
+    pub fn error(&self, error: &str) -> ErrorMessage {
+        ErrorMessage::for_call(&self.call, error.to_string().into())
+    }
+}
+
+/// What to do with a CALL, answered by [`ConcurrencyTracker::dispatch`].
+#[derive(Debug, Clone)]
+pub enum Dispatch {
+    /// Send the INVOCATION, then report it with [`ConcurrencyTracker::start`].
+    Invoke(Endpoint),
+    /// Every callee is busy, the call waits in the registration's queue.
+    Queued,
+    /// Answer the call with `error`: `wamp.error.no_such_procedure` without callees,
+    /// `wamp.error.unavailable` when they are busy.
+    Rejected {
+        call: QueuedCall,
+        error: &'static str,
+    },
+}
+
+/// Dealer-side bookkeeping of the `concurrency` registration option.
+///
+/// Each callee of a registration runs at most its `concurrency` invocations at once, the
+/// [`BusyPolicy`] decides what happens to calls beyond that. Queued calls are handed out
+/// again by [`finish`](ConcurrencyTracker::finish) as invocations complete, or given up on
+/// by [`expire`](ConcurrencyTracker::expire).
+/// # Examples
+/// ```
+/// use std::time::{Duration, Instant};
+/// use wamp_helpers::dealer::{BusyPolicy, ConcurrencyTracker, Dispatch, Endpoint};
+/// use wamp_helpers::messages::Call;
+///
+/// let timeout = Duration::from_secs(5);
+/// let mut tracker = ConcurrencyTracker::new().policy(BusyPolicy::Queue { max: 1, timeout });
+/// let endpoint = Endpoint { registration: 9, callee: 100 };
+/// tracker.register(endpoint, &json::object! { "concurrency": 1 });
+///
+/// let start = Instant::now();
+/// let call: Call = r#"[48, 1, {}, "com.example.slow"]"#.parse().unwrap();
+/// let Dispatch::Invoke(invoked) = tracker.dispatch(9, &[100], 200, call.clone(), start) else {
+///     panic!()
+/// };
+/// assert_eq!(invoked, endpoint);
+/// tracker.start(endpoint, 5001);
+///
+/// assert!(matches!(tracker.dispatch(9, &[100], 201, call.clone(), start), Dispatch::Queued));
+/// assert!(matches!(
+///     tracker.dispatch(9, &[100], 202, call.clone(), start),
+///     Dispatch::Rejected { error: "wamp.error.unavailable", .. }
+/// ));
+///
+/// // The YIELD of 5001 frees the callee for the queued call of session 201.
+/// let (next, queued) = tracker.finish(5001).unwrap();
+/// assert_eq!((next, queued.caller), (endpoint, 201));
+/// tracker.start(next, 5002);
+///
+/// // Nobody frees the callee for session 203 in time.
+/// assert!(matches!(tracker.dispatch(9, &[100], 203, call, start), Dispatch::Queued));
+/// let expired = tracker.expire(start + timeout);
+/// assert_eq!(expired[0].error("wamp.error.unavailable").request, 1);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ConcurrencyTracker {
+    policy: BusyPolicy,
+    limits: HashMap<Endpoint, u32>,
+    running: HashMap<Endpoint, u32>,
+    /// Endpoint of every outstanding INVOCATION, by its request id.
+    invocations: HashMap<WampId, Endpoint>,
+    queues: HashMap<WampId, VecDeque<QueuedCall>>,
+}
+
+impl ConcurrencyTracker {
+    /// Spills over to other callees, without queueing.
+    pub fn new() -> Self {
+        ConcurrencyTracker::default()
+    }
+
+    pub fn policy(mut self, policy: BusyPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Record the `concurrency` option a callee registered with.
+    pub fn register(&mut self, endpoint: Endpoint, options: &Options) {
+        match RegisterOptions::from(options).concurrency {
+            Some(limit) => self.limits.insert(endpoint, limit),
+            None => self.limits.remove(&endpoint),
+        };
+    }
+
+    /// Forget a callee that unregistered. Returns the calls queued on the registration when
+    /// it was the last callee, they have to be answered with an error.
+    pub fn unregister(&mut self, endpoint: Endpoint, last: bool) -> Vec<QueuedCall> {
+        self.limits.remove(&endpoint);
+        self.running.remove(&endpoint);
+        self.invocations.retain(|_, running| *running != endpoint);
+        if last {
+            self.queues
+                .remove(&endpoint.registration)
+                .map(Vec::from)
+                .unwrap_or_default()
+        } else {
+            Vec::new()
+        }
+    }
+
+    /// Forget every endpoint of a callee whose session ended, its outstanding invocations
+    /// no longer count. `callees` returns the callees of a registration, like for
+    /// [`Rerouter::callee_lost`]. Returns the calls queued on the registrations the callee
+    /// was the last one of, they have to be answered with an error as for
+    /// [`unregister`](Self::unregister).
+    pub fn remove_callee(
+        &mut self,
+        callee: WampId,
+        callees: impl Fn(WampId) -> Vec<WampId>,
+    ) -> Vec<QueuedCall> {
+        self.limits.retain(|endpoint, _| endpoint.callee != callee);
+        self.running.retain(|endpoint, _| endpoint.callee != callee);
+        self.invocations
+            .retain(|_, endpoint| endpoint.callee != callee);
+        // Whether or not `callees` still counts the callee that left.
+        let orphaned: Vec<WampId> = self
+            .queues
+            .keys()
+            .copied()
+            .filter(|registration| callees(*registration).iter().all(|left| *left == callee))
+            .collect();
+        orphaned
+            .into_iter()
+            .filter_map(|registration| self.queues.remove(&registration))
+            .flatten()
+            .collect()
+    }
+
+    /// Whether `endpoint` can take another invocation now.
+    pub fn is_available(&self, endpoint: Endpoint) -> bool {
+        match self.limits.get(&endpoint) {
+            Some(limit) => self.running.get(&endpoint).copied().unwrap_or(0) < *limit,
+            None => true,
+        }
+    }
+
+    /// Route a CALL to one of `callees`, the candidates in the order the invocation policy
+    /// prefers them. Only the first is tried under [`BusyPolicy::Reject`].
+    pub fn dispatch(
+        &mut self,
+        registration: WampId,
+        callees: &[WampId],
+        caller: WampId,
+        call: Call,
+        now: Instant,
+    ) -> Dispatch {
+        let candidates = match self.policy {
+            BusyPolicy::Reject => &callees[..callees.len().min(1)],
+            BusyPolicy::Spillover | BusyPolicy::Queue { .. } => callees,
+        };
+        let available = candidates
+            .iter()
+            .map(|callee| Endpoint {
+                registration,
+                callee: *callee,
+            })
+            .find(|endpoint| self.is_available(*endpoint));
+        if let Some(endpoint) = available {
+            return Dispatch::Invoke(endpoint);
+        }
+
+        let queued = QueuedCall {
+            caller,
+            call,
+            queued_at: now,
+        };
+        if callees.is_empty() {
+            return Dispatch::Rejected {
+                call: queued,
+                error: NO_SUCH_PROCEDURE,
+            };
+        }
+        if let BusyPolicy::Queue { max, .. } = self.policy {
+            let queue = self.queues.entry(registration).or_default();
+            if queue.len() < max {
+                queue.push_back(queued);
+                return Dispatch::Queued;
+            }
+        }
+        Dispatch::Rejected {
+            call: queued,
+            error: UNAVAILABLE,
+        }
+    }
+
+    /// Remove the queued calls that waited for their timeout by `now`, answer them with
+    /// `wamp.error.unavailable`: no callee ran them, so callers may safely retry.
+    pub fn expire(&mut self, now: Instant) -> Vec<QueuedCall> {
+        let BusyPolicy::Queue { timeout, .. } = self.policy else {
+            return Vec::new();
+        };
+        let mut expired = Vec::new();
+        for queue in self.queues.values_mut() {
+            // Queues are in arrival order, the expired calls are at the front.
+            while queue
+                .front()
+                .is_some_and(|queued| now.saturating_duration_since(queued.queued_at) >= timeout)
+            {
+                expired.extend(queue.pop_front());
+            }
+        }
+        self.queues.retain(|_, queue| !queue.is_empty());
+        expired
+    }
+
+    /// Count the INVOCATION `invocation` sent to `endpoint`.
+    pub fn start(&mut self, endpoint: Endpoint, invocation: WampId) {
+        *self.running.entry(endpoint).or_default() += 1;
+        self.invocations.insert(invocation, endpoint);
+    }
+
+    /// The final YIELD or ERROR of `invocation` arrived, or it was interrupted. Returns the
+    /// next queued call of the registration together with the callee to invoke, who is free
+    /// now.
+    pub fn finish(&mut self, invocation: WampId) -> Option<(Endpoint, QueuedCall)> {
+        let endpoint = self.invocations.remove(&invocation)?;
+        if let Some(running) = self.running.get_mut(&endpoint) {
+            *running = running.saturating_sub(1);
+        }
+        let queued = self.queues.get_mut(&endpoint.registration)?.pop_front()?;
+        Some((endpoint, queued))
+    }
+
+    /// Take the CALL `request` of `caller` out of its queue, e.g. because the caller
+    /// canceled it before a callee became free.
+    pub fn cancel(&mut self, caller: WampId, request: WampId) -> Option<QueuedCall> {
+        let (registration, index) = self.queues.iter().find_map(|(registration, queue)| {
+            queue
+                .iter()
+                .position(|queued| queued.caller == caller && queued.call.request == request)
+                .map(|index| (*registration, index))
+        })?;
+        let queue = self.queues.get_mut(&registration)?;
+        let queued = queue.remove(index);
+        if queue.is_empty() {
+            self.queues.remove(&registration);
+        }
+        queued
+    }
+
+    /// Invocations outstanding at `endpoint`.
+    pub fn running(&self, endpoint: Endpoint) -> u32 {
+        self.running.get(&endpoint).copied().unwrap_or(0)
+    }
+
+    /// Calls queued on `registration`.
+    pub fn queued(&self, registration: WampId) -> usize {
+        self.queues.get(&registration).map_or(0, VecDeque::len)
+    }
+}
+
+/// A CALL in flight at one callee, remembering the callees that already refused it.
+#[derive(Debug, Clone)]
+pub struct RoutedCall {
+    pub endpoint: Endpoint,
+    pub caller: WampId,
+    pub call: Call,
+    tried: Vec<WampId>,
+}
+
+impl RoutedCall {
+    pub fn new(endpoint: Endpoint, caller: WampId, call: Call) -> Self {
+        RoutedCall {
+            endpoint,
+            caller,
+            call,
+            tried: Vec::new(),
+        }
+    }
+
+    /// Callees that answered `wamp.error.unavailable` or left so far.
+    pub fn tried(&self) -> &[WampId] {
+        &self.tried
+    }
+
+    /// The INVOCATION to send to the current endpoint, see [`invocation`].
+    pub fn invocation(&self, request: WampId) -> Invocation {
+        invocation(request, self.endpoint.registration, &self.call)
+    }
+}
+
+/// What to do with the ERROR a callee answered an INVOCATION with.
+#[derive(Debug, Clone)]
+pub enum Reroute {
+    /// Invoke `call.endpoint` instead, then report it with [`Rerouter::invoked`].
+    Retry(RoutedCall),
+    /// Send `error` to the caller: the callee failed the call, or no untried callee is left.
+    Forward { caller: WampId, error: ErrorMessage },
+}
+
+/// Reroute counters, overall and per registration.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RerouteStats {
+    pub rerouted: u64,
+    /// Calls that failed because every callee was unavailable.
+    pub exhausted: u64,
+    pub by_registration: HashMap<WampId, u64>,
+}
+
+/// Moves calls a callee refused with `wamp.error.unavailable` to another callee of the
+/// shared registration, so the caller only sees the error once no callee is left.
+///
+/// Every callee is tried at most once per call and at most `max_attempts` invocations are
+/// made in total, so callees bouncing a call between each other cannot loop.
+/// # Examples
+/// ```
+/// use wamp_helpers::dealer::{Endpoint, Reroute, RoutedCall, Rerouter};
+/// use wamp_helpers::messages::{Call, ErrorMessage};
+///
+/// let mut rerouter = Rerouter::new(3);
+/// let call: Call = r#"[48, 7, {}, "com.example.work"]"#.parse().unwrap();
+/// let first = Endpoint { registration: 9, callee: 100 };
+/// rerouter.invoked(5001, RoutedCall::new(first, 200, call));
+///
+/// let unavailable: ErrorMessage = r#"[8, 68, 5001, {}, "wamp.error.unavailable"]"#.parse().unwrap();
+/// let Some(Reroute::Retry(retry)) = rerouter.on_error(&unavailable, &[100, 101]) else { panic!() };
+/// assert_eq!(retry.endpoint.callee, 101);
+/// rerouter.invoked(5002, retry);
+///
+/// let unavailable: ErrorMessage = r#"[8, 68, 5002, {}, "wamp.error.unavailable"]"#.parse().unwrap();
+/// let Some(Reroute::Forward { caller, error }) = rerouter.on_error(&unavailable, &[100, 101]) else {
+///     panic!()
+/// };
+/// assert_eq!((caller, error.request), (200, 7));
+/// assert_eq!((rerouter.stats().rerouted, rerouter.stats().exhausted), (1, 1));
+/// ```
+#[derive(Debug, Clone)]
+pub struct Rerouter {
+    max_attempts: usize,
+    /// Calls in flight by the request id of their INVOCATION.
+    invocations: HashMap<WampId, RoutedCall>,
+    stats: RerouteStats,
+}
+
+impl Rerouter {
+    /// Allow up to `max_attempts` invocations per call, the first included.
+    pub fn new(max_attempts: usize) -> Self {
+        Rerouter {
+            max_attempts: max_attempts.max(1),
+            invocations: HashMap::new(),
+            stats: RerouteStats::default(),
+        }
+    }
+
+    /// Remember the INVOCATION `invocation` sent for `call`.
+    pub fn invoked(&mut self, invocation: WampId, call: RoutedCall) {
+        self.invocations.insert(invocation, call);
+    }
+
+    /// The call `invocation` was sent for, while it is in flight.
+    pub fn get(&self, invocation: WampId) -> Option<&RoutedCall> {
+        self.invocations.get(&invocation)
+    }
+
+    /// The final YIELD of `invocation` arrived or it was interrupted.
+    pub fn completed(&mut self, invocation: WampId) -> Option<RoutedCall> {
+        self.invocations.remove(&invocation)
+    }
+
+    /// Handle an ERROR answering an INVOCATION, `callees` being the current callees of the
+    /// registration in the order the invocation policy prefers them. `None` when the
+    /// invocation is unknown.
+    pub fn on_error(&mut self, error: &ErrorMessage, callees: &[WampId]) -> Option<Reroute> {
+        let mut routed = self.invocations.remove(&error.request)?;
+        let forward = |routed: &RoutedCall, uri: &str| {
+            let mut answer = ErrorMessage::for_call(&routed.call, uri.to_string().into());
+            answer.details = error.details.clone();
+            answer.args = error.args.clone();
+            answer.kwargs = error.kwargs.clone();
+            Reroute::Forward {
+                caller: routed.caller,
+                error: answer,
+            }
+        };
+        if error.error != UNAVAILABLE {
+            return Some(forward(&routed, &error.error));
+        }
+
+        routed.tried.push(routed.endpoint.callee);
+        let next = callees.iter().find(|callee| !routed.tried.contains(callee));
+        match next {
+            Some(callee) if routed.tried.len() < self.max_attempts => {
+                routed.endpoint.callee = *callee;
+                self.stats.rerouted += 1;
+                *self
+                    .stats
+                    .by_registration
+                    .entry(routed.endpoint.registration)
+                    .or_default() += 1;
+                Some(Reroute::Retry(routed))
+            }
+            _ => {
+                self.stats.exhausted += 1;
+                Some(forward(&routed, UNAVAILABLE))
+            }
+        }
+    }
+
+    /// Forget the calls in flight at a callee that left, returns them so they can be
+    /// rerouted or failed.
+    pub fn remove_callee(&mut self, callee: WampId) -> Vec<RoutedCall> {
+        let gone: Vec<WampId> = self
+            .invocations
+            .iter()
+            .filter(|(_, routed)| routed.endpoint.callee == callee)
+            .map(|(invocation, _)| *invocation)
+            .collect();
+        gone.into_iter()
+            .filter_map(|invocation| self.invocations.remove(&invocation))
+            .collect()
+    }
+
+    /// The session of `callee` ended with invocations outstanding. Each of their calls moves
+    /// to another callee of its registration that has not been tried, `callees` returning
+    /// the callees a registration has left. Calls without one are answered with
+    /// `wamp.error.canceled`.
+    ///
+    /// A YIELD or ERROR the callee managed to send before leaving finds no invocation any
+    /// more and is dropped by [`completed`](Self::completed) and [`on_error`](Self::on_error).
+    /// # Examples
+    /// ```
+    /// use wamp_helpers::dealer::{Endpoint, Reroute, RoutedCall, Rerouter};
+    /// use wamp_helpers::messages::Call;
+    ///
+    /// let mut rerouter = Rerouter::new(3);
+    /// let call: Call = r#"[48, 7, {}, "com.example.work"]"#.parse().unwrap();
+    /// rerouter.invoked(5001, RoutedCall::new(Endpoint { registration: 9, callee: 100 }, 200, call.clone()));
+    /// rerouter.invoked(5002, RoutedCall::new(Endpoint { registration: 10, callee: 100 }, 201, call));
+    ///
+    /// // Registration 9 is shared with callee 101, registration 10 is gone with callee 100.
+    /// let callees = |registration| if registration == 9 { vec![101] } else { vec![] };
+    /// let mut outcomes = rerouter.callee_lost(100, callees);
+    /// outcomes.sort_by_key(|outcome| matches!(outcome, Reroute::Forward { .. }));
+    /// assert!(matches!(&outcomes[0], Reroute::Retry(retry) if retry.endpoint.callee == 101));
+    /// assert!(matches!(
+    ///     &outcomes[1],
+    ///     Reroute::Forward { caller: 201, error } if error.error == "wamp.error.canceled"
+    /// ));
+    /// assert!(rerouter.completed(5001).is_none());
+    /// ```
+    pub fn callee_lost(
+        &mut self,
+        callee: WampId,
+        callees: impl Fn(WampId) -> Vec<WampId>,
+    ) -> Vec<Reroute> {
+        let mut outcomes = Vec::new();
+        for mut routed in self.remove_callee(callee) {
+            routed.tried.push(callee);
+            let next = callees(routed.endpoint.registration)
+                .into_iter()
+                .find(|candidate| !routed.tried.contains(candidate));
+            match next {
+                Some(next) if routed.tried.len() < self.max_attempts => {
+                    routed.endpoint.callee = next;
+                    self.stats.rerouted += 1;
+                    *self
+                        .stats
+                        .by_registration
+                        .entry(routed.endpoint.registration)
+                        .or_default() += 1;
+                    outcomes.push(Reroute::Retry(routed));
+                }
+                _ => {
+                    let mut error = ErrorMessage::for_call(&routed.call, CANCELED.into());
+                    error.details = json::object! { message: "the callee left" };
+                    outcomes.push(Reroute::Forward {
+                        caller: routed.caller,
+                        error,
+                    });
+                }
+            }
+        }
+        outcomes
+    }
+
+    pub fn stats(&self) -> &RerouteStats {
+        &self.stats
+    }
+}
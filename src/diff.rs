@@ -0,0 +1,155 @@
+use crate::arity::arity;
+use crate::messages::Message;
+use json::JsonValue;
+use std::fmt;
+
+/// One element or dictionary entry that differs between two messages.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Difference {
+    /// Field name from the [`ARITY_TABLE`](crate::arity::ARITY_TABLE) followed by the keys and
+    /// indices into it, e.g. `Options.timeout` or `Arguments[1]`.
+    pub path: String,
+    /// Value in the left message, `None` when it is missing there.
+    pub left: Option<JsonValue>,
+    /// Value in the right message, `None` when it is missing there.
+    pub right: Option<JsonValue>,
+}
+
+impl fmt::Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let show = |value: &Option<JsonValue>| match value {
+            Some(value) => value.dump(),
+            None => "<missing>".to_string(),
+        };
+        write!(
+            f,
+            "{}: {} != {}",
+            self.path,
+            show(&self.left),
+            show(&self.right)
+        )
+    }
+}
+
+/// Field-by-field differences between two messages, see [`Message::diff`].
+///
+/// Displays as one line per difference, in wire order.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MessageDiff {
+    pub differences: Vec<Difference>,
+}
+
+impl MessageDiff {
+    pub fn is_empty(&self) -> bool {
+        self.differences.is_empty()
+    }
+
+    /// The difference at `path`, if any.
+    pub fn get(&self, path: &str) -> Option<&Difference> {
+        self.differences
+            .iter()
+            .find(|difference| difference.path == path)
+    }
+}
+
+impl fmt::Display for MessageDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for difference in &self.differences {
+            writeln!(f, "{difference}")?;
+        }
+        Ok(())
+    }
+}
+
+pub(crate) fn diff(left: &Message, right: &Message) -> MessageDiff {
+    // Messages that fail to serialize compare as null.
+    let frame = |message: &Message| message.clone().to_json().unwrap_or(JsonValue::Null);
+    let (left, right) = (frame(left), frame(right));
+    let mut differences = Vec::new();
+
+    if left[0] != right[0] {
+        differences.push(Difference {
+            path: "Code".to_string(),
+            left: Some(left[0].clone()),
+            right: Some(right[0].clone()),
+        });
+        return MessageDiff { differences };
+    }
+
+    let fields = left[0].as_u8().and_then(arity).map(|arity| arity.fields);
+    for index in 0..left.len().max(right.len()) {
+        let path = match fields.and_then(|fields| fields.get(index)) {
+            Some(field) => field.name.to_string(),
+            None => format!("[{index}]"),
+        };
+        compare(
+            path,
+            element(&left, index),
+            element(&right, index),
+            &mut differences,
+        );
+    }
+    MessageDiff { differences }
+}
+
+fn element(frame: &JsonValue, index: usize) -> Option<&JsonValue> {
+    (index < frame.len()).then(|| &frame[index])
+}
+
+fn compare(
+    path: String,
+    left: Option<&JsonValue>,
+    right: Option<&JsonValue>,
+    differences: &mut Vec<Difference>,
+) {
+    match (left, right) {
+        (Some(left), Some(right)) if left.is_object() && right.is_object() => {
+            for (key, value) in left.entries() {
+                compare(
+                    key_path(&path, key),
+                    Some(value),
+                    entry(right, key),
+                    differences,
+                );
+            }
+            for (key, value) in right.entries() {
+                if !left.has_key(key) {
+                    compare(key_path(&path, key), None, Some(value), differences);
+                }
+            }
+        }
+        (Some(left), Some(right)) if left.is_array() && right.is_array() => {
+            for index in 0..left.len().max(right.len()) {
+                compare(
+                    format!("{path}[{index}]"),
+                    element(left, index),
+                    element(right, index),
+                    differences,
+                );
+            }
+        }
+        (left, right) if left != right => differences.push(Difference {
+            path,
+            left: left.cloned(),
+            right: right.cloned(),
+        }),
+        _ => {}
+    }
+}
+
+fn entry<'a>(object: &'a JsonValue, key: &str) -> Option<&'a JsonValue> {
+    object.has_key(key).then(|| &object[key])
+}
+
+/// `path.key`, or `path["key"]` when the key could be mistaken for a separator.
+fn key_path(path: &str, key: &str) -> String {
+    if !key.is_empty()
+        && key
+            .chars()
+            .all(|ch| ch.is_alphanumeric() || ch == '_' || ch == '-')
+    {
+        format!("{path}.{key}")
+    } else {
+        format!("{path}[{}]", JsonValue::from(key).dump())
+    }
+}
@@ -0,0 +1,489 @@
+//! Authorization and authentication delegated to WAMP procedures, like Crossbar's dynamic
+//! authorizers and authenticators.
+//!
+//! The router calls the configured procedure from an internal session, the dealer routes the
+//! CALL to whichever callee registered it, and the answer decides. Nothing answered in time
+//! is denied.
+
+use crate::bus::{AUTHENTICATION_FAILED, NOT_AUTHORIZED};
+use crate::config::Action;
+use crate::messages::{
+    Abort, Authenticate, Call, Challenge, Details, Hello, Message, Uri, WampId, WampMessageTrait,
+};
+use crate::options::TIMEOUT;
+use crate::sim::IdGenerator;
+use crate::value::WampValue;
+use std::collections::{BTreeMap, HashMap};
+use std::time::{Duration, Instant};
+
+/// How long the router waits for a delegated decision by default.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Errors of an authenticator that become the ABORT reason as they are. Other `wamp.` errors
+/// come from the dealer, not the authenticator, and abort with
+/// `wamp.error.authentication_failed`.
+pub const AUTHENTICATION_REASONS: [&str; 7] = [
+    AUTHENTICATION_FAILED,
+    NOT_AUTHORIZED,
+    "wamp.error.authentication_denied",
+    "wamp.error.no_auth_method",
+    "wamp.error.no_such_principal",
+    "wamp.error.no_such_realm",
+    "wamp.error.no_such_role",
+];
+
+/// Answer of a dynamic authorizer.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Authorization {
+    pub allow: bool,
+    /// Disclose the caller or publisher to the callee or subscribers.
+    pub disclose: bool,
+    /// The decision may be kept in an [`AuthorizationCache`](crate::config::AuthorizationCache).
+    pub cache: bool,
+}
+
+impl Authorization {
+    /// What an error, a timeout or a result that is not understood amounts to.
+    pub const DENY: Authorization = Authorization {
+        allow: false,
+        disclose: false,
+        cache: false,
+    };
+
+    /// Read the first result argument: a bare boolean, or a dictionary with `allow`,
+    /// `disclose` and `cache`, each `false` when absent. Anything else denies.
+    pub fn from_result(args: Option<&[WampValue]>) -> Self {
+        let flag = |entries: &BTreeMap<String, WampValue>, key: &str| {
+            matches!(entries.get(key), Some(WampValue::Bool(true)))
+        };
+        match args.and_then(<[WampValue]>::first) {
+            Some(WampValue::Bool(allow)) => Authorization {
+                allow: *allow,
+                ..Authorization::DENY
+            },
+            Some(WampValue::Dict(entries)) => Authorization {
+                allow: flag(entries, "allow"),
+                disclose: flag(entries, "disclose"),
+                cache: flag(entries, "cache"),
+            },
+            _ => Authorization::DENY,
+        }
+    }
+}
+
+/// The result arguments of a delegated call, or the URI of its ERROR.
+type Answer<'m> = Result<Option<&'m [WampValue]>, &'m Uri>;
+
+/// Calls to a delegate waiting for their answer, by request id, with their token and
+/// deadline.
+#[derive(Debug)]
+struct Pending<T> {
+    procedure: Uri,
+    timeout: Duration,
+    calls: HashMap<WampId, (T, Instant)>,
+}
+
+impl<T> Pending<T> {
+    fn new(procedure: Uri) -> Self {
+        Pending {
+            procedure,
+            timeout: DEFAULT_TIMEOUT,
+            calls: HashMap::new(),
+        }
+    }
+
+    fn call(
+        &mut self,
+        token: T,
+        args: Vec<WampValue>,
+        ids: &mut impl IdGenerator,
+        now: Instant,
+    ) -> Call {
+        let request = ids.next_id();
+        self.calls.insert(request, (token, now + self.timeout));
+        Call {
+            request,
+            options: json::object! { [TIMEOUT]: self.timeout.as_millis() as u64 },
+            procedure: self.procedure.clone(),
+            args: Some(args),
+            kwargs: None,
+        }
+    }
+
+    /// The token of the call `message` answers, with its result arguments or its ERROR. The
+    /// CALL does not ask for progressive results, a progressive RESULT sent anyway is not the
+    /// answer and is skipped.
+    fn answer<'m>(&mut self, message: &'m Message) -> Option<(T, Answer<'m>)> {
+        let (request, answer) = match message {
+            Message::MessageResult(result)
+                if result.details["progress"].as_bool() == Some(true) =>
+            {
+                return None
+            }
+            Message::MessageResult(result) => (result.request, Ok(result.args.as_deref())),
+            Message::ErrorMessage(error) if error.request_type == Call::ID => {
+                (error.request, Err(&error.error))
+            }
+            _ => return None,
+        };
+        let (token, _) = self.calls.remove(&request)?;
+        Some((token, answer))
+    }
+
+    fn expire(&mut self, now: Instant) -> Vec<T> {
+        let expired: Vec<WampId> = self
+            .calls
+            .iter()
+            .filter(|(_, (_, deadline))| *deadline <= now)
+            .map(|(request, _)| *request)
+            .collect();
+        expired
+            .into_iter()
+            .filter_map(|request| self.calls.remove(&request))
+            .map(|(token, _)| token)
+            .collect()
+    }
+}
+
+/// Asks an authorizer procedure whether a session may perform an action on a URI, calling it
+/// with the session details, the URI and the action name.
+///
+/// `T` is whatever the router needs to resume once the decision is in, e.g. the session id
+/// and the held message. Fail-closed: an ERROR, a result that is not understood or no answer
+/// within the timeout all deny. Progressive results are not asked for and are ignored, only
+/// the final RESULT decides.
+/// # Examples
+/// ```
+/// use std::time::{Duration, Instant};
+/// use wamp_helpers::config::Action;
+/// use wamp_helpers::dynamic::{Authorization, DynamicAuthorizer};
+/// use wamp_helpers::messages::Message;
+/// use wamp_helpers::sim::SequentialIdGenerator;
+///
+/// let mut authorizer = DynamicAuthorizer::new("com.example.authorize")
+///     .timeout(Duration::from_secs(2));
+/// let mut ids = SequentialIdGenerator::default();
+/// let start = Instant::now();
+/// let session = json::object! { session: 7, authid: "alice", authrole: "user" };
+///
+/// let call = authorizer.authorize("first", &session, "com.example.add", Action::Call, &mut ids, start);
+/// assert_eq!(call.procedure, "com.example.authorize");
+/// assert_eq!(call.options["timeout"], 2000);
+/// assert_eq!(call.args.as_ref().unwrap()[2], "call".into());
+///
+/// // A progressive result is not the decision.
+/// let progress = format!(r#"[50, {}, {{"progress": true}}, [{{"allow": false}}]]"#, call.request);
+/// assert!(authorizer.on_message(&Message::parse_message(&progress).unwrap()).is_none());
+///
+/// let result = format!(r#"[50, {}, {{}}, [{{"allow": true, "cache": true}}]]"#, call.request);
+/// let (token, decision) = authorizer.on_message(&Message::parse_message(&result).unwrap()).unwrap();
+/// assert_eq!(token, "first");
+/// assert_eq!(decision, Authorization { allow: true, disclose: false, cache: true });
+///
+/// // The authorizer does not answer in time, the action is denied.
+/// authorizer.authorize("second", &session, "com.example.add", Action::Call, &mut ids, start);
+/// assert!(authorizer.expire(start + Duration::from_secs(1)).is_empty());
+/// let expired = authorizer.expire(start + Duration::from_secs(2));
+/// assert_eq!(expired, [("second", Authorization::DENY)]);
+/// ```
+#[derive(Debug)]
+pub struct DynamicAuthorizer<T> {
+    pending: Pending<T>,
+}
+
+impl<T> DynamicAuthorizer<T> {
+    pub fn new(procedure: impl Into<Uri>) -> Self {
+        DynamicAuthorizer {
+            pending: Pending::new(procedure.into()),
+        }
+    }
+
+    /// How long to wait for the authorizer, also sent as the CALL timeout so the dealer
+    /// gives up as well. Defaults to [`DEFAULT_TIMEOUT`].
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.pending.timeout = timeout;
+        self
+    }
+
+    pub fn procedure(&self) -> &Uri {
+        &self.pending.procedure
+    }
+
+    /// The CALL asking whether the session with `session` details may perform `action` on
+    /// `uri`, the decision comes back with `token`.
+    pub fn authorize(
+        &mut self,
+        token: T,
+        session: &Details,
+        uri: &str,
+        action: Action,
+        ids: &mut impl IdGenerator,
+        now: Instant,
+    ) -> Call {
+        let args = vec![WampValue::from(session), uri.into(), action.as_str().into()];
+        self.pending.call(token, args, ids, now)
+    }
+
+    /// The decision `message` carries, with the token it was asked with. ERRORs deny.
+    pub fn on_message(&mut self, message: &Message) -> Option<(T, Authorization)> {
+        let (token, answer) = self.pending.answer(message)?;
+        let decision = answer.map_or(Authorization::DENY, Authorization::from_result);
+        Some((token, decision))
+    }
+
+    /// Deny every request whose deadline passed at `now`.
+    pub fn expire(&mut self, now: Instant) -> Vec<(T, Authorization)> {
+        let expired = self.pending.expire(now).into_iter();
+        expired.map(|token| (token, Authorization::DENY)).collect()
+    }
+
+    /// Number of requests waiting for a decision.
+    pub fn pending(&self) -> usize {
+        self.pending.calls.len()
+    }
+}
+
+/// The principal a dynamic authenticator admitted.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AuthenticatedPrincipal {
+    /// The authid to welcome the session with, `None` to keep the one from HELLO.
+    pub authid: Option<String>,
+    pub authrole: String,
+    /// Realm to join instead of the requested one.
+    pub realm: Option<Uri>,
+    /// WAMP-CRA secret of the principal: the router challenges the client with it before
+    /// welcoming, see `CraSecret` in the `cra` module.
+    pub secret: Option<String>,
+    /// Other entries of the answer, e.g. `salt`, `iterations` and `keylen` or `extra`.
+    pub extra: BTreeMap<String, WampValue>,
+}
+
+/// What the router does after a dynamic authenticator answered.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Authentication {
+    /// Admit the principal.
+    Principal(AuthenticatedPrincipal),
+    /// Send the CHALLENGE the authenticator asked for, its AUTHENTICATE goes to
+    /// [`on_authenticate`](DynamicAuthenticator::on_authenticate).
+    Challenge(Challenge),
+    /// Refuse the session.
+    Abort(Abort),
+}
+
+impl Authentication {
+    /// Refuse with `reason`, telling the client why in `message`.
+    pub fn abort(reason: impl Into<Uri>, message: &str) -> Self {
+        Authentication::Abort(Abort {
+            details: json::object! { message: message },
+            reason: reason.into(),
+        })
+    }
+
+    /// Read the first result argument: a role name, a dictionary with a `challenge`
+    /// dictionary and the `authmethod` it is for, which is required, or a principal dictionary with at least a
+    /// `role`. Anything else refuses the session.
+    pub fn from_result(args: Option<&[WampValue]>) -> Self {
+        let string = |value: Option<&WampValue>| match value {
+            Some(WampValue::String(text)) => Some(text.clone()),
+            _ => None,
+        };
+        let mut entries = match args.and_then(<[WampValue]>::first) {
+            Some(WampValue::String(role)) => {
+                return Authentication::Principal(AuthenticatedPrincipal {
+                    authrole: role.clone(),
+                    ..AuthenticatedPrincipal::default()
+                })
+            }
+            Some(WampValue::Dict(entries)) => entries.clone(),
+            _ => {
+                return Authentication::abort(
+                    AUTHENTICATION_FAILED,
+                    "unexpected authenticator answer",
+                )
+            }
+        };
+        if let Some(challenge) = entries.remove("challenge") {
+            let Some(authmethod) = string(entries.get("authmethod")) else {
+                return Authentication::abort(
+                    AUTHENTICATION_FAILED,
+                    "authenticator gave no authmethod",
+                );
+            };
+            return Authentication::Challenge(Challenge {
+                authmethod,
+                details: challenge.into(),
+            });
+        }
+        let Some(authrole) = string(entries.get("role")) else {
+            return Authentication::abort(AUTHENTICATION_FAILED, "authenticator gave no role");
+        };
+        entries.remove("role");
+        Authentication::Principal(AuthenticatedPrincipal {
+            authid: string(entries.remove("authid").as_ref()),
+            authrole,
+            realm: string(entries.remove("realm").as_ref()).map(Uri::from),
+            secret: string(entries.remove("secret").as_ref()),
+            extra: entries,
+        })
+    }
+}
+
+/// Hands authentication to an authenticator procedure, called with the realm, the authid and
+/// a details dictionary: the HELLO details on [`on_hello`](Self::on_hello), the signature
+/// and AUTHENTICATE details on [`on_authenticate`](Self::on_authenticate).
+///
+/// The authenticator answers with the principal, or with a challenge for the client first.
+/// An ERROR aborts with its URI when it is one of [`AUTHENTICATION_REASONS`] or an
+/// application error, a dealer error such as `wamp.error.no_such_procedure`, an answer that is
+/// not understood or none within the timeout with `wamp.error.authentication_failed`.
+/// # Examples
+/// ```
+/// use std::time::Instant;
+/// use wamp_helpers::dynamic::{Authentication, DynamicAuthenticator};
+/// use wamp_helpers::messages::{Authenticate, Hello, Message, Roles};
+/// use wamp_helpers::sim::SequentialIdGenerator;
+///
+/// let mut authenticator = DynamicAuthenticator::new("com.example.authenticate");
+/// let mut ids = SequentialIdGenerator::default();
+/// let now = Instant::now();
+/// let mut hello = Hello::default("realm1".to_string(), vec![Roles::Caller], Some(vec!["ticket".to_string()]));
+/// hello.details["authid"] = "alice".into();
+///
+/// // The authenticator wants a ticket before it decides.
+/// let call = authenticator.on_hello(1, &hello, &mut ids, now);
+/// assert_eq!(call.args.as_ref().unwrap()[1], "alice".into());
+/// let result = format!(r#"[50, {}, {{}}, [{{"authmethod": "ticket", "challenge": {{}}}}]]"#, call.request);
+/// let (session, Authentication::Challenge(challenge)) =
+///     authenticator.on_message(&Message::parse_message(&result).unwrap()).unwrap()
+/// else {
+///     panic!()
+/// };
+/// assert_eq!((session, challenge.authmethod.as_str()), (1, "ticket"));
+///
+/// let authenticate = Authenticate { signature: "s3cr3t".to_string(), details: json::object! {} };
+/// let call = authenticator.on_authenticate(1, "realm1", "alice", &authenticate, &mut ids, now);
+/// assert_eq!(call.args.as_ref().unwrap()[2], json::object! { signature: "s3cr3t" }.into());
+/// let result = format!(r#"[50, {}, {{}}, [{{"role": "user", "extra": {{"tier": 2}}}}]]"#, call.request);
+/// let (_, Authentication::Principal(principal)) =
+///     authenticator.on_message(&Message::parse_message(&result).unwrap()).unwrap()
+/// else {
+///     panic!()
+/// };
+/// assert_eq!(principal.authrole, "user");
+/// assert!(principal.extra.contains_key("extra"));
+///
+/// // A wrong ticket: the authenticator's error becomes the ABORT reason.
+/// let call = authenticator.on_authenticate(2, "realm1", "alice", &authenticate, &mut ids, now);
+/// let error = format!(r#"[8, 48, {}, {{}}, "wamp.error.not_authorized"]"#, call.request);
+/// let (_, Authentication::Abort(abort)) =
+///     authenticator.on_message(&Message::parse_message(&error).unwrap()).unwrap()
+/// else {
+///     panic!()
+/// };
+/// assert_eq!(abort.reason, "wamp.error.not_authorized");
+///
+/// // Without a registered authenticator the client is not told about the dealer.
+/// let call = authenticator.on_hello(3, &hello, &mut ids, now);
+/// let error = format!(r#"[8, 48, {}, {{}}, "wamp.error.no_such_procedure"]"#, call.request);
+/// let (_, Authentication::Abort(abort)) =
+///     authenticator.on_message(&Message::parse_message(&error).unwrap()).unwrap()
+/// else {
+///     panic!()
+/// };
+/// assert_eq!(abort.reason, "wamp.error.authentication_failed");
+///
+/// // A challenge needs the method it is for.
+/// let args = [json::object! { challenge: {} }.into()];
+/// assert!(matches!(Authentication::from_result(Some(&args)), Authentication::Abort(_)));
+/// ```
+#[derive(Debug)]
+pub struct DynamicAuthenticator<T> {
+    pending: Pending<T>,
+}
+
+impl<T> DynamicAuthenticator<T> {
+    pub fn new(procedure: impl Into<Uri>) -> Self {
+        DynamicAuthenticator {
+            pending: Pending::new(procedure.into()),
+        }
+    }
+
+    /// How long to wait for the authenticator, defaults to [`DEFAULT_TIMEOUT`].
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.pending.timeout = timeout;
+        self
+    }
+
+    pub fn procedure(&self) -> &Uri {
+        &self.pending.procedure
+    }
+
+    /// The CALL for a HELLO: realm, authid and the HELLO details.
+    pub fn on_hello(
+        &mut self,
+        token: T,
+        hello: &Hello,
+        ids: &mut impl IdGenerator,
+        now: Instant,
+    ) -> Call {
+        let authid = hello.details["authid"].as_str().unwrap_or_default();
+        let args = vec![
+            hello.realm.as_str().into(),
+            authid.into(),
+            WampValue::from(&hello.details),
+        ];
+        self.pending.call(token, args, ids, now)
+    }
+
+    /// The CALL for the AUTHENTICATE answering a challenge: realm, authid and the
+    /// AUTHENTICATE details with the `signature` added.
+    pub fn on_authenticate(
+        &mut self,
+        token: T,
+        realm: &str,
+        authid: &str,
+        authenticate: &Authenticate,
+        ids: &mut impl IdGenerator,
+        now: Instant,
+    ) -> Call {
+        let mut details = authenticate.details.clone();
+        details["signature"] = authenticate.signature.as_str().into();
+        let args = vec![realm.into(), authid.into(), WampValue::from(&details)];
+        self.pending.call(token, args, ids, now)
+    }
+
+    /// The outcome `message` carries, with the token it was asked with.
+    pub fn on_message(&mut self, message: &Message) -> Option<(T, Authentication)> {
+        let (token, answer) = self.pending.answer(message)?;
+        let outcome = match answer {
+            Ok(args) => Authentication::from_result(args),
+            Err(error)
+                if AUTHENTICATION_REASONS.contains(&error.as_str())
+                    || !error.starts_with("wamp.") =>
+            {
+                Authentication::Abort(Abort {
+                    details: json::object! {},
+                    reason: error.clone(),
+                })
+            }
+            Err(_) => Authentication::abort(AUTHENTICATION_FAILED, "authenticator failed"),
+        };
+        Some((token, outcome))
+    }
+
+    /// Abort every authentication whose deadline passed at `now`.
+    pub fn expire(&mut self, now: Instant) -> Vec<(T, Authentication)> {
+        let expired = self.pending.expire(now).into_iter();
+        expired
+            .map(|token| {
+                let abort = Authentication::abort(AUTHENTICATION_FAILED, "authenticator timed out");
+                (token, abort)
+            })
+            .collect()
+    }
+
+    /// Number of authentications waiting for an answer.
+    pub fn pending(&self) -> usize {
+        self.pending.calls.len()
+    }
+}
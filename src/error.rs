@@ -7,10 +7,40 @@ pub enum Error {
     InvalidId,
     ExtensionMessage,
     NonMatchingMessageId { offense: u8 },
+    InvalidRequestType {offense: u8},
     InvalidJsonU8 {offense: JsonValue},
     InvalidJsonDict {offense: JsonValue},
     InvalidJsonArray {offense: JsonValue},
     InvalidJsonU64 {offense: JsonValue},
-    InvalidJsonStr {offense: JsonValue}
+    NonIntegerId {offense: JsonValue},
+    NegativeId {offense: JsonValue},
+    IdOutOfRange {offense: JsonValue},
+    InvalidJsonStr {offense: JsonValue},
+    DuplicateKey {key: String},
+    ReservedKey {key: String},
+    TooManyElements {id: u8, len: usize},
+    InvalidChunk {offense: JsonValue},
+    InvalidUriComponent {component: String},
+    UriCollision {uri: String},
+    InvalidErrorUri {uri: String, suggestion: Option<&'static str>},
+    DictionaryTooLarge {keys: usize, limit: usize},
+    ValueTooLarge {key: String, len: usize, limit: usize},
+    PayloadTooLarge {limit: usize},
+    TransportClosed,
+    InvalidHandshake {line: String},
+    InvalidProxyHeader {reason: &'static str},
+    Handshake {code: u8},
+    UnsupportedSerializer {subprotocol: &'static str},
+    InvalidBatch {offset: usize},
+    InvalidConfig {reason: String},
+    MessageNotAllowed {authrole: String, message_type: u8},
+    SpoofedIdentity {key: String},
+    ForwardingLoop {hops: usize},
+    FeatureNotNegotiated {feature: &'static str, implemented: bool},
+    InvalidFrame {reason: &'static str},
+    ReplayDiverged {entry: usize, reason: String},
+    Codec(Box<dyn std::error::Error + Send + Sync>),
+    Transport(Box<dyn std::error::Error + Send + Sync>),
+    Io(std::io::Error)
 }
 
@@ -0,0 +1,495 @@
+use crate::acceptor::Serializer;
+use crate::error::Error;
+use crate::messages::Message;
+use crate::pool::BufferPool;
+use crate::transcode::{decode, encode_into};
+use crate::transport::{PeerInfo, Transport};
+use crate::value::WampValue;
+#[cfg(feature = "futures")]
+use futures_io::{AsyncRead, AsyncWrite};
+use json::JsonValue;
+#[cfg(feature = "futures")]
+use std::pin::Pin;
+#[cfg(feature = "futures")]
+use std::task::{ready, Context, Poll};
+
+/// Largest payload a RawSocket frame header can describe.
+pub const MAX_RAWSOCKET_LEN: usize = (1 << 24) - 1;
+
+/// Type of a RawSocket frame, the low bits of its first header byte.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameKind {
+    Message = 0,
+    Ping = 1,
+    Pong = 2,
+}
+
+/// Splits the byte stream of an established RawSocket connection into frames, and builds the
+/// frames to write to it.
+///
+/// The codec does no I/O: feed it whatever the socket produced, from a blocking `Read` or any
+/// async runtime's reader, and take the complete frames out. This is what an embedder needs
+/// to implement [`Transport`] over TCP or TLS, then [`Framed`] turns the transport into
+/// messages.
+/// # Examples
+/// ```
+/// use wamp_helpers::framed::{FrameKind, RawSocketCodec};
+///
+/// let mut wire = Vec::new();
+/// RawSocketCodec::encode(FrameKind::Message, br#"[36, 1, 2, {}]"#, &mut wire).unwrap();
+/// RawSocketCodec::encode(FrameKind::Ping, b"1234", &mut wire).unwrap();
+///
+/// let mut codec = RawSocketCodec::new(1 << 16);
+/// // Bytes arrive in arbitrary pieces.
+/// codec.extend(&wire[..6]);
+/// assert_eq!(codec.decode().unwrap(), None);
+/// codec.extend(&wire[6..]);
+/// assert_eq!(codec.decode().unwrap(), Some((FrameKind::Message, br#"[36, 1, 2, {}]"#.to_vec())));
+/// assert_eq!(codec.decode().unwrap(), Some((FrameKind::Ping, b"1234".to_vec())));
+/// assert_eq!(codec.decode().unwrap(), None);
+/// ```
+#[derive(Debug, Clone)]
+pub struct RawSocketCodec {
+    buffer: Vec<u8>,
+    max_len: usize,
+}
+
+impl RawSocketCodec {
+    /// `max_len` is the receive limit announced in the handshake.
+    pub fn new(max_len: usize) -> Self {
+        RawSocketCodec {
+            buffer: Vec::new(),
+            max_len,
+        }
+    }
+
+    /// Append bytes read from the connection.
+    pub fn extend(&mut self, bytes: &[u8]) {
+        self.buffer.extend_from_slice(bytes);
+    }
+
+    /// Bytes received but not yet returned as a frame.
+    pub fn buffered(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// The next complete frame, `None` until enough bytes arrived. Errors are fatal for the
+    /// connection: the peer used reserved bits or exceeded the receive limit.
+    pub fn decode(&mut self) -> Result<Option<(FrameKind, Vec<u8>)>, Error> {
+        let Some(&[kind, high, middle, low]) = self.buffer.get(..4) else {
+            return Ok(None);
+        };
+        let kind = match kind {
+            0 => FrameKind::Message,
+            1 => FrameKind::Ping,
+            2 => FrameKind::Pong,
+            _ => {
+                return Err(Error::InvalidFrame {
+                    reason: "reserved frame type",
+                })
+            }
+        };
+        let len = u32::from_be_bytes([0, high, middle, low]) as usize;
+        if len > self.max_len {
+            return Err(Error::InvalidFrame {
+                reason: "frame exceeds the receive limit",
+            });
+        }
+        let Some(payload) = self.buffer.get(4..4 + len) else {
+            return Ok(None);
+        };
+        let payload = payload.to_vec();
+        self.buffer.drain(..4 + len);
+        Ok(Some((kind, payload)))
+    }
+
+    /// Append the frame carrying `payload` to `out`.
+    pub fn encode(kind: FrameKind, payload: &[u8], out: &mut Vec<u8>) -> Result<(), Error> {
+        if payload.len() > MAX_RAWSOCKET_LEN {
+            return Err(Error::InvalidFrame {
+                reason: "payload too large for a frame",
+            });
+        }
+        let len = (payload.len() as u32).to_be_bytes();
+        out.extend_from_slice(&[kind as u8, len[1], len[2], len[3]]);
+        out.extend_from_slice(payload);
+        Ok(())
+    }
+}
+
+/// Parse a frame of `serializer` into a message.
+pub fn decode_message(frame: &[u8], serializer: Serializer) -> Result<Message, Error> {
+    match serializer {
+        Serializer::Json => {
+            let text = std::str::from_utf8(frame).map_err(|error| Error::Codec(Box::new(error)))?;
+            Message::parse_message(text)
+        }
+        _ => Message::parse_message(&JsonValue::from(decode(frame, serializer)?).dump()),
+    }
+}
+
+/// Serialize `message` into a frame of `serializer`.
+pub fn encode_message(message: Message, serializer: Serializer) -> Result<Vec<u8>, Error> {
+    let mut frame = Vec::with_capacity(message.encoded_size_hint(serializer));
+    let json = message.to_json()?;
+    match serializer {
+        Serializer::Json => json.write(&mut frame).map_err(Error::Io)?,
+        _ => encode_into(WampValue::from(json), serializer, &mut frame)?,
+    }
+    Ok(frame)
+}
+
+/// Messages over any [`Transport`]: received frames are parsed with the negotiated
+/// serializer, sent messages serialized with it.
+///
+/// [`next`](Framed::next) and [`send`](Framed::send) are the message-level counterparts of
+/// the transport's methods. With the `futures` feature, a `Framed` over a [`RawSocket`] is
+/// also a `futures` `Stream` of received messages and `Sink` of messages to send.
+/// # Examples
+/// ```
+/// use std::future::Future;
+/// use std::task::{Context, Poll, Waker};
+/// use wamp_helpers::acceptor::Serializer;
+/// use wamp_helpers::framed::Framed;
+/// use wamp_helpers::memory::MemoryTransport;
+/// use wamp_helpers::messages::Message;
+///
+/// fn now<F: Future>(future: F) -> F::Output {
+///     let mut future = std::pin::pin!(future);
+///     match future.as_mut().poll(&mut Context::from_waker(Waker::noop())) {
+///         Poll::Ready(output) => output,
+///         Poll::Pending => panic!("future is not ready"),
+///     }
+/// }
+///
+/// let (client, router) = MemoryTransport::pair();
+/// let mut client = Framed::new(client, Serializer::Json);
+/// let mut router = Framed::new(router, Serializer::Json);
+///
+/// let hello = Message::parse_message(r#"[1, "realm1", {"roles": {"caller": {}}}]"#).unwrap();
+/// now(client.send(hello.clone())).unwrap();
+/// assert_eq!(now(router.next()).unwrap().unwrap(), hello);
+///
+/// now(router.close("wamp.close.normal")).unwrap();
+/// assert!(now(client.next()).is_none());
+/// ```
+#[derive(Debug)]
+pub struct Framed<T> {
+    inner: T,
+    serializer: Serializer,
+    pool: Option<BufferPool>,
+}
+
+impl<T: Transport + Send> Framed<T> {
+    pub fn new(inner: T, serializer: Serializer) -> Self {
+        Framed {
+            inner,
+            serializer,
+            pool: None,
+        }
+    }
+
+    /// Encode sent messages into buffers from `pool`, and give it back received frames once
+    /// parsed. Frames cycle through the pool when both ends share it, otherwise the
+    /// transport gives the sent frames back once written.
+    /// # Examples
+    /// ```
+    /// # use std::future::Future;
+    /// # use std::task::{Context, Poll, Waker};
+    /// # fn now<F: Future>(future: F) -> F::Output {
+    /// #     let mut future = std::pin::pin!(future);
+    /// #     match future.as_mut().poll(&mut Context::from_waker(Waker::noop())) {
+    /// #         Poll::Ready(output) => output,
+    /// #         Poll::Pending => panic!("future is not ready"),
+    /// #     }
+    /// # }
+    /// use wamp_helpers::acceptor::Serializer;
+    /// use wamp_helpers::framed::Framed;
+    /// use wamp_helpers::memory::MemoryTransport;
+    /// use wamp_helpers::messages::Message;
+    /// use wamp_helpers::pool::BufferPool;
+    ///
+    /// let pool = BufferPool::new();
+    /// let (client, router) = MemoryTransport::pair();
+    /// let mut client = Framed::new(client, Serializer::Json).pool(pool.clone());
+    /// let mut router = Framed::new(router, Serializer::Json).pool(pool.clone());
+    /// let publish = Message::parse_message(r#"[16, 1, {}, "com.example.topic"]"#).unwrap();
+    /// for _ in 0..3 {
+    ///     now(client.send(publish.clone())).unwrap();
+    ///     assert_eq!(now(router.next()).unwrap().unwrap(), publish);
+    /// }
+    /// assert_eq!((pool.stats().hits, pool.stats().misses), (2, 1));
+    /// ```
+    pub fn pool(mut self, pool: BufferPool) -> Self {
+        self.pool = Some(pool);
+        self
+    }
+
+    pub fn serializer(&self) -> Serializer {
+        self.serializer
+    }
+
+    /// Wait for the next message, `None` once the peer closed the transport. A frame that
+    /// fails to parse is returned as an error, the following ones are still read.
+    pub async fn next(&mut self) -> Option<Result<Message, Error>> {
+        let frame = match self.inner.next().await? {
+            Ok(frame) => frame,
+            Err(error) => return Some(Err(error)),
+        };
+        let message = decode_message(&frame, self.serializer);
+        if let Some(pool) = &self.pool {
+            pool.give_bytes(frame);
+        }
+        Some(message)
+    }
+
+    pub async fn send(&mut self, message: Message) -> Result<(), Error> {
+        let frame = match &self.pool {
+            Some(pool) => pool.encode(message, self.serializer)?,
+            None => encode_message(message, self.serializer)?,
+        };
+        self.inner.send(frame).await
+    }
+
+    pub async fn close(&mut self, reason: &str) -> Result<(), Error> {
+        self.inner.close(reason).await
+    }
+
+    pub fn peer(&self) -> Option<&PeerInfo> {
+        self.inner.peer()
+    }
+
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+/// Bytes of queued frames above which [`Framed`]'s `Sink` writes them out before taking more.
+#[cfg(feature = "futures")]
+pub const WRITE_HIGH_WATER: usize = 64 * 1024;
+
+/// [`Transport`] over the byte stream of an established RawSocket connection, any
+/// `futures-io` `AsyncRead + AsyncWrite` such as a TCP or TLS stream after the handshake.
+///
+/// Frames are cut with a [`RawSocketCodec`]. Pings are answered with a pong on the next flush,
+/// pongs are dropped, only message frames are returned.
+/// # Examples
+/// ```
+/// # use std::io;
+/// # use std::pin::Pin;
+/// # use std::task::{Context, Poll};
+/// # use futures_io::{AsyncRead, AsyncWrite};
+/// # /// Reads back what was written.
+/// # #[derive(Default)]
+/// # struct Loopback(std::collections::VecDeque<u8>);
+/// # impl AsyncRead for Loopback {
+/// #     fn poll_read(mut self: Pin<&mut Self>, _: &mut Context<'_>, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+/// #         let n = buf.len().min(self.0.len());
+/// #         for (slot, byte) in buf.iter_mut().zip(self.0.drain(..n)) { *slot = byte; }
+/// #         Poll::Ready(Ok(n))
+/// #     }
+/// # }
+/// # impl AsyncWrite for Loopback {
+/// #     fn poll_write(mut self: Pin<&mut Self>, _: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+/// #         self.0.extend(buf);
+/// #         Poll::Ready(Ok(buf.len()))
+/// #     }
+/// #     fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> { Poll::Ready(Ok(())) }
+/// #     fn poll_close(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> { Poll::Ready(Ok(())) }
+/// # }
+/// use std::task::Waker;
+/// use futures_core::Stream;
+/// use futures_sink::Sink;
+/// use wamp_helpers::acceptor::Serializer;
+/// use wamp_helpers::framed::{Framed, RawSocket};
+/// use wamp_helpers::messages::Message;
+///
+/// // `Loopback` is an in-memory stream reading back what was written to it.
+/// let mut framed = Framed::new(RawSocket::new(Loopback::default(), 1 << 16), Serializer::Json);
+/// let mut cx = Context::from_waker(Waker::noop());
+/// let publish = Message::parse_message(r#"[16, 1, {}, "com.example.topic"]"#).unwrap();
+///
+/// let mut sink = Pin::new(&mut framed);
+/// assert!(sink.as_mut().poll_ready(&mut cx).is_ready());
+/// sink.as_mut().start_send(publish.clone()).unwrap();
+/// assert!(sink.as_mut().poll_flush(&mut cx).is_ready());
+///
+/// let Poll::Ready(Some(Ok(received))) = Pin::new(&mut framed).poll_next(&mut cx) else {
+///     panic!()
+/// };
+/// assert_eq!(received, publish);
+/// ```
+#[cfg(feature = "futures")]
+#[derive(Debug)]
+pub struct RawSocket<S> {
+    io: S,
+    codec: RawSocketCodec,
+    /// Encoded frames not written yet, from `written` on.
+    write: Vec<u8>,
+    written: usize,
+    eof: bool,
+}
+
+#[cfg(feature = "futures")]
+impl<S: AsyncRead + AsyncWrite + Unpin> RawSocket<S> {
+    /// `max_len` is the receive limit announced in the handshake.
+    pub fn new(io: S, max_len: usize) -> Self {
+        RawSocket {
+            io,
+            codec: RawSocketCodec::new(max_len),
+            write: Vec::new(),
+            written: 0,
+            eof: false,
+        }
+    }
+
+    /// The payload of the next message frame, `None` once the peer closed the connection.
+    pub fn poll_frame(&mut self, cx: &mut Context<'_>) -> Poll<Option<Result<Vec<u8>, Error>>> {
+        let mut chunk = [0; 8192];
+        loop {
+            match self.codec.decode() {
+                Ok(Some((FrameKind::Message, payload))) => return Poll::Ready(Some(Ok(payload))),
+                Ok(Some((FrameKind::Ping, payload))) => {
+                    if let Err(error) =
+                        RawSocketCodec::encode(FrameKind::Pong, &payload, &mut self.write)
+                    {
+                        return Poll::Ready(Some(Err(error)));
+                    }
+                    continue;
+                }
+                Ok(Some((FrameKind::Pong, _))) => continue,
+                Ok(None) => {}
+                Err(error) => return Poll::Ready(Some(Err(error))),
+            }
+            if self.eof {
+                return Poll::Ready(None);
+            }
+            match ready!(Pin::new(&mut self.io).poll_read(cx, &mut chunk)) {
+                Ok(0) => self.eof = true,
+                Ok(read) => self.codec.extend(chunk.get(..read).unwrap_or_default()),
+                Err(error) => return Poll::Ready(Some(Err(Error::Io(error)))),
+            }
+        }
+    }
+
+    /// Queue a message frame carrying `payload`, written on the next flush.
+    pub fn start_frame(&mut self, payload: &[u8]) -> Result<(), Error> {
+        RawSocketCodec::encode(FrameKind::Message, payload, &mut self.write)
+    }
+
+    /// Bytes of queued frames not written yet.
+    pub fn pending(&self) -> usize {
+        self.write.len() - self.written
+    }
+
+    /// Write every queued frame and flush the stream.
+    pub fn poll_flush(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
+        while let Some(unwritten) = self
+            .write
+            .get(self.written..)
+            .filter(|rest| !rest.is_empty())
+        {
+            match ready!(Pin::new(&mut self.io).poll_write(cx, unwritten)) {
+                Ok(0) => return Poll::Ready(Err(Error::Io(std::io::ErrorKind::WriteZero.into()))),
+                Ok(written) => self.written += written,
+                Err(error) => return Poll::Ready(Err(Error::Io(error))),
+            }
+        }
+        self.write.clear();
+        self.written = 0;
+        Pin::new(&mut self.io).poll_flush(cx).map_err(Error::Io)
+    }
+
+    /// Flush, then close the stream.
+    pub fn poll_close(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
+        ready!(self.poll_flush(cx))?;
+        Pin::new(&mut self.io).poll_close(cx).map_err(Error::Io)
+    }
+
+    pub fn get_ref(&self) -> &S {
+        &self.io
+    }
+
+    pub fn into_inner(self) -> S {
+        self.io
+    }
+}
+
+#[cfg(feature = "futures")]
+impl<S: AsyncRead + AsyncWrite + Unpin + Send> Transport for RawSocket<S> {
+    async fn send(&mut self, frame: Vec<u8>) -> Result<(), Error> {
+        self.start_frame(&frame)?;
+        std::future::poll_fn(|cx| self.poll_flush(cx)).await
+    }
+
+    async fn next(&mut self) -> Option<Result<Vec<u8>, Error>> {
+        std::future::poll_fn(|cx| self.poll_frame(cx)).await
+    }
+
+    /// RawSocket has no close reason, the stream is flushed and closed.
+    async fn close(&mut self, _reason: &str) -> Result<(), Error> {
+        std::future::poll_fn(|cx| self.poll_close(cx)).await
+    }
+}
+
+#[cfg(feature = "futures")]
+impl<S: AsyncRead + AsyncWrite + Unpin> futures_core::Stream for Framed<RawSocket<S>> {
+    type Item = Result<Message, Error>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        let frame = match ready!(this.inner.poll_frame(cx)) {
+            Some(Ok(frame)) => frame,
+            Some(Err(error)) => return Poll::Ready(Some(Err(error))),
+            None => return Poll::Ready(None),
+        };
+        let message = decode_message(&frame, this.serializer);
+        if let Some(pool) = &this.pool {
+            pool.give_bytes(frame);
+        }
+        Poll::Ready(Some(message))
+    }
+}
+
+#[cfg(feature = "futures")]
+impl<S: AsyncRead + AsyncWrite + Unpin> futures_sink::Sink<Message> for Framed<RawSocket<S>> {
+    type Error = Error;
+
+    /// Ready while less than [`WRITE_HIGH_WATER`] bytes are queued, writes them out otherwise.
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
+        let this = self.get_mut();
+        if this.inner.pending() < WRITE_HIGH_WATER {
+            return Poll::Ready(Ok(()));
+        }
+        this.inner.poll_flush(cx)
+    }
+
+    fn start_send(self: Pin<&mut Self>, message: Message) -> Result<(), Error> {
+        let this = self.get_mut();
+        let frame = match &this.pool {
+            Some(pool) => pool.encode(message, this.serializer)?,
+            None => encode_message(message, this.serializer)?,
+        };
+        let queued = this.inner.start_frame(&frame);
+        if let Some(pool) = &this.pool {
+            pool.give_bytes(frame);
+        }
+        queued
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
+        self.get_mut().inner.poll_flush(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
+        self.get_mut().inner.poll_close(cx)
+    }
+}
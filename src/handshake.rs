@@ -0,0 +1,624 @@
+use crate::error::Error;
+use crate::messages::{
+    Details, ErrorMessage, Hello, Message, RequestType, Roles, Uri, WampId, Welcome,
+};
+use crate::uri::is_valid_uri;
+use std::collections::BTreeSet;
+
+/// Something wrong with a HELLO, in the order the checks run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HelloIssue {
+    InvalidRealm,
+    /// `Details.roles` is missing or not a dictionary.
+    MissingRoles,
+    /// `Details.roles` is a dictionary without any role.
+    NoRoles,
+    /// A role only routers play, such as `dealer`.
+    RouterRole(Roles),
+    UnknownRole(String),
+    /// `Details.authmethods` is not a list of strings.
+    MalformedAuthmethods,
+    /// `Details.authid` is not a string.
+    MalformedAuthid,
+}
+
+/// How a router should answer a HELLO, see [`HelloAnalysis::decide`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HelloDecision {
+    Welcome,
+    /// Send a CHALLENGE for this authentication method.
+    Challenge(String),
+    /// Send an ABORT with this reason.
+    Abort(Uri),
+}
+
+/// The typed content of a HELLO's Details, together with everything that violates the spec.
+/// # Examples
+/// ```
+/// use wamp_helpers::handshake::{HelloAnalysis, HelloDecision};
+/// use wamp_helpers::messages::{Hello, Roles};
+///
+/// let hello: Hello = r#"[1, "com.example.realm", {
+///     "authmethods": ["wampcra", "ticket"],
+///     "authid": "joe",
+///     "roles": {"caller": {"features": {"progressive_call_results": true}}, "subscriber": {}}
+/// }]"#.parse().unwrap();
+///
+/// let analysis = HelloAnalysis::from(&hello);
+/// assert!(analysis.issues.is_empty());
+/// assert_eq!(analysis.authid.as_deref(), Some("joe"));
+/// assert_eq!(analysis.roles, [Roles::Caller, Roles::Subscriber]);
+/// assert!(analysis.has_feature(Roles::Caller, "progressive_call_results"));
+/// assert_eq!(analysis.decide(&["ticket"]), HelloDecision::Challenge("ticket".to_string()));
+/// assert_eq!(
+///     analysis.decide(&["anonymous"]),
+///     HelloDecision::Abort("wamp.error.no_auth_method".into())
+/// );
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HelloAnalysis {
+    pub realm: Uri,
+    /// Requested authentication methods in order of preference, empty for anonymous.
+    pub authmethods: Vec<String>,
+    pub authid: Option<String>,
+    /// Announced client roles in the order they appear.
+    pub roles: Vec<Roles>,
+    /// Features announced as `true`, per role.
+    pub features: Vec<(Roles, BTreeSet<String>)>,
+    pub issues: Vec<HelloIssue>,
+}
+
+impl HelloAnalysis {
+    pub fn has_role(&self, role: Roles) -> bool {
+        self.roles.contains(&role)
+    }
+
+    pub fn has_feature(&self, role: Roles, feature: &str) -> bool {
+        self.features
+            .iter()
+            .any(|(announced, features)| *announced == role && features.contains(feature))
+    }
+
+    /// Pick the answer to the HELLO given the authentication methods the router supports.
+    ///
+    /// The client's order of preference wins, a client requesting no method is treated as
+    /// requesting `anonymous`, which is answered with a WELCOME right away.
+    pub fn decide(&self, supported: &[&str]) -> HelloDecision {
+        if !self.issues.is_empty() {
+            return HelloDecision::Abort("wamp.error.protocol_violation".into());
+        }
+
+        let anonymous = ["anonymous".to_string()];
+        let requested = if self.authmethods.is_empty() {
+            &anonymous[..]
+        } else {
+            &self.authmethods[..]
+        };
+        match requested
+            .iter()
+            .find(|method| supported.contains(&method.as_str()))
+        {
+            Some(method) if method == "anonymous" => HelloDecision::Welcome,
+            Some(method) => HelloDecision::Challenge(method.clone()),
+            None => HelloDecision::Abort("wamp.error.no_auth_method".into()),
+        }
+    }
+}
+
+impl From<&Hello> for HelloAnalysis {
+    fn from(hello: &Hello) -> Self {
+        let details = &hello.details;
+        let mut issues = Vec::new();
+
+        if !is_valid_uri(&hello.realm, false) {
+            issues.push(HelloIssue::InvalidRealm);
+        }
+
+        let mut roles = Vec::new();
+        let mut features = Vec::new();
+        if details["roles"].is_object() {
+            for (name, role_details) in details["roles"].entries() {
+                let role = match name {
+                    "callee" => Roles::Callee,
+                    "caller" => Roles::Caller,
+                    "publisher" => Roles::Publisher,
+                    "subscriber" => Roles::Subscriber,
+                    "dealer" => Roles::Dealer,
+                    "broker" => Roles::Broker,
+                    _ => {
+                        issues.push(HelloIssue::UnknownRole(name.to_string()));
+                        continue;
+                    }
+                };
+                if matches!(role, Roles::Dealer | Roles::Broker) {
+                    issues.push(HelloIssue::RouterRole(role));
+                    continue;
+                }
+
+                let announced: BTreeSet<String> = role_details["features"]
+                    .entries()
+                    .filter(|(_, enabled)| enabled.as_bool() == Some(true))
+                    .map(|(feature, _)| feature.to_string())
+                    .collect();
+                if !announced.is_empty() {
+                    features.push((role, announced));
+                }
+                roles.push(role);
+            }
+            if details["roles"].is_empty() {
+                issues.push(HelloIssue::NoRoles);
+            }
+        } else {
+            issues.push(HelloIssue::MissingRoles);
+        }
+
+        let mut authmethods = Vec::new();
+        if !details["authmethods"].is_null() {
+            let valid = details["authmethods"].is_array()
+                && details["authmethods"]
+                    .members()
+                    .all(|method| method.as_str().is_some());
+            if valid {
+                authmethods = details["authmethods"]
+                    .members()
+                    .filter_map(|method| method.as_str())
+                    .map(str::to_string)
+                    .collect();
+            } else {
+                issues.push(HelloIssue::MalformedAuthmethods);
+            }
+        }
+
+        let authid = match &details["authid"] {
+            json::JsonValue::Null => None,
+            authid => match authid.as_str() {
+                Some(authid) => Some(authid.to_string()),
+                None => {
+                    issues.push(HelloIssue::MalformedAuthid);
+                    None
+                }
+            },
+        };
+
+        HelloAnalysis {
+            realm: hello.realm.clone(),
+            authmethods,
+            authid,
+            roles,
+            features,
+            issues,
+        }
+    }
+}
+
+/// Advanced features announced for the broker role: those the broker helpers of this crate
+/// implement, e.g. patterns and black/whitelisting in
+/// [`SubscriptionStore`](crate::broker::SubscriptionStore).
+pub const BROKER_FEATURES: &[&str] = &[
+    "pattern_based_subscription",
+    "publisher_exclusion",
+    "session_meta_api",
+    "subscriber_blackwhite_listing",
+    "subscription_meta_api",
+];
+
+/// Advanced features announced for the dealer role: those the dealer helpers of this crate
+/// implement.
+pub const DEALER_FEATURES: &[&str] = &[
+    "call_timeout",
+    "caller_identification",
+    "pattern_based_registration",
+    "progressive_call_results",
+    "registration_meta_api",
+    "session_meta_api",
+    "shared_registration",
+];
+
+/// Default `agent` of a WELCOME.
+pub const AGENT: &str = concat!("wamp-helpers/", env!("CARGO_PKG_VERSION"));
+
+/// Builds the Details of a router's WELCOME: the router roles with their
+/// [`BROKER_FEATURES`] and [`DEALER_FEATURES`], the agent, and what authentication settled.
+/// # Examples
+/// ```
+/// use wamp_helpers::handshake::{WelcomeDetails, DEALER_FEATURES};
+/// use wamp_helpers::messages::Roles;
+///
+/// let welcome = WelcomeDetails::new()
+///     .roles(&[Roles::Dealer])
+///     .agent("example-router/1.0")
+///     .auth("alice", "user", "wampcra")
+///     .authprovider("static")
+///     .welcome(9129137332);
+///
+/// let details = &welcome.details;
+/// assert_eq!(details["agent"], "example-router/1.0");
+/// assert_eq!(details["authrole"], "user");
+/// assert!(details["roles"]["broker"].is_null());
+/// let features = &details["roles"]["dealer"]["features"];
+/// assert_eq!(features.len(), DEALER_FEATURES.len());
+/// assert_eq!(features["progressive_call_results"], true);
+///
+/// // An anonymous session without any of the auth keys.
+/// let details = WelcomeDetails::new().details();
+/// assert!(details["roles"].has_key("broker") && details["roles"].has_key("dealer"));
+/// assert!(!details.has_key("authid"));
+///
+/// // A router implementing fewer features announces only those.
+/// let details = WelcomeDetails::new()
+///     .supported(Roles::Broker, &["publisher_exclusion"])
+///     .details();
+/// assert_eq!(details["roles"]["broker"]["features"].dump(), r#"{"publisher_exclusion":true}"#);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WelcomeDetails {
+    roles: Vec<Roles>,
+    broker: Vec<String>,
+    dealer: Vec<String>,
+    agent: String,
+    authid: Option<String>,
+    authrole: Option<String>,
+    authmethod: Option<String>,
+    authprovider: Option<String>,
+    authextra: Option<Details>,
+}
+
+impl Default for WelcomeDetails {
+    fn default() -> Self {
+        let owned =
+            |features: &[&str]| features.iter().map(|feature| feature.to_string()).collect();
+        WelcomeDetails {
+            roles: vec![Roles::Broker, Roles::Dealer],
+            broker: owned(BROKER_FEATURES),
+            dealer: owned(DEALER_FEATURES),
+            agent: AGENT.to_string(),
+            authid: None,
+            authrole: None,
+            authmethod: None,
+            authprovider: None,
+            authextra: None,
+        }
+    }
+}
+
+impl WelcomeDetails {
+    /// Broker and dealer, with the [`AGENT`] of this crate.
+    pub fn new() -> Self {
+        WelcomeDetails::default()
+    }
+
+    /// The router roles the realm plays, client roles are ignored.
+    pub fn roles(mut self, roles: &[Roles]) -> Self {
+        self.roles = roles
+            .iter()
+            .copied()
+            .filter(|role| matches!(role, Roles::Broker | Roles::Dealer))
+            .collect();
+        self
+    }
+
+    /// The features announced for `role`, `Broker` or `Dealer`, by default
+    /// [`BROKER_FEATURES`] and [`DEALER_FEATURES`]. Pass the same lists to
+    /// [`FeatureGate::supported`].
+    pub fn supported(mut self, role: Roles, features: &[&str]) -> Self {
+        let features = features.iter().map(|feature| feature.to_string()).collect();
+        match role {
+            Roles::Broker => self.broker = features,
+            Roles::Dealer => self.dealer = features,
+            _ => {}
+        }
+        self
+    }
+
+    pub fn agent(mut self, agent: impl Into<String>) -> Self {
+        self.agent = agent.into();
+        self
+    }
+
+    /// Who the session authenticated as, and how.
+    pub fn auth(
+        mut self,
+        authid: impl Into<String>,
+        authrole: impl Into<String>,
+        authmethod: impl Into<String>,
+    ) -> Self {
+        self.authid = Some(authid.into());
+        self.authrole = Some(authrole.into());
+        self.authmethod = Some(authmethod.into());
+        self
+    }
+
+    /// Where the principal came from, e.g. `static` or `dynamic`.
+    pub fn authprovider(mut self, authprovider: impl Into<String>) -> Self {
+        self.authprovider = Some(authprovider.into());
+        self
+    }
+
+    /// Extra information for the client, e.g. from a dynamic authenticator.
+    pub fn authextra(mut self, authextra: Details) -> Self {
+        self.authextra = Some(authextra);
+        self
+    }
+
+    pub fn details(&self) -> Details {
+        let mut details = json::object! { agent: self.agent.as_str(), roles: {} };
+        for role in &self.roles {
+            let (name, features) = match role {
+                Roles::Broker => ("broker", &self.broker),
+                _ => ("dealer", &self.dealer),
+            };
+            let mut announced = json::object! {};
+            for feature in features {
+                announced[feature.as_str()] = true.into();
+            }
+            details["roles"][name] = json::object! { features: announced };
+        }
+        let auth = [
+            ("authid", &self.authid),
+            ("authrole", &self.authrole),
+            ("authmethod", &self.authmethod),
+            ("authprovider", &self.authprovider),
+        ];
+        for (key, value) in auth {
+            if let Some(value) = value {
+                details[key] = value.as_str().into();
+            }
+        }
+        if let Some(authextra) = &self.authextra {
+            details["authextra"] = authextra.clone();
+        }
+        details
+    }
+
+    pub fn welcome(&self, session: WampId) -> Welcome {
+        Welcome {
+            session,
+            details: self.details(),
+        }
+    }
+}
+
+/// ERROR for an option whose feature the router does not implement.
+pub const FEATURE_NOT_SUPPORTED: &str = "wamp.error.feature_not_supported";
+/// ERROR for an option whose feature the client did not announce in its HELLO.
+pub const OPTION_NOT_ALLOWED: &str = "wamp.error.option_not_allowed";
+
+/// An option that needs an advanced feature, for a request type.
+struct Gated {
+    message_id: u8,
+    key: &'static str,
+    feature: &'static str,
+    /// Whether the value asks for the feature, defaults such as `timeout: 0` do not.
+    uses: fn(&json::JsonValue) -> bool,
+}
+
+fn enabled(value: &json::JsonValue) -> bool {
+    value.as_bool() == Some(true)
+}
+
+fn disabled(value: &json::JsonValue) -> bool {
+    value.as_bool() == Some(false)
+}
+
+fn positive(value: &json::JsonValue) -> bool {
+    value.as_u64().is_some_and(|value| value > 0)
+}
+
+fn pattern(value: &json::JsonValue) -> bool {
+    value.as_str().is_some_and(|policy| policy != "exact")
+}
+
+fn shared(value: &json::JsonValue) -> bool {
+    value.as_str().is_some_and(|policy| policy != "single")
+}
+
+fn non_empty(value: &json::JsonValue) -> bool {
+    !value.is_empty()
+}
+
+const GATED: &[Gated] = &[
+    Gated {
+        message_id: 48,
+        key: "receive_progress",
+        feature: "progressive_call_results",
+        uses: enabled,
+    },
+    Gated {
+        message_id: 48,
+        key: "timeout",
+        feature: "call_timeout",
+        uses: positive,
+    },
+    Gated {
+        message_id: 48,
+        key: "disclose_me",
+        feature: "caller_identification",
+        uses: enabled,
+    },
+    Gated {
+        message_id: 64,
+        key: "match",
+        feature: "pattern_based_registration",
+        uses: pattern,
+    },
+    Gated {
+        message_id: 64,
+        key: "invoke",
+        feature: "shared_registration",
+        uses: shared,
+    },
+    Gated {
+        message_id: 32,
+        key: "match",
+        feature: "pattern_based_subscription",
+        uses: pattern,
+    },
+    Gated {
+        message_id: 16,
+        key: "exclude_me",
+        feature: "publisher_exclusion",
+        uses: disabled,
+    },
+    Gated {
+        message_id: 16,
+        key: "exclude",
+        feature: "subscriber_blackwhite_listing",
+        uses: non_empty,
+    },
+    Gated {
+        message_id: 16,
+        key: "eligible",
+        feature: "subscriber_blackwhite_listing",
+        uses: non_empty,
+    },
+    Gated {
+        message_id: 16,
+        key: "disclose_me",
+        feature: "publisher_identification",
+        uses: enabled,
+    },
+];
+
+/// What a [`FeatureGate`] does with options whose feature was not negotiated.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum FeaturePolicy {
+    /// Remove the options and route the message without them.
+    Strip,
+    /// Answer the request with an ERROR.
+    #[default]
+    Reject,
+}
+
+/// Checks the advanced options of a client's CALL, REGISTER, PUBLISH and SUBSCRIBE against
+/// the features negotiated in the handshake: the client must have announced the feature for
+/// the role it acts in, and the router must implement it.
+/// # Examples
+/// ```
+/// use wamp_helpers::handshake::{feature_denial, FeatureGate, FeaturePolicy, HelloAnalysis};
+/// use wamp_helpers::messages::{Hello, Message};
+///
+/// let hello: Hello = r#"[1, "realm1", {"roles": {
+///     "caller": {"features": {"call_timeout": true}},
+///     "subscriber": {}
+/// }}]"#.parse().unwrap();
+/// let gate = FeatureGate::new(&HelloAnalysis::from(&hello));
+///
+/// let mut call = Message::parse_message(
+///     r#"[48, 7, {"timeout": 500, "receive_progress": true}, "com.example.slow"]"#,
+/// ).unwrap();
+/// let error = gate.check(&mut call).unwrap_err();
+/// let reply = feature_denial(&call, &error).unwrap();
+/// assert_eq!((reply.request, reply.error.as_str()), (7, "wamp.error.option_not_allowed"));
+///
+/// // Stripping keeps the announced timeout and drops progressive results.
+/// let stripped = gate.clone().policy(FeaturePolicy::Strip).check(&mut call).unwrap();
+/// assert_eq!(stripped, ["receive_progress"]);
+/// assert_eq!(call.details().unwrap().dump(), r#"{"timeout":500}"#);
+///
+/// // The router does not implement publisher identification.
+/// let mut subscribe = Message::parse_message(r#"[32, 8, {"match": "exact"}, "com.example.tick"]"#).unwrap();
+/// assert!(gate.check(&mut subscribe).is_ok());
+/// let mut publish = Message::parse_message(r#"[16, 9, {"disclose_me": true}, "com.example.tick"]"#).unwrap();
+/// let error = gate.check(&mut publish).unwrap_err();
+/// assert_eq!(feature_denial(&publish, &error).unwrap().error, "wamp.error.feature_not_supported");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FeatureGate {
+    announced: Vec<(Roles, BTreeSet<String>)>,
+    broker: Vec<String>,
+    dealer: Vec<String>,
+    policy: FeaturePolicy,
+}
+
+impl FeatureGate {
+    /// Gate for the client that sent the analysed HELLO, with the router implementing
+    /// [`BROKER_FEATURES`] and [`DEALER_FEATURES`].
+    pub fn new(analysis: &HelloAnalysis) -> Self {
+        let owned =
+            |features: &[&str]| features.iter().map(|feature| feature.to_string()).collect();
+        FeatureGate {
+            announced: analysis.features.clone(),
+            broker: owned(BROKER_FEATURES),
+            dealer: owned(DEALER_FEATURES),
+            policy: FeaturePolicy::default(),
+        }
+    }
+
+    pub fn policy(mut self, policy: FeaturePolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// The features the router implements for `role`, `Broker` or `Dealer`.
+    pub fn supported(mut self, role: Roles, features: &[&str]) -> Self {
+        let features = features.iter().map(|feature| feature.to_string()).collect();
+        match role {
+            Roles::Broker => self.broker = features,
+            Roles::Dealer => self.dealer = features,
+            _ => {}
+        }
+        self
+    }
+
+    /// Check the Options of a message the client sent, returns the keys that were stripped.
+    /// Other messages pass untouched. Answer a refused request with [`feature_denial`].
+    pub fn check(&self, message: &mut Message) -> Result<Vec<&'static str>, Error> {
+        let (client, router) = match message {
+            Message::Call(_) => (Roles::Caller, &self.dealer),
+            Message::Register(_) => (Roles::Callee, &self.dealer),
+            Message::Publish(_) => (Roles::Publisher, &self.broker),
+            Message::Subscribe(_) => (Roles::Subscriber, &self.broker),
+            _ => return Ok(Vec::new()),
+        };
+        let message_id = message.message_id();
+        let Some(options) = message.details_mut() else {
+            return Ok(Vec::new());
+        };
+
+        let mut stripped = Vec::new();
+        for gated in GATED.iter().filter(|gated| gated.message_id == message_id) {
+            if !(gated.uses)(&options[gated.key]) {
+                continue;
+            }
+            let implemented = router.iter().any(|feature| feature == gated.feature);
+            let announced = self
+                .announced
+                .iter()
+                .any(|(role, features)| *role == client && features.contains(gated.feature));
+            if implemented && announced {
+                continue;
+            }
+            if self.policy == FeaturePolicy::Reject {
+                return Err(Error::FeatureNotNegotiated {
+                    feature: gated.feature,
+                    implemented,
+                });
+            }
+            stripped.push(gated.key);
+        }
+        for key in &stripped {
+            options.remove(key);
+        }
+        Ok(stripped)
+    }
+}
+
+/// ERROR answering a request refused by [`FeatureGate::check`]: [`OPTION_NOT_ALLOWED`] when
+/// the client did not announce the feature, [`FEATURE_NOT_SUPPORTED`] when the router lacks
+/// it. `None` for other errors and messages that are not requests.
+pub fn feature_denial(message: &Message, error: &Error) -> Option<ErrorMessage> {
+    let Error::FeatureNotNegotiated { implemented, .. } = error else {
+        return None;
+    };
+    let request_type = RequestType::try_from(message.message_id()).ok()?;
+    let error = if *implemented {
+        OPTION_NOT_ALLOWED
+    } else {
+        FEATURE_NOT_SUPPORTED
+    };
+    Some(ErrorMessage::for_request(
+        request_type,
+        message.request_id()?,
+        error.into(),
+    ))
+}
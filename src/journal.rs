@@ -0,0 +1,309 @@
+use crate::arity::arity;
+use crate::clock::MockClock;
+use crate::correlation::Direction;
+use crate::error::Error;
+use crate::messages::{Message, WampId};
+use crate::session::Session;
+use json::JsonValue;
+use std::io::{BufRead, Write};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// A message with when and how it was seen, the unit of a [`Journal`].
+///
+/// With the `serde` feature it is (de)serialized like its journal line: `received_at` in
+/// microseconds since the Unix epoch and the message as its frame.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Envelope {
+    #[cfg_attr(feature = "serde", serde(with = "micros"))]
+    pub received_at: SystemTime,
+    pub direction: Direction,
+    /// Session the message belongs to, `None` before WELCOME.
+    pub session_id: Option<WampId>,
+    #[cfg_attr(feature = "serde", serde(with = "frame"))]
+    pub message: Message,
+}
+
+impl Envelope {
+    /// Envelope for a message seen just now.
+    pub fn now(direction: Direction, session_id: Option<WampId>, message: Message) -> Self {
+        Envelope {
+            received_at: SystemTime::now(),
+            direction,
+            session_id,
+            message,
+        }
+    }
+
+    /// The journal line of the envelope, without the newline.
+    pub fn to_json(&self) -> Result<JsonValue, Error> {
+        Ok(json::object! {
+            received_at: unix_micros(self.received_at),
+            direction: match self.direction {
+                Direction::Inbound => "inbound",
+                Direction::Outbound => "outbound",
+            },
+            session_id: self.session_id,
+            message: self.message.clone().to_json()?,
+        })
+    }
+
+    /// Read an envelope back from its journal line.
+    pub fn from_json(mut line: JsonValue) -> Result<Self, Error> {
+        let invalid = |reason| Error::InvalidFrame { reason };
+        let micros = line["received_at"]
+            .as_u64()
+            .ok_or(invalid("journal entry without a received_at timestamp"))?;
+        let direction = match line["direction"].as_str() {
+            Some("inbound") => Direction::Inbound,
+            Some("outbound") => Direction::Outbound,
+            _ => return Err(invalid("journal entry without a direction")),
+        };
+        let session_id = match &line["session_id"] {
+            JsonValue::Null => None,
+            id => Some(
+                id.as_u64()
+                    .ok_or(invalid("journal entry with a bad session_id"))?,
+            ),
+        };
+        let message = line["message"].take();
+        if !message.is_array() {
+            return Err(invalid("journal entry without a message"));
+        }
+        Ok(Envelope {
+            received_at: UNIX_EPOCH + Duration::from_micros(micros),
+            direction,
+            session_id,
+            message: Message::parse_message(&message.dump())?,
+        })
+    }
+}
+
+/// Microseconds since the Unix epoch, 0 for earlier times.
+fn unix_micros(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_micros() as u64
+}
+
+#[cfg(feature = "serde")]
+mod micros {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    pub fn serialize<S: Serializer>(time: &SystemTime, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u64(super::unix_micros(*time))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<SystemTime, D::Error> {
+        Ok(UNIX_EPOCH + Duration::from_micros(u64::deserialize(deserializer)?))
+    }
+}
+
+#[cfg(feature = "serde")]
+mod frame {
+    use crate::messages::Message;
+    use crate::value::WampValue;
+    use json::JsonValue;
+    use serde::de::Error as _;
+    use serde::ser::Error as _;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(message: &Message, serializer: S) -> Result<S::Ok, S::Error> {
+        let frame = message
+            .clone()
+            .to_json()
+            .map_err(|error| S::Error::custom(format!("{error:?}")))?;
+        WampValue::from(frame).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Message, D::Error> {
+        let frame = JsonValue::from(WampValue::deserialize(deserializer)?);
+        Message::parse_message(&frame.dump())
+            .map_err(|error| D::Error::custom(format!("{error:?}")))
+    }
+}
+
+/// Message journal as JSON lines, one [`Envelope`] per line: append to it while the session
+/// runs, read it back for a postmortem.
+///
+/// Reading yields the envelopes in the order they were written, ready to feed to a
+/// [`Validator`](crate::validator::Validator) or a [`Correlator`](crate::correlation::Correlator).
+/// # Examples
+/// ```
+/// use std::io::Cursor;
+/// use wamp_helpers::correlation::{Correlator, Direction};
+/// use wamp_helpers::journal::{Envelope, Journal};
+/// use wamp_helpers::messages::Message;
+///
+/// let mut journal = Journal::new(Vec::new());
+/// for (direction, frame) in [
+///     (Direction::Outbound, r#"[48, 1, {}, "com.example.add", [1, 2]]"#),
+///     (Direction::Inbound, r#"[50, 1, {}, [3]]"#),
+/// ] {
+///     let message = Message::parse_message(frame).unwrap();
+///     journal.append(&Envelope::now(direction, Some(42), message)).unwrap();
+/// }
+/// let written = journal.into_inner();
+///
+/// let mut correlator = Correlator::new();
+/// let start = std::time::Instant::now();
+/// let mut exchanges = Vec::new();
+/// for envelope in Journal::new(Cursor::new(written)) {
+///     let envelope = envelope.unwrap();
+///     assert_eq!(envelope.session_id, Some(42));
+///     exchanges.extend(correlator.observe(envelope.direction, &envelope.message, start));
+/// }
+/// assert_eq!(exchanges.len(), 1);
+/// ```
+#[derive(Debug)]
+pub struct Journal<T> {
+    inner: T,
+    line: String,
+}
+
+impl<T> Journal<T> {
+    pub fn new(inner: T) -> Self {
+        Journal {
+            inner,
+            line: String::new(),
+        }
+    }
+
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<W: Write> Journal<W> {
+    /// Write `envelope` as one line.
+    pub fn append(&mut self, envelope: &Envelope) -> Result<(), Error> {
+        let mut line = envelope.to_json()?.dump();
+        line.push('\n');
+        self.inner.write_all(line.as_bytes()).map_err(Error::Io)
+    }
+
+    pub fn flush(&mut self) -> Result<(), Error> {
+        self.inner.flush().map_err(Error::Io)
+    }
+}
+
+impl<R: BufRead> Journal<R> {
+    /// The next envelope, `None` at the end of the journal. Blank lines are skipped.
+    pub fn read(&mut self) -> Result<Option<Envelope>, Error> {
+        loop {
+            self.line.clear();
+            if self.inner.read_line(&mut self.line).map_err(Error::Io)? == 0 {
+                return Ok(None);
+            }
+            let line = self.line.trim();
+            if !line.is_empty() {
+                let line = json::parse(line).map_err(Error::JsonError)?;
+                return Envelope::from_json(line).map(Some);
+            }
+        }
+    }
+
+    /// Feed the recorded messages to `session` in order, returns how many were replayed.
+    ///
+    /// Every message was exchanged in the recorded session, so the replay fails with
+    /// [`Error::ReplayDiverged`] as soon as `session` refuses one or ends up with another
+    /// session id than the one recorded, e.g. after a change to the state machine.
+    /// # Examples
+    /// ```
+    /// use std::io::Cursor;
+    /// use wamp_helpers::error::Error;
+    /// use wamp_helpers::journal::Journal;
+    /// use wamp_helpers::session::{Session, SessionState, Side};
+    ///
+    /// let capture = concat!(
+    ///     r#"{"received_at": 1000, "direction": "outbound", "session_id": null, "message": [1, "realm1", {"roles": {"caller": {}}}]}"#, "\n",
+    ///     r#"{"received_at": 2000, "direction": "inbound", "session_id": 7, "message": [2, 7, {"roles": {"dealer": {}}}]}"#, "\n",
+    ///     r#"{"received_at": 3000, "direction": "outbound", "session_id": 7, "message": [48, 1, {}, "com.example.add"]}"#, "\n",
+    /// );
+    /// let mut session = Session::new(Side::Client);
+    /// let replayed = Journal::new(Cursor::new(capture)).replay_into(&mut session).unwrap();
+    /// assert_eq!((replayed, session.state()), (3, SessionState::Established));
+    ///
+    /// // Replayed from the router's side, the HELLO travels the wrong way.
+    /// let mut router = Session::new(Side::Router);
+    /// let diverged = Journal::new(Cursor::new(capture)).replay_into(&mut router);
+    /// assert!(matches!(diverged, Err(Error::ReplayDiverged { entry: 0, .. })));
+    /// ```
+    pub fn replay_into(&mut self, session: &mut Session) -> Result<usize, Error> {
+        self.replay(session, None)
+    }
+
+    /// Same as [`replay_into`](Self::replay_into), advancing `clock` by the recorded time
+    /// between two messages before feeding the second, so deadlines measured on the clock
+    /// pass where they passed in the recording.
+    /// ```
+    /// use std::io::Cursor;
+    /// use std::time::Duration;
+    /// use wamp_helpers::clock::{Clock, MockClock};
+    /// use wamp_helpers::journal::Journal;
+    /// use wamp_helpers::session::{Session, Side};
+    ///
+    /// let capture = concat!(
+    ///     r#"{"received_at": 1000000, "direction": "inbound", "session_id": null, "message": [1, "realm1", {"roles": {"caller": {}}}]}"#, "\n",
+    ///     r#"{"received_at": 3500000, "direction": "outbound", "session_id": 7, "message": [2, 7, {"roles": {"dealer": {}}}]}"#, "\n",
+    /// );
+    /// let clock = MockClock::new();
+    /// let start = clock.now();
+    /// let mut session = Session::new(Side::Router);
+    /// Journal::new(Cursor::new(capture)).replay_timed(&mut session, &clock).unwrap();
+    /// assert_eq!(clock.elapsed(start), Duration::from_millis(2500));
+    /// ```
+    pub fn replay_timed(
+        &mut self,
+        session: &mut Session,
+        clock: &MockClock,
+    ) -> Result<usize, Error> {
+        self.replay(session, Some(clock))
+    }
+
+    fn replay(&mut self, session: &mut Session, clock: Option<&MockClock>) -> Result<usize, Error> {
+        let mut entry = 0;
+        let mut previous = None;
+        while let Some(envelope) = self.read()? {
+            if let (Some(clock), Some(previous)) = (clock, previous) {
+                let gap = envelope
+                    .received_at
+                    .duration_since(previous)
+                    .unwrap_or_default();
+                clock.advance(gap);
+            }
+            previous = Some(envelope.received_at);
+
+            let diverged = |reason| Error::ReplayDiverged { entry, reason };
+            let name = arity(envelope.message.message_id()).map_or("unknown", |arity| arity.name);
+            if let Err(state) = session.transition(envelope.direction, &envelope.message) {
+                return Err(diverged(format!(
+                    "{:?} {name} refused in state {state:?}",
+                    envelope.direction
+                )));
+            }
+            if let (Some(recorded), Some(replayed)) = (envelope.session_id, session.session_id()) {
+                if recorded != replayed {
+                    return Err(diverged(format!(
+                        "{name} recorded in session {recorded}, replayed in session {replayed}"
+                    )));
+                }
+            }
+            entry += 1;
+        }
+        Ok(entry)
+    }
+}
+
+impl<R: BufRead> Iterator for Journal<R> {
+    type Item = Result<Envelope, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read().transpose()
+    }
+}
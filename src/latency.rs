@@ -0,0 +1,224 @@
+use crate::correlation::Exchange;
+use crate::messages::{Call, ErrorMessage, Message, Uri, WampResult};
+use crate::meta::{INVALID_ARGUMENT, NO_SUCH_REGISTRATION, NO_SUCH_SUBSCRIPTION};
+use crate::value::WampValue;
+use hdrhistogram::Histogram;
+use std::collections::{BTreeMap, HashMap};
+use std::time::Duration;
+
+/// Meta procedure answering the latency summary of the procedure given as first argument.
+pub const REGISTRATION_LATENCY: &str = "wamp.registration.latency";
+/// Meta procedure answering the latency summary of the topic given as first argument.
+pub const SUBSCRIPTION_LATENCY: &str = "wamp.subscription.latency";
+
+/// Longest latency the histograms hold, longer ones are clamped.
+pub const MAX_LATENCY: Duration = Duration::from_secs(3600);
+
+/// Default number of URIs a [`LatencyRecorder`] keeps a histogram for.
+pub const DEFAULT_MAX_URIS: usize = 1024;
+/// URI under which latencies of URIs beyond the limit are recorded together.
+pub const OVERFLOW_URI: &str = "wamp.latency.overflow";
+
+/// What a latency was measured for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum LatencyKind {
+    /// CALL until its final RESULT or ERROR.
+    Procedure,
+    /// Acknowledged PUBLISH until its PUBLISHED or ERROR.
+    Topic,
+}
+
+/// Percentiles of the latencies recorded for one URI.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LatencySummary {
+    pub count: u64,
+    pub min: Duration,
+    pub p50: Duration,
+    pub p90: Duration,
+    pub p99: Duration,
+    pub max: Duration,
+}
+
+impl LatencySummary {
+    /// Dictionary with the count and microsecond values, as returned by the meta procedures.
+    pub fn to_value(&self) -> WampValue {
+        let micros = |duration: Duration| WampValue::Integer(duration.as_micros() as i64);
+        WampValue::Dict(BTreeMap::from([
+            ("count".to_string(), WampValue::Integer(self.count as i64)),
+            ("min_us".to_string(), micros(self.min)),
+            ("p50_us".to_string(), micros(self.p50)),
+            ("p90_us".to_string(), micros(self.p90)),
+            ("p99_us".to_string(), micros(self.p99)),
+            ("max_us".to_string(), micros(self.max)),
+        ]))
+    }
+}
+
+/// Latency histograms keyed by procedure and topic URI, to find slow callees.
+///
+/// Feed it the exchanges a [`Correlator`](crate::correlation::Correlator) completes. Values
+/// are kept in microseconds with three significant digits. Each histogram takes a few hundred
+/// kilobytes, so at most [`max_uris`](LatencyRecorder::with_max_uris) URIs get their own and
+/// latencies of any further URI are recorded under [`OVERFLOW_URI`].
+/// # Examples
+/// ```
+/// use std::time::{Duration, Instant};
+/// use wamp_helpers::correlation::{Correlator, Direction};
+/// use wamp_helpers::latency::{LatencyKind, LatencyRecorder, OVERFLOW_URI};
+/// use wamp_helpers::messages::Message;
+///
+/// let mut correlator = Correlator::new();
+/// let mut latencies = LatencyRecorder::new();
+/// let start = Instant::now();
+/// for (request, millis) in [(1, 5), (2, 7), (3, 250)] {
+///     let call = format!(r#"[48, {request}, {{}}, "com.example.report"]"#);
+///     let result = format!("[50, {request}, {{}}]");
+///     correlator.observe(Direction::Outbound, &Message::parse_message(&call).unwrap(), start);
+///     let at = start + Duration::from_millis(millis);
+///     let exchange = correlator.observe(Direction::Inbound, &Message::parse_message(&result).unwrap(), at);
+///     latencies.observe(&exchange.unwrap());
+/// }
+///
+/// let summary = latencies.summary(LatencyKind::Procedure, "com.example.report").unwrap();
+/// assert_eq!(summary.count, 3);
+/// assert!(summary.p50 < Duration::from_millis(8) && summary.max >= Duration::from_millis(249));
+/// assert_eq!(latencies.slowest(LatencyKind::Procedure, 1)[0].0, "com.example.report");
+///
+/// let meta: wamp_helpers::messages::Call =
+///     r#"[48, 9, {}, "wamp.registration.latency", ["com.example.report"]]"#.parse().unwrap();
+/// let Some(Message::MessageResult(answer)) = latencies.handle_meta_call(&meta) else { panic!() };
+/// assert_eq!(answer.args.unwrap().len(), 1);
+///
+/// // A peer making up URIs ends up in the overflow histogram.
+/// let mut latencies = LatencyRecorder::with_max_uris(2);
+/// for uri in ["com.example.a", "com.example.b", "com.example.c", "com.example.d"] {
+///     latencies.record(LatencyKind::Procedure, uri, Duration::from_millis(1));
+/// }
+/// assert!(latencies.summary(LatencyKind::Procedure, "com.example.c").is_none());
+/// let overflow = latencies.summary(LatencyKind::Procedure, OVERFLOW_URI).unwrap();
+/// assert_eq!(overflow.count, 2);
+/// ```
+#[derive(Debug, Clone)]
+pub struct LatencyRecorder {
+    histograms: HashMap<(LatencyKind, Uri), Histogram<u64>>,
+    max_uris: usize,
+}
+
+impl Default for LatencyRecorder {
+    fn default() -> Self {
+        LatencyRecorder::with_max_uris(DEFAULT_MAX_URIS)
+    }
+}
+
+impl LatencyRecorder {
+    pub fn new() -> Self {
+        LatencyRecorder::default()
+    }
+
+    /// Recorder keeping separate histograms for at most `max_uris` URIs, not counting the
+    /// overflow histograms.
+    pub fn with_max_uris(max_uris: usize) -> Self {
+        LatencyRecorder {
+            histograms: HashMap::new(),
+            max_uris,
+        }
+    }
+
+    pub fn record(&mut self, kind: LatencyKind, uri: &str, latency: Duration) {
+        let mut key = (kind, Uri::from(uri.to_string()));
+        if !self.histograms.contains_key(&key) {
+            let tracked = self
+                .histograms
+                .keys()
+                .filter(|(_, uri)| uri.as_str() != OVERFLOW_URI)
+                .count();
+            if tracked >= self.max_uris {
+                key.1 = OVERFLOW_URI.to_string().into();
+            }
+        }
+        let histogram = self.histograms.entry(key).or_insert_with(|| {
+            // Only fails for invalid bounds, these are constant and valid.
+            Histogram::new_with_bounds(1, MAX_LATENCY.as_micros() as u64, 3)
+                .unwrap_or_else(|_| unreachable!())
+        });
+        histogram.saturating_record((latency.as_micros() as u64).max(1));
+    }
+
+    /// Record a completed exchange of a CALL or PUBLISH, others and progressive results are
+    /// ignored.
+    pub fn observe(&mut self, exchange: &Exchange) {
+        if exchange.progress {
+            return;
+        }
+        match &exchange.request {
+            Message::Call(call) => {
+                self.record(LatencyKind::Procedure, &call.procedure, exchange.latency)
+            }
+            Message::Publish(publish) => {
+                self.record(LatencyKind::Topic, &publish.topic, exchange.latency)
+            }
+            _ => {}
+        }
+    }
+
+    pub fn summary(&self, kind: LatencyKind, uri: &str) -> Option<LatencySummary> {
+        let histogram = self.histograms.get(&(kind, uri.to_string().into()))?;
+        let micros = Duration::from_micros;
+        Some(LatencySummary {
+            count: histogram.len(),
+            min: micros(histogram.min()),
+            p50: micros(histogram.value_at_quantile(0.5)),
+            p90: micros(histogram.value_at_quantile(0.9)),
+            p99: micros(histogram.value_at_quantile(0.99)),
+            max: micros(histogram.max()),
+        })
+    }
+
+    /// The `n` URIs of `kind` with the highest 99th percentile, slowest first.
+    pub fn slowest(&self, kind: LatencyKind, n: usize) -> Vec<(Uri, LatencySummary)> {
+        let mut summaries: Vec<(Uri, LatencySummary)> = self
+            .histograms
+            .keys()
+            .filter(|(recorded, _)| *recorded == kind)
+            .filter_map(|(_, uri)| Some((uri.clone(), self.summary(kind, uri)?)))
+            .collect();
+        summaries.sort_by(|a, b| b.1.p99.cmp(&a.1.p99).then_with(|| a.0.cmp(&b.0)));
+        summaries.truncate(n);
+        summaries
+    }
+
+    /// Forget everything recorded, e.g. at the start of a reporting interval.
+    pub fn reset(&mut self) {
+        self.histograms.clear();
+    }
+
+    /// Answer the [`REGISTRATION_LATENCY`] and [`SUBSCRIPTION_LATENCY`] meta procedures,
+    /// `None` for other procedures. URIs without recorded latencies are answered with
+    /// `no_such_registration` or `no_such_subscription`.
+    pub fn handle_meta_call(&self, call: &Call) -> Option<Message> {
+        let (kind, missing) = match call.procedure.as_str() {
+            REGISTRATION_LATENCY => (LatencyKind::Procedure, NO_SUCH_REGISTRATION),
+            SUBSCRIPTION_LATENCY => (LatencyKind::Topic, NO_SUCH_SUBSCRIPTION),
+            _ => return None,
+        };
+        let error =
+            |uri: &str| Message::ErrorMessage(ErrorMessage::for_call(call, uri.to_string().into()));
+        let uri = call
+            .args
+            .as_ref()
+            .and_then(|args| args.first())
+            .and_then(WampValue::as_str);
+        Some(match uri {
+            None => error(INVALID_ARGUMENT),
+            Some(uri) => match self.summary(kind, uri) {
+                None => error(missing),
+                Some(summary) => Message::MessageResult(WampResult {
+                    request: call.request,
+                    details: json::object! {},
+                    args: Some(vec![summary.to_value()]),
+                    kwargs: None,
+                }),
+            },
+        })
+    }
+}
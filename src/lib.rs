@@ -1,3 +1,82 @@
 extern crate json;
-pub mod messages;
+#[macro_use]
+mod macros;
+
+/// Declare the modules handling untrusted input: constructs that can panic are denied,
+/// failures are errors.
+macro_rules! untrusted_input {
+    ($($module:ident),* $(,)?) => {
+        $(
+            #[deny(
+                clippy::indexing_slicing,
+                clippy::unwrap_used,
+                clippy::expect_used,
+                clippy::panic,
+                clippy::unreachable
+            )]
+            pub mod $module;
+        )*
+    };
+}
+
+untrusted_input!(
+    messages, canonical, value, parse, uri, proxy, transcode, batch, arena, scan, framed,
+);
+
 pub mod error;
+pub mod arity;
+pub mod prelude;
+pub mod correlation;
+pub mod session;
+pub mod validator;
+pub mod transport;
+pub mod memory;
+pub mod rng;
+pub mod clock;
+pub mod sim;
+pub mod schema;
+pub mod handshake;
+pub mod client;
+pub mod streaming;
+pub mod ack;
+pub mod options;
+pub mod stats;
+pub mod meta;
+pub mod acceptor;
+pub mod v1;
+pub mod broker;
+pub mod bus;
+pub mod nonce;
+pub mod dealer;
+pub mod bridge;
+pub mod autobahn;
+pub mod pool;
+pub mod diff;
+pub mod mux;
+pub mod persist;
+pub mod journal;
+#[cfg(feature = "chaos")]
+pub mod chaos;
+#[cfg(feature = "compression")]
+pub mod compression;
+#[cfg(feature = "cra")]
+pub mod cra;
+#[cfg(feature = "serde")]
+pub mod config;
+#[cfg(feature = "audit")]
+pub mod audit;
+#[cfg(feature = "latency")]
+pub mod latency;
+#[cfg(feature = "serde")]
+pub mod rpc;
+#[cfg(feature = "serde")]
+pub mod dynamic;
+#[cfg(feature = "tower")]
+pub mod service;
+#[cfg(feature = "runtime")]
+pub mod runtime;
+
+#[doc(hidden)]
+pub mod __private {
+    pub use json::JsonValue;
+}
@@ -0,0 +1,197 @@
+/// Declare an extension message together with its `WampMessageTrait` impl, `FromStr` impl
+/// and direction table.
+///
+/// Fields are listed in wire order, each with one of the kinds `id`, `u8`, `uri`, `str` or
+/// `dict`. A trailing `payload;` line appends the optional `args`/`kwargs` pair used by CALL,
+/// EVENT and friends. Every role has to be listed in `directions` as `(receives, sends)`.
+///
+/// The generated type can be parsed alongside the standard messages with
+/// [`Message::parse_with_extension`](crate::messages::Message::parse_with_extension).
+/// # Examples
+/// ```
+/// use wamp_helpers::messages::{Message, Extended, Roles, WampMessageTrait};
+/// use wamp_helpers::wamp_message;
+///
+/// wamp_message! {
+///     /// In-house heartbeat carrying a sequence number.
+///     pub struct Heartbeat = 200 {
+///         sequence: id,
+///         details: dict,
+///     }
+///     payload;
+///     directions {
+///         Callee => (true, true),
+///         Caller => (true, true),
+///         Publisher => (true, true),
+///         Subscriber => (true, true),
+///         Dealer => (true, true),
+///         Broker => (true, true),
+///     }
+/// }
+///
+/// let heartbeat: Heartbeat = "[200, 7, {}]".parse().unwrap();
+/// assert_eq!(heartbeat.sequence, 7);
+/// assert!(heartbeat.args.is_none());
+/// assert_eq!(heartbeat.to_json().unwrap().dump(), "[200,7,{}]");
+/// assert!(Heartbeat::get_message_direction(Roles::Dealer).sends);
+///
+/// # #[cfg(not(feature = "spec_strict"))]
+/// match Message::parse_with_extension::<Heartbeat>("[200, 8, {}, [1]]").unwrap() {
+///     Extended::Extension(heartbeat) => assert_eq!(heartbeat.sequence, 8),
+///     Extended::Standard(_) => unreachable!(),
+/// }
+/// ```
+#[macro_export]
+macro_rules! wamp_message {
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident = $id:literal {
+            $( $field:ident : $kind:ident ),* $(,)?
+        }
+        $( $payload:ident; )?
+        directions {
+            $( $role:ident => ($receives:literal, $sends:literal) ),* $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone)]
+        $vis struct $name {
+            $( pub $field: $crate::__wamp_field!(type $kind), )*
+            $(
+                pub args: $crate::__wamp_payload!(type $payload, Args),
+                pub kwargs: $crate::__wamp_payload!(type $payload, Kwargs),
+            )?
+        }
+
+        impl $name {
+            /// The canonical form of the frame, what equality and hashing use, as for the
+            /// standard messages.
+            fn canonical_frame(&self) -> String {
+                let mut frame = $crate::canonical::FrameWriter::new($id);
+                $( frame.$kind(&self.$field); )*
+                $(
+                    $crate::__wamp_payload!(canonical $payload, frame, &self.args, &self.kwargs);
+                )?
+                frame.finish()
+            }
+        }
+
+        impl ::std::cmp::PartialEq for $name {
+            fn eq(&self, other: &Self) -> bool {
+                self.canonical_frame() == other.canonical_frame()
+            }
+        }
+
+        impl ::std::cmp::Eq for $name {}
+
+        impl ::std::hash::Hash for $name {
+            fn hash<H: ::std::hash::Hasher>(&self, state: &mut H) {
+                ::std::hash::Hash::hash(&self.canonical_frame(), state);
+            }
+        }
+
+        impl $crate::messages::WampMessageTrait for $name {
+            const ID: u8 = $id;
+
+            fn to_json(self) -> Result<$crate::__private::JsonValue, $crate::error::Error> {
+                let mut data = $crate::__private::JsonValue::new_array();
+                data.push(Self::ID).map_err($crate::error::Error::JsonError)?;
+                $(
+                    data.push(self.$field).map_err($crate::error::Error::JsonError)?;
+                )*
+                $(
+                    $crate::__wamp_payload!(push $payload, data, self.args, self.kwargs);
+                )?
+                Ok(data)
+            }
+
+            fn get_message_direction(
+                role: $crate::messages::Roles,
+            ) -> &'static $crate::messages::MessageDirection {
+                match role {
+                    $(
+                        $crate::messages::Roles::$role => &$crate::messages::MessageDirection {
+                            receives: &$receives,
+                            sends: &$sends,
+                        },
+                    )*
+                }
+            }
+        }
+
+        impl ::std::str::FromStr for $name {
+            type Err = $crate::error::Error;
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                use $crate::messages::WampMessageTrait;
+                let mut data = Self::parse_raw_json(s.to_string())?;
+                let _id = Self::validate_id(data.array_remove(0))?;
+                $(
+                    let $field = $crate::__wamp_field!(parse $kind, data.array_remove(0))?;
+                )*
+                $(
+                    let args = $crate::__wamp_payload!(parse $payload, args, data.array_remove(0))?;
+                    let kwargs = $crate::__wamp_payload!(parse $payload, kwargs, data.array_remove(0))?;
+                )?
+                Ok($name {
+                    $( $field, )*
+                    $( args: $crate::__wamp_payload!(value $payload, args), kwargs, )?
+                })
+            }
+        }
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __wamp_field {
+    (type id) => {
+        $crate::messages::WampId
+    };
+    (type u8) => {
+        u8
+    };
+    (type uri) => {
+        $crate::messages::Uri
+    };
+    (type str) => {
+        String
+    };
+    (type dict) => {
+        $crate::messages::Details
+    };
+
+    (parse id, $value:expr) => {
+        $crate::messages::validate_u64_argument($value)
+    };
+    (parse u8, $value:expr) => {
+        $crate::messages::validate_u8_argument($value)
+    };
+    (parse uri, $value:expr) => {
+        $crate::messages::validate_str_argument($value)
+    };
+    (parse str, $value:expr) => {
+        $crate::messages::validate_str_argument($value)
+    };
+    (parse dict, $value:expr) => {
+        $crate::messages::validate_dict_argument($value)
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __wamp_payload {
+    (type payload, $ty:ident) => { Option<$crate::messages::$ty> };
+
+    (parse payload, args, $value:expr) => { $crate::messages::validate_args($value) };
+    (parse payload, kwargs, $value:expr) => { $crate::messages::validate_kwargs($value) };
+
+    (value payload, $value:ident) => { $value };
+
+    (push payload, $data:ident, $args:expr, $kwargs:expr) => {
+        $crate::messages::push_payload(&mut $data, $args, $kwargs)?;
+    };
+    (canonical payload, $frame:ident, $args:expr, $kwargs:expr) => {
+        $frame.payload($args, $kwargs);
+    };
+}
@@ -0,0 +1,324 @@
+use crate::acceptor::Serializer;
+use crate::correlation::Direction;
+use crate::error::Error;
+use crate::framed::{decode_message, encode_message};
+use crate::messages::{Hello, Message, Roles, Uri, WampId, Welcome};
+use crate::session::{Session, Side};
+use crate::sim::{IdGenerator, SequentialIdGenerator};
+use crate::transport::{PeerInfo, Transport};
+use std::collections::VecDeque;
+use std::future::{poll_fn, ready, Future};
+use std::sync::{Arc, Mutex};
+use std::task::{Poll, Waker};
+
+/// A fault applied to the next frame sent through a [`MemoryTransport`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fault {
+    /// The frame is silently lost.
+    Drop,
+    /// The frame is delivered twice.
+    Duplicate,
+    /// The frame is held back until this many further frames were sent, then delivered.
+    /// Frames still held back when this end closes are delivered before the close, those
+    /// held back when the peer closed first are lost with the connection.
+    Delay(usize),
+}
+
+#[derive(Debug, Default)]
+struct Channel {
+    frames: VecDeque<Vec<u8>>,
+    closed: bool,
+    waker: Option<Waker>,
+}
+
+impl Channel {
+    fn push(&mut self, frame: Vec<u8>) {
+        self.frames.push_back(frame);
+        if let Some(waker) = self.waker.take() {
+            waker.wake();
+        }
+    }
+
+    fn close(&mut self) {
+        self.closed = true;
+        if let Some(waker) = self.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+/// One end of an in-memory transport, created in pairs by [`MemoryTransport::pair`].
+///
+/// Frames arrive in the order they were sent unless a [`Fault`] was injected, which makes
+/// protocol tests reproducible without sockets.
+/// # Examples
+/// ```
+/// use std::future::Future;
+/// use std::task::{Context, Poll, Waker};
+/// use wamp_helpers::memory::{Fault, MemoryTransport};
+/// use wamp_helpers::transport::Transport;
+///
+/// fn now<F: Future>(future: F) -> F::Output {
+///     let mut future = std::pin::pin!(future);
+///     match future.as_mut().poll(&mut Context::from_waker(Waker::noop())) {
+///         Poll::Ready(output) => output,
+///         Poll::Pending => panic!("future is not ready"),
+///     }
+/// }
+///
+/// let (mut client, mut router) = MemoryTransport::pair();
+/// client.inject(Fault::Delay(1));
+/// now(client.send(b"first".to_vec())).unwrap();
+/// now(client.send(b"second".to_vec())).unwrap();
+///
+/// assert_eq!(now(router.next()).unwrap().unwrap(), b"second");
+/// assert_eq!(now(router.next()).unwrap().unwrap(), b"first");
+/// assert!(router.try_next().is_none());
+///
+/// now(router.close("wamp.close.normal")).unwrap();
+/// assert!(now(client.next()).is_none());
+/// ```
+#[derive(Debug)]
+pub struct MemoryTransport {
+    incoming: Arc<Mutex<Channel>>,
+    outgoing: Arc<Mutex<Channel>>,
+    faults: VecDeque<Fault>,
+    delayed: Vec<(usize, Vec<u8>)>,
+    peer: Option<PeerInfo>,
+}
+
+impl MemoryTransport {
+    /// Create two connected ends, conventionally the client end and the router end.
+    pub fn pair() -> (MemoryTransport, MemoryTransport) {
+        let forward = Arc::new(Mutex::new(Channel::default()));
+        let backward = Arc::new(Mutex::new(Channel::default()));
+        let client = MemoryTransport {
+            incoming: backward.clone(),
+            outgoing: forward.clone(),
+            faults: VecDeque::new(),
+            delayed: Vec::new(),
+            peer: None,
+        };
+        let router = MemoryTransport {
+            incoming: forward,
+            outgoing: backward,
+            faults: VecDeque::new(),
+            delayed: Vec::new(),
+            peer: None,
+        };
+        (client, router)
+    }
+
+    /// Queue a fault for the next frame sent from this end, faults apply one per frame in
+    /// the order they were injected.
+    pub fn inject(&mut self, fault: Fault) {
+        self.faults.push_back(fault);
+    }
+
+    /// Pretend the connection was opened by `peer`, e.g. to test authenticators.
+    pub fn set_peer(&mut self, peer: PeerInfo) {
+        self.peer = Some(peer);
+    }
+
+    /// Take the next frame if one is already waiting.
+    pub fn try_next(&mut self) -> Option<Vec<u8>> {
+        lock(&self.incoming).frames.pop_front()
+    }
+
+    fn deliver(&mut self, frame: Vec<u8>) -> Result<(), Error> {
+        let mut outgoing = lock(&self.outgoing);
+        if outgoing.closed {
+            return Err(Error::TransportClosed);
+        }
+
+        let mut released = Vec::new();
+        self.delayed.retain_mut(|(remaining, frame)| {
+            *remaining -= 1;
+            if *remaining == 0 {
+                released.push(std::mem::take(frame));
+                false
+            } else {
+                true
+            }
+        });
+
+        match self.faults.pop_front() {
+            Some(Fault::Drop) => {}
+            Some(Fault::Duplicate) => {
+                outgoing.push(frame.clone());
+                outgoing.push(frame);
+            }
+            Some(Fault::Delay(0)) | None => outgoing.push(frame),
+            Some(Fault::Delay(frames)) => self.delayed.push((frames, frame)),
+        }
+
+        for frame in released {
+            outgoing.push(frame);
+        }
+        Ok(())
+    }
+}
+
+fn lock(channel: &Mutex<Channel>) -> std::sync::MutexGuard<'_, Channel> {
+    channel
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+impl Transport for MemoryTransport {
+    fn send(&mut self, frame: Vec<u8>) -> impl Future<Output = Result<(), Error>> + Send {
+        ready(self.deliver(frame))
+    }
+
+    fn next(&mut self) -> impl Future<Output = Option<Result<Vec<u8>, Error>>> + Send {
+        let incoming = self.incoming.clone();
+        poll_fn(move |context| {
+            let mut channel = lock(&incoming);
+            if let Some(frame) = channel.frames.pop_front() {
+                Poll::Ready(Some(Ok(frame)))
+            } else if channel.closed {
+                Poll::Ready(None)
+            } else {
+                channel.waker = Some(context.waker().clone());
+                Poll::Pending
+            }
+        })
+    }
+
+    fn close(&mut self, _reason: &str) -> impl Future<Output = Result<(), Error>> + Send {
+        let mut outgoing = lock(&self.outgoing);
+        if !outgoing.closed {
+            for (_, frame) in self.delayed.drain(..) {
+                outgoing.push(frame);
+            }
+        }
+        outgoing.close();
+        drop(outgoing);
+        lock(&self.incoming).close();
+        ready(Ok(()))
+    }
+
+    fn peer(&self) -> Option<&PeerInfo> {
+        self.peer.as_ref()
+    }
+}
+
+/// One end of an established in-process session.
+#[derive(Debug)]
+pub struct InProcessPeer {
+    pub transport: MemoryTransport,
+    /// Session state as seen from this end, already [`Established`](crate::session::SessionState::Established).
+    pub session: Session,
+}
+
+impl InProcessPeer {
+    pub fn session_id(&self) -> Option<WampId> {
+        self.session.session_id()
+    }
+}
+
+/// A realm of client sessions connected over [`MemoryTransport`]s, for messaging between
+/// components of one process or full-stack tests without sockets.
+///
+/// [`pair`](InProcess::pair) runs the HELLO and WELCOME handshake of every session, clients
+/// announcing all four client roles and the router the dealer and broker roles, with JSON
+/// frames. The router ends are handed to the embedding router loop, which is not part of
+/// this crate, and the client ends to the components.
+/// # Examples
+/// ```
+/// use std::future::Future;
+/// use std::task::{Context, Poll, Waker};
+/// use wamp_helpers::acceptor::Serializer;
+/// use wamp_helpers::framed::Framed;
+/// use wamp_helpers::memory::InProcess;
+/// use wamp_helpers::messages::Message;
+/// use wamp_helpers::session::SessionState;
+///
+/// fn now<F: Future>(future: F) -> F::Output {
+///     let mut future = std::pin::pin!(future);
+///     match future.as_mut().poll(&mut Context::from_waker(Waker::noop())) {
+///         Poll::Ready(output) => output,
+///         Poll::Pending => panic!("future is not ready"),
+///     }
+/// }
+///
+/// let realm = InProcess::pair("com.example.internal", 2).unwrap();
+/// assert_eq!(realm.clients.len(), 2);
+/// assert_eq!(realm.clients[1].session.state(), SessionState::Established);
+/// assert_eq!(realm.clients[1].session_id(), realm.router[1].session_id());
+///
+/// let (router, clients) = realm.into_parts();
+/// let mut publisher = Framed::new(clients.into_iter().next().unwrap().transport, Serializer::Json);
+/// let mut router = Framed::new(router.into_iter().next().unwrap().transport, Serializer::Json);
+/// let publish = Message::parse_message(r#"[16, 1, {}, "com.example.started"]"#).unwrap();
+/// now(publisher.send(publish.clone())).unwrap();
+/// assert_eq!(now(router.next()).unwrap().unwrap(), publish);
+/// ```
+#[derive(Debug)]
+pub struct InProcess {
+    pub realm: Uri,
+    /// Router ends, in the order of `clients`.
+    pub router: Vec<InProcessPeer>,
+    pub clients: Vec<InProcessPeer>,
+}
+
+impl InProcess {
+    /// Connect `clients` sessions to `realm`, with session ids counting up from 1.
+    pub fn pair(realm: &str, clients: usize) -> Result<InProcess, Error> {
+        let mut ids = SequentialIdGenerator::default();
+        let mut in_process = InProcess {
+            realm: Uri::from(realm.to_string()),
+            router: Vec::with_capacity(clients),
+            clients: Vec::with_capacity(clients),
+        };
+        for _ in 0..clients {
+            let (client, router) = MemoryTransport::pair();
+            let mut client = InProcessPeer {
+                transport: client,
+                session: Session::new(Side::Client),
+            };
+            let mut router = InProcessPeer {
+                transport: router,
+                session: Session::new(Side::Router),
+            };
+            let hello = Message::Hello(Hello::default(
+                realm.to_string(),
+                vec![
+                    Roles::Caller,
+                    Roles::Callee,
+                    Roles::Publisher,
+                    Roles::Subscriber,
+                ],
+                None,
+            ));
+            client.handshake(&mut router, hello)?;
+            let welcome = Message::Welcome(Welcome {
+                session: ids.next_id(),
+                details: json::object! { roles: { dealer: {}, broker: {} } },
+            });
+            router.handshake(&mut client, welcome)?;
+            in_process.clients.push(client);
+            in_process.router.push(router);
+        }
+        Ok(in_process)
+    }
+
+    /// The router ends and the client ends.
+    pub fn into_parts(self) -> (Vec<InProcessPeer>, Vec<InProcessPeer>) {
+        (self.router, self.clients)
+    }
+}
+
+impl InProcessPeer {
+    /// Send `message` to `peer` and apply it to both session states.
+    fn handshake(&mut self, peer: &mut InProcessPeer, message: Message) -> Result<(), Error> {
+        self.transport
+            .deliver(encode_message(message, Serializer::Json)?)?;
+        let frame = peer.transport.try_next().ok_or(Error::TransportClosed)?;
+        let received = decode_message(&frame, Serializer::Json)?;
+        // Fresh sessions always accept the HELLO and then the WELCOME.
+        let _ = self.session.transition(Direction::Outbound, &received);
+        let _ = peer.session.transition(Direction::Inbound, &received);
+        Ok(())
+    }
+}
@@ -1,11 +1,188 @@
+use crate::acceptor::Serializer;
+use crate::arity::check_trailing_elements;
+use crate::canonical::FrameWriter;
+use crate::diff::MessageDiff;
 use crate::error::Error;
+use crate::parse::{check_duplicate_keys, check_reserved_keys, ParseOptions};
+use crate::transcode::{
+    container_size_hint, integer_size_hint, json_size_hint, size_hint, str_size_hint,
+};
+use crate::value::WampValue;
 use json::JsonValue;
+use std::borrow::{Borrow, Cow};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::ops::Deref;
 use std::str::FromStr;
 
 pub type WampId = u64;
-pub type Uri = String;
 
-#[derive(Debug)]
+/// A procedure, topic or error URI.
+///
+/// URIs built from string literals borrow them, so clients naming the same constant
+/// procedure in every CALL do not allocate for it. Parsed URIs own their text. A `Uri`
+/// dereferences to `str` and compares equal to strings.
+/// # Examples
+/// ```
+/// use wamp_helpers::messages::{Call, Uri};
+///
+/// const ECHO: &str = "com.myapp.echo";
+/// let call = Call {
+///     request: 1,
+///     options: json::object! {},
+///     procedure: ECHO.into(),
+///     args: None,
+///     kwargs: None,
+/// };
+/// assert!(call.procedure.is_static());
+/// assert_eq!(call.procedure, ECHO);
+///
+/// let parsed: Call = r#"[48, 2, {}, "com.myapp.echo"]"#.parse().unwrap();
+/// assert!(!parsed.procedure.is_static());
+/// assert_eq!(parsed.procedure, call.procedure);
+/// assert!(parsed.procedure.starts_with("com.myapp."));
+/// assert_eq!(Uri::from(String::from("a.b")).into_string(), "a.b");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
+pub struct Uri(Cow<'static, str>);
+
+impl Uri {
+    /// A URI borrowing a string with static lifetime, usable in constants.
+    pub const fn from_static(uri: &'static str) -> Self {
+        Uri(Cow::Borrowed(uri))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Whether the URI borrows its text instead of owning it.
+    pub fn is_static(&self) -> bool {
+        matches!(self.0, Cow::Borrowed(_))
+    }
+
+    pub fn into_string(self) -> String {
+        self.0.into_owned()
+    }
+
+    /// Append to the URI, copying a borrowed text first.
+    pub fn push_str(&mut self, text: &str) {
+        self.0.to_mut().push_str(text);
+    }
+
+    pub fn push(&mut self, character: char) {
+        self.0.to_mut().push(character);
+    }
+}
+
+impl Deref for Uri {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl AsRef<str> for Uri {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Borrow<str> for Uri {
+    fn borrow(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for Uri {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl From<&'static str> for Uri {
+    fn from(uri: &'static str) -> Self {
+        Uri(Cow::Borrowed(uri))
+    }
+}
+
+impl From<String> for Uri {
+    fn from(uri: String) -> Self {
+        Uri(Cow::Owned(uri))
+    }
+}
+
+impl From<&String> for Uri {
+    fn from(uri: &String) -> Self {
+        Uri(Cow::Owned(uri.clone()))
+    }
+}
+
+impl From<Uri> for String {
+    fn from(uri: Uri) -> Self {
+        uri.into_string()
+    }
+}
+
+impl From<Uri> for JsonValue {
+    fn from(uri: Uri) -> Self {
+        JsonValue::String(uri.into_string())
+    }
+}
+
+impl From<&Uri> for JsonValue {
+    fn from(uri: &Uri) -> Self {
+        JsonValue::String(uri.to_string())
+    }
+}
+
+impl From<Uri> for WampValue {
+    fn from(uri: Uri) -> Self {
+        WampValue::String(uri.into_string())
+    }
+}
+
+impl PartialEq<str> for Uri {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for Uri {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl PartialEq<String> for Uri {
+    fn eq(&self, other: &String) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<Uri> for str {
+    fn eq(&self, other: &Uri) -> bool {
+        self == other.as_str()
+    }
+}
+
+impl PartialEq<Uri> for &str {
+    fn eq(&self, other: &Uri) -> bool {
+        *self == other.as_str()
+    }
+}
+
+impl PartialEq<Uri> for String {
+    fn eq(&self, other: &Uri) -> bool {
+        self == other.as_str()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Roles {
     Callee,
     Caller,
@@ -15,26 +192,119 @@ pub enum Roles {
     Broker,
 }
 
+/// Message codes an ERROR may refer to in its `REQUEST.Type` element.
+///
+/// Parsing rejects an ERROR referring to any other code.
+/// ```
+/// use wamp_helpers::error::Error;
+/// use wamp_helpers::messages::{ErrorMessage, Message};
+///
+/// let frame = r#"[8, 2, 1, {}, "wamp.error.invalid_argument"]"#;
+/// let rejected = Message::parse_message(frame);
+/// assert!(matches!(rejected, Err(Error::InvalidRequestType { offense: 2 })));
+/// assert!(frame.parse::<ErrorMessage>().is_err());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RequestType {
+    Publish = 16,
+    Subscribe = 32,
+    Unsubscribe = 34,
+    Call = 48,
+    Register = 64,
+    Unregister = 66,
+    Invocation = 68,
+}
+
+impl TryFrom<u8> for RequestType {
+    type Error = Error;
+
+    fn try_from(code: u8) -> Result<Self, Self::Error> {
+        match code {
+            16 => Ok(RequestType::Publish),
+            32 => Ok(RequestType::Subscribe),
+            34 => Ok(RequestType::Unsubscribe),
+            48 => Ok(RequestType::Call),
+            64 => Ok(RequestType::Register),
+            66 => Ok(RequestType::Unregister),
+            68 => Ok(RequestType::Invocation),
+            _ => Err(Error::InvalidRequestType { offense: code }),
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, PartialOrd)]
 pub struct MessageDirection {
     pub receives: &'static bool,
     pub sends: &'static bool,
 }
 
-pub type Args = JsonValue;
-pub type Kwargs = JsonValue;
+/// Details/Options key carrying the time a message was sent or captured, in milliseconds
+/// since the Unix epoch. Set by recording proxies, read by [`Message::sort_key`].
+pub const TIMESTAMP: &str = "x_timestamp";
+
+/// Key ordering messages from interleaved logs deterministically, see [`Message::sort_key`].
+///
+/// Fields compare in declaration order, absent values before present ones.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SortKey {
+    /// The [`TIMESTAMP`] in the Details/Options.
+    pub timestamp: Option<u64>,
+    /// The session a WELCOME opens.
+    pub session: Option<WampId>,
+    pub request: Option<WampId>,
+    /// Type of the request opening the exchange the message belongs to, e.g. CALL for a
+    /// RESULT or an ERROR answering a CALL.
+    pub exchange: u8,
+    /// 0 for the request, 1 for a CANCEL or INTERRUPT of it, 2 for its response or ERROR.
+    pub phase: u8,
+    pub message_type: u8,
+    /// Every ID of the message, see [`Message::ids`].
+    pub ids: Vec<WampId>,
+}
+
+pub type Args = Vec<WampValue>;
+pub type Kwargs = BTreeMap<String, WampValue>;
 pub type Details = JsonValue;
 pub type Options = JsonValue;
 
-fn validate_u64_argument(value: JsonValue) -> Result<u64, Error> {
-    if let Some(value) = value.as_u64() {
-        Ok(value)
+/// Largest value a WAMP ID may take, IDs have to be representable exactly by an IEEE double.
+/// # Examples
+/// ```
+/// use wamp_helpers::error::Error;
+/// use wamp_helpers::messages::Welcome;
+/// assert!("[2, 9007199254740992, {}]".parse::<Welcome>().is_ok());
+/// assert!(matches!("[2, 9007199254740993, {}]".parse::<Welcome>(), Err(Error::IdOutOfRange { .. })));
+/// assert!(matches!("[2, 1.0, {}]".parse::<Welcome>(), Err(Error::NonIntegerId { .. })));
+/// assert!(matches!("[2, 1e3, {}]".parse::<Welcome>(), Err(Error::NonIntegerId { .. })));
+/// assert!(matches!("[2, -1, {}]".parse::<Welcome>(), Err(Error::NegativeId { .. })));
+/// assert!(matches!("[2, \"1\", {}]".parse::<Welcome>(), Err(Error::InvalidJsonU64 { .. })));
+/// ```
+pub const MAX_ID: u64 = 1 << 53;
+
+#[doc(hidden)]
+pub fn validate_u64_argument(value: JsonValue) -> Result<u64, Error> {
+    let (positive, mantissa, exponent) = match value.as_number() {
+        Some(number) => number.as_parts(),
+        None => return Err(Error::InvalidJsonU64 { offense: value }),
+    };
+
+    if !positive && mantissa != 0 {
+        Err(Error::NegativeId { offense: value })
+    } else if exponent != 0 {
+        if exponent > 0 && value.as_f64().is_some_and(|float| float > MAX_ID as f64) {
+            Err(Error::IdOutOfRange { offense: value })
+        } else {
+            Err(Error::NonIntegerId { offense: value })
+        }
+    } else if mantissa > MAX_ID {
+        Err(Error::IdOutOfRange { offense: value })
     } else {
-        Err(Error::InvalidJsonU64 { offense: value })
+        Ok(mantissa)
     }
 }
 
-fn validate_dict_argument(value: JsonValue) -> Result<JsonValue, Error> {
+#[doc(hidden)]
+pub fn validate_dict_argument(value: JsonValue) -> Result<JsonValue, Error> {
     if value.is_object() {
         Ok(value)
     } else {
@@ -42,7 +312,17 @@ fn validate_dict_argument(value: JsonValue) -> Result<JsonValue, Error> {
     }
 }
 
-fn validate_array_argument(value: JsonValue) -> Result<JsonValue, Error> {
+#[doc(hidden)]
+pub fn validate_optional_dict(value: JsonValue) -> Result<Option<JsonValue>, Error> {
+    if value.is_null() {
+        Ok(None)
+    } else {
+        validate_dict_argument(value).map(Some)
+    }
+}
+
+#[doc(hidden)]
+pub fn validate_array_argument(value: JsonValue) -> Result<JsonValue, Error> {
     if value.is_array() {
         Ok(value)
     } else {
@@ -50,7 +330,8 @@ fn validate_array_argument(value: JsonValue) -> Result<JsonValue, Error> {
     }
 }
 
-fn validate_u8_argument(value: JsonValue) -> Result<u8, Error> {
+#[doc(hidden)]
+pub fn validate_u8_argument(value: JsonValue) -> Result<u8, Error> {
     if let Some(id) = value.as_u8() {
         Ok(id)
     } else {
@@ -58,7 +339,8 @@ fn validate_u8_argument(value: JsonValue) -> Result<u8, Error> {
     }
 }
 
-fn validate_str_argument(value: JsonValue) -> Result<String, Error> {
+#[doc(hidden)]
+pub fn validate_str_argument(value: JsonValue) -> Result<String, Error> {
     if let Some(value) = value.as_str() {
         Ok(value.to_string())
     } else {
@@ -66,20 +348,54 @@ fn validate_str_argument(value: JsonValue) -> Result<String, Error> {
     }
 }
 
-fn validate_args(value: JsonValue) -> Result<Option<JsonValue>, Error> {
+#[doc(hidden)]
+pub fn validate_args(value: JsonValue) -> Result<Option<Args>, Error> {
     if value.is_null() {
         Ok(None)
     } else {
-        Ok(Some(validate_array_argument(value)?))
+        let value = validate_array_argument(value)?;
+        Ok(Some(value.members().map(WampValue::from).collect()))
     }
 }
 
-fn validate_kwargs(value: JsonValue) -> Result<Option<JsonValue>, Error> {
+#[doc(hidden)]
+pub fn validate_kwargs(value: JsonValue) -> Result<Option<Kwargs>, Error> {
     if value.is_null() {
         Ok(None)
     } else {
-        Ok(Some(validate_dict_argument(value)?))
+        let value = validate_dict_argument(value)?;
+        Ok(Some(
+            value
+                .entries()
+                .map(|(key, item)| (key.to_string(), WampValue::from(item)))
+                .collect(),
+        ))
+    }
+}
+
+/// Append the optional trailing `Arguments|list` and `ArgumentsKw|dict` elements, adding an
+/// empty list when only keyword arguments are present.
+#[doc(hidden)]
+pub fn push_payload(
+    data: &mut JsonValue,
+    args: Option<Args>,
+    kwargs: Option<Kwargs>,
+) -> Result<(), Error> {
+    let has_args = if let Some(args) = args {
+        data.push(JsonValue::from(WampValue::List(args)))
+            .map_err(Error::JsonError)?;
+        true
+    } else {
+        false
+    };
+    if let Some(kwargs) = kwargs {
+        if !has_args {
+            data.push(json::array![]).map_err(Error::JsonError)?;
+        }
+        data.push(JsonValue::from(WampValue::Dict(kwargs)))
+            .map_err(Error::JsonError)?;
     }
+    Ok(())
 }
 
 pub trait WampMessageTrait {
@@ -87,6 +403,14 @@ pub trait WampMessageTrait {
 
     fn to_json(self) -> Result<JsonValue, Error>;
 
+    /// Serialize the message as canonical JSON, see [`to_canonical_string`](crate::canonical::to_canonical_string).
+    fn to_canonical_json(self) -> Result<String, Error>
+    where
+        Self: Sized,
+    {
+        Ok(crate::canonical::to_canonical_string(&self.to_json()?))
+    }
+
     fn get_message_direction(role: Roles) -> &'static MessageDirection
     where
         Self: Sized;
@@ -121,7 +445,7 @@ impl Hello {
     /// Create a help message with default details object containing roles and auth methods.
     /// # Examples
     /// ```
-    /// use wamp_v1::messages::{Hello, Roles};
+    /// use wamp_helpers::messages::{Hello, Roles};
     /// let hello = Hello::default(
     ///     "some.realm.uri".to_string(),
     ///     vec![Roles::Callee, Roles::Caller, Roles::Publisher, Roles::Subscriber],
@@ -153,7 +477,10 @@ impl Hello {
             }
         };
 
-        Hello { realm, details }
+        Hello {
+            realm: realm.into(),
+            details,
+        }
     }
 }
 
@@ -164,7 +491,7 @@ impl WampMessageTrait for Hello {
     /// use json::object;
     /// // To create a new Hello Message
     /// let hello = Hello {
-    ///     realm: "some.uri.path".to_string(),
+    ///     realm: "some.uri.path".into(),
     ///     details: object!{
     ///         authmethods: ["ticket"], // For advanced wamp configurations
     ///         roles: { // Roles are required by Wamp
@@ -222,7 +549,10 @@ impl FromStr for Hello {
         let _id = Self::validate_id(data.array_remove(0))?;
         let realm = validate_str_argument(data.array_remove(0))?;
         let details = validate_dict_argument(data.array_remove(0))?;
-        Ok(Hello { realm, details })
+        Ok(Hello {
+            realm: realm.into(),
+            details,
+        })
     }
 }
 
@@ -298,26 +628,26 @@ impl WampMessageTrait for Abort {
         match role {
             Roles::Callee => &MessageDirection {
                 receives: &true,
-                sends: &false,
+                sends: &true,
             },
             Roles::Caller => &MessageDirection {
                 receives: &true,
-                sends: &false,
+                sends: &true,
             },
             Roles::Publisher => &MessageDirection {
                 receives: &true,
-                sends: &false,
+                sends: &true,
             },
             Roles::Subscriber => &MessageDirection {
                 receives: &true,
-                sends: &false,
+                sends: &true,
             },
             Roles::Dealer => &MessageDirection {
-                receives: &false,
+                receives: &true,
                 sends: &true,
             },
             Roles::Broker => &MessageDirection {
-                receives: &false,
+                receives: &true,
                 sends: &true,
             },
         }
@@ -332,7 +662,10 @@ impl FromStr for Abort {
         let _id = Self::validate_id(data.array_remove(0))?;
         let details = validate_dict_argument(data.array_remove(0))?;
         let reason = validate_str_argument(data.array_remove(0))?;
-        Ok(Abort { details, reason })
+        Ok(Abort {
+            details,
+            reason: reason.into(),
+        })
     }
 }
 
@@ -342,6 +675,92 @@ pub struct Goodbye {
     pub reason: Uri,
 }
 
+/// The Details of a GOODBYE in typed form, unknown keys are kept in `extra`.
+///
+/// With the `serde` feature, absent keys are omitted when serialized, like the `From`
+/// conversion does.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(from = "Kwargs"))]
+pub struct GoodbyeDetails {
+    /// Human readable reason for closing.
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub message: Option<String>,
+    /// Whether the closing peer allows resuming the session later.
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub resumable: Option<bool>,
+    /// Token to present when resuming.
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub resume_token: Option<String>,
+    /// Details without a typed field, and typed ones of an unexpected type.
+    #[cfg_attr(feature = "serde", serde(flatten))]
+    pub extra: BTreeMap<String, WampValue>,
+}
+
+impl From<&Details> for GoodbyeDetails {
+    fn from(details: &Details) -> Self {
+        let parse = |details: &Details| GoodbyeDetails {
+            message: details["message"].as_str().map(str::to_string),
+            resumable: details["resumable"].as_bool(),
+            resume_token: details["resume_token"].as_str().map(str::to_string),
+            extra: BTreeMap::new(),
+        };
+        let (typed, extra) = crate::options::split_typed(details, parse, Details::from);
+        GoodbyeDetails { extra, ..typed }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl From<Kwargs> for GoodbyeDetails {
+    fn from(details: Kwargs) -> Self {
+        GoodbyeDetails::from(&Details::from(WampValue::Dict(details)))
+    }
+}
+
+impl From<GoodbyeDetails> for Details {
+    fn from(goodbye: GoodbyeDetails) -> Self {
+        let mut details = json::object! {};
+        if let Some(message) = goodbye.message {
+            details["message"] = message.into();
+        }
+        if let Some(resumable) = goodbye.resumable {
+            details["resumable"] = resumable.into();
+        }
+        if let Some(resume_token) = goodbye.resume_token {
+            details["resume_token"] = resume_token.into();
+        }
+        crate::options::insert_extra(&mut details, goodbye.extra);
+        details
+    }
+}
+
+impl Goodbye {
+    /// ```
+    /// use wamp_helpers::messages::{Goodbye, GoodbyeDetails, WampMessageTrait};
+    ///
+    /// let details = GoodbyeDetails {
+    ///     message: Some("The host is shutting down now.".to_string()),
+    ///     ..GoodbyeDetails::default()
+    /// };
+    /// let goodbye = Goodbye::new("wamp.close.system_shutdown".into(), details.clone());
+    /// assert_eq!(goodbye.typed_details(), details);
+    /// assert_eq!(
+    ///     goodbye.to_json().unwrap().dump(),
+    ///     r#"[6,{"message":"The host is shutting down now."},"wamp.close.system_shutdown"]"#
+    /// );
+    /// ```
+    pub fn new(reason: Uri, details: GoodbyeDetails) -> Self {
+        Goodbye {
+            details: details.into(),
+            reason,
+        }
+    }
+
+    pub fn typed_details(&self) -> GoodbyeDetails {
+        GoodbyeDetails::from(&self.details)
+    }
+}
+
 impl WampMessageTrait for Goodbye {
     const ID: u8 = 6;
 
@@ -365,7 +784,7 @@ impl WampMessageTrait for Goodbye {
             },
             Roles::Subscriber => &MessageDirection {
                 receives: &true,
-                sends: &false,
+                sends: &true,
             },
             Roles::Dealer => &MessageDirection {
                 receives: &true,
@@ -387,7 +806,10 @@ impl FromStr for Goodbye {
         let _id = Self::validate_id(data.array_remove(0))?;
         let details = validate_dict_argument(data.array_remove(0))?;
         let reason = validate_str_argument(data.array_remove(0))?;
-        Ok(Goodbye { details, reason })
+        Ok(Goodbye {
+            details,
+            reason: reason.into(),
+        })
     }
 }
 
@@ -401,6 +823,84 @@ pub struct ErrorMessage {
     pub kwargs: Option<Kwargs>,
 }
 
+impl ErrorMessage {
+    /// ERROR answering the request `request` of type `request_type`, with empty Details and no
+    /// payload.
+    pub fn for_request(request_type: RequestType, request: WampId, error: Uri) -> Self {
+        ErrorMessage {
+            request_type: request_type as u8,
+            request,
+            details: json::object! {},
+            error,
+            args: None,
+            kwargs: None,
+        }
+    }
+
+    /// Like [`for_request`](Self::for_request) but refuses error URIs that fail strict
+    /// checking, see [`check_error_uri`](crate::uri::check_error_uri).
+    /// ```
+    /// use wamp_helpers::error::Error;
+    /// use wamp_helpers::messages::{ErrorMessage, RequestType};
+    ///
+    /// let error = ErrorMessage::try_for_request(RequestType::Call, 1, "wamp.error.no_such_procedure".into());
+    /// assert!(error.is_ok());
+    /// let error = ErrorMessage::try_for_request(RequestType::Call, 1, "wamp.error.no_such_procedur".into());
+    /// assert!(matches!(error, Err(Error::InvalidErrorUri { suggestion: Some("wamp.error.no_such_procedure"), .. })));
+    /// ```
+    pub fn try_for_request(
+        request_type: RequestType,
+        request: WampId,
+        error: Uri,
+    ) -> Result<Self, Error> {
+        crate::uri::check_error_uri(&error, true)?;
+        Ok(Self::for_request(request_type, request, error))
+    }
+
+    /// ERROR a dealer sends to the caller of `call`.
+    /// ```
+    /// use wamp_helpers::messages::{Call, ErrorMessage, RequestType};
+    ///
+    /// let call: Call = r#"[48, 7814135, {}, "com.myapp.echo"]"#.parse().unwrap();
+    /// let error = ErrorMessage::for_call(&call, "wamp.error.no_such_procedure".into());
+    /// assert_eq!(error.request, 7814135);
+    /// assert_eq!(error.typed_request_type(), Some(RequestType::Call));
+    /// ```
+    pub fn for_call(call: &Call, error: Uri) -> Self {
+        Self::for_request(RequestType::Call, call.request, error)
+    }
+
+    /// ERROR a callee sends for `invocation`.
+    pub fn for_invocation(invocation: &Invocation, error: Uri) -> Self {
+        Self::for_request(RequestType::Invocation, invocation.request, error)
+    }
+
+    pub fn for_publish(publish: &Publish, error: Uri) -> Self {
+        Self::for_request(RequestType::Publish, publish.request, error)
+    }
+
+    pub fn for_subscribe(subscribe: &Subscribe, error: Uri) -> Self {
+        Self::for_request(RequestType::Subscribe, subscribe.request, error)
+    }
+
+    pub fn for_unsubscribe(unsubscribe: &Unsubscribe, error: Uri) -> Self {
+        Self::for_request(RequestType::Unsubscribe, unsubscribe.request, error)
+    }
+
+    pub fn for_register(register: &Register, error: Uri) -> Self {
+        Self::for_request(RequestType::Register, register.request, error)
+    }
+
+    pub fn for_unregister(unregister: &Unregister, error: Uri) -> Self {
+        Self::for_request(RequestType::Unregister, unregister.request, error)
+    }
+
+    /// `request_type` as a [`RequestType`], `None` for codes no request can have.
+    pub fn typed_request_type(&self) -> Option<RequestType> {
+        RequestType::try_from(self.request_type).ok()
+    }
+}
+
 impl WampMessageTrait for ErrorMessage {
     const ID: u8 = 8;
 
@@ -412,26 +912,7 @@ impl WampMessageTrait for ErrorMessage {
             self.details,
             self.error
         ];
-
-        let is_array = if let Some(args) = self.args {
-            let n = args.is_array();
-            if n {
-                data.push(args).map_err(|err| Error::JsonError(err))?;
-            }
-            n
-        } else {
-            false
-        };
-        if let Some(kwargs) = self.kwargs {
-            if kwargs.is_object() {
-                if !is_array {
-                    data.push(json::array![])
-                        .map_err(|err| Error::JsonError(err))?;
-                }
-
-                data.push(kwargs).map_err(|err| Error::JsonError(err))?;
-            };
-        }
+        push_payload(&mut data, self.args, self.kwargs)?;
         Ok(data)
     }
 
@@ -470,8 +951,9 @@ impl FromStr for ErrorMessage {
 
     fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
         let mut data = Self::parse_raw_json(s.to_string())?;
-        let _id = validate_u8_argument(data.array_remove(0))?;
+        let _id = Self::validate_id(data.array_remove(0))?;
         let request_type = validate_u8_argument(data.array_remove(0))?;
+        RequestType::try_from(request_type)?;
         let request = validate_u64_argument(data.array_remove(0))?;
         let details = validate_dict_argument(data.array_remove(0))?;
         let error = validate_str_argument(data.array_remove(0))?;
@@ -481,7 +963,7 @@ impl FromStr for ErrorMessage {
             request_type,
             request,
             details,
-            error,
+            error: error.into(),
             args,
             kwargs,
         })
@@ -502,25 +984,7 @@ impl WampMessageTrait for Publish {
 
     fn to_json(self) -> Result<JsonValue, Error> {
         let mut data = json::array![Self::ID, self.request, self.options, self.topic];
-        let is_array = if let Some(args) = self.args {
-            let n = args.is_array();
-            if n {
-                data.push(args).map_err(|err| Error::JsonError(err))?;
-            }
-            n
-        } else {
-            false
-        };
-        if let Some(kwargs) = self.kwargs {
-            if kwargs.is_object() {
-                if !is_array {
-                    data.push(json::array![])
-                        .map_err(|err| Error::JsonError(err))?;
-                }
-
-                data.push(kwargs).map_err(|err| Error::JsonError(err))?;
-            };
-        }
+        push_payload(&mut data, self.args, self.kwargs)?;
         Ok(data)
     }
 
@@ -571,7 +1035,7 @@ impl FromStr for Publish {
             Ok(Publish {
                 request,
                 options,
-                topic,
+                topic: topic.into(),
                 args,
                 kwargs,
             })
@@ -627,7 +1091,7 @@ impl FromStr for Published {
 
     fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
         let mut data = Self::parse_raw_json(s.to_string())?;
-        let _id = validate_u8_argument(data.array_remove(0))?;
+        let _id = Self::validate_id(data.array_remove(0))?;
         let request = validate_u64_argument(data.array_remove(0))?;
         let publication = validate_u64_argument(data.array_remove(0))?;
         Ok(Published {
@@ -698,7 +1162,7 @@ impl FromStr for Subscribe {
         Ok(Subscribe {
             request,
             options,
-            topic,
+            topic: topic.into(),
         })
     }
 }
@@ -822,13 +1286,20 @@ impl FromStr for Unsubscribe {
 #[derive(Debug, Clone)]
 pub struct Unsubscribed {
     pub request: WampId,
+    /// Only present when the router revokes a subscription on its own, with request `0` and the
+    /// `subscription` and `reason` keys.
+    pub details: Option<Details>,
 }
 
 impl WampMessageTrait for Unsubscribed {
     const ID: u8 = 35;
 
     fn to_json(self) -> Result<JsonValue, Error> {
-        Ok(json::array![Self::ID, self.request])
+        let mut message = json::array![Self::ID, self.request];
+        if let Some(details) = self.details {
+            message.push(details).map_err(Error::JsonError)?;
+        }
+        Ok(message)
     }
 
     fn get_message_direction(role: Roles) -> &'static MessageDirection
@@ -869,8 +1340,10 @@ impl FromStr for Unsubscribed {
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let mut data = Self::parse_raw_json(s.to_string())?;
+        let _id = Self::validate_id(data.array_remove(0))?;
         let request = validate_u64_argument(data.array_remove(0))?;
-        Ok(Unsubscribed { request })
+        let details = validate_optional_dict(data.array_remove(0))?;
+        Ok(Unsubscribed { request, details })
     }
 }
 
@@ -888,25 +1361,7 @@ impl WampMessageTrait for Event {
 
     fn to_json(self) -> Result<JsonValue, Error> {
         let mut data = json::array![Self::ID, self.subscription, self.publication, self.details];
-        let is_array = if let Some(args) = self.args {
-            let n = args.is_array();
-            if n {
-                data.push(args).map_err(|err| Error::JsonError(err))?;
-            }
-            n
-        } else {
-            false
-        };
-        if let Some(kwargs) = self.kwargs {
-            if kwargs.is_object() {
-                if !is_array {
-                    data.push(json::array![])
-                        .map_err(|err| Error::JsonError(err))?;
-                }
-
-                data.push(kwargs).map_err(|err| Error::JsonError(err))?;
-            };
-        }
+        push_payload(&mut data, self.args, self.kwargs)?;
         Ok(data)
     }
 
@@ -975,26 +1430,7 @@ impl WampMessageTrait for Call {
 
     fn to_json(self) -> Result<JsonValue, Error> {
         let mut data = json::array![Self::ID, self.request, self.options, self.procedure];
-
-        let is_array = if let Some(args) = self.args {
-            let n = args.is_array();
-            if n {
-                data.push(args).map_err(|err| Error::JsonError(err))?;
-            }
-            n
-        } else {
-            false
-        };
-        if let Some(kwargs) = self.kwargs {
-            if kwargs.is_object() {
-                if !is_array {
-                    data.push(json::array![])
-                        .map_err(|err| Error::JsonError(err))?;
-                }
-
-                data.push(kwargs).map_err(|err| Error::JsonError(err))?;
-            };
-        }
+        push_payload(&mut data, self.args, self.kwargs)?;
         Ok(data)
     }
 
@@ -1042,46 +1478,28 @@ impl FromStr for Call {
         Ok(Call {
             request,
             options,
-            procedure,
+            procedure: procedure.into(),
             args,
             kwargs,
         })
     }
 }
 
+/// The RESULT message, named to avoid clashing with `std::result::Result`.
 #[derive(Debug, Clone)]
-pub struct MessageResult {
+pub struct WampResult {
     pub request: WampId,
     pub details: Details,
     pub args: Option<Args>,
     pub kwargs: Option<Kwargs>,
 }
 
-impl WampMessageTrait for MessageResult {
+impl WampMessageTrait for WampResult {
     const ID: u8 = 50;
 
     fn to_json(self) -> Result<JsonValue, Error> {
         let mut data = json::array![Self::ID, self.request, self.details];
-
-        let is_array = if let Some(args) = self.args {
-            let n = args.is_array();
-            if n {
-                data.push(args).map_err(|err| Error::JsonError(err))?;
-            }
-            n
-        } else {
-            false
-        };
-        if let Some(kwargs) = self.kwargs {
-            if kwargs.is_object() {
-                if !is_array {
-                    data.push(json::array![])
-                        .map_err(|err| Error::JsonError(err))?;
-                }
-
-                data.push(kwargs).map_err(|err| Error::JsonError(err))?;
-            };
-        }
+        push_payload(&mut data, self.args, self.kwargs)?;
         Ok(data)
     }
 
@@ -1115,7 +1533,7 @@ impl WampMessageTrait for MessageResult {
     }
 }
 
-impl FromStr for MessageResult {
+impl FromStr for WampResult {
     type Err = Error;
 
     fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
@@ -1125,7 +1543,7 @@ impl FromStr for MessageResult {
         let details = validate_dict_argument(data.array_remove(0))?;
         let args = validate_args(data.array_remove(0))?;
         let kwargs = validate_kwargs(data.array_remove(0))?;
-        Ok(MessageResult {
+        Ok(WampResult {
             request,
             details,
             args,
@@ -1134,6 +1552,9 @@ impl FromStr for MessageResult {
     }
 }
 
+#[deprecated(note = "renamed to `WampResult`")]
+pub type MessageResult = WampResult;
+
 #[derive(Debug, Clone)]
 pub struct Register {
     pub request: WampId,
@@ -1145,8 +1566,14 @@ impl WampMessageTrait for Register {
     const ID: u8 = 64;
 
     fn to_json(self) -> Result<JsonValue, Error> {
-        Ok(json::array![self.request, self.options, self.procedure])
+        Ok(json::array![
+            Self::ID,
+            self.request,
+            self.options,
+            self.procedure
+        ])
     }
+
     fn get_message_direction(role: Roles) -> &'static MessageDirection {
         match role {
             Roles::Callee => &MessageDirection {
@@ -1182,13 +1609,14 @@ impl FromStr for Register {
 
     fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
         let mut data = Self::parse_raw_json(s.to_string())?;
+        let _id = Self::validate_id(data.array_remove(0))?;
         let request = validate_u64_argument(data.array_remove(0))?;
         let options = validate_dict_argument(data.array_remove(0))?;
         let procedure = validate_str_argument(data.array_remove(0))?;
         Ok(Register {
             request,
             options,
-            procedure,
+            procedure: procedure.into(),
         })
     }
 }
@@ -1312,13 +1740,20 @@ impl FromStr for Unregister {
 #[derive(Debug, Clone)]
 pub struct Unregistered {
     pub request: WampId,
+    /// Only present when the router revokes a registration on its own, with request `0` and the
+    /// `registration` and `reason` keys.
+    pub details: Option<Details>,
 }
 
 impl WampMessageTrait for Unregistered {
     const ID: u8 = 67;
 
     fn to_json(self) -> Result<JsonValue, Error> {
-        Ok(json::array![Self::ID, self.request])
+        let mut message = json::array![Self::ID, self.request];
+        if let Some(details) = self.details {
+            message.push(details).map_err(Error::JsonError)?;
+        }
+        Ok(message)
     }
 
     fn get_message_direction(role: Roles) -> &'static MessageDirection {
@@ -1358,7 +1793,8 @@ impl FromStr for Unregistered {
         let mut data = Self::parse_raw_json(s.to_string())?;
         let _id = Self::validate_id(data.array_remove(0))?;
         let request = validate_u64_argument(data.array_remove(0))?;
-        Ok(Unregistered { request })
+        let details = validate_optional_dict(data.array_remove(0))?;
+        Ok(Unregistered { request, details })
     }
 }
 
@@ -1376,26 +1812,7 @@ impl WampMessageTrait for Invocation {
 
     fn to_json(self) -> Result<JsonValue, Error> {
         let mut data = json::array![Self::ID, self.request, self.registration, self.details];
-
-        let is_array = if let Some(args) = self.args {
-            let n = args.is_array();
-            if n {
-                data.push(args).map_err(|err| Error::JsonError(err))?;
-            }
-            n
-        } else {
-            false
-        };
-        if let Some(kwargs) = self.kwargs {
-            if kwargs.is_object() {
-                if !is_array {
-                    data.push(json::array![])
-                        .map_err(|err| Error::JsonError(err))?;
-                }
-
-                data.push(kwargs).map_err(|err| Error::JsonError(err))?;
-            };
-        }
+        push_payload(&mut data, self.args, self.kwargs)?;
         Ok(data)
     }
 
@@ -1462,27 +1879,8 @@ impl WampMessageTrait for Yield {
     const ID: u8 = 70;
 
     fn to_json(self) -> Result<JsonValue, Error> {
-        let mut data = json::array![Self::ID, self.options];
-
-        let is_array = if let Some(args) = self.args {
-            let n = args.is_array();
-            if n {
-                data.push(args).map_err(|err| Error::JsonError(err))?;
-            }
-            n
-        } else {
-            false
-        };
-
-        if let Some(kwargs) = self.kwargs {
-            if kwargs.is_object() {
-                if !is_array {
-                    data.push(json::array![])
-                        .map_err(|err| Error::JsonError(err))?;
-                }
-                data.push(kwargs).map_err(|err| Error::JsonError(err))?;
-            };
-        }
+        let mut data = json::array![Self::ID, self.request, self.options];
+        push_payload(&mut data, self.args, self.kwargs)?;
         Ok(data)
     }
 
@@ -1538,7 +1936,7 @@ impl FromStr for Yield {
 #[derive(Debug, Clone)]
 pub struct Challenge {
     pub authmethod: String,
-    pub details: Kwargs,
+    pub details: Details,
 }
 
 impl WampMessageTrait for Challenge {
@@ -1596,7 +1994,7 @@ impl FromStr for Challenge {
 #[derive(Debug, Clone)]
 pub struct Authenticate {
     pub signature: String,
-    pub details: Kwargs,
+    pub details: Details,
 }
 
 impl WampMessageTrait for Authenticate {
@@ -1758,8 +2156,34 @@ impl FromStr for Interrupt {
     }
 }
 
+/// Result of [`Message::parse_with_extension`], either a standard message or the extension type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Extended<T> {
+    Standard(Message),
+    Extension(T),
+}
+
+/// Any WAMP message, requests and responses alike.
+///
+/// Messages compare field by field, dictionaries regardless of key order and numbers by value.
+/// `Hash` agrees with that equality, so messages can be deduplicated in a set or used as map
+/// keys.
+/// # Examples
+/// ```
+/// use std::collections::HashSet;
+/// use wamp_helpers::messages::Message;
+///
+/// let first = Message::parse_message(r#"[16, 1, {"a": 1, "b": 2.0}, "com.example.topic"]"#).unwrap();
+/// let reordered = Message::parse_message(r#"[16, 1, {"b": 2, "a": 1}, "com.example.topic"]"#).unwrap();
+/// let other = Message::parse_message(r#"[16, 2, {}, "com.example.topic"]"#).unwrap();
+/// assert_eq!(first, reordered);
+/// assert_ne!(first, other);
+///
+/// let unique: HashSet<_> = [first, reordered, other].into_iter().collect();
+/// assert_eq!(unique.len(), 2);
+/// ```
 #[derive(Debug, Clone)]
-pub enum Events {
+pub enum Message {
     Hello(Hello),
     Welcome(Welcome),
     Abort(Abort),
@@ -1776,7 +2200,7 @@ pub enum Events {
     Event(Event),
     Call(Call),
     Cancel(Cancel),
-    MessageResult(MessageResult),
+    MessageResult(WampResult),
     Register(Register),
     Registered(Registered),
     Unregister(Unregister),
@@ -1786,9 +2210,154 @@ pub enum Events {
     Yield(Yield),
 }
 
-impl Events {
-    pub fn parse_message(raw_message_string: &String) -> Result<Self, Error> {
-        let mut data = json::parse(raw_message_string).map_err(|err| Error::JsonError(err))?;
+/// Implement equality and hashing of message structs on their [canonical](crate::canonical)
+/// frame, written field by field in wire order with the [`FrameWriter`] method of each kind.
+macro_rules! canonical_equality {
+    ($( $name:ident { $( $field:ident: $kind:ident ),* $(; $payload:ident)? } )*) => {$(
+        impl $name {
+            /// The [canonical](crate::canonical) form of the frame, what equality and
+            /// hashing use.
+            fn canonical_frame(&self) -> String {
+                let mut frame = FrameWriter::new(<Self as WampMessageTrait>::ID);
+                $( frame.$kind(&self.$field); )*
+                $( frame.$payload(&self.args, &self.kwargs); )?
+                frame.finish()
+            }
+        }
+
+        impl PartialEq for $name {
+            fn eq(&self, other: &Self) -> bool {
+                self.canonical_frame() == other.canonical_frame()
+            }
+        }
+
+        impl Eq for $name {}
+
+        impl Hash for $name {
+            fn hash<H: Hasher>(&self, state: &mut H) {
+                self.canonical_frame().hash(state);
+            }
+        }
+    )*};
+}
+
+canonical_equality! {
+    Hello { realm: uri, details: dict }
+    Welcome { session: id, details: dict }
+    Abort { details: dict, reason: uri }
+    Challenge { authmethod: str, details: dict }
+    Authenticate { signature: str, details: dict }
+    Goodbye { details: dict, reason: uri }
+    ErrorMessage { request_type: u8, request: id, details: dict, error: uri; payload }
+    Publish { request: id, options: dict, topic: uri; payload }
+    Published { request: id, publication: id }
+    Subscribe { request: id, options: dict, topic: uri }
+    Subscribed { request: id, subscription: id }
+    Unsubscribe { request: id, subscription: id }
+    Unsubscribed { request: id, details: optional_dict }
+    Event { subscription: id, publication: id, details: dict; payload }
+    Call { request: id, options: dict, procedure: uri; payload }
+    Cancel { request: id, options: dict }
+    WampResult { request: id, details: dict; payload }
+    Register { request: id, options: dict, procedure: uri }
+    Registered { request: id, registration: id }
+    Unregister { request: id, registration: id }
+    Unregistered { request: id, details: optional_dict }
+    Invocation { request: id, registration: id, details: dict; payload }
+    Interrupt { request: id, options: dict }
+    Yield { request: id, options: dict; payload }
+}
+
+impl Message {
+    /// The [canonical](crate::canonical) form of the frame, what equality and hashing use.
+    fn canonical_frame(&self) -> String {
+        match self {
+            Self::Hello(message) => message.canonical_frame(),
+            Self::Welcome(message) => message.canonical_frame(),
+            Self::Abort(message) => message.canonical_frame(),
+            Self::Challenge(message) => message.canonical_frame(),
+            Self::Authenticate(message) => message.canonical_frame(),
+            Self::Goodbye(message) => message.canonical_frame(),
+            Self::ErrorMessage(message) => message.canonical_frame(),
+            Self::Publish(message) => message.canonical_frame(),
+            Self::Published(message) => message.canonical_frame(),
+            Self::Subscribe(message) => message.canonical_frame(),
+            Self::Subscribed(message) => message.canonical_frame(),
+            Self::Unsubscribe(message) => message.canonical_frame(),
+            Self::Unsubscribed(message) => message.canonical_frame(),
+            Self::Event(message) => message.canonical_frame(),
+            Self::Call(message) => message.canonical_frame(),
+            Self::Cancel(message) => message.canonical_frame(),
+            Self::MessageResult(message) => message.canonical_frame(),
+            Self::Register(message) => message.canonical_frame(),
+            Self::Registered(message) => message.canonical_frame(),
+            Self::Unregister(message) => message.canonical_frame(),
+            Self::Unregistered(message) => message.canonical_frame(),
+            Self::Invocation(message) => message.canonical_frame(),
+            Self::Interrupt(message) => message.canonical_frame(),
+            Self::Yield(message) => message.canonical_frame(),
+        }
+    }
+}
+
+/// Messages are equal when their [canonical](crate::canonical) frames are, rather than by
+/// comparing the JSON trees, whose number comparison can overflow. The message structs
+/// compare the same way.
+/// ```
+/// use wamp_helpers::messages::{Message, Publish};
+///
+/// let huge = r#"[16, 1, {"x": 1844674407370955162e1}, "t"]"#;
+/// let small = r#"[16, 1, {"x": 4}, "t"]"#;
+/// assert_ne!(Message::parse_message(huge).unwrap(), Message::parse_message(small).unwrap());
+/// assert_ne!(huge.parse::<Publish>().unwrap(), small.parse::<Publish>().unwrap());
+/// ```
+impl PartialEq for Message {
+    fn eq(&self, other: &Self) -> bool {
+        self.message_id() == other.message_id() && self.canonical_frame() == other.canonical_frame()
+    }
+}
+
+impl Eq for Message {}
+
+/// Hashes the canonical frame, the same for equal messages.
+impl Hash for Message {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.canonical_frame().hash(state);
+    }
+}
+
+/// Former name of [`Message`].
+#[deprecated(note = "renamed to `Message`")]
+pub type Events = Message;
+
+/// Same as [`Message::parse_message`].
+/// ```
+/// use wamp_helpers::messages::Message;
+///
+/// let message: Message = r#"[36, 5512315355, 4429313566, {}]"#.parse().unwrap();
+/// assert!(matches!(message, Message::Event(_)));
+/// ```
+impl FromStr for Message {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Message::parse_message(s)
+    }
+}
+
+impl Message {
+    /// Parse a standard message. With the `spec_strict` feature IDs outside `[1, 2^53]` are
+    /// rejected as well, see [`check_id_range`](crate::parse::check_id_range).
+    pub fn parse_message(raw_message_string: &str) -> Result<Self, Error> {
+        let message = Self::parse_standard(raw_message_string)?;
+        if crate::parse::SPEC_STRICT {
+            crate::parse::check_id_range(&message)?;
+        }
+        Ok(message)
+    }
+
+    fn parse_standard(raw_message_string: &str) -> Result<Self, Error> {
+        let mut data = json::parse(raw_message_string).map_err(Error::JsonError)?;
 
         let id = data.array_remove(0).as_u8();
 
@@ -1798,7 +2367,10 @@ impl Events {
                     let realm = validate_str_argument(data.array_remove(0))?;
                     let details = validate_dict_argument(data.array_remove(0))?;
 
-                    Ok(Self::Hello(Hello { realm, details }))
+                    Ok(Self::Hello(Hello {
+                        realm: realm.into(),
+                        details,
+                    }))
                 }
 
                 Welcome::ID => {
@@ -1811,7 +2383,10 @@ impl Events {
                 Abort::ID => {
                     let details = validate_dict_argument(data.array_remove(0))?;
                     let reason = validate_str_argument(data.array_remove(0))?;
-                    Ok(Self::Abort(Abort { details, reason }))
+                    Ok(Self::Abort(Abort {
+                        details,
+                        reason: reason.into(),
+                    }))
                 }
 
                 Challenge::ID => {
@@ -1833,11 +2408,15 @@ impl Events {
                 Goodbye::ID => {
                     let details = validate_dict_argument(data.array_remove(0))?;
                     let reason = validate_str_argument(data.array_remove(0))?;
-                    Ok(Self::Goodbye(Goodbye { details, reason }))
+                    Ok(Self::Goodbye(Goodbye {
+                        details,
+                        reason: reason.into(),
+                    }))
                 }
 
                 ErrorMessage::ID => {
                     let request_type = validate_u8_argument(data.array_remove(0))?;
+                    RequestType::try_from(request_type)?;
                     let request = validate_u64_argument(data.array_remove(0))?;
                     let details = validate_dict_argument(data.array_remove(0))?;
                     let error = validate_str_argument(data.array_remove(0))?;
@@ -1847,7 +2426,7 @@ impl Events {
                         request_type,
                         request,
                         details,
-                        error,
+                        error: error.into(),
                         args,
                         kwargs,
                     }))
@@ -1862,7 +2441,7 @@ impl Events {
                     Ok(Self::Publish(Publish {
                         request,
                         options,
-                        topic,
+                        topic: topic.into(),
                         args,
                         kwargs,
                     }))
@@ -1884,7 +2463,7 @@ impl Events {
                     Ok(Self::Subscribe(Subscribe {
                         request,
                         options,
-                        topic,
+                        topic: topic.into(),
                     }))
                 }
 
@@ -1908,7 +2487,8 @@ impl Events {
 
                 Unsubscribed::ID => {
                     let request = validate_u64_argument(data.array_remove(0))?;
-                    Ok(Self::Unsubscribed(Unsubscribed { request }))
+                    let details = validate_optional_dict(data.array_remove(0))?;
+                    Ok(Self::Unsubscribed(Unsubscribed { request, details }))
                 }
 
                 Event::ID => {
@@ -1935,7 +2515,7 @@ impl Events {
                     Ok(Self::Call(Call {
                         request,
                         options,
-                        procedure,
+                        procedure: procedure.into(),
                         args,
                         kwargs,
                     }))
@@ -1947,12 +2527,12 @@ impl Events {
                     Ok(Self::Cancel(Cancel { request, options }))
                 }
 
-                MessageResult::ID => {
+                WampResult::ID => {
                     let request = validate_u64_argument(data.array_remove(0))?;
                     let details = validate_dict_argument(data.array_remove(0))?;
                     let args = validate_args(data.array_remove(0))?;
                     let kwargs = validate_kwargs(data.array_remove(0))?;
-                    Ok(Self::MessageResult(MessageResult {
+                    Ok(Self::MessageResult(WampResult {
                         request,
                         details,
                         args,
@@ -1967,7 +2547,7 @@ impl Events {
                     Ok(Self::Register(Register {
                         request,
                         options,
-                        procedure,
+                        procedure: procedure.into(),
                     }))
                 }
 
@@ -1991,7 +2571,8 @@ impl Events {
 
                 Unregistered::ID => {
                     let request = validate_u64_argument(data.array_remove(0))?;
-                    Ok(Self::Unregistered(Unregistered { request }))
+                    let details = validate_optional_dict(data.array_remove(0))?;
+                    Ok(Self::Unregistered(Unregistered { request, details }))
                 }
 
                 Invocation::ID => {
@@ -2035,23 +2616,503 @@ impl Events {
         }
     }
 
-    pub fn is_basic(&self) -> bool {
+    /// Parse a message and run the additional checks enabled in `options`.
+    pub fn parse_message_with(
+        raw_message_string: &str,
+        options: &ParseOptions,
+    ) -> Result<Self, Error> {
+        if options.reject_duplicate_keys || options.reject_trailing_elements {
+            // The duplicate scan relies on the frame being valid JSON.
+            let data = json::parse(raw_message_string).map_err(Error::JsonError)?;
+            if options.reject_trailing_elements {
+                check_trailing_elements(&data)?;
+            }
+            if options.reject_duplicate_keys {
+                check_duplicate_keys(raw_message_string)?;
+            }
+        }
+
+        let mut event = Self::parse_message(raw_message_string)?;
+
+        if let (Some(budget), Some(details)) = (&options.dict_budget, event.details_mut()) {
+            budget.apply(details)?;
+        }
+        if options.reject_reserved_keys {
+            if let Some(details) = event.details() {
+                check_reserved_keys(details)?;
+            }
+        }
+        if options.spec_strict {
+            crate::parse::check_id_range(&event)?;
+        }
+        if options.validate_error_uris {
+            if let Self::ErrorMessage(error) = &event {
+                crate::uri::check_error_uri(&error.error, true)?;
+            }
+        }
+        Ok(event)
+    }
+
+    /// Serialize the wrapped message, see [`WampMessageTrait::to_json`].
+    pub fn to_json(self) -> Result<JsonValue, Error> {
         match self {
-            Self::Challenge(_challenge) => false,
-            Self::Authenticate(_authenticate) => false,
-            Self::Cancel(_cancel) => false,
-            Self::Interrupt(_interrupt) => false,
-            _ => true,
+            Self::Hello(message) => message.to_json(),
+            Self::Welcome(message) => message.to_json(),
+            Self::Abort(message) => message.to_json(),
+            Self::Challenge(message) => message.to_json(),
+            Self::Authenticate(message) => message.to_json(),
+            Self::Goodbye(message) => message.to_json(),
+            Self::ErrorMessage(message) => message.to_json(),
+            Self::Publish(message) => message.to_json(),
+            Self::Published(message) => message.to_json(),
+            Self::Subscribe(message) => message.to_json(),
+            Self::Subscribed(message) => message.to_json(),
+            Self::Unsubscribe(message) => message.to_json(),
+            Self::Unsubscribed(message) => message.to_json(),
+            Self::Event(message) => message.to_json(),
+            Self::Call(message) => message.to_json(),
+            Self::Cancel(message) => message.to_json(),
+            Self::MessageResult(message) => message.to_json(),
+            Self::Register(message) => message.to_json(),
+            Self::Registered(message) => message.to_json(),
+            Self::Unregister(message) => message.to_json(),
+            Self::Unregistered(message) => message.to_json(),
+            Self::Invocation(message) => message.to_json(),
+            Self::Interrupt(message) => message.to_json(),
+            Self::Yield(message) => message.to_json(),
         }
     }
 
-    pub fn is_advanced(&self) -> bool {
+    /// Direction table of the wrapped message, see [`WampMessageTrait::get_message_direction`].
+    pub fn get_message_direction(&self, role: Roles) -> &'static MessageDirection {
+        match self {
+            Self::Hello(_) => Hello::get_message_direction(role),
+            Self::Welcome(_) => Welcome::get_message_direction(role),
+            Self::Abort(_) => Abort::get_message_direction(role),
+            Self::Challenge(_) => Challenge::get_message_direction(role),
+            Self::Authenticate(_) => Authenticate::get_message_direction(role),
+            Self::Goodbye(_) => Goodbye::get_message_direction(role),
+            Self::ErrorMessage(_) => ErrorMessage::get_message_direction(role),
+            Self::Publish(_) => Publish::get_message_direction(role),
+            Self::Published(_) => Published::get_message_direction(role),
+            Self::Subscribe(_) => Subscribe::get_message_direction(role),
+            Self::Subscribed(_) => Subscribed::get_message_direction(role),
+            Self::Unsubscribe(_) => Unsubscribe::get_message_direction(role),
+            Self::Unsubscribed(_) => Unsubscribed::get_message_direction(role),
+            Self::Event(_) => Event::get_message_direction(role),
+            Self::Call(_) => Call::get_message_direction(role),
+            Self::Cancel(_) => Cancel::get_message_direction(role),
+            Self::MessageResult(_) => WampResult::get_message_direction(role),
+            Self::Register(_) => Register::get_message_direction(role),
+            Self::Registered(_) => Registered::get_message_direction(role),
+            Self::Unregister(_) => Unregister::get_message_direction(role),
+            Self::Unregistered(_) => Unregistered::get_message_direction(role),
+            Self::Invocation(_) => Invocation::get_message_direction(role),
+            Self::Interrupt(_) => Interrupt::get_message_direction(role),
+            Self::Yield(_) => Yield::get_message_direction(role),
+        }
+    }
+
+    /// The message code of the wrapped message.
+    pub fn message_id(&self) -> u8 {
+        match self {
+            Self::Hello(_) => Hello::ID,
+            Self::Welcome(_) => Welcome::ID,
+            Self::Abort(_) => Abort::ID,
+            Self::Challenge(_) => Challenge::ID,
+            Self::Authenticate(_) => Authenticate::ID,
+            Self::Goodbye(_) => Goodbye::ID,
+            Self::ErrorMessage(_) => ErrorMessage::ID,
+            Self::Publish(_) => Publish::ID,
+            Self::Published(_) => Published::ID,
+            Self::Subscribe(_) => Subscribe::ID,
+            Self::Subscribed(_) => Subscribed::ID,
+            Self::Unsubscribe(_) => Unsubscribe::ID,
+            Self::Unsubscribed(_) => Unsubscribed::ID,
+            Self::Event(_) => Event::ID,
+            Self::Call(_) => Call::ID,
+            Self::Cancel(_) => Cancel::ID,
+            Self::MessageResult(_) => WampResult::ID,
+            Self::Register(_) => Register::ID,
+            Self::Registered(_) => Registered::ID,
+            Self::Unregister(_) => Unregister::ID,
+            Self::Unregistered(_) => Unregistered::ID,
+            Self::Invocation(_) => Invocation::ID,
+            Self::Interrupt(_) => Interrupt::ID,
+            Self::Yield(_) => Yield::ID,
+        }
+    }
+
+    /// The `Request|id` of the message, for ERROR this is the id of the failed request.
+    /// # Examples
+    /// ```
+    /// use wamp_helpers::messages::Message;
+    /// let call = Message::parse_message(r#"[48, 7, {}, "com.example.add", [1, 2]]"#).unwrap();
+    /// assert_eq!(call.request_id(), Some(7));
+    /// assert_eq!(call.uri(), Some("com.example.add"));
+    /// assert_eq!(call.args().map(|args| args.len()), Some(2));
+    /// assert!(call.kwargs().is_none());
+    /// ```
+    pub fn request_id(&self) -> Option<WampId> {
+        match self {
+            Self::ErrorMessage(error) => Some(error.request),
+            Self::Publish(publish) => Some(publish.request),
+            Self::Published(published) => Some(published.request),
+            Self::Subscribe(subscribe) => Some(subscribe.request),
+            Self::Subscribed(subscribed) => Some(subscribed.request),
+            Self::Unsubscribe(unsubscribe) => Some(unsubscribe.request),
+            Self::Unsubscribed(unsubscribed) => Some(unsubscribed.request),
+            Self::Call(call) => Some(call.request),
+            Self::Cancel(cancel) => Some(cancel.request),
+            Self::MessageResult(result) => Some(result.request),
+            Self::Register(register) => Some(register.request),
+            Self::Registered(registered) => Some(registered.request),
+            Self::Unregister(unregister) => Some(unregister.request),
+            Self::Unregistered(unregistered) => Some(unregistered.request),
+            Self::Invocation(invocation) => Some(invocation.request),
+            Self::Interrupt(interrupt) => Some(interrupt.request),
+            Self::Yield(yield_message) => Some(yield_message.request),
+            Self::Hello(_)
+            | Self::Welcome(_)
+            | Self::Abort(_)
+            | Self::Challenge(_)
+            | Self::Authenticate(_)
+            | Self::Goodbye(_)
+            | Self::Event(_) => None,
+        }
+    }
+
+    /// Every ID the message carries: session, request, subscription, publication and
+    /// registration ids.
+    pub fn ids(&self) -> Vec<WampId> {
+        match self {
+            Self::Welcome(welcome) => vec![welcome.session],
+            Self::Published(published) => vec![published.request, published.publication],
+            Self::Subscribed(subscribed) => vec![subscribed.request, subscribed.subscription],
+            Self::Unsubscribe(unsubscribe) => vec![unsubscribe.request, unsubscribe.subscription],
+            Self::Event(event) => vec![event.subscription, event.publication],
+            Self::Registered(registered) => vec![registered.request, registered.registration],
+            Self::Unregister(unregister) => vec![unregister.request, unregister.registration],
+            Self::Invocation(invocation) => vec![invocation.request, invocation.registration],
+            _ => self.request_id().into_iter().collect(),
+        }
+    }
+
+    /// The URI carried by the message: realm, reason, error, topic or procedure.
+    pub fn uri(&self) -> Option<&str> {
+        match self {
+            Self::Hello(hello) => Some(&hello.realm),
+            Self::Abort(abort) => Some(&abort.reason),
+            Self::Goodbye(goodbye) => Some(&goodbye.reason),
+            Self::ErrorMessage(error) => Some(&error.error),
+            Self::Publish(publish) => Some(&publish.topic),
+            Self::Subscribe(subscribe) => Some(&subscribe.topic),
+            Self::Call(call) => Some(&call.procedure),
+            Self::Register(register) => Some(&register.procedure),
+            _ => None,
+        }
+    }
+
+    /// The positional payload of the message, if present.
+    pub fn args(&self) -> Option<&Args> {
+        match self {
+            Self::ErrorMessage(error) => error.args.as_ref(),
+            Self::Publish(publish) => publish.args.as_ref(),
+            Self::Event(event) => event.args.as_ref(),
+            Self::Call(call) => call.args.as_ref(),
+            Self::MessageResult(result) => result.args.as_ref(),
+            Self::Invocation(invocation) => invocation.args.as_ref(),
+            Self::Yield(yield_message) => yield_message.args.as_ref(),
+            _ => None,
+        }
+    }
+
+    /// The keyword payload of the message, if present.
+    pub fn kwargs(&self) -> Option<&Kwargs> {
+        match self {
+            Self::ErrorMessage(error) => error.kwargs.as_ref(),
+            Self::Publish(publish) => publish.kwargs.as_ref(),
+            Self::Event(event) => event.kwargs.as_ref(),
+            Self::Call(call) => call.kwargs.as_ref(),
+            Self::MessageResult(result) => result.kwargs.as_ref(),
+            Self::Invocation(invocation) => invocation.kwargs.as_ref(),
+            Self::Yield(yield_message) => yield_message.kwargs.as_ref(),
+            _ => None,
+        }
+    }
+
+    /// The Details or Options dictionary of the message, if it carries one.
+    pub fn details(&self) -> Option<&Details> {
         match self {
-            Self::Challenge(_challenge) => true,
-            Self::Authenticate(_authenticate) => true,
-            Self::Cancel(_cancel) => true,
-            Self::Interrupt(_interrupt) => true,
-            _ => false,
+            Self::Hello(hello) => Some(&hello.details),
+            Self::Welcome(welcome) => Some(&welcome.details),
+            Self::Abort(abort) => Some(&abort.details),
+            Self::Challenge(challenge) => Some(&challenge.details),
+            Self::Authenticate(authenticate) => Some(&authenticate.details),
+            Self::Goodbye(goodbye) => Some(&goodbye.details),
+            Self::ErrorMessage(error) => Some(&error.details),
+            Self::Publish(publish) => Some(&publish.options),
+            Self::Subscribe(subscribe) => Some(&subscribe.options),
+            Self::Event(event) => Some(&event.details),
+            Self::Call(call) => Some(&call.options),
+            Self::Cancel(cancel) => Some(&cancel.options),
+            Self::MessageResult(result) => Some(&result.details),
+            Self::Register(register) => Some(&register.options),
+            Self::Invocation(invocation) => Some(&invocation.details),
+            Self::Interrupt(interrupt) => Some(&interrupt.options),
+            Self::Yield(yield_message) => Some(&yield_message.options),
+            Self::Unsubscribed(unsubscribed) => unsubscribed.details.as_ref(),
+            Self::Unregistered(unregistered) => unregistered.details.as_ref(),
+            Self::Published(_)
+            | Self::Subscribed(_)
+            | Self::Unsubscribe(_)
+            | Self::Registered(_)
+            | Self::Unregister(_) => None,
         }
     }
+
+    /// Mutable access to the dictionary [`details`](Self::details) returns.
+    pub fn details_mut(&mut self) -> Option<&mut Details> {
+        match self {
+            Self::Hello(hello) => Some(&mut hello.details),
+            Self::Welcome(welcome) => Some(&mut welcome.details),
+            Self::Abort(abort) => Some(&mut abort.details),
+            Self::Challenge(challenge) => Some(&mut challenge.details),
+            Self::Authenticate(authenticate) => Some(&mut authenticate.details),
+            Self::Goodbye(goodbye) => Some(&mut goodbye.details),
+            Self::ErrorMessage(error) => Some(&mut error.details),
+            Self::Publish(publish) => Some(&mut publish.options),
+            Self::Subscribe(subscribe) => Some(&mut subscribe.options),
+            Self::Event(event) => Some(&mut event.details),
+            Self::Call(call) => Some(&mut call.options),
+            Self::Cancel(cancel) => Some(&mut cancel.options),
+            Self::MessageResult(result) => Some(&mut result.details),
+            Self::Register(register) => Some(&mut register.options),
+            Self::Invocation(invocation) => Some(&mut invocation.details),
+            Self::Interrupt(interrupt) => Some(&mut interrupt.options),
+            Self::Yield(yield_message) => Some(&mut yield_message.options),
+            Self::Unsubscribed(unsubscribed) => unsubscribed.details.as_mut(),
+            Self::Unregistered(unregistered) => unregistered.details.as_mut(),
+            Self::Published(_)
+            | Self::Subscribed(_)
+            | Self::Unsubscribe(_)
+            | Self::Registered(_)
+            | Self::Unregister(_) => None,
+        }
+    }
+
+    /// Mutable access to the dictionary, `args` and `kwargs` of the messages carrying a
+    /// payload: ERROR, PUBLISH, EVENT, CALL, RESULT, INVOCATION and YIELD.
+    pub fn payload_mut(
+        &mut self,
+    ) -> Option<(&mut Details, &mut Option<Args>, &mut Option<Kwargs>)> {
+        match self {
+            Self::ErrorMessage(error) => {
+                Some((&mut error.details, &mut error.args, &mut error.kwargs))
+            }
+            Self::Publish(publish) => {
+                Some((&mut publish.options, &mut publish.args, &mut publish.kwargs))
+            }
+            Self::Event(event) => Some((&mut event.details, &mut event.args, &mut event.kwargs)),
+            Self::Call(call) => Some((&mut call.options, &mut call.args, &mut call.kwargs)),
+            Self::MessageResult(result) => {
+                Some((&mut result.details, &mut result.args, &mut result.kwargs))
+            }
+            Self::Invocation(invocation) => Some((
+                &mut invocation.details,
+                &mut invocation.args,
+                &mut invocation.kwargs,
+            )),
+            Self::Yield(yield_message) => Some((
+                &mut yield_message.options,
+                &mut yield_message.args,
+                &mut yield_message.kwargs,
+            )),
+            _ => None,
+        }
+    }
+
+    /// Key to sort captured messages by: timestamp when the Details/Options carry one, then
+    /// session, request ID, the exchange and the phase in it, so a request sorts before its
+    /// CANCEL and its response, an ERROR included.
+    /// # Examples
+    /// ```
+    /// use wamp_helpers::messages::Message;
+    ///
+    /// let mut log: Vec<Message> = [
+    ///     r#"[50, 7, {"x_timestamp": 1700000000250}, [3]]"#,
+    ///     r#"[48, 7, {"x_timestamp": 1700000000100}, "com.example.add", [1, 2]]"#,
+    ///     r#"[48, 8, {"x_timestamp": 1700000000100}, "com.example.add", [2, 2]]"#,
+    ///     r#"[50, 8, {"x_timestamp": 1700000000250}, [4]]"#,
+    /// ]
+    /// .into_iter()
+    /// .map(|frame| Message::parse_message(frame).unwrap())
+    /// .collect();
+    ///
+    /// log.sort_by_cached_key(Message::sort_key);
+    /// let order: Vec<_> = log
+    ///     .iter()
+    ///     .map(|message| (message.message_id(), message.request_id().unwrap()))
+    ///     .collect();
+    /// assert_eq!(order, [(48, 7), (48, 8), (50, 7), (50, 8)]);
+    ///
+    /// // Without timestamps, the ERROR still follows the SUBSCRIBE it answers.
+    /// let error = Message::parse_message(r#"[8, 32, 3, {}, "wamp.error.not_authorized"]"#).unwrap();
+    /// let subscribe = Message::parse_message(r#"[32, 3, {}, "com.example.topic"]"#).unwrap();
+    /// assert!(subscribe.sort_key() < error.sort_key());
+    /// ```
+    pub fn sort_key(&self) -> SortKey {
+        let (exchange, phase) = self.exchange();
+        SortKey {
+            timestamp: self
+                .details()
+                .and_then(|details| details[TIMESTAMP].as_u64()),
+            session: match self {
+                Self::Welcome(welcome) => Some(welcome.session),
+                _ => None,
+            },
+            request: self.request_id(),
+            exchange,
+            phase,
+            message_type: self.message_id(),
+            ids: self.ids(),
+        }
+    }
+
+    /// Type of the request opening the exchange of the message, and its phase in it, see
+    /// [`SortKey`].
+    fn exchange(&self) -> (u8, u8) {
+        match self {
+            Self::ErrorMessage(error) => (error.request_type, 2),
+            Self::Published(_) => (Publish::ID, 2),
+            Self::Subscribed(_) => (Subscribe::ID, 2),
+            Self::Unsubscribed(_) => (Unsubscribe::ID, 2),
+            Self::Cancel(_) => (Call::ID, 1),
+            Self::MessageResult(_) => (Call::ID, 2),
+            Self::Registered(_) => (Register::ID, 2),
+            Self::Unregistered(_) => (Unregister::ID, 2),
+            Self::Interrupt(_) => (Invocation::ID, 1),
+            Self::Yield(_) => (Invocation::ID, 2),
+            _ => (self.message_id(), 0),
+        }
+    }
+
+    /// Estimated size of the message encoded with `serializer`, computed from its fields
+    /// without encoding it, e.g. to size a buffer or refuse an oversized message up front.
+    ///
+    /// Exact for messages without floats, see [`size_hint`](crate::transcode::size_hint).
+    /// # Examples
+    /// ```
+    /// use wamp_helpers::acceptor::Serializer;
+    /// use wamp_helpers::messages::Message;
+    ///
+    /// let call = Message::parse_message(r#"[48, 7, {}, "com.example.add", [1, 2]]"#).unwrap();
+    /// let hint = call.encoded_size_hint(Serializer::Json);
+    /// assert_eq!(hint, call.to_json().unwrap().dump().len());
+    /// ```
+    pub fn encoded_size_hint(&self, serializer: Serializer) -> usize {
+        let ids = self.ids();
+        let mut elements = 1 + ids.len();
+        let mut size = integer_size_hint(self.message_id().into(), serializer)
+            + ids
+                .into_iter()
+                .map(|id| integer_size_hint(id, serializer))
+                .sum::<usize>();
+        if let Self::ErrorMessage(error) = self {
+            elements += 1;
+            size += integer_size_hint(error.request_type.into(), serializer);
+        }
+        let text = match self {
+            Self::Challenge(challenge) => Some(challenge.authmethod.as_str()),
+            Self::Authenticate(authenticate) => Some(authenticate.signature.as_str()),
+            _ => self.uri(),
+        };
+        if let Some(text) = text {
+            elements += 1;
+            size += str_size_hint(text, serializer);
+        }
+        if let Some(details) = self.details() {
+            elements += 1;
+            size += json_size_hint(details, serializer);
+        }
+        let kwargs = self.kwargs();
+        // An empty list stands in for absent arguments when keyword arguments follow.
+        if let Some(args) = self.args().map(Vec::as_slice).or(kwargs.map(|_| &[][..])) {
+            elements += 1;
+            size += container_size_hint(args.len(), false, serializer)
+                + args
+                    .iter()
+                    .map(|arg| size_hint(arg, serializer))
+                    .sum::<usize>();
+        }
+        if let Some(kwargs) = kwargs {
+            elements += 1;
+            size += container_size_hint(kwargs.len(), true, serializer)
+                + kwargs
+                    .iter()
+                    .map(|(key, value)| {
+                        str_size_hint(key, serializer) + size_hint(value, serializer)
+                    })
+                    .sum::<usize>();
+        }
+        size + container_size_hint(elements, false, serializer)
+    }
+
+    /// Field-by-field differences from `other`, descending into dictionaries and lists, for
+    /// readable assertion failures. Messages of different types only report their `Code`.
+    /// # Examples
+    /// ```
+    /// use wamp_helpers::messages::Message;
+    ///
+    /// let expected = Message::parse_message(
+    ///     r#"[48, 7, {"timeout": 1000, "receive_progress": true}, "com.example.add", [1, 2]]"#,
+    /// )
+    /// .unwrap();
+    /// let actual = Message::parse_message(
+    ///     r#"[48, 7, {"timeout": 2000}, "com.example.add", [1, 3]]"#,
+    /// )
+    /// .unwrap();
+    ///
+    /// let diff = expected.diff(&actual);
+    /// assert_eq!(
+    ///     diff.to_string(),
+    ///     "Options.timeout: 1000 != 2000\n\
+    ///      Options.receive_progress: true != <missing>\n\
+    ///      Arguments[1]: 2 != 3\n"
+    /// );
+    /// assert!(expected.diff(&expected).is_empty());
+    /// ```
+    pub fn diff(&self, other: &Message) -> MessageDiff {
+        crate::diff::diff(self, other)
+    }
+
+    /// Parse a message, falling back to the extension type `T` (usually declared with
+    /// [`wamp_message!`](crate::wamp_message)) when the message code is not a standard one.
+    /// With the `spec_strict` feature extension messages are always refused.
+    pub fn parse_with_extension<T>(raw_message_string: &str) -> Result<Extended<T>, Error>
+    where
+        T: WampMessageTrait + FromStr<Err = Error>,
+    {
+        match Self::parse_message(raw_message_string) {
+            Ok(event) => Ok(Extended::Standard(event)),
+            Err(Error::ExtensionMessage) if !crate::parse::SPEC_STRICT => {
+                Ok(Extended::Extension(T::from_str(raw_message_string)?))
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    pub fn is_basic(&self) -> bool {
+        !matches!(
+            self,
+            Self::Challenge(_) | Self::Authenticate(_) | Self::Cancel(_) | Self::Interrupt(_)
+        )
+    }
+
+    pub fn is_advanced(&self) -> bool {
+        matches!(
+            self,
+            Self::Challenge(_) | Self::Authenticate(_) | Self::Cancel(_) | Self::Interrupt(_)
+        )
+    }
 }
@@ -0,0 +1,494 @@
+use crate::arity::arity;
+use crate::messages::{
+    Call, ErrorMessage, Goodbye, GoodbyeDetails, Kwargs, Message, Publish, Uri, WampId, WampResult,
+};
+use crate::value::WampValue;
+use json::JsonValue;
+
+pub const SESSION_COUNT: &str = "wamp.session.count";
+pub const SESSION_LIST: &str = "wamp.session.list";
+pub const SESSION_GET: &str = "wamp.session.get";
+pub const SUBSCRIPTION_LOOKUP: &str = "wamp.subscription.lookup";
+pub const SUBSCRIPTION_GET: &str = "wamp.subscription.get";
+pub const SUBSCRIPTION_LIST_SUBSCRIBERS: &str = "wamp.subscription.list_subscribers";
+pub const SUBSCRIPTION_COUNT_SUBSCRIBERS: &str = "wamp.subscription.count_subscribers";
+pub const REGISTRATION_LOOKUP: &str = "wamp.registration.lookup";
+pub const REGISTRATION_GET: &str = "wamp.registration.get";
+pub const REGISTRATION_LIST_CALLEES: &str = "wamp.registration.list_callees";
+pub const REGISTRATION_COUNT_CALLEES: &str = "wamp.registration.count_callees";
+
+pub const NO_SUCH_SESSION: &str = "wamp.error.no_such_session";
+pub const NO_SUCH_SUBSCRIPTION: &str = "wamp.error.no_such_subscription";
+pub const NO_SUCH_REGISTRATION: &str = "wamp.error.no_such_registration";
+pub const INVALID_ARGUMENT: &str = "wamp.error.invalid_argument";
+
+/// Read-only view of a realm's state, implemented by the router to answer meta procedures.
+///
+/// Dictionaries returned by the `*_details` methods are passed to the caller as they are, the
+/// spec lists the keys expected for each of them.
+pub trait RealmIntrospection {
+    fn session_ids(&self) -> Vec<WampId>;
+    fn session_details(&self, session: WampId) -> Option<JsonValue>;
+    /// Subscription id for `topic` under the `match` policy, `"exact"` when absent.
+    fn subscription_lookup(&self, topic: &str, match_policy: &str) -> Option<WampId>;
+    fn subscription_details(&self, subscription: WampId) -> Option<JsonValue>;
+    fn subscribers(&self, subscription: WampId) -> Option<Vec<WampId>>;
+    fn registration_lookup(&self, procedure: &str, match_policy: &str) -> Option<WampId>;
+    fn registration_details(&self, registration: WampId) -> Option<JsonValue>;
+    fn callees(&self, registration: WampId) -> Option<Vec<WampId>>;
+}
+
+/// Whether `procedure` lies in the reserved `wamp.` namespace routers answer themselves.
+pub fn is_meta_procedure(procedure: &str) -> bool {
+    procedure.starts_with("wamp.")
+}
+
+fn id_argument(call: &Call, index: usize) -> Option<WampId> {
+    match call.args.as_ref()?.get(index)? {
+        WampValue::Integer(id) if *id >= 0 => Some(*id as WampId),
+        _ => None,
+    }
+}
+
+fn str_argument(call: &Call, index: usize) -> Option<&str> {
+    match call.args.as_ref()?.get(index)? {
+        WampValue::String(value) => Some(value),
+        _ => None,
+    }
+}
+
+/// `match` policy of a lookup, from the optional options dictionary after the URI.
+fn match_policy(call: &Call) -> &str {
+    match call.args.as_ref().and_then(|args| args.get(1)) {
+        Some(WampValue::Dict(options)) => match options.get("match") {
+            Some(WampValue::String(policy)) => policy,
+            _ => "exact",
+        },
+        _ => "exact",
+    }
+}
+
+fn ids(ids: Vec<WampId>) -> WampValue {
+    WampValue::List(
+        ids.into_iter()
+            .map(|id| WampValue::Integer(id as i64))
+            .collect(),
+    )
+}
+
+fn optional_id(id: Option<WampId>) -> WampValue {
+    id.map_or(WampValue::Null, |id| WampValue::Integer(id as i64))
+}
+
+/// Answer a CALL to one of the session, subscription or registration meta procedures,
+/// `None` when `call` targets another procedure.
+/// # Examples
+/// ```
+/// use json::JsonValue;
+/// use wamp_helpers::messages::{Call, Message, WampId};
+/// use wamp_helpers::meta::{handle_meta_call, RealmIntrospection};
+///
+/// struct Realm;
+///
+/// impl RealmIntrospection for Realm {
+///     fn session_ids(&self) -> Vec<WampId> { vec![71, 72] }
+///     fn session_details(&self, session: WampId) -> Option<JsonValue> {
+///         (session == 71).then(|| json::object! { session: 71, authid: "joe" })
+///     }
+///     fn subscription_lookup(&self, _: &str, _: &str) -> Option<WampId> { None }
+///     fn subscription_details(&self, _: WampId) -> Option<JsonValue> { None }
+///     fn subscribers(&self, _: WampId) -> Option<Vec<WampId>> { None }
+///     fn registration_lookup(&self, _: &str, _: &str) -> Option<WampId> { None }
+///     fn registration_details(&self, _: WampId) -> Option<JsonValue> { None }
+///     fn callees(&self, _: WampId) -> Option<Vec<WampId>> { None }
+/// }
+///
+/// let count: Call = r#"[48, 1, {}, "wamp.session.count"]"#.parse().unwrap();
+/// let answer = handle_meta_call(&count, &Realm).unwrap();
+/// assert_eq!(answer.to_json().unwrap().dump(), "[50,1,{},[2]]");
+///
+/// let get: Call = r#"[48, 2, {}, "wamp.session.get", [99]]"#.parse().unwrap();
+/// match handle_meta_call(&get, &Realm).unwrap() {
+///     Message::ErrorMessage(error) => assert_eq!(error.error, "wamp.error.no_such_session"),
+///     other => panic!("{:?}", other),
+/// }
+///
+/// let other: Call = r#"[48, 3, {}, "com.example.add"]"#.parse().unwrap();
+/// assert!(handle_meta_call(&other, &Realm).is_none());
+/// ```
+pub fn handle_meta_call(call: &Call, realm: &impl RealmIntrospection) -> Option<Message> {
+    let result = |value: WampValue| {
+        Message::MessageResult(WampResult {
+            request: call.request,
+            details: json::object! {},
+            args: Some(vec![value]),
+            kwargs: None,
+        })
+    };
+    let error =
+        |uri: &str| Message::ErrorMessage(ErrorMessage::for_call(call, uri.to_string().into()));
+    let with_id =
+        |missing: &str, lookup: &dyn Fn(WampId) -> Option<WampValue>| match id_argument(call, 0) {
+            None => error(INVALID_ARGUMENT),
+            Some(id) => lookup(id).map_or_else(|| error(missing), result),
+        };
+
+    let answer = match call.procedure.as_str() {
+        SESSION_COUNT => result(WampValue::Integer(realm.session_ids().len() as i64)),
+        SESSION_LIST => result(ids(realm.session_ids())),
+        SESSION_GET => with_id(NO_SUCH_SESSION, &|session| {
+            realm.session_details(session).map(WampValue::from)
+        }),
+        SUBSCRIPTION_LOOKUP => match str_argument(call, 0) {
+            Some(topic) => result(optional_id(
+                realm.subscription_lookup(topic, match_policy(call)),
+            )),
+            None => error(INVALID_ARGUMENT),
+        },
+        SUBSCRIPTION_GET => with_id(NO_SUCH_SUBSCRIPTION, &|subscription| {
+            realm
+                .subscription_details(subscription)
+                .map(WampValue::from)
+        }),
+        SUBSCRIPTION_LIST_SUBSCRIBERS => with_id(NO_SUCH_SUBSCRIPTION, &|subscription| {
+            realm.subscribers(subscription).map(ids)
+        }),
+        SUBSCRIPTION_COUNT_SUBSCRIBERS => with_id(NO_SUCH_SUBSCRIPTION, &|subscription| {
+            realm
+                .subscribers(subscription)
+                .map(|subscribers| WampValue::Integer(subscribers.len() as i64))
+        }),
+        REGISTRATION_LOOKUP => match str_argument(call, 0) {
+            Some(procedure) => result(optional_id(
+                realm.registration_lookup(procedure, match_policy(call)),
+            )),
+            None => error(INVALID_ARGUMENT),
+        },
+        REGISTRATION_GET => with_id(NO_SUCH_REGISTRATION, &|registration| {
+            realm
+                .registration_details(registration)
+                .map(WampValue::from)
+        }),
+        REGISTRATION_LIST_CALLEES => with_id(NO_SUCH_REGISTRATION, &|registration| {
+            realm.callees(registration).map(ids)
+        }),
+        REGISTRATION_COUNT_CALLEES => with_id(NO_SUCH_REGISTRATION, &|registration| {
+            realm
+                .callees(registration)
+                .map(|callees| WampValue::Integer(callees.len() as i64))
+        }),
+        _ => return None,
+    };
+    Some(answer)
+}
+
+pub const SESSION_KILL: &str = "wamp.session.kill";
+pub const SESSION_KILL_BY_AUTHID: &str = "wamp.session.kill_by_authid";
+pub const SESSION_KILL_BY_AUTHROLE: &str = "wamp.session.kill_by_authrole";
+/// Default GOODBYE reason sent to killed sessions.
+pub const CLOSE_KILLED: &str = "wamp.close.killed";
+
+/// Which sessions a kill meta procedure targets.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KillTarget {
+    Session(WampId),
+    Authid(String),
+    Authrole(String),
+}
+
+/// A parsed call to `wamp.session.kill`, `kill_by_authid` or `kill_by_authrole`.
+/// # Examples
+/// ```
+/// use json::JsonValue;
+/// use wamp_helpers::messages::{Call, WampId, WampMessageTrait};
+/// use wamp_helpers::meta::{kill_by_authid, KillRequest, KillTarget, RealmIntrospection};
+///
+/// struct Realm;
+///
+/// impl RealmIntrospection for Realm {
+///     fn session_ids(&self) -> Vec<WampId> { vec![71, 72, 73] }
+///     fn session_details(&self, session: WampId) -> Option<JsonValue> {
+///         Some(json::object! { session: session, authid: if session == 73 { "admin" } else { "joe" } })
+///     }
+///     fn subscription_lookup(&self, _: &str, _: &str) -> Option<WampId> { None }
+///     fn subscription_details(&self, _: WampId) -> Option<JsonValue> { None }
+///     fn subscribers(&self, _: WampId) -> Option<Vec<WampId>> { None }
+///     fn registration_lookup(&self, _: &str, _: &str) -> Option<WampId> { None }
+///     fn registration_details(&self, _: WampId) -> Option<JsonValue> { None }
+///     fn callees(&self, _: WampId) -> Option<Vec<WampId>> { None }
+/// }
+///
+/// // The admin session 73 kills every session of "joe".
+/// let call = kill_by_authid(1, "joe", None, Some("maintenance"));
+/// let request = KillRequest::from_call(&call).unwrap().unwrap();
+/// assert_eq!(request.target, KillTarget::Authid("joe".to_string()));
+///
+/// let killed = request.targets(73, &Realm);
+/// assert_eq!(killed, [71, 72]);
+/// let goodbye = request.goodbye().to_json().unwrap().dump();
+/// assert_eq!(goodbye, r#"[6,{"message":"maintenance"},"wamp.close.killed"]"#);
+/// assert_eq!(request.result(&call, &killed).to_json().unwrap().dump(), "[50,1,{},[[71,72]]]");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KillRequest {
+    pub target: KillTarget,
+    /// GOODBYE reason, [`CLOSE_KILLED`] unless the caller gave one.
+    pub reason: Uri,
+    pub message: Option<String>,
+}
+
+impl KillRequest {
+    /// Parse a kill call, `None` for other procedures and `Some(Err(..))` with the ERROR to
+    /// answer for malformed arguments.
+    pub fn from_call(call: &Call) -> Option<Result<Self, Message>> {
+        let invalid =
+            || Message::ErrorMessage(ErrorMessage::for_call(call, INVALID_ARGUMENT.into()));
+        let target = match call.procedure.as_str() {
+            SESSION_KILL => id_argument(call, 0).map(KillTarget::Session),
+            SESSION_KILL_BY_AUTHID => {
+                str_argument(call, 0).map(|authid| KillTarget::Authid(authid.to_string()))
+            }
+            SESSION_KILL_BY_AUTHROLE => {
+                str_argument(call, 0).map(|authrole| KillTarget::Authrole(authrole.to_string()))
+            }
+            _ => return None,
+        };
+        let Some(target) = target else {
+            return Some(Err(invalid()));
+        };
+
+        let kwargs = call.kwargs.as_ref();
+        let text = |key: &str| match kwargs.and_then(|kwargs| kwargs.get(key)) {
+            Some(WampValue::String(value)) => Some(value.clone()),
+            _ => None,
+        };
+        Some(Ok(KillRequest {
+            target,
+            reason: text("reason").map_or(CLOSE_KILLED.into(), Uri::from),
+            message: text("message"),
+        }))
+    }
+
+    /// Sessions to kill, never including the `caller` session itself.
+    pub fn targets(&self, caller: WampId, realm: &impl RealmIntrospection) -> Vec<WampId> {
+        let matches = |session: WampId, key: &str, expected: &str| {
+            realm
+                .session_details(session)
+                .is_some_and(|details| details[key] == expected)
+        };
+        match &self.target {
+            KillTarget::Session(session) if *session != caller => realm
+                .session_ids()
+                .into_iter()
+                .filter(|id| id == session)
+                .collect(),
+            KillTarget::Session(_) => Vec::new(),
+            KillTarget::Authid(authid) => realm
+                .session_ids()
+                .into_iter()
+                .filter(|session| *session != caller && matches(*session, "authid", authid))
+                .collect(),
+            KillTarget::Authrole(authrole) => realm
+                .session_ids()
+                .into_iter()
+                .filter(|session| *session != caller && matches(*session, "authrole", authrole))
+                .collect(),
+        }
+    }
+
+    /// GOODBYE to send to every killed session before detaching it.
+    pub fn goodbye(&self) -> Goodbye {
+        Goodbye::new(
+            self.reason.clone(),
+            GoodbyeDetails {
+                message: self.message.clone(),
+                ..GoodbyeDetails::default()
+            },
+        )
+    }
+
+    /// Answer to the kill call: `wamp.session.kill` fails with `no_such_session` when nothing
+    /// was killed, the `by_*` variants return the list of killed sessions.
+    pub fn result(&self, call: &Call, killed: &[WampId]) -> Message {
+        match self.target {
+            KillTarget::Session(_) if killed.is_empty() => {
+                Message::ErrorMessage(ErrorMessage::for_call(call, NO_SUCH_SESSION.into()))
+            }
+            KillTarget::Session(_) => Message::MessageResult(WampResult {
+                request: call.request,
+                details: json::object! {},
+                args: None,
+                kwargs: None,
+            }),
+            _ => Message::MessageResult(WampResult {
+                request: call.request,
+                details: json::object! {},
+                args: Some(vec![ids(killed.to_vec())]),
+                kwargs: None,
+            }),
+        }
+    }
+}
+
+fn kill_call(
+    request: WampId,
+    procedure: &str,
+    target: WampValue,
+    reason: Option<&str>,
+    message: Option<&str>,
+) -> Call {
+    let mut kwargs = Kwargs::new();
+    if let Some(reason) = reason {
+        kwargs.insert("reason".to_string(), reason.into());
+    }
+    if let Some(message) = message {
+        kwargs.insert("message".to_string(), message.into());
+    }
+    Call {
+        request,
+        options: json::object! {},
+        procedure: procedure.to_string().into(),
+        args: Some(vec![target]),
+        kwargs: (!kwargs.is_empty()).then_some(kwargs),
+    }
+}
+
+/// CALL killing one session.
+pub fn kill_session(
+    request: WampId,
+    session: WampId,
+    reason: Option<&str>,
+    message: Option<&str>,
+) -> Call {
+    let target = WampValue::Integer(session as i64);
+    kill_call(request, SESSION_KILL, target, reason, message)
+}
+
+/// CALL killing every session authenticated as `authid`.
+pub fn kill_by_authid(
+    request: WampId,
+    authid: &str,
+    reason: Option<&str>,
+    message: Option<&str>,
+) -> Call {
+    kill_call(
+        request,
+        SESSION_KILL_BY_AUTHID,
+        authid.into(),
+        reason,
+        message,
+    )
+}
+
+/// CALL killing every session with role `authrole`.
+pub fn kill_by_authrole(
+    request: WampId,
+    authrole: &str,
+    reason: Option<&str>,
+    message: Option<&str>,
+) -> Call {
+    kill_call(
+        request,
+        SESSION_KILL_BY_AUTHROLE,
+        authrole.into(),
+        reason,
+        message,
+    )
+}
+
+/// Sessions reported as killed in the RESULT of a `kill_by_*` call.
+pub fn killed_sessions(result: &WampResult) -> Vec<WampId> {
+    match result.args.as_ref().and_then(|args| args.first()) {
+        Some(WampValue::List(sessions)) => sessions
+            .iter()
+            .filter_map(|session| match session {
+                WampValue::Integer(session) if *session >= 0 => Some(*session as WampId),
+                _ => None,
+            })
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Meta topic the router publishes to when a callee is added to a registration, with the
+/// session and registration ids as arguments.
+pub const REGISTRATION_ON_REGISTER: &str = "wamp.registration.on_register";
+/// Meta topic the router publishes to when a callee is removed from a registration.
+pub const REGISTRATION_ON_UNREGISTER: &str = "wamp.registration.on_unregister";
+
+/// PUBLISH announcing that the callee `session` stopped taking invocations of
+/// `registration` because it was paused, or takes them again because it was resumed. The
+/// `paused` keyword argument tells these events from actual (un)registrations.
+/// # Examples
+/// ```
+/// use wamp_helpers::meta::{registration_pause_publication, REGISTRATION_ON_UNREGISTER};
+/// use wamp_helpers::value::WampValue;
+///
+/// let publish = registration_pause_publication(1, 9129137332, 7, true);
+/// assert_eq!(publish.topic, REGISTRATION_ON_UNREGISTER);
+/// assert_eq!(publish.args.unwrap(), [WampValue::Integer(9129137332), WampValue::Integer(7)]);
+/// assert_eq!(publish.kwargs.unwrap()["paused"], WampValue::Bool(true));
+/// ```
+pub fn registration_pause_publication(
+    request: WampId,
+    session: WampId,
+    registration: WampId,
+    paused: bool,
+) -> Publish {
+    let mut kwargs = Kwargs::new();
+    kwargs.insert("paused".to_string(), paused.into());
+    Publish {
+        request,
+        options: json::object! {},
+        topic: if paused {
+            REGISTRATION_ON_UNREGISTER
+        } else {
+            REGISTRATION_ON_REGISTER
+        }
+        .into(),
+        args: Some(vec![
+            WampValue::Integer(session as i64),
+            WampValue::Integer(registration as i64),
+        ]),
+        kwargs: Some(kwargs),
+    }
+}
+
+/// Meta topic the router publishes to when a session sends a message its role may not
+/// send, see [`RealmConfig::check_message`](crate::config::RealmConfig::check_message).
+pub const SESSION_ON_MESSAGE_DENIED: &str = "wamp.session.on_message_denied";
+
+/// PUBLISH announcing that `session`, authenticated as `authrole`, sent a message of type
+/// `message_type` it is not allowed to send. The session id is the argument, the role and
+/// the message name are keyword arguments.
+/// # Examples
+/// ```
+/// use wamp_helpers::meta::{message_denied_publication, SESSION_ON_MESSAGE_DENIED};
+///
+/// let publish = message_denied_publication(1, 9129137332, "frontend", 64);
+/// assert_eq!(publish.topic, SESSION_ON_MESSAGE_DENIED);
+/// assert_eq!(publish.kwargs.unwrap()["message"].as_str(), Some("REGISTER"));
+/// ```
+pub fn message_denied_publication(
+    request: WampId,
+    session: WampId,
+    authrole: &str,
+    message_type: u8,
+) -> Publish {
+    let mut kwargs = Kwargs::new();
+    kwargs.insert("authrole".to_string(), authrole.into());
+    kwargs.insert(
+        "message".to_string(),
+        match arity(message_type) {
+            Some(arity) => arity.name.into(),
+            None => WampValue::Integer(message_type.into()),
+        },
+    );
+    Publish {
+        request,
+        options: json::object! {},
+        topic: SESSION_ON_MESSAGE_DENIED.into(),
+        args: Some(vec![WampValue::Integer(session as i64)]),
+        kwargs: Some(kwargs),
+    }
+}
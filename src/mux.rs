@@ -0,0 +1,219 @@
+use crate::error::Error;
+use crate::messages::{Hello, Welcome};
+use std::collections::BTreeSet;
+
+/// HELLO and WELCOME detail announcing and accepting multiplexing, not part of the spec.
+pub const MULTIPLEX: &str = "x_multiplex";
+
+/// Logical connection inside a multiplexed transport.
+pub type ChannelId = u32;
+
+/// Channel of the session whose HELLO negotiated multiplexing.
+pub const FIRST_CHANNEL: ChannelId = 0;
+
+/// Default number of channels open at once, including the first one.
+pub const DEFAULT_MAX_CHANNELS: usize = 64;
+
+/// Where a connection stands in the negotiation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MuxState {
+    /// The first session's HELLO offered multiplexing, its WELCOME has not arrived.
+    Offered,
+    /// Both peers multiplex, every frame after the WELCOME carries a channel.
+    Multiplexed,
+    /// The peer did not accept, the connection carries one plain session.
+    Single,
+}
+
+/// Whether the client sending `hello` offers to multiplex sessions.
+pub fn offered(hello: &Hello) -> bool {
+    hello.details[MULTIPLEX].as_bool() == Some(true)
+}
+
+/// Several sessions, each with its own realm, over one transport.
+///
+/// The first session's HELLO offers multiplexing with the [`MULTIPLEX`] detail. A router
+/// that supports it sets the detail in its WELCOME and from then on every frame in both
+/// directions starts with the 4-byte big-endian [`ChannelId`] it belongs to, the first
+/// session being [`FIRST_CHANNEL`]. Further sessions are started by sending their HELLO on a
+/// new channel. A router without support ignores the unknown detail, and the connection
+/// falls back to [`MuxState::Single`]: other realms then need connections of their own.
+///
+/// This is experimental, both peers have to run this crate.
+/// # Examples
+/// ```
+/// use wamp_helpers::mux::{offered, MuxState, Multiplexer, FIRST_CHANNEL};
+/// use wamp_helpers::messages::{Hello, Welcome};
+///
+/// // Client side.
+/// let mut client = Multiplexer::client();
+/// let mut hello: Hello = r#"[1, "realm1", {"roles": {"caller": {}}}]"#.parse().unwrap();
+/// client.offer(&mut hello);
+///
+/// // Router side.
+/// assert!(offered(&hello));
+/// let mut router = Multiplexer::router();
+/// let mut welcome: Welcome = r#"[2, 9129137332, {"roles": {"dealer": {}}}]"#.parse().unwrap();
+/// router.accept(&mut welcome);
+///
+/// client.on_welcome(&welcome);
+/// assert_eq!(client.state(), MuxState::Multiplexed);
+///
+/// // A second realm on the same connection.
+/// let channel = client.open_channel().unwrap();
+/// let frame = client.wrap(channel, br#"[1, "realm2", {"roles": {"caller": {}}}]"#);
+/// let (received, payload) = router.demux(&frame).unwrap();
+/// assert_eq!(received, channel);
+/// assert!(router.is_open(channel) && router.is_open(FIRST_CHANNEL));
+/// assert!(payload.starts_with(br#"[1, "realm2""#));
+///
+/// // The peer cannot open channels past the limit.
+/// let mut router = Multiplexer::router().max_channels(2);
+/// router.accept(&mut welcome);
+/// assert!(router.demux(&router.wrap(1, b"[]")).is_ok());
+/// assert!(router.demux(&router.wrap(2, b"[]")).is_err());
+/// assert!(router.demux(&router.wrap(1, b"[]")).is_ok());
+/// ```
+///
+/// Without support on the router, frames stay untouched:
+/// ```
+/// use wamp_helpers::mux::{MuxState, Multiplexer};
+/// use wamp_helpers::messages::{Hello, Welcome};
+///
+/// let mut client = Multiplexer::client();
+/// let mut hello: Hello = r#"[1, "realm1", {}]"#.parse().unwrap();
+/// client.offer(&mut hello);
+/// client.on_welcome(&r#"[2, 9129137332, {}]"#.parse::<Welcome>().unwrap());
+///
+/// assert_eq!(client.state(), MuxState::Single);
+/// assert_eq!(client.open_channel(), None);
+/// assert_eq!(client.wrap(0, b"[6, {}, \"wamp.close.normal\"]"), b"[6, {}, \"wamp.close.normal\"]");
+/// ```
+#[derive(Debug, Clone)]
+pub struct Multiplexer {
+    state: MuxState,
+    channels: BTreeSet<ChannelId>,
+    next_channel: ChannelId,
+    max_channels: usize,
+}
+
+impl Multiplexer {
+    /// The connecting side, [`offer`](Self::offer) multiplexing in the first HELLO.
+    pub fn client() -> Self {
+        Multiplexer {
+            state: MuxState::Offered,
+            channels: BTreeSet::from([FIRST_CHANNEL]),
+            next_channel: FIRST_CHANNEL + 1,
+            max_channels: DEFAULT_MAX_CHANNELS,
+        }
+    }
+
+    /// The accepting side, [`accept`](Self::accept) an offer in the first WELCOME.
+    pub fn router() -> Self {
+        Multiplexer {
+            state: MuxState::Single,
+            ..Multiplexer::client()
+        }
+    }
+
+    /// How many channels may be open at once, defaults to [`DEFAULT_MAX_CHANNELS`].
+    pub fn max_channels(mut self, max_channels: usize) -> Self {
+        self.max_channels = max_channels;
+        self
+    }
+
+    pub fn state(&self) -> MuxState {
+        self.state
+    }
+
+    pub fn is_multiplexed(&self) -> bool {
+        self.state == MuxState::Multiplexed
+    }
+
+    /// Mark the first session's HELLO as offering multiplexing.
+    pub fn offer(&mut self, hello: &mut Hello) {
+        // Details that are not an object are rejected by the router anyway.
+        let _ = hello.details.insert(MULTIPLEX, true);
+        self.state = MuxState::Offered;
+    }
+
+    /// Accept the offer in the WELCOME answering it, frames after it are multiplexed.
+    pub fn accept(&mut self, welcome: &mut Welcome) {
+        let _ = welcome.details.insert(MULTIPLEX, true);
+        self.state = MuxState::Multiplexed;
+    }
+
+    /// Settle the negotiation with the WELCOME answering the first HELLO.
+    pub fn on_welcome(&mut self, welcome: &Welcome) {
+        if self.state == MuxState::Offered {
+            self.state = match welcome.details[MULTIPLEX].as_bool() {
+                Some(true) => MuxState::Multiplexed,
+                _ => MuxState::Single,
+            };
+        }
+    }
+
+    /// A channel for one more session, `None` unless the connection is multiplexed and fewer
+    /// than [`max_channels`](Self::max_channels) are open.
+    pub fn open_channel(&mut self) -> Option<ChannelId> {
+        if !self.is_multiplexed() || self.channels.len() >= self.max_channels {
+            return None;
+        }
+        while self.channels.contains(&self.next_channel) {
+            self.next_channel = self.next_channel.wrapping_add(1);
+        }
+        let channel = self.next_channel;
+        self.channels.insert(channel);
+        self.next_channel = channel.wrapping_add(1);
+        Some(channel)
+    }
+
+    /// Forget a channel whose session ended, returns whether it was open.
+    pub fn close_channel(&mut self, channel: ChannelId) -> bool {
+        self.channels.remove(&channel)
+    }
+
+    pub fn is_open(&self, channel: ChannelId) -> bool {
+        self.channels.contains(&channel)
+    }
+
+    /// Channels with a session, including the first one.
+    pub fn channels(&self) -> impl Iterator<Item = ChannelId> + '_ {
+        self.channels.iter().copied()
+    }
+
+    /// The frame to send for `payload` on `channel`, `payload` itself unless multiplexed.
+    pub fn wrap(&self, channel: ChannelId, payload: &[u8]) -> Vec<u8> {
+        if !self.is_multiplexed() {
+            return payload.to_vec();
+        }
+        let mut frame = Vec::with_capacity(4 + payload.len());
+        frame.extend_from_slice(&channel.to_be_bytes());
+        frame.extend_from_slice(payload);
+        frame
+    }
+
+    /// The channel a received frame belongs to and its payload. A frame on a channel not
+    /// seen before opens it, the peer starts a session there with a HELLO, and is refused
+    /// when [`max_channels`](Self::max_channels) are open already.
+    pub fn demux<'f>(&mut self, frame: &'f [u8]) -> Result<(ChannelId, &'f [u8]), Error> {
+        if !self.is_multiplexed() {
+            return Ok((FIRST_CHANNEL, frame));
+        }
+        let Some((channel, payload)) = frame.split_first_chunk::<4>() else {
+            return Err(Error::InvalidFrame {
+                reason: "multiplexed frame without a channel",
+            });
+        };
+        let channel = ChannelId::from_be_bytes(*channel);
+        if !self.channels.contains(&channel) {
+            if self.channels.len() >= self.max_channels {
+                return Err(Error::InvalidFrame {
+                    reason: "too many multiplexed channels",
+                });
+            }
+            self.channels.insert(channel);
+        }
+        Ok((channel, payload))
+    }
+}
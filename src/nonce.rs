@@ -0,0 +1,84 @@
+use crate::error::Error;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+
+/// Default number of random bytes in a nonce.
+pub const DEFAULT_NONCE_LEN: usize = 32;
+
+/// Text form of a nonce.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NonceEncoding {
+    #[default]
+    Base64,
+    /// Lowercase hexadecimal.
+    Hex,
+}
+
+/// Fill a buffer of `len` bytes from the operating system's secure random source.
+pub fn random_bytes(len: usize) -> Result<Vec<u8>, Error> {
+    let mut bytes = vec![0u8; len];
+    getrandom::fill(&mut bytes).map_err(|error| Error::Io(error.into()))?;
+    Ok(bytes)
+}
+
+/// Encode bytes as lowercase hexadecimal.
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+/// Generates the nonces router-side authenticators put into CHALLENGE messages.
+///
+/// Randomness always comes from the operating system, never from a seeded generator such as
+/// [`SeededRng`](crate::rng::SeededRng).
+/// # Examples
+/// ```
+/// use wamp_helpers::nonce::{NonceEncoding, NonceGenerator};
+///
+/// let hex = NonceGenerator::new().len(16).encoding(NonceEncoding::Hex);
+/// let nonce = hex.generate().unwrap();
+/// assert_eq!(nonce.len(), 32);
+/// assert!(nonce.chars().all(|c| c.is_ascii_hexdigit()));
+/// assert_ne!(nonce, hex.generate().unwrap());
+///
+/// // 32 bytes in base64 by default.
+/// assert_eq!(NonceGenerator::new().generate().unwrap().len(), 44);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NonceGenerator {
+    len: usize,
+    encoding: NonceEncoding,
+}
+
+impl Default for NonceGenerator {
+    fn default() -> Self {
+        NonceGenerator {
+            len: DEFAULT_NONCE_LEN,
+            encoding: NonceEncoding::default(),
+        }
+    }
+}
+
+impl NonceGenerator {
+    pub fn new() -> Self {
+        NonceGenerator::default()
+    }
+
+    /// Number of random bytes, before encoding.
+    pub fn len(mut self, len: usize) -> Self {
+        self.len = len;
+        self
+    }
+
+    pub fn encoding(mut self, encoding: NonceEncoding) -> Self {
+        self.encoding = encoding;
+        self
+    }
+
+    pub fn generate(&self) -> Result<String, Error> {
+        let bytes = random_bytes(self.len)?;
+        Ok(match self.encoding {
+            NonceEncoding::Base64 => STANDARD.encode(bytes),
+            NonceEncoding::Hex => to_hex(&bytes),
+        })
+    }
+}
@@ -0,0 +1,524 @@
+use crate::error::Error;
+#[cfg(feature = "serde")]
+use crate::messages::Kwargs;
+use crate::messages::{Details, Message, Options, Uri, WampId};
+use crate::uri::MatchPolicy;
+use crate::value::WampValue;
+use json::JsonValue;
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+/// Options key carrying an application provided deduplication key, forwarded by the broker
+/// into the EVENT Details.
+pub const DEDUP_KEY: &str = "x_dedup_key";
+/// Entries of `raw` that `typed`, the dictionary rebuilt from a typed struct, does not
+/// reproduce: unknown keys such as `x_` custom options, and known keys whose value the
+/// typed field cannot hold. Adding them back restores `raw` exactly.
+fn unrepresented(raw: &JsonValue, typed: &JsonValue) -> BTreeMap<String, WampValue> {
+    raw.entries()
+        .filter(|(key, value)| !typed.has_key(key) || typed[*key] != **value)
+        .map(|(key, value)| (key.to_string(), WampValue::from(value)))
+        .collect()
+}
+
+/// Split `raw` into the typed fields `parse` reads and the [`unrepresented`] entries, with
+/// `rebuild` turning typed fields back into a dictionary. A known key the typed field cannot
+/// hold exactly only ends up in the entries, its field is left at the default, so no key is
+/// on both sides.
+pub(crate) fn split_typed<T: Clone>(
+    raw: &JsonValue,
+    parse: impl Fn(&JsonValue) -> T,
+    rebuild: impl Fn(T) -> JsonValue,
+) -> (T, BTreeMap<String, WampValue>) {
+    let extra = unrepresented(raw, &rebuild(parse(raw)));
+    let mut representable = raw.clone();
+    for key in extra.keys() {
+        representable.remove(key);
+    }
+    (parse(&representable), extra)
+}
+
+/// Add the `extra` entries of a typed struct to its dictionary.
+pub(crate) fn insert_extra(dictionary: &mut JsonValue, extra: BTreeMap<String, WampValue>) {
+    for (key, value) in extra {
+        dictionary[key.as_str()] = value.into();
+    }
+}
+
+/// Options and Details key listing the router link sessions a PUBLISH, CALL, EVENT or
+/// INVOCATION was forwarded through, oldest first.
+pub const FORWARD_FOR: &str = "forward_for";
+
+/// Most router links a message may cross, see [`forward_through`].
+pub const MAX_FORWARD_HOPS: usize = 8;
+
+/// One entry of [`FORWARD_FOR`]: the session that handed the message to a router link.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ForwardHop {
+    pub session: WampId,
+    pub authid: String,
+    pub authrole: String,
+}
+
+impl From<ForwardHop> for JsonValue {
+    fn from(hop: ForwardHop) -> Self {
+        json::object! {
+            session: hop.session,
+            authid: hop.authid,
+            authrole: hop.authrole,
+        }
+    }
+}
+
+/// The [`FORWARD_FOR`] chain of a dictionary, entries without a session are skipped.
+pub fn forward_for(dictionary: &JsonValue) -> Vec<ForwardHop> {
+    dictionary[FORWARD_FOR]
+        .members()
+        .filter_map(|hop| {
+            Some(ForwardHop {
+                session: hop["session"].as_u64()?,
+                authid: hop["authid"].as_str().unwrap_or_default().to_string(),
+                authrole: hop["authrole"].as_str().unwrap_or_default().to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Append `hop` to the [`FORWARD_FOR`] chain of a message a router link is about to send to
+/// the other router.
+///
+/// Refused with [`Error::ForwardingLoop`] when the chain already holds `hop`, the message
+/// came back over a link it went out on, or it would cross more than [`MAX_FORWARD_HOPS`]
+/// links. The link drops the message then, which breaks event cycles between routers.
+/// # Examples
+/// ```
+/// use wamp_helpers::error::Error;
+/// use wamp_helpers::messages::Message;
+/// use wamp_helpers::options::{forward_for, forward_through, ForwardHop};
+///
+/// let hop = |session: u64, authid: &str| ForwardHop {
+///     session,
+///     authid: authid.to_string(),
+///     authrole: "rlink".to_string(),
+/// };
+/// let mut publish = Message::parse_message(r#"[16, 1, {}, "com.example.tick"]"#).unwrap();
+///
+/// // Router A forwards to B, B forwards to C.
+/// forward_through(&mut publish, hop(11, "router-a")).unwrap();
+/// forward_through(&mut publish, hop(22, "router-b")).unwrap();
+/// let chain = forward_for(publish.details().unwrap());
+/// assert_eq!(chain, [hop(11, "router-a"), hop(22, "router-b")]);
+///
+/// // C is linked back to A, which would forward the publication around again.
+/// assert!(matches!(
+///     forward_through(&mut publish, hop(11, "router-a")),
+///     Err(Error::ForwardingLoop { hops: 2 })
+/// ));
+/// ```
+pub fn forward_through(message: &mut Message, hop: ForwardHop) -> Result<(), Error> {
+    let Some(details) = message.details_mut() else {
+        return Ok(());
+    };
+    let chain = forward_for(details);
+    if chain.len() >= MAX_FORWARD_HOPS || chain.contains(&hop) {
+        return Err(Error::ForwardingLoop { hops: chain.len() });
+    }
+    if !details[FORWARD_FOR].is_array() {
+        details[FORWARD_FOR] = JsonValue::new_array();
+    }
+    details[FORWARD_FOR].push(hop).map_err(Error::JsonError)
+}
+
+/// REGISTER option limiting how many invocations the dealer hands a callee at once.
+pub const CONCURRENCY: &str = "concurrency";
+
+/// The Options of a PUBLISH in typed form, unknown keys are kept in `extra`.
+///
+/// With the `serde` feature, defaults such as `acknowledge: false` or an empty `exclude` list
+/// are omitted when serialized, like the `From` conversion does.
+/// # Examples
+/// ```
+/// use wamp_helpers::messages::Options;
+/// use wamp_helpers::options::PublishOptions;
+///
+/// let options = PublishOptions {
+///     acknowledge: true,
+///     exclude: vec![7],
+///     dedup_key: Some("order-42".to_string()),
+///     ..PublishOptions::default()
+/// };
+/// let raw = Options::from(options.clone());
+/// assert_eq!(raw.dump(), r#"{"acknowledge":true,"exclude":[7],"x_dedup_key":"order-42"}"#);
+/// assert_eq!(PublishOptions::from(&raw), options);
+///
+/// // Custom options survive the round trip.
+/// let raw = json::object! { "acknowledge": true, "x_priority": 3 };
+/// assert_eq!(Options::from(PublishOptions::from(&raw)), raw);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(from = "Kwargs"))]
+pub struct PublishOptions {
+    /// Ask the broker for a PUBLISHED.
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "std::ops::Not::not"))]
+    pub acknowledge: bool,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub exclude_me: Option<bool>,
+    /// Session ids the event is not delivered to.
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Vec::is_empty"))]
+    pub exclude: Vec<WampId>,
+    /// Session ids the event is only delivered to, empty for everyone.
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Vec::is_empty"))]
+    pub eligible: Vec<WampId>,
+    /// Key subscribers use to drop events they already processed, see [`DEDUP_KEY`].
+    #[cfg_attr(
+        feature = "serde",
+        serde(rename = "x_dedup_key", skip_serializing_if = "Option::is_none")
+    )]
+    pub dedup_key: Option<String>,
+    /// Router links the publication crossed, see [`FORWARD_FOR`].
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Vec::is_empty"))]
+    pub forward_for: Vec<ForwardHop>,
+    /// Options without a typed field, and typed ones of an unexpected type.
+    #[cfg_attr(feature = "serde", serde(flatten))]
+    pub extra: BTreeMap<String, WampValue>,
+}
+
+impl From<&Options> for PublishOptions {
+    fn from(options: &Options) -> Self {
+        let parse = |options: &Options| {
+            let ids = |key: &str| -> Vec<WampId> {
+                options[key]
+                    .members()
+                    .filter_map(|id| id.as_u64())
+                    .collect()
+            };
+            PublishOptions {
+                acknowledge: options["acknowledge"].as_bool().unwrap_or(false),
+                exclude_me: options["exclude_me"].as_bool(),
+                exclude: ids("exclude"),
+                eligible: ids("eligible"),
+                dedup_key: options[DEDUP_KEY].as_str().map(str::to_string),
+                forward_for: forward_for(options),
+                extra: BTreeMap::new(),
+            }
+        };
+        let (typed, extra) = split_typed(options, parse, Options::from);
+        PublishOptions { extra, ..typed }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl From<Kwargs> for PublishOptions {
+    fn from(options: Kwargs) -> Self {
+        PublishOptions::from(&Options::from(WampValue::Dict(options)))
+    }
+}
+
+impl From<PublishOptions> for Options {
+    fn from(publish: PublishOptions) -> Self {
+        let mut options = json::object! {};
+        if publish.acknowledge {
+            options["acknowledge"] = true.into();
+        }
+        if let Some(exclude_me) = publish.exclude_me {
+            options["exclude_me"] = exclude_me.into();
+        }
+        if !publish.exclude.is_empty() {
+            options["exclude"] = publish.exclude.into();
+        }
+        if !publish.eligible.is_empty() {
+            options["eligible"] = publish.eligible.into();
+        }
+        if let Some(dedup_key) = publish.dedup_key {
+            options[DEDUP_KEY] = dedup_key.into();
+        }
+        if !publish.forward_for.is_empty() {
+            options[FORWARD_FOR] = publish.forward_for.into();
+        }
+        insert_extra(&mut options, publish.extra);
+        options
+    }
+}
+
+/// The Options of a REGISTER in typed form, unknown keys are kept in `extra`.
+/// # Examples
+/// ```
+/// use wamp_helpers::messages::Options;
+/// use wamp_helpers::options::RegisterOptions;
+/// use wamp_helpers::uri::MatchPolicy;
+///
+/// let raw = json::object! { "match": "prefix", "invoke": "roundrobin", "concurrency": 4 };
+/// let options = RegisterOptions::from(&raw);
+/// assert_eq!(options.match_policy, Some(MatchPolicy::Prefix));
+/// assert_eq!(options.concurrency, Some(4));
+/// assert_eq!(Options::from(options), raw);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(from = "Kwargs"))]
+pub struct RegisterOptions {
+    #[cfg_attr(
+        feature = "serde",
+        serde(rename = "match", skip_serializing_if = "Option::is_none")
+    )]
+    pub match_policy: Option<MatchPolicy>,
+    /// Shared registration policy, see [`InvocationPolicy`](crate::sim::InvocationPolicy).
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub invoke: Option<String>,
+    /// Most invocations the callee runs at once, unlimited when absent.
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub concurrency: Option<u32>,
+    /// Options without a typed field, and typed ones of an unexpected type.
+    #[cfg_attr(feature = "serde", serde(flatten))]
+    pub extra: BTreeMap<String, WampValue>,
+}
+
+impl From<&Options> for RegisterOptions {
+    fn from(options: &Options) -> Self {
+        let parse = |options: &Options| RegisterOptions {
+            match_policy: options["match"]
+                .as_str()
+                .and(MatchPolicy::from_options(options)),
+            invoke: options["invoke"].as_str().map(str::to_string),
+            concurrency: options[CONCURRENCY].as_u32().filter(|limit| *limit > 0),
+            extra: BTreeMap::new(),
+        };
+        let (typed, extra) = split_typed(options, parse, Options::from);
+        RegisterOptions { extra, ..typed }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl From<Kwargs> for RegisterOptions {
+    fn from(options: Kwargs) -> Self {
+        RegisterOptions::from(&Options::from(WampValue::Dict(options)))
+    }
+}
+
+impl From<RegisterOptions> for Options {
+    fn from(register: RegisterOptions) -> Self {
+        let mut options = json::object! {};
+        if let Some(match_policy) = register.match_policy {
+            options["match"] = match_policy.as_str().into();
+        }
+        if let Some(invoke) = register.invoke {
+            options["invoke"] = invoke.into();
+        }
+        if let Some(concurrency) = register.concurrency {
+            options[CONCURRENCY] = concurrency.into();
+        }
+        insert_extra(&mut options, register.extra);
+        options
+    }
+}
+
+/// CALL option and INVOCATION detail giving the call timeout in milliseconds, 0 or absent
+/// for none.
+pub const TIMEOUT: &str = "timeout";
+
+/// The Details of an INVOCATION in typed form, unknown keys are kept in `extra`.
+///
+/// The dealer builds them with [`for_call`](InvocationDetails::for_call), as
+/// [`dealer::invocation`](crate::dealer::invocation) does, which forwards the
+/// caller's timeout so the callee can see how long it has left with
+/// [`remaining`](InvocationDetails::remaining) and give up cooperatively.
+/// # Examples
+/// ```
+/// use std::time::Duration;
+/// use wamp_helpers::clock::{Clock, MockClock};
+/// use wamp_helpers::messages::{Details, Message};
+/// use wamp_helpers::options::InvocationDetails;
+///
+/// let call = Message::parse_message(
+///     r#"[48, 7, {"timeout": 2000, "receive_progress": true}, "com.example.slow"]"#,
+/// )
+/// .unwrap();
+/// let mut details = InvocationDetails::for_call(call.details().unwrap());
+/// details.caller = Some(9129137332);
+/// assert_eq!(
+///     Details::from(details).dump(),
+///     r#"{"caller":9129137332,"receive_progress":true,"timeout":2000}"#
+/// );
+///
+/// // On the callee side, the clock starts when the INVOCATION arrives.
+/// let clock = MockClock::new();
+/// let received = clock.now();
+/// let details = InvocationDetails::from(&json::object! { "timeout": 2000 });
+/// clock.advance(Duration::from_millis(1500));
+/// assert_eq!(details.remaining(received, clock.now()), Some(Duration::from_millis(500)));
+/// clock.advance(Duration::from_secs(1));
+/// assert_eq!(details.remaining(received, clock.now()), Some(Duration::ZERO));
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(from = "Kwargs"))]
+pub struct InvocationDetails {
+    /// The caller's session, when disclosed.
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub caller: Option<WampId>,
+    /// The called procedure, for pattern-based registrations.
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub procedure: Option<Uri>,
+    /// The caller accepts progressive results.
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "std::ops::Not::not"))]
+    pub receive_progress: bool,
+    /// Milliseconds the caller waits for the result, see [`TIMEOUT`].
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub timeout: Option<u64>,
+    /// Router links the call crossed, see [`FORWARD_FOR`].
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Vec::is_empty"))]
+    pub forward_for: Vec<ForwardHop>,
+    /// Details without a typed field, and typed ones of an unexpected type.
+    #[cfg_attr(feature = "serde", serde(flatten))]
+    pub extra: BTreeMap<String, WampValue>,
+}
+
+impl InvocationDetails {
+    /// Details for the INVOCATION of a CALL with `options`, carrying over its timeout and
+    /// `receive_progress`. [`IDENTITY_KEYS`] such as `caller` and [`FORWARD_FOR`] are never
+    /// taken from the caller, the dealer sets them itself, e.g. with [`forward_for`] for a
+    /// CALL that came in over a router link.
+    pub fn for_call(options: &Options) -> Self {
+        InvocationDetails {
+            receive_progress: options["receive_progress"].as_bool().unwrap_or(false),
+            timeout: options[TIMEOUT].as_u64().filter(|timeout| *timeout > 0),
+            ..InvocationDetails::default()
+        }
+    }
+
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout.map(Duration::from_millis)
+    }
+
+    /// Time left before the caller gives up, for an INVOCATION `received` at that instant,
+    /// zero once it passed. `None` without a timeout.
+    pub fn remaining(&self, received: Instant, now: Instant) -> Option<Duration> {
+        let deadline = received + self.timeout()?;
+        Some(deadline.saturating_duration_since(now))
+    }
+}
+
+impl From<&Details> for InvocationDetails {
+    fn from(details: &Details) -> Self {
+        let parse = |details: &Details| InvocationDetails {
+            caller: details["caller"].as_u64(),
+            procedure: details["procedure"]
+                .as_str()
+                .map(|procedure| Uri::from(procedure.to_string())),
+            receive_progress: details["receive_progress"].as_bool().unwrap_or(false),
+            timeout: details[TIMEOUT].as_u64().filter(|timeout| *timeout > 0),
+            forward_for: forward_for(details),
+            extra: BTreeMap::new(),
+        };
+        let (typed, extra) = split_typed(details, parse, Details::from);
+        InvocationDetails { extra, ..typed }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl From<Kwargs> for InvocationDetails {
+    fn from(details: Kwargs) -> Self {
+        InvocationDetails::from(&Details::from(WampValue::Dict(details)))
+    }
+}
+
+impl From<InvocationDetails> for Details {
+    fn from(invocation: InvocationDetails) -> Self {
+        let mut details = json::object! {};
+        if let Some(caller) = invocation.caller {
+            details["caller"] = caller.into();
+        }
+        if let Some(procedure) = invocation.procedure {
+            details["procedure"] = procedure.into();
+        }
+        if invocation.receive_progress {
+            details["receive_progress"] = true.into();
+        }
+        if let Some(timeout) = invocation.timeout {
+            details[TIMEOUT] = timeout.into();
+        }
+        if !invocation.forward_for.is_empty() {
+            details[FORWARD_FOR] = invocation.forward_for.into();
+        }
+        insert_extra(&mut details, invocation.extra);
+        details
+    }
+}
+
+/// Details keys identifying the caller, publisher or callee. Only the router sets them, when
+/// it discloses an identity, so a client sending them in its Options or Details is spoofing.
+/// The same goes for [`FORWARD_FOR`], which only router links may set.
+pub const IDENTITY_KEYS: &[&str] = &[
+    "caller",
+    "caller_authid",
+    "caller_authrole",
+    "publisher",
+    "publisher_authid",
+    "publisher_authrole",
+    "callee",
+    "callee_authid",
+    "callee_authrole",
+    "trustlevel",
+    FORWARD_FOR,
+];
+
+/// What [`guard_identity`] does with [`IDENTITY_KEYS`] found in a client's message.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum SpoofPolicy {
+    /// Remove the keys and route the message.
+    #[default]
+    Strip,
+    /// Refuse the message with [`Error::SpoofedIdentity`].
+    Reject,
+}
+
+/// Remove or refuse the [`IDENTITY_KEYS`] in the Options/Details of a message received from
+/// a client, before the broker or dealer routes it. Returns the keys that were stripped, for
+/// logging.
+///
+/// Messages received from a router link are not guarded, they carry a [`FORWARD_FOR`] chain.
+/// # Examples
+/// ```
+/// use wamp_helpers::error::Error;
+/// use wamp_helpers::messages::Message;
+/// use wamp_helpers::options::{guard_identity, SpoofPolicy};
+///
+/// let frame = r#"[48, 7, {"caller": 1, "caller_authrole": "admin", "timeout": 500}, "com.example.reset"]"#;
+/// let mut call = Message::parse_message(frame).unwrap();
+/// assert_eq!(guard_identity(&mut call, SpoofPolicy::Strip).unwrap(), ["caller", "caller_authrole"]);
+/// assert_eq!(call.details().unwrap().dump(), r#"{"timeout":500}"#);
+///
+/// let mut call = Message::parse_message(frame).unwrap();
+/// assert!(matches!(
+///     guard_identity(&mut call, SpoofPolicy::Reject),
+///     Err(Error::SpoofedIdentity { key }) if key == "caller"
+/// ));
+///
+/// let frame = r#"[16, 1, {"forward_for": [{"session": 1}]}, "com.example.topic"]"#;
+/// let mut publish = Message::parse_message(frame).unwrap();
+/// assert_eq!(guard_identity(&mut publish, SpoofPolicy::Strip).unwrap(), ["forward_for"]);
+/// ```
+pub fn guard_identity(
+    message: &mut Message,
+    policy: SpoofPolicy,
+) -> Result<Vec<&'static str>, Error> {
+    let Some(details) = message.details_mut() else {
+        return Ok(Vec::new());
+    };
+    let found: Vec<_> = IDENTITY_KEYS
+        .iter()
+        .copied()
+        .filter(|key| details.has_key(key))
+        .collect();
+    if let (SpoofPolicy::Reject, Some(key)) = (policy, found.first()) {
+        return Err(Error::SpoofedIdentity {
+            key: key.to_string(),
+        });
+    }
+    for key in &found {
+        details.remove(key);
+    }
+    Ok(found)
+}
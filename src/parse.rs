@@ -0,0 +1,240 @@
+use crate::error::Error;
+use crate::messages::Message;
+use json::JsonValue;
+use std::collections::HashSet;
+
+/// Whether the crate was built with the `spec_strict` feature, under which non-standard
+/// traffic is never tolerated: extension messages and serializers are refused and IDs must be
+/// in `[1, 2^53]`.
+pub const SPEC_STRICT: bool = cfg!(feature = "spec_strict");
+
+/// Prefix reserved by the spec for URIs and keys defined by WAMP itself.
+pub const RESERVED_KEY_PREFIX: &str = "wamp.";
+
+/// Knobs for the additional checks [`Message::parse_message_with`](crate::messages::Message::parse_message_with)
+/// runs on top of the plain structural parse.
+///
+/// Every check is off by default so `ParseOptions::default()` behaves exactly like
+/// [`Message::parse_message`](crate::messages::Message::parse_message).
+/// # Examples
+/// ```
+/// use wamp_helpers::error::Error;
+/// use wamp_helpers::messages::Message;
+/// use wamp_helpers::parse::ParseOptions;
+///
+/// let options = ParseOptions::strict();
+/// let duplicate = r#"[1, "realm", {"roles": {}, "roles": {"caller": {}}}]"#;
+/// assert!(Message::parse_message(duplicate).is_ok());
+/// assert!(matches!(Message::parse_message_with(duplicate, &options), Err(Error::DuplicateKey { .. })));
+///
+/// let reserved = r#"[1, "realm", {"wamp.roles": {}}]"#;
+/// assert!(matches!(Message::parse_message_with(reserved, &options), Err(Error::ReservedKey { .. })));
+///
+/// let trailing = r#"[65, 1, 2, "surplus"]"#;
+/// assert!(Message::parse_message(trailing).is_ok());
+/// assert!(matches!(Message::parse_message_with(trailing, &options), Err(Error::TooManyElements { id: 65, len: 4 })));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ParseOptions {
+    /// Reject frames where any dictionary repeats a key, the `json` crate would otherwise keep
+    /// the last value silently.
+    pub reject_duplicate_keys: bool,
+    /// Reject `wamp.` prefixed keys at the top level of Details/Options.
+    pub reject_reserved_keys: bool,
+    /// Reject frames with more elements than the spec allows for their message type, see
+    /// [`ARITY_TABLE`](crate::arity::ARITY_TABLE).
+    pub reject_trailing_elements: bool,
+    /// Reject ERROR messages whose error URI fails strict checking, see
+    /// [`check_error_uri`](crate::uri::check_error_uri).
+    pub validate_error_uris: bool,
+    /// Reject IDs outside `[1, 2^53]`, always on with the `spec_strict` feature.
+    pub spec_strict: bool,
+    /// Size limits of the Details/Options dictionary, for frames from untrusted peers.
+    pub dict_budget: Option<DictBudget>,
+}
+
+impl ParseOptions {
+    /// All checks enabled.
+    pub fn strict() -> Self {
+        ParseOptions {
+            reject_duplicate_keys: true,
+            reject_reserved_keys: true,
+            reject_trailing_elements: true,
+            validate_error_uris: true,
+            spec_strict: true,
+            dict_budget: Some(DictBudget::default()),
+        }
+    }
+}
+
+/// Limits on the Details/Options dictionary of a message.
+///
+/// The frame as a whole is bounded by the transport's maximum message length, this keeps one
+/// frame from filling a dictionary that is kept around, e.g. in a session's HELLO details.
+/// # Examples
+/// ```
+/// use wamp_helpers::error::Error;
+/// use wamp_helpers::messages::Message;
+/// use wamp_helpers::parse::{DictBudget, ParseOptions};
+///
+/// let budget = DictBudget { max_keys: 2, max_value_len: 16, truncate: false };
+/// let options = ParseOptions { dict_budget: Some(budget), ..ParseOptions::default() };
+/// let hostile = r#"[48, 1, {"a": 1, "b": 2, "c": 3}, "com.example.add"]"#;
+/// assert!(matches!(
+///     Message::parse_message_with(hostile, &options),
+///     Err(Error::DictionaryTooLarge { keys: 3, limit: 2 })
+/// ));
+///
+/// let options = ParseOptions { dict_budget: Some(DictBudget { truncate: true, ..budget }), ..options };
+/// let huge = r#"[48, 1, {"a": 1, "blob": "0123456789abcdefghij"}, "com.example.add"]"#;
+/// let call = Message::parse_message_with(huge, &options).unwrap();
+/// assert_eq!(call.details().unwrap().dump(), r#"{"a":1}"#);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DictBudget {
+    /// Most top-level keys.
+    pub max_keys: usize,
+    /// Longest serialized value of one key, in bytes.
+    pub max_value_len: usize,
+    /// Drop the keys beyond the limits instead of rejecting the message.
+    pub truncate: bool,
+}
+
+impl Default for DictBudget {
+    /// Generous limits for the options the spec defines, rejecting beyond them.
+    fn default() -> Self {
+        DictBudget {
+            max_keys: 64,
+            max_value_len: 16 * 1024,
+            truncate: false,
+        }
+    }
+}
+
+impl DictBudget {
+    /// Check `dictionary` against the budget, truncating it when configured to. Keys are
+    /// kept in the order they were received.
+    pub fn apply(&self, dictionary: &mut JsonValue) -> Result<(), Error> {
+        let keys = dictionary.len();
+        if keys > self.max_keys && !self.truncate {
+            return Err(Error::DictionaryTooLarge {
+                keys,
+                limit: self.max_keys,
+            });
+        }
+        let mut kept = JsonValue::new_object();
+        for (key, value) in dictionary.entries_mut() {
+            let len = value.dump().len();
+            if len > self.max_value_len {
+                if !self.truncate {
+                    return Err(Error::ValueTooLarge {
+                        key: key.to_string(),
+                        len,
+                        limit: self.max_value_len,
+                    });
+                }
+            } else if self.truncate && kept.len() < self.max_keys {
+                kept[key] = value.take();
+            }
+        }
+        if self.truncate {
+            *dictionary = kept;
+        }
+        Ok(())
+    }
+}
+
+/// Scan a raw frame that is already known to be valid JSON for dictionaries repeating a key.
+pub fn check_duplicate_keys(raw: &str) -> Result<(), Error> {
+    // One entry per open container, `Some` for dictionaries holding the keys seen so far and
+    // whether the next string is a key.
+    let mut stack: Vec<Option<(HashSet<String>, bool)>> = Vec::new();
+    let bytes = raw.as_bytes();
+    let mut index = 0;
+
+    while let Some(&byte) = bytes.get(index) {
+        match byte {
+            b'{' => stack.push(Some((HashSet::new(), true))),
+            b'[' => stack.push(None),
+            b'}' | b']' => {
+                stack.pop();
+            }
+            b',' => {
+                if let Some(Some((_, expecting_key))) = stack.last_mut() {
+                    *expecting_key = true;
+                }
+            }
+            b'"' => {
+                let start = index;
+                index += 1;
+                while let Some(&byte) = bytes.get(index).filter(|byte| **byte != b'"') {
+                    if byte == b'\\' {
+                        index += 1;
+                    }
+                    index += 1;
+                }
+
+                if let Some(Some((keys, expecting_key))) = stack.last_mut() {
+                    if *expecting_key {
+                        *expecting_key = false;
+                        let literal = raw
+                            .get(start..=index)
+                            .or_else(|| raw.get(start..))
+                            .unwrap_or_default();
+                        let key = match json::parse(literal) {
+                            Ok(key) => key.as_str().unwrap_or(literal).to_string(),
+                            Err(err) => return Err(Error::JsonError(err)),
+                        };
+                        if !keys.insert(key.clone()) {
+                            return Err(Error::DuplicateKey { key });
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+        index += 1;
+    }
+    Ok(())
+}
+
+/// Reject `wamp.` prefixed keys at the top level of a Details/Options dictionary.
+pub fn check_reserved_keys(dictionary: &JsonValue) -> Result<(), Error> {
+    for (key, _) in dictionary.entries() {
+        if key.starts_with(RESERVED_KEY_PREFIX) {
+            return Err(Error::ReservedKey {
+                key: key.to_string(),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Reject IDs of 0, the spec's ID range starts at 1. Router-initiated UNSUBSCRIBED and
+/// UNREGISTERED revocations are the exception, they carry request id 0.
+/// ```
+/// use wamp_helpers::error::Error;
+/// use wamp_helpers::messages::Message;
+/// use wamp_helpers::parse::check_id_range;
+///
+/// assert!(check_id_range(&Message::parse_message("[33, 1, 5512315355]").unwrap()).is_ok());
+/// # #[cfg(not(feature = "spec_strict"))]
+/// assert!(matches!(
+///     check_id_range(&Message::parse_message("[33, 1, 0]").unwrap()),
+///     Err(Error::IdOutOfRange { .. })
+/// ));
+/// ```
+pub fn check_id_range(message: &Message) -> Result<(), Error> {
+    let revocation = match message {
+        Message::Unsubscribed(unsubscribed) => unsubscribed.details.is_some(),
+        Message::Unregistered(unregistered) => unregistered.details.is_some(),
+        _ => false,
+    };
+    if revocation {
+        return Ok(());
+    }
+    match message.ids().into_iter().find(|id| *id == 0) {
+        Some(id) => Err(Error::IdOutOfRange { offense: id.into() }),
+        None => Ok(()),
+    }
+}
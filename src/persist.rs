@@ -0,0 +1,275 @@
+use crate::error::Error;
+#[cfg(feature = "sled")]
+use crate::messages::WampMessageTrait;
+use crate::messages::{Event, Publish};
+use std::collections::BTreeMap;
+
+/// Storage for publications that were sent but not acknowledged yet, keyed by their
+/// deduplication key, which unlike the request id stays the same across sessions.
+///
+/// Save every publication the [`Outbox`](crate::client::Outbox) hands out before sending it,
+/// remove it once it is settled, and [`restore`](crate::client::Outbox::restore) what
+/// [`load`](OutboxStore::load) returns after a restart.
+pub trait OutboxStore {
+    fn save(&mut self, key: &str, publish: &Publish) -> Result<(), Error>;
+
+    fn remove(&mut self, key: &str) -> Result<(), Error>;
+
+    /// Every saved publication, in the order they were published.
+    fn load(&self) -> Result<Vec<Publish>, Error>;
+}
+
+/// Storage for events received but not processed yet.
+///
+/// Push an event when it arrives and acknowledge it once the application handled it, so an
+/// event received right before a crash is handled after the restart.
+pub trait InboxStore {
+    /// Store `event`, returns the sequence number to [`ack`](InboxStore::ack) it with.
+    fn push(&mut self, event: &Event) -> Result<u64, Error>;
+
+    fn ack(&mut self, sequence: u64) -> Result<(), Error>;
+
+    /// Events not acknowledged yet, in arrival order.
+    fn pending(&self) -> Result<Vec<(u64, Event)>, Error>;
+}
+
+#[cfg(feature = "sled")]
+fn encode<T: WampMessageTrait + Clone>(message: &T) -> Result<Vec<u8>, Error> {
+    Ok(message.clone().to_json()?.dump().into_bytes())
+}
+
+#[cfg(feature = "sled")]
+fn decode<T: std::str::FromStr<Err = Error>>(bytes: &[u8]) -> Result<T, Error> {
+    std::str::from_utf8(bytes)
+        .map_err(|error| Error::Codec(Box::new(error)))?
+        .parse()
+}
+
+/// Saved publications ordered by the sequence number ending their deduplication key, so
+/// `device-7-10` comes after `device-7-9`. Keys without one sort by themselves at the end.
+fn publication_order(mut saved: Vec<(String, Publish)>) -> Vec<Publish> {
+    saved.sort_by_cached_key(|(key, _)| {
+        let (prefix, number) = key.rsplit_once('-').unwrap_or(("", key));
+        match number.parse::<u64>() {
+            Ok(number) => (false, prefix.to_string(), number, String::new()),
+            Err(_) => (true, String::new(), 0, key.clone()),
+        }
+    });
+    saved.into_iter().map(|(_, publish)| publish).collect()
+}
+
+/// Outbox and inbox kept in memory, for tests and clients that only need to survive
+/// reconnects, not restarts.
+/// # Examples
+/// ```
+/// use wamp_helpers::client::Outbox;
+/// use wamp_helpers::messages::Publish;
+/// use wamp_helpers::options::DEDUP_KEY;
+/// use wamp_helpers::persist::{MemoryStore, OutboxStore};
+///
+/// let mut store = MemoryStore::new();
+/// let mut outbox = Outbox::new("device-7");
+/// let publish: Publish = r#"[16, 1, {}, "com.example.reading", [21]]"#.parse().unwrap();
+/// let sent = outbox.publish(publish);
+/// store.save(sent.options[DEDUP_KEY].as_str().unwrap(), &sent).unwrap();
+///
+/// // After a restart, the new outbox picks up where the old one stopped.
+/// let mut outbox = Outbox::new("device-7");
+/// outbox.restore(store.load().unwrap());
+/// assert_eq!(outbox.len(), 1);
+/// let next = outbox.publish(r#"[16, 2, {}, "com.example.reading", [22]]"#.parse().unwrap());
+/// assert_eq!(next.options[DEDUP_KEY], "device-7-2");
+///
+/// // Loaded by sequence number, not as strings.
+/// for number in [10, 9] {
+///     let publish = format!(r#"[16, {number}, {{}}, "com.example.reading"]"#);
+///     store.save(&format!("device-8-{number}"), &publish.parse().unwrap()).unwrap();
+/// }
+/// let requests: Vec<_> = store.load().unwrap().iter().map(|publish| publish.request).collect();
+/// assert_eq!(requests, [1, 9, 10]);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct MemoryStore {
+    outbox: BTreeMap<String, Publish>,
+    inbox: BTreeMap<u64, Event>,
+    next_sequence: u64,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        MemoryStore::default()
+    }
+}
+
+impl OutboxStore for MemoryStore {
+    fn save(&mut self, key: &str, publish: &Publish) -> Result<(), Error> {
+        self.outbox.insert(key.to_string(), publish.clone());
+        Ok(())
+    }
+
+    fn remove(&mut self, key: &str) -> Result<(), Error> {
+        self.outbox.remove(key);
+        Ok(())
+    }
+
+    fn load(&self) -> Result<Vec<Publish>, Error> {
+        let saved = self
+            .outbox
+            .iter()
+            .map(|(key, publish)| (key.clone(), publish.clone()))
+            .collect();
+        Ok(publication_order(saved))
+    }
+}
+
+impl InboxStore for MemoryStore {
+    fn push(&mut self, event: &Event) -> Result<u64, Error> {
+        let sequence = self.next_sequence;
+        self.next_sequence += 1;
+        self.inbox.insert(sequence, event.clone());
+        Ok(sequence)
+    }
+
+    fn ack(&mut self, sequence: u64) -> Result<(), Error> {
+        self.inbox.remove(&sequence);
+        Ok(())
+    }
+
+    fn pending(&self) -> Result<Vec<(u64, Event)>, Error> {
+        Ok(self
+            .inbox
+            .iter()
+            .map(|(sequence, event)| (*sequence, event.clone()))
+            .collect())
+    }
+}
+
+/// Outbox and inbox in a [sled](https://docs.rs/sled) database, surviving restarts.
+///
+/// Messages are stored as JSON, each write is flushed before it returns so an acknowledged
+/// save is on disk.
+/// # Examples
+/// ```
+/// use wamp_helpers::messages::Event;
+/// use wamp_helpers::persist::{InboxStore, SledStore};
+///
+/// let dir = std::env::temp_dir().join(format!("wamp-inbox-{}", std::process::id()));
+/// let event: Event = r#"[36, 5512315355, 4429313566, {}, [21]]"#.parse().unwrap();
+/// {
+///     let mut store = SledStore::open(&dir).unwrap();
+///     store.push(&event).unwrap();
+/// }
+///
+/// // Reopened after a crash, the event is still waiting. sled's background threads let go of
+/// // the lock on the directory shortly after the last handle is dropped.
+/// let reopen = || {
+///     SledStore::open(&dir)
+///         .inspect_err(|_| std::thread::sleep(std::time::Duration::from_millis(10)))
+///         .ok()
+/// };
+/// let mut store = std::iter::repeat_with(reopen).take(100).flatten().next().unwrap();
+/// let pending = store.pending().unwrap();
+/// assert_eq!(pending.len(), 1);
+/// assert_eq!(pending[0].1, event);
+/// store.ack(pending[0].0).unwrap();
+/// assert!(store.pending().unwrap().is_empty());
+/// # drop(store);
+/// # std::fs::remove_dir_all(&dir).unwrap();
+/// ```
+#[cfg(feature = "sled")]
+#[derive(Debug, Clone)]
+pub struct SledStore {
+    db: sled::Db,
+    outbox: sled::Tree,
+    inbox: sled::Tree,
+}
+
+#[cfg(feature = "sled")]
+impl SledStore {
+    pub fn open(path: impl AsRef<std::path::Path>) -> Result<Self, Error> {
+        // Every write is flushed already, a background flusher would only hold the database
+        // lock past the store's drop.
+        let db = sled::Config::new()
+            .path(path)
+            .flush_every_ms(None)
+            .open()
+            .map_err(storage)?;
+        SledStore::with_db(db)
+    }
+
+    /// Use the `wamp_outbox` and `wamp_inbox` trees of an open database.
+    pub fn with_db(db: sled::Db) -> Result<Self, Error> {
+        Ok(SledStore {
+            outbox: db.open_tree("wamp_outbox").map_err(storage)?,
+            inbox: db.open_tree("wamp_inbox").map_err(storage)?,
+            db,
+        })
+    }
+
+    fn flush(&self, tree: &sled::Tree) -> Result<(), Error> {
+        tree.flush().map(drop).map_err(storage)
+    }
+}
+
+#[cfg(feature = "sled")]
+fn storage(error: sled::Error) -> Error {
+    Error::Io(error.into())
+}
+
+#[cfg(feature = "sled")]
+impl OutboxStore for SledStore {
+    fn save(&mut self, key: &str, publish: &Publish) -> Result<(), Error> {
+        self.outbox
+            .insert(key.as_bytes(), encode(publish)?)
+            .map_err(storage)?;
+        self.flush(&self.outbox)
+    }
+
+    fn remove(&mut self, key: &str) -> Result<(), Error> {
+        self.outbox.remove(key.as_bytes()).map_err(storage)?;
+        self.flush(&self.outbox)
+    }
+
+    fn load(&self) -> Result<Vec<Publish>, Error> {
+        let saved = self
+            .outbox
+            .iter()
+            .map(|entry| {
+                let (key, value) = entry.map_err(storage)?;
+                Ok((String::from_utf8_lossy(&key).into_owned(), decode(&value)?))
+            })
+            .collect::<Result<_, Error>>()?;
+        Ok(publication_order(saved))
+    }
+}
+
+#[cfg(feature = "sled")]
+impl InboxStore for SledStore {
+    fn push(&mut self, event: &Event) -> Result<u64, Error> {
+        // Ids are increasing across restarts, big-endian keys keep them in order.
+        let sequence = self.db.generate_id().map_err(storage)?;
+        self.inbox
+            .insert(sequence.to_be_bytes(), encode(event)?)
+            .map_err(storage)?;
+        self.flush(&self.inbox)?;
+        Ok(sequence)
+    }
+
+    fn ack(&mut self, sequence: u64) -> Result<(), Error> {
+        self.inbox.remove(sequence.to_be_bytes()).map_err(storage)?;
+        self.flush(&self.inbox)
+    }
+
+    fn pending(&self) -> Result<Vec<(u64, Event)>, Error> {
+        self.inbox
+            .iter()
+            .map(|entry| {
+                let (key, value) = entry.map_err(storage)?;
+                let sequence = <[u8; 8]>::try_from(key.as_ref())
+                    .map(u64::from_be_bytes)
+                    .map_err(|error| Error::Codec(Box::new(error)))?;
+                Ok((sequence, decode(&value)?))
+            })
+            .collect()
+    }
+}
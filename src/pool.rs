@@ -0,0 +1,198 @@
+use crate::acceptor::Serializer;
+use crate::error::Error;
+use crate::messages::Message;
+use crate::transcode::encode_into;
+use crate::value::WampValue;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Default number of buffers of each kind a pool keeps.
+pub const DEFAULT_MAX_POOLED: usize = 64;
+/// Default capacity above which returned buffers are dropped instead of kept.
+pub const DEFAULT_MAX_CAPACITY: usize = 64 * 1024;
+
+/// Counters of a [`BufferPool`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PoolStats {
+    /// Buffers handed out from the pool.
+    pub hits: u64,
+    /// Buffers allocated because the pool was empty.
+    pub misses: u64,
+    /// Buffers given back and kept.
+    pub returned: u64,
+    /// Buffers given back and dropped, the pool was full or they were too large.
+    pub discarded: u64,
+}
+
+impl PoolStats {
+    /// Share of buffers served from the pool, 0 before the first one was taken.
+    pub fn hit_rate(&self) -> f64 {
+        match self.hits + self.misses {
+            0 => 0.0,
+            taken => self.hits as f64 / taken as f64,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    hits: AtomicU64,
+    misses: AtomicU64,
+    returned: AtomicU64,
+    discarded: AtomicU64,
+}
+
+#[derive(Debug, Default)]
+struct Shared {
+    bytes: Mutex<Vec<Vec<u8>>>,
+    strings: Mutex<Vec<String>>,
+    counters: Counters,
+}
+
+/// Reusable `Vec<u8>` and `String` buffers for encoding and decoding, shared between
+/// threads.
+///
+/// Take a buffer for each frame and give it back once the frame was written to the
+/// transport, or once a received frame was parsed. Buffers come back empty but keep their
+/// capacity, so a busy broker stops allocating frame buffers after warming up. Buffers
+/// that grew beyond [`max_capacity`](BufferPool::max_capacity) are dropped when given back,
+/// so one huge message does not pin its memory.
+///
+/// Cloning a pool gives another handle to the same buffers.
+/// # Examples
+/// ```
+/// use wamp_helpers::acceptor::Serializer;
+/// use wamp_helpers::messages::Message;
+/// use wamp_helpers::pool::BufferPool;
+///
+/// let pool = BufferPool::new();
+/// for request in 1..=3 {
+///     let call = Message::parse_message(&format!(r#"[48, {request}, {{}}, "com.example.add"]"#)).unwrap();
+///     let frame = pool.encode(call, Serializer::Json).unwrap();
+///     assert_eq!(frame, format!(r#"[48,{request},{{}},"com.example.add"]"#).as_bytes());
+///     // Sent, the buffer can serve the next message.
+///     pool.give_bytes(frame);
+/// }
+///
+/// let stats = pool.stats();
+/// assert_eq!((stats.hits, stats.misses), (2, 1));
+/// assert!(stats.hit_rate() > 0.6);
+/// ```
+#[derive(Debug, Clone)]
+pub struct BufferPool {
+    shared: Arc<Shared>,
+    max_pooled: usize,
+    max_capacity: usize,
+}
+
+impl Default for BufferPool {
+    fn default() -> Self {
+        BufferPool {
+            shared: Arc::default(),
+            max_pooled: DEFAULT_MAX_POOLED,
+            max_capacity: DEFAULT_MAX_CAPACITY,
+        }
+    }
+}
+
+impl BufferPool {
+    pub fn new() -> Self {
+        BufferPool::default()
+    }
+
+    /// Buffers of each kind kept for reuse.
+    pub fn max_pooled(mut self, max_pooled: usize) -> Self {
+        self.max_pooled = max_pooled;
+        self
+    }
+
+    /// Capacity above which a returned buffer is dropped.
+    pub fn max_capacity(mut self, max_capacity: usize) -> Self {
+        self.max_capacity = max_capacity;
+        self
+    }
+
+    fn take<T>(&self, pooled: &Mutex<Vec<T>>, new: impl FnOnce() -> T) -> T {
+        // Buffers are only pushed and popped under the lock, a poisoned pool is still valid.
+        let buffer = pooled
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .pop();
+        let counters = &self.shared.counters;
+        match buffer {
+            Some(buffer) => {
+                counters.hits.fetch_add(1, Ordering::Relaxed);
+                buffer
+            }
+            None => {
+                counters.misses.fetch_add(1, Ordering::Relaxed);
+                new()
+            }
+        }
+    }
+
+    fn give<T>(&self, pooled: &Mutex<Vec<T>>, buffer: T, capacity: usize) {
+        let counters = &self.shared.counters;
+        if capacity <= self.max_capacity {
+            let mut pooled = pooled
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+            if pooled.len() < self.max_pooled {
+                pooled.push(buffer);
+                counters.returned.fetch_add(1, Ordering::Relaxed);
+                return;
+            }
+        }
+        counters.discarded.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// An empty byte buffer.
+    pub fn take_bytes(&self) -> Vec<u8> {
+        self.take(&self.shared.bytes, Vec::new)
+    }
+
+    pub fn give_bytes(&self, mut buffer: Vec<u8>) {
+        buffer.clear();
+        let capacity = buffer.capacity();
+        self.give(&self.shared.bytes, buffer, capacity);
+    }
+
+    /// An empty string buffer.
+    pub fn take_string(&self) -> String {
+        self.take(&self.shared.strings, String::new)
+    }
+
+    pub fn give_string(&self, mut buffer: String) {
+        buffer.clear();
+        let capacity = buffer.capacity();
+        self.give(&self.shared.strings, buffer, capacity);
+    }
+
+    /// Encode `message` into a pooled buffer reserved to its
+    /// [`encoded_size_hint`](Message::encoded_size_hint).
+    pub fn encode(&self, message: Message, serializer: Serializer) -> Result<Vec<u8>, Error> {
+        let mut frame = self.take_bytes();
+        frame.reserve(message.encoded_size_hint(serializer));
+        let encoded = message.to_json().and_then(|json| match serializer {
+            Serializer::Json => json.write(&mut frame).map_err(Error::Io),
+            _ => encode_into(WampValue::from(json), serializer, &mut frame),
+        });
+        match encoded {
+            Ok(()) => Ok(frame),
+            Err(error) => {
+                self.give_bytes(frame);
+                Err(error)
+            }
+        }
+    }
+
+    pub fn stats(&self) -> PoolStats {
+        let counters = &self.shared.counters;
+        PoolStats {
+            hits: counters.hits.load(Ordering::Relaxed),
+            misses: counters.misses.load(Ordering::Relaxed),
+            returned: counters.returned.load(Ordering::Relaxed),
+            discarded: counters.discarded.load(Ordering::Relaxed),
+        }
+    }
+}
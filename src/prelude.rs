@@ -0,0 +1,16 @@
+//! Single import path for the message types, traits and errors.
+//! ```
+//! use wamp_helpers::prelude::*;
+//! let hello = Hello::default("some.realm".to_string(), vec![Roles::Caller], None);
+//! assert_eq!(Hello::ID, 1);
+//! ```
+pub use crate::error::Error;
+pub use crate::messages::{
+    Abort, Args, Authenticate, Call, Cancel, Challenge, Details, ErrorMessage, Event, Extended,
+    Goodbye, GoodbyeDetails, Hello, Interrupt, Invocation, Kwargs, Message, MessageDirection,
+    Options, Publish, Published, Register, Registered, RequestType, Roles, Subscribe, Subscribed,
+    Unregister, Unregistered, Unsubscribe, Unsubscribed, Uri, WampId, WampMessageTrait, WampResult,
+    Welcome, Yield,
+};
+pub use crate::parse::ParseOptions;
+pub use crate::value::WampValue;
@@ -0,0 +1,155 @@
+use crate::error::Error;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+/// First bytes of a PROXY protocol v2 header.
+pub const V2_SIGNATURE: [u8; 12] = *b"\r\n\r\n\0\r\nQUIT\n";
+/// Longest possible v1 header, including the trailing CRLF.
+pub const V1_MAX_LEN: usize = 107;
+
+/// Connection endpoints reported by a load balancer in a PROXY protocol header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProxyHeader {
+    /// The original client, `None` for `UNKNOWN`/`LOCAL` headers such as health checks.
+    pub source: Option<SocketAddr>,
+    pub destination: Option<SocketAddr>,
+}
+
+fn invalid(reason: &'static str) -> Error {
+    Error::InvalidProxyHeader { reason }
+}
+
+/// Parse a PROXY protocol v1 or v2 header at the start of `buf`.
+///
+/// Returns `Ok(None)` while more bytes are needed, otherwise the header and its length in
+/// bytes, the RawSocket handshake starts right after it.
+/// # Examples
+/// ```
+/// use wamp_helpers::proxy::{parse_proxy_header, V2_SIGNATURE};
+///
+/// let v1 = b"PROXY TCP4 203.0.113.7 10.0.0.1 56324 8080\r\n\x7f\xf1\x00\x00";
+/// let (header, len) = parse_proxy_header(v1).unwrap().unwrap();
+/// assert_eq!(header.source, Some("203.0.113.7:56324".parse().unwrap()));
+/// assert_eq!(&v1[len..], b"\x7f\xf1\x00\x00");
+///
+/// let mut v2 = V2_SIGNATURE.to_vec();
+/// v2.extend([0x21, 0x11, 0, 12, 203, 0, 113, 7, 10, 0, 0, 1, 0xdc, 0x04, 0x1f, 0x90]);
+/// let (header, len) = parse_proxy_header(&v2).unwrap().unwrap();
+/// assert_eq!(header.source, Some("203.0.113.7:56324".parse().unwrap()));
+/// assert_eq!(len, v2.len());
+///
+/// assert!(parse_proxy_header(b"PROXY TCP4 203.0").unwrap().is_none());
+/// assert!(parse_proxy_header(b"\x7f\xf1\x00\x00").is_err());
+/// ```
+pub fn parse_proxy_header(buf: &[u8]) -> Result<Option<(ProxyHeader, usize)>, Error> {
+    if starts_like(buf, &V2_SIGNATURE) {
+        return if buf.len() < V2_SIGNATURE.len() {
+            Ok(None)
+        } else {
+            parse_v2(buf)
+        };
+    }
+    if starts_like(buf, b"PROXY ") {
+        return if buf.len() < 6 {
+            Ok(None)
+        } else {
+            parse_v1(buf)
+        };
+    }
+    Err(invalid("missing PROXY protocol signature"))
+}
+
+/// Whether `buf` is `signature` or the start of it, or starts with it.
+fn starts_like(buf: &[u8], signature: &[u8]) -> bool {
+    buf.iter()
+        .zip(signature)
+        .all(|(byte, expected)| byte == expected)
+}
+
+/// The `N` bytes at `offset`, `None` past the end.
+fn read<const N: usize>(bytes: &[u8], offset: usize) -> Option<[u8; N]> {
+    bytes.get(offset..offset.checked_add(N)?)?.try_into().ok()
+}
+
+fn parse_v1(buf: &[u8]) -> Result<Option<(ProxyHeader, usize)>, Error> {
+    let Some(end) = buf.windows(2).position(|window| window == b"\r\n") else {
+        return if buf.len() >= V1_MAX_LEN {
+            Err(invalid("v1 header too long"))
+        } else {
+            Ok(None)
+        };
+    };
+    let line = buf.get(..end).unwrap_or_default();
+    let line = std::str::from_utf8(line).map_err(|_| invalid("v1 header is not ASCII"))?;
+    let fields: Vec<&str> = line.split(' ').collect();
+    let header = match fields.as_slice() {
+        ["PROXY", "UNKNOWN", ..] => ProxyHeader {
+            source: None,
+            destination: None,
+        },
+        ["PROXY", "TCP4" | "TCP6", source, destination, source_port, destination_port] => {
+            let address = |ip: &str, port: &str| -> Result<SocketAddr, Error> {
+                let ip: IpAddr = ip.parse().map_err(|_| invalid("v1 address"))?;
+                let port: u16 = port.parse().map_err(|_| invalid("v1 port"))?;
+                Ok(SocketAddr::new(ip, port))
+            };
+            ProxyHeader {
+                source: Some(address(source, source_port)?),
+                destination: Some(address(destination, destination_port)?),
+            }
+        }
+        _ => return Err(invalid("v1 header fields")),
+    };
+    Ok(Some((header, end + 2)))
+}
+
+fn parse_v2(buf: &[u8]) -> Result<Option<(ProxyHeader, usize)>, Error> {
+    let Some([version_command, family, high, low]) = read(buf, 12) else {
+        return Ok(None);
+    };
+    let len = u16::from_be_bytes([high, low]) as usize;
+    if version_command >> 4 != 2 {
+        return Err(invalid("v2 version"));
+    }
+    let Some(addresses) = buf.get(16..16 + len) else {
+        return Ok(None);
+    };
+    let socket = |ip: Option<IpAddr>, port: usize| {
+        Some(SocketAddr::new(
+            ip?,
+            u16::from_be_bytes(read(addresses, port)?),
+        ))
+    };
+
+    let header = match (version_command & 0x0f, family >> 4) {
+        // LOCAL connections, e.g. health checks, carry no addresses worth reporting.
+        (0x0, _) => ProxyHeader {
+            source: None,
+            destination: None,
+        },
+        (0x1, 0x1) if len >= 12 => {
+            let ip = |offset| {
+                read(addresses, offset).map(|octets: [u8; 4]| Ipv4Addr::from(octets).into())
+            };
+            ProxyHeader {
+                source: socket(ip(0), 8),
+                destination: socket(ip(4), 10),
+            }
+        }
+        (0x1, 0x2) if len >= 36 => {
+            let ip = |offset| {
+                read(addresses, offset).map(|octets: [u8; 16]| Ipv6Addr::from(octets).into())
+            };
+            ProxyHeader {
+                source: socket(ip(0), 32),
+                destination: socket(ip(16), 34),
+            }
+        }
+        (0x1, 0x0 | 0x3) => ProxyHeader {
+            source: None,
+            destination: None,
+        },
+        (0x1, _) => return Err(invalid("v2 address block too short")),
+        _ => return Err(invalid("v2 command")),
+    };
+    Ok(Some((header, 16 + len)))
+}
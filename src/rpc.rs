@@ -0,0 +1,240 @@
+use crate::client::{CANCELED, TIMEOUT};
+use crate::messages::{Args, Call, Kwargs, Message, Uri, WampId, WampMessageTrait};
+use crate::value::WampValue;
+use serde::de::DeserializeOwned;
+use std::collections::HashMap;
+use std::future::Future;
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+
+/// Why a call did not produce a value.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RpcError {
+    /// The callee or dealer answered with an ERROR.
+    Error {
+        error: Uri,
+        args: Option<Args>,
+        kwargs: Option<Kwargs>,
+    },
+    /// No answer in time, either reported by the dealer or given up on locally.
+    Timeout,
+    Canceled,
+    /// The session ended before the call was answered.
+    TransportLost,
+    /// The RESULT payload does not deserialize into the expected type.
+    Payload(String),
+}
+
+#[derive(Debug, Default)]
+struct Slot {
+    outcome: Option<Result<WampValue, RpcError>>,
+    waker: Option<Waker>,
+}
+
+impl Slot {
+    fn complete(slot: &Mutex<Slot>, outcome: Result<WampValue, RpcError>) {
+        let mut slot = slot.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        slot.outcome = Some(outcome);
+        if let Some(waker) = slot.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+/// The value a RESULT stands for: a lone positional result as is, several as a list,
+/// keyword results as a dictionary when there are no positional ones, and null otherwise.
+pub fn result_value(args: &Option<Args>, kwargs: &Option<Kwargs>) -> WampValue {
+    match (args.as_deref(), kwargs) {
+        (Some([single]), _) => single.clone(),
+        (Some(args), _) if !args.is_empty() => WampValue::List(args.to_vec()),
+        (_, Some(kwargs)) if !kwargs.is_empty() => WampValue::Dict(kwargs.clone()),
+        _ => WampValue::Null,
+    }
+}
+
+/// Resolves to the outcome of one CALL, handed out by [`PendingCalls::call`].
+#[derive(Debug)]
+pub struct CallFuture<T> {
+    request: WampId,
+    slot: Arc<Mutex<Slot>>,
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<T> CallFuture<T> {
+    pub fn request(&self) -> WampId {
+        self.request
+    }
+}
+
+impl<T: DeserializeOwned> Future for CallFuture<T> {
+    type Output = Result<T, RpcError>;
+
+    fn poll(self: Pin<&mut Self>, context: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut slot = self
+            .slot
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        match slot.outcome.take() {
+            Some(Ok(value)) => Poll::Ready(deserialize_value(value)),
+            Some(Err(error)) => Poll::Ready(Err(error)),
+            None => {
+                slot.waker = Some(context.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+/// Caller-side calls awaiting their RESULT or ERROR.
+///
+/// Register every CALL sent with [`call`](PendingCalls::call) and feed incoming messages to
+/// [`on_message`](PendingCalls::on_message). Progressive results are left to the caller,
+/// only the final RESULT completes a call.
+/// # Examples
+/// ```
+/// use std::future::Future;
+/// use std::task::{Context, Poll, Waker};
+/// use wamp_helpers::messages::{Call, Message};
+/// use wamp_helpers::rpc::{PendingCalls, RpcError};
+///
+/// fn now<F: Future>(future: F) -> F::Output {
+///     let mut future = std::pin::pin!(future);
+///     match future.as_mut().poll(&mut Context::from_waker(Waker::noop())) {
+///         Poll::Ready(output) => output,
+///         Poll::Pending => panic!("future is not ready"),
+///     }
+/// }
+///
+/// let mut calls = PendingCalls::new();
+/// let add: Call = r#"[48, 1, {}, "com.example.add", [1, 2]]"#.parse().unwrap();
+/// let sum = calls.call::<i64>(&add);
+/// let div: Call = r#"[48, 2, {}, "com.example.div", [1, 0]]"#.parse().unwrap();
+/// let quotient = calls.call::<f64>(&div);
+///
+/// calls.on_message(&Message::parse_message("[50, 1, {}, [3]]").unwrap());
+/// calls.on_message(
+///     &Message::parse_message(r#"[8, 48, 2, {}, "com.example.division_by_zero"]"#).unwrap(),
+/// );
+/// assert_eq!(now(sum), Ok(3));
+/// assert!(matches!(now(quotient), Err(RpcError::Error { error, .. }) if error == "com.example.division_by_zero"));
+/// ```
+#[derive(Debug, Default)]
+pub struct PendingCalls {
+    calls: HashMap<WampId, Arc<Mutex<Slot>>>,
+}
+
+impl PendingCalls {
+    pub fn new() -> Self {
+        PendingCalls::default()
+    }
+
+    /// Track `call`, the future resolves once its answer is passed to
+    /// [`on_message`](Self::on_message).
+    pub fn call<T: DeserializeOwned>(&mut self, call: &Call) -> CallFuture<T> {
+        let slot = Arc::new(Mutex::new(Slot::default()));
+        self.calls.insert(call.request, Arc::clone(&slot));
+        CallFuture {
+            request: call.request,
+            slot,
+            marker: PhantomData,
+        }
+    }
+
+    /// Complete the call `message` answers, returns whether it answered one.
+    pub fn on_message(&mut self, message: &Message) -> bool {
+        let (request, outcome) = match message {
+            Message::MessageResult(result) => {
+                if result.details["progress"].as_bool() == Some(true) {
+                    return false;
+                }
+                (
+                    result.request,
+                    Ok(result_value(&result.args, &result.kwargs)),
+                )
+            }
+            Message::ErrorMessage(error) if error.request_type == Call::ID => {
+                let outcome = match error.error.as_str() {
+                    TIMEOUT => RpcError::Timeout,
+                    CANCELED => RpcError::Canceled,
+                    _ => RpcError::Error {
+                        error: error.error.clone(),
+                        args: error.args.clone(),
+                        kwargs: error.kwargs.clone(),
+                    },
+                };
+                (error.request, Err(outcome))
+            }
+            _ => return false,
+        };
+        self.complete(request, outcome)
+    }
+
+    /// Give up on `request` locally, it resolves to [`RpcError::Timeout`].
+    pub fn timeout(&mut self, request: WampId) -> bool {
+        self.complete(request, Err(RpcError::Timeout))
+    }
+
+    /// The session ended, every pending call resolves to [`RpcError::TransportLost`].
+    pub fn transport_lost(&mut self) {
+        for (_, slot) in self.calls.drain() {
+            Slot::complete(&slot, Err(RpcError::TransportLost));
+        }
+    }
+
+    /// Stop tracking `request` without resolving it, e.g. because its future was dropped.
+    /// Returns whether it was pending.
+    pub fn forget(&mut self, request: WampId) -> bool {
+        self.calls.remove(&request).is_some()
+    }
+
+    fn complete(&mut self, request: WampId, outcome: Result<WampValue, RpcError>) -> bool {
+        match self.calls.remove(&request) {
+            Some(slot) => {
+                Slot::complete(&slot, outcome);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.calls.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.calls.is_empty()
+    }
+}
+
+/// Deserialize `T` from the payload of a RESULT, following [`result_value`].
+/// ```
+/// use serde::Deserialize;
+/// use wamp_helpers::messages::WampResult;
+/// use wamp_helpers::rpc::{deserialize_result, RpcError};
+///
+/// #[derive(Debug, PartialEq, Deserialize)]
+/// struct Point {
+///     x: i64,
+///     y: i64,
+/// }
+///
+/// let result: WampResult = r#"[50, 7, {}, [], {"x": 1, "y": 2}]"#.parse().unwrap();
+/// let point: Point = deserialize_result(&result.args, &result.kwargs).unwrap();
+/// assert_eq!(point, Point { x: 1, y: 2 });
+/// assert!(matches!(
+///     deserialize_result::<String>(&result.args, &result.kwargs),
+///     Err(RpcError::Payload(_))
+/// ));
+/// ```
+pub fn deserialize_result<T: DeserializeOwned>(
+    args: &Option<Args>,
+    kwargs: &Option<Kwargs>,
+) -> Result<T, RpcError> {
+    deserialize_value(result_value(args, kwargs))
+}
+
+fn deserialize_value<T: DeserializeOwned>(value: WampValue) -> Result<T, RpcError> {
+    T::deserialize(value).map_err(|error| RpcError::Payload(error.to_string()))
+}
@@ -0,0 +1,984 @@
+//! A ready-to-run router on tokio, built from the crate's broker and dealer pieces.
+//!
+//! Every realm is an actor task owning its [`SubscriptionStore`] and registrations, fed
+//! through a bounded inbox. Every connection is a session task that runs the handshake, then
+//! checks the client's messages with a [`Validator`] and a [`FeatureGate`], forwards them to
+//! its realm and writes what the realm puts in its bounded mailbox. Sessions join
+//! anonymously, HELLO is answered with WELCOME at once.
+//!
+//! The realm routes calls like a dealer built from [`ConcurrencyTracker`] and [`Rerouter`]:
+//! shared registrations pick callees by their `invoke` policy, busy callees spill over or
+//! queue according to the [`BusyPolicy`], and calls a callee refuses as unavailable move on
+//! to the next one.
+//!
+//! A callee can be drained for a deploy with [`RouterHandle::pause`]: it gets no new
+//! INVOCATIONs or EVENTs, while the calls it is running still complete. Subscribers of
+//! `wamp.registration.on_unregister` and `on_register` see its registrations go and return.
+//!
+//! Supervision: a session task that ends, panicked or not, is removed from its realm, which
+//! drops its subscriptions and registrations and fails the calls it was running. A realm
+//! actor that panics is restarted with empty state, the sessions of the old one are closed.
+
+use crate::acceptor::Serializer;
+use crate::broker::SubscriptionStore;
+use crate::client::{CANCELED, UNAVAILABLE};
+use crate::correlation::Direction;
+use crate::dealer::{
+    BusyPolicy, ConcurrencyTracker, Dispatch, Endpoint, Reroute, Rerouter, RoutedCall,
+    NO_SUCH_PROCEDURE,
+};
+use crate::framed::Framed;
+use crate::handshake::{feature_denial, FeatureGate, FeaturePolicy, HelloAnalysis, WelcomeDetails};
+use crate::messages::{
+    Abort, Call, Cancel, ErrorMessage, Event, Goodbye, GoodbyeDetails, Interrupt, Message, Publish,
+    Published, Register, Registered, RequestType, Roles, Subscribed, Unregister, Unregistered,
+    Unsubscribed, Uri, WampId, WampResult, Yield,
+};
+use crate::meta::{
+    registration_pause_publication, INVALID_ARGUMENT, NO_SUCH_REGISTRATION, NO_SUCH_SUBSCRIPTION,
+};
+use crate::options::{guard_identity, PublishOptions, SpoofPolicy};
+use crate::session::{Session, SessionState, Side};
+use crate::sim::{
+    CalleeSelector, IdGenerator, InvocationPolicy, RandomIdGenerator, SequentialIdGenerator,
+};
+use crate::transport::Transport;
+use crate::uri::MatchPolicy;
+use crate::validator::{Validator, Verdict, ViolationKind, ViolationPolicy};
+use std::collections::HashMap;
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::{mpsc, oneshot, Mutex};
+use tokio::task::JoinHandle;
+use tokio::time::Interval;
+
+/// Messages a session's mailbox holds by default, see [`Router::mailbox`].
+pub const DEFAULT_MAILBOX: usize = 256;
+/// Commands a realm's inbox holds by default, see [`Router::inbox`].
+pub const DEFAULT_INBOX: usize = 1024;
+
+/// Advanced broker features the runtime implements and announces in its WELCOME.
+pub const BROKER_FEATURES: &[&str] = &[
+    "pattern_based_subscription",
+    "publisher_exclusion",
+    "subscriber_blackwhite_listing",
+];
+/// Advanced dealer features the runtime implements and announces in its WELCOME.
+pub const DEALER_FEATURES: &[&str] = &[
+    "call_canceling",
+    "progressive_call_results",
+    "shared_registration",
+];
+
+pub const NO_SUCH_REALM: &str = "wamp.error.no_such_realm";
+pub const PROCEDURE_ALREADY_EXISTS: &str = "wamp.error.procedure_already_exists";
+pub const PROCEDURE_EXISTS_INVOCATION_POLICY_CONFLICT: &str =
+    "wamp.error.procedure_exists_invocation_policy_conflict";
+pub const PROTOCOL_VIOLATION: &str = "wamp.error.protocol_violation";
+pub const GOODBYE_AND_OUT: &str = "wamp.close.goodbye_and_out";
+pub const SYSTEM_SHUTDOWN: &str = "wamp.close.system_shutdown";
+
+/// Configuration of a router, [`start`](Router::start) spawns it.
+/// # Examples
+/// ```
+/// use wamp_helpers::acceptor::Serializer;
+/// use wamp_helpers::framed::Framed;
+/// use wamp_helpers::memory::MemoryTransport;
+/// use wamp_helpers::messages::Message;
+/// use wamp_helpers::runtime::Router;
+///
+/// let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+/// runtime.block_on(async {
+///     let router = Router::new().realm("realm1").start();
+///     let (client, server) = MemoryTransport::pair();
+///     router.attach(server, Serializer::Json);
+///
+///     let mut client = Framed::new(client, Serializer::Json);
+///     let hello = r#"[1, "realm1", {"roles": {"caller": {}, "callee": {}}}]"#;
+///     client.send(Message::parse_message(hello).unwrap()).await.unwrap();
+///     assert!(matches!(client.next().await, Some(Ok(Message::Welcome(_)))));
+///
+///     for raw in [r#"[64, 1, {}, "com.example.add"]"#, r#"[48, 2, {}, "com.example.add", [1, 2]]"#] {
+///         client.send(Message::parse_message(raw).unwrap()).await.unwrap();
+///     }
+///     assert!(matches!(client.next().await, Some(Ok(Message::Registered(_)))));
+///     let Some(Ok(Message::Invocation(invocation))) = client.next().await else { panic!() };
+///     let answer = format!(r#"[70, {}, {{}}, [3]]"#, invocation.request);
+///     client.send(Message::parse_message(&answer).unwrap()).await.unwrap();
+///     let Some(Ok(Message::MessageResult(result))) = client.next().await else { panic!() };
+///     assert_eq!(result.request, 2);
+/// });
+/// ```
+#[derive(Debug, Clone)]
+pub struct Router {
+    realms: Vec<Uri>,
+    mailbox: usize,
+    inbox: usize,
+    policies: Policies,
+}
+
+impl Default for Router {
+    fn default() -> Self {
+        Router {
+            realms: Vec::new(),
+            mailbox: DEFAULT_MAILBOX,
+            inbox: DEFAULT_INBOX,
+            policies: Policies::default(),
+        }
+    }
+}
+
+impl Router {
+    pub fn new() -> Self {
+        Router::default()
+    }
+
+    /// Serve `realm`, HELLOs for other realms are aborted with `wamp.error.no_such_realm`.
+    pub fn realm(mut self, realm: &str) -> Self {
+        self.realms.push(Uri::from(realm.to_string()));
+        self
+    }
+
+    /// Messages queued for one session before it counts as too slow. The realm never waits
+    /// for a session, one whose mailbox is full is disconnected.
+    pub fn mailbox(mut self, capacity: usize) -> Self {
+        self.mailbox = capacity.max(1);
+        self
+    }
+
+    /// Commands queued for one realm, sessions wait for room before reading further.
+    pub fn inbox(mut self, capacity: usize) -> Self {
+        self.inbox = capacity.max(1);
+        self
+    }
+
+    /// How protocol violations of clients are answered, see [`Validator::enforce`].
+    pub fn violations(mut self, policy: ViolationPolicy) -> Self {
+        self.policies.violations = policy;
+        self
+    }
+
+    /// What happens to options of features a client did not announce, see [`FeatureGate`].
+    pub fn features(mut self, policy: FeaturePolicy) -> Self {
+        self.policies.features = policy;
+        self
+    }
+
+    /// What happens to calls while every callee of a registration runs as many invocations
+    /// as its `concurrency` option allows. Queued calls time out on tokio's timer, which
+    /// must be enabled on the runtime for [`BusyPolicy::Queue`].
+    pub fn busy(mut self, policy: BusyPolicy) -> Self {
+        self.policies.busy = policy;
+        self
+    }
+
+    /// Spawn the realm actors on the current tokio runtime, panics outside of one.
+    pub fn start(self) -> RouterHandle {
+        let mut realms = HashMap::new();
+        for realm in self.realms {
+            let (sender, commands) = mpsc::channel(self.inbox);
+            // Made here, so a runtime without timers fails now rather than in the actor.
+            let expiry = match self.policies.busy {
+                BusyPolicy::Queue { timeout, .. } => Some(tokio::time::interval(
+                    (timeout / 4).max(Duration::from_millis(1)),
+                )),
+                _ => None,
+            };
+            supervise(Inbox { commands, expiry }, self.policies.busy);
+            realms.insert(realm, sender);
+        }
+        RouterHandle {
+            realms: Arc::new(realms),
+            mailbox: self.mailbox,
+            policies: self.policies,
+        }
+    }
+}
+
+/// A running router, clones share it. The realms stop once every handle and session is gone.
+#[derive(Debug, Clone)]
+pub struct RouterHandle {
+    realms: Arc<HashMap<Uri, mpsc::Sender<Command>>>,
+    mailbox: usize,
+    policies: Policies,
+}
+
+impl RouterHandle {
+    /// Serve a new connection in its own task. The handle completes once the session ended
+    /// and its realm forgot it.
+    pub fn attach<T>(&self, transport: T, serializer: Serializer) -> JoinHandle<()>
+    where
+        T: Transport + Send + 'static,
+    {
+        let membership = Arc::new(OnceLock::new());
+        let session = tokio::spawn(serve(
+            Framed::new(transport, serializer),
+            Arc::clone(&self.realms),
+            self.mailbox,
+            self.policies,
+            Arc::clone(&membership),
+        ));
+        tokio::spawn(async move {
+            // Ended or panicked, the realm must drop the session's state either way.
+            let _ = session.await;
+            if let Some((realm, session)) = membership.get() {
+                let _ = realm.send(Command::Leave { session: *session }).await;
+            }
+        })
+    }
+
+    /// Stop dispatching new INVOCATIONs and EVENTs to `session` of `realm`, see
+    /// [`Session::pause`]. Calls of its registrations go to the other callees or fail with
+    /// `wamp.error.unavailable`, the invocations it is running still complete.
+    pub async fn pause(&self, realm: &str, session: WampId) {
+        self.set_paused(realm, session, true).await
+    }
+
+    /// Dispatch to `session` again after [`pause`](Self::pause).
+    pub async fn resume(&self, realm: &str, session: WampId) {
+        self.set_paused(realm, session, false).await
+    }
+
+    async fn set_paused(&self, realm: &str, session: WampId, paused: bool) {
+        if let Some(realm) = self.realms.get(&Uri::from(realm.to_string())) {
+            let _ = realm.send(Command::Pause { session, paused }).await;
+        }
+    }
+
+    /// Say GOODBYE with `wamp.close.system_shutdown` to every session. Sessions end as their
+    /// clients answer.
+    pub async fn shutdown(&self) {
+        for realm in self.realms.values() {
+            let _ = realm.send(Command::Shutdown).await;
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Policies {
+    violations: ViolationPolicy,
+    features: FeaturePolicy,
+    busy: BusyPolicy,
+}
+
+#[derive(Debug)]
+enum Command {
+    Join {
+        mailbox: mpsc::Sender<Message>,
+        joined: oneshot::Sender<WampId>,
+    },
+    Inbound {
+        session: WampId,
+        message: Message,
+    },
+    Leave {
+        session: WampId,
+    },
+    Pause {
+        session: WampId,
+        paused: bool,
+    },
+    Shutdown,
+}
+
+/// What a realm actor waits on, handed to the next actor when one panics.
+#[derive(Debug)]
+struct Inbox {
+    commands: mpsc::Receiver<Command>,
+    /// Ticks to time out queued calls, only for [`BusyPolicy::Queue`].
+    expiry: Option<Interval>,
+}
+
+/// The realm and session id a session task joined as.
+type Membership = Arc<OnceLock<(mpsc::Sender<Command>, WampId)>>;
+
+/// Run realm actors on `inbox` until it closes, starting a fresh one after each panic.
+fn supervise(inbox: Inbox, busy: BusyPolicy) {
+    let inbox = Arc::new(Mutex::new(inbox));
+    tokio::spawn(async move {
+        loop {
+            let actor = tokio::spawn(Realm::new(busy).run(Arc::clone(&inbox)));
+            match actor.await {
+                Err(error) if error.is_panic() => continue,
+                _ => break,
+            }
+        }
+    });
+}
+
+async fn serve<T: Transport + Send>(
+    mut framed: Framed<T>,
+    realms: Arc<HashMap<Uri, mpsc::Sender<Command>>>,
+    capacity: usize,
+    policies: Policies,
+    membership: Membership,
+) {
+    let mut validator = Validator::new(Side::Router).policy(policies.violations);
+    let hello = match framed.next().await {
+        Some(Ok(Message::Hello(hello))) => hello,
+        Some(_) => return abort(&mut framed, PROTOCOL_VIOLATION, "expected HELLO").await,
+        None => return,
+    };
+    let gate = FeatureGate::new(&HelloAnalysis::from(&hello))
+        .policy(policies.features)
+        .supported(Roles::Broker, BROKER_FEATURES)
+        .supported(Roles::Dealer, DEALER_FEATURES);
+    let Some(realm) = realms.get(&hello.realm).cloned() else {
+        return abort(&mut framed, NO_SUCH_REALM, "no such realm").await;
+    };
+    if let Verdict::Abort(abort, _) = validator.enforce(Direction::Inbound, &Message::Hello(hello))
+    {
+        let _ = framed.send(Message::Abort(abort)).await;
+        let _ = framed.close(PROTOCOL_VIOLATION).await;
+        return;
+    }
+
+    let (mailbox, mut received) = mpsc::channel(capacity);
+    let (joined, id) = oneshot::channel();
+    if realm.send(Command::Join { mailbox, joined }).await.is_err() {
+        return;
+    }
+    let Ok(id) = id.await else {
+        return;
+    };
+    let _ = membership.set((realm.clone(), id));
+    let welcome = WelcomeDetails::new()
+        .supported(Roles::Broker, BROKER_FEATURES)
+        .supported(Roles::Dealer, DEALER_FEATURES)
+        .welcome(id);
+    let welcome = Message::Welcome(welcome);
+    validator.observe(Direction::Outbound, &welcome);
+    if framed.send(welcome).await.is_err() {
+        return;
+    }
+
+    loop {
+        tokio::select! {
+            outbound = received.recv() => {
+                // The realm dropped the mailbox: the session fell behind or the realm restarted.
+                let Some(message) = outbound else {
+                    break;
+                };
+                // Work the realm routed after its GOODBYE is not sent.
+                let late = validator
+                    .observe(Direction::Outbound, &message)
+                    .iter()
+                    .any(|violation| violation.kind == ViolationKind::BadSequencing);
+                if !late && framed.send(message).await.is_err() {
+                    break;
+                }
+            }
+            inbound = framed.next() => {
+                let mut message = match inbound {
+                    Some(Ok(Message::Abort(_))) | None => break,
+                    Some(Ok(message)) => message,
+                    Some(Err(_)) => {
+                        abort(&mut framed, PROTOCOL_VIOLATION, "malformed message").await;
+                        break;
+                    }
+                };
+                let refusal = match validator.enforce(Direction::Inbound, &message) {
+                    Verdict::Accept(_) => gate
+                        .check(&mut message)
+                        .err()
+                        .and_then(|error| feature_denial(&message, &error)),
+                    Verdict::Reject(error, _) => Some(error),
+                    Verdict::Abort(abort, _) => {
+                        let _ = framed.send(Message::Abort(abort)).await;
+                        break;
+                    }
+                };
+                if let Some(error) = refusal {
+                    let error = Message::ErrorMessage(error);
+                    validator.observe(Direction::Outbound, &error);
+                    if framed.send(error).await.is_err() {
+                        break;
+                    }
+                    continue;
+                }
+                match validator.session().state() {
+                    SessionState::Closing { .. } => {
+                        let goodbye = Goodbye::new(GOODBYE_AND_OUT.into(), GoodbyeDetails::default());
+                        let _ = framed.send(Message::Goodbye(goodbye)).await;
+                        break;
+                    }
+                    // The client answered the router's GOODBYE.
+                    SessionState::Closed => break,
+                    _ => {
+                        let command = Command::Inbound { session: id, message };
+                        if realm.send(command).await.is_err() {
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+    }
+    let _ = framed.close("wamp.close.normal").await;
+}
+
+async fn abort<T: Transport + Send>(framed: &mut Framed<T>, reason: &str, message: &str) {
+    let abort = Abort {
+        details: json::object! { message: message },
+        reason: reason.to_string().into(),
+    };
+    let _ = framed.send(Message::Abort(abort)).await;
+    let _ = framed.close(reason).await;
+}
+
+/// A procedure and the callees sharing its registration.
+#[derive(Debug)]
+struct Procedure {
+    registration: WampId,
+    policy: InvocationPolicy,
+    selector: CalleeSelector,
+    /// In registration order.
+    callees: Vec<WampId>,
+}
+
+/// A session joined to a realm.
+#[derive(Debug)]
+struct Member {
+    mailbox: mpsc::Sender<Message>,
+    /// Only tracks whether the session is paused, the session task validates the rest.
+    session: Session,
+}
+
+/// State of one realm, owned by its actor task.
+#[derive(Debug)]
+struct Realm {
+    sessions: HashMap<WampId, Member>,
+    /// Global scope ids: sessions and publications.
+    global_ids: RandomIdGenerator,
+    /// Router scope ids: registrations and invocations.
+    router_ids: SequentialIdGenerator,
+    subscriptions: SubscriptionStore,
+    procedures: HashMap<Uri, Procedure>,
+    registrations: HashMap<WampId, Uri>,
+    tracker: ConcurrencyTracker,
+    rerouter: Rerouter,
+    /// The INVOCATION of every call in flight, by caller and CALL request id.
+    calls: HashMap<(WampId, WampId), WampId>,
+}
+
+impl Realm {
+    fn new(busy: BusyPolicy) -> Self {
+        let seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_nanos() as u64);
+        Realm {
+            sessions: HashMap::new(),
+            global_ids: RandomIdGenerator::new(seed),
+            router_ids: SequentialIdGenerator::default(),
+            subscriptions: SubscriptionStore::new(1),
+            procedures: HashMap::new(),
+            registrations: HashMap::new(),
+            tracker: ConcurrencyTracker::new().policy(busy),
+            // Every callee of a shared registration is tried once at most.
+            rerouter: Rerouter::new(usize::MAX),
+            calls: HashMap::new(),
+        }
+    }
+
+    async fn run(mut self, inbox: Arc<Mutex<Inbox>>) {
+        // A panic drops the guard, the restarted actor takes over the inbox.
+        let mut inbox = inbox.lock().await;
+        let Inbox { commands, expiry } = &mut *inbox;
+        loop {
+            let command = tokio::select! {
+                command = commands.recv() => command,
+                _ = tick(expiry) => {
+                    self.expire();
+                    continue;
+                }
+            };
+            let Some(command) = command else {
+                break;
+            };
+            match command {
+                Command::Join { mailbox, joined } => self.join(mailbox, joined),
+                Command::Inbound { session, message } => {
+                    // Sessions of a previous actor are not known here, they are closing.
+                    if self.sessions.contains_key(&session) {
+                        self.route(session, message);
+                    }
+                }
+                Command::Leave { session } => self.leave(session),
+                Command::Pause { session, paused } => self.pause(session, paused),
+                Command::Shutdown => {
+                    let sessions: Vec<WampId> = self.sessions.keys().copied().collect();
+                    for session in sessions {
+                        let goodbye =
+                            Goodbye::new(SYSTEM_SHUTDOWN.into(), GoodbyeDetails::default());
+                        self.deliver(session, Message::Goodbye(goodbye));
+                    }
+                }
+            }
+        }
+    }
+
+    fn join(&mut self, mailbox: mpsc::Sender<Message>, joined: oneshot::Sender<WampId>) {
+        let mut session = self.global_ids.next_id();
+        while self.sessions.contains_key(&session) {
+            session = self.global_ids.next_id();
+        }
+        if joined.send(session).is_ok() {
+            let member = Member {
+                mailbox,
+                session: Session::new(Side::Router),
+            };
+            self.sessions.insert(session, member);
+        }
+    }
+
+    /// Queue `message` for `session` without waiting. A session whose mailbox is full is
+    /// dropped, its task closes the connection and then leaves. EVENTs for a paused
+    /// session are skipped.
+    fn deliver(&mut self, session: WampId, message: Message) {
+        if let Some(member) = self.sessions.get(&session) {
+            if !member.session.accepts_dispatch(&message) {
+                return;
+            }
+            if member.mailbox.try_send(message).is_err() {
+                self.sessions.remove(&session);
+            }
+        }
+    }
+
+    fn is_paused(&self, session: WampId) -> bool {
+        self.sessions
+            .get(&session)
+            .is_some_and(|member| member.session.is_paused())
+    }
+
+    /// Pause or resume `session`, announcing it on the registration meta topics for each
+    /// registration it is a callee of.
+    fn pause(&mut self, session: WampId, paused: bool) {
+        let Some(member) = self.sessions.get_mut(&session) else {
+            return;
+        };
+        let changed = if paused {
+            member.session.pause()
+        } else {
+            member.session.resume()
+        };
+        if !changed {
+            return;
+        }
+        let registrations: Vec<WampId> = self
+            .procedures
+            .values()
+            .filter(|procedure| procedure.callees.contains(&session))
+            .map(|procedure| procedure.registration)
+            .collect();
+        for registration in registrations {
+            let request = self.router_ids.next_id();
+            let publish = registration_pause_publication(request, session, registration, paused);
+            // The realm publishes as no session, nobody is excluded.
+            self.publish(0, publish);
+        }
+    }
+
+    fn route(&mut self, session: WampId, mut message: Message) {
+        // Only the realm discloses callers and publishers, whatever the client claims is
+        // dropped before routing.
+        let _ = guard_identity(&mut message, SpoofPolicy::Strip);
+        match message {
+            Message::Subscribe(subscribe) => match MatchPolicy::from_options(&subscribe.options) {
+                Some(policy) => {
+                    let (subscription, _) =
+                        self.subscriptions
+                            .subscribe_with(&subscribe.topic, policy, session);
+                    let subscribed = Subscribed {
+                        request: subscribe.request,
+                        subscription,
+                    };
+                    self.deliver(session, Message::Subscribed(subscribed));
+                }
+                None => {
+                    let error = ErrorMessage::for_subscribe(&subscribe, INVALID_ARGUMENT.into());
+                    self.deliver(session, Message::ErrorMessage(error));
+                }
+            },
+            Message::Unsubscribe(unsubscribe) => {
+                let answer = match self
+                    .subscriptions
+                    .unsubscribe(unsubscribe.subscription, session)
+                {
+                    Some(_) => Message::Unsubscribed(Unsubscribed {
+                        request: unsubscribe.request,
+                        details: None,
+                    }),
+                    None => Message::ErrorMessage(ErrorMessage::for_unsubscribe(
+                        &unsubscribe,
+                        NO_SUCH_SUBSCRIPTION.into(),
+                    )),
+                };
+                self.deliver(session, answer);
+            }
+            Message::Publish(publish) => self.publish(session, publish),
+            Message::Register(register) => self.register(session, register),
+            Message::Unregister(unregister) => self.unregister(session, unregister),
+            Message::Call(call) => self.call(session, call),
+            Message::Cancel(cancel) => self.cancel(session, cancel),
+            Message::Yield(answer) => self.answer(session, answer),
+            Message::ErrorMessage(error) if error.request_type == RequestType::Invocation as u8 => {
+                self.fail(session, error)
+            }
+            // Other messages are not sent by clients.
+            _ => {}
+        }
+    }
+
+    fn publish(&mut self, publisher: WampId, publish: Publish) {
+        let options = PublishOptions::from(&publish.options);
+        let publication = self.global_ids.next_id();
+        for matched in self
+            .subscriptions
+            .would_receive(&publish.topic, publisher, &options)
+        {
+            let mut details = json::object! {};
+            if matched.match_policy != MatchPolicy::Exact {
+                details["topic"] = publish.topic.as_str().into();
+            }
+            for receiver in matched.receivers {
+                let event = Event {
+                    subscription: matched.subscription,
+                    publication,
+                    details: details.clone(),
+                    args: publish.args.clone(),
+                    kwargs: publish.kwargs.clone(),
+                };
+                self.deliver(receiver, Message::Event(event));
+            }
+        }
+        if options.acknowledge {
+            let published = Published {
+                request: publish.request,
+                publication,
+            };
+            self.deliver(publisher, Message::Published(published));
+        }
+    }
+
+    fn register(&mut self, session: WampId, register: Register) {
+        let policy = InvocationPolicy::from_options(&register.options);
+        let registration = match self.procedures.get_mut(&register.procedure) {
+            Some(procedure) if procedure.policy != policy => {
+                Err(PROCEDURE_EXISTS_INVOCATION_POLICY_CONFLICT)
+            }
+            Some(procedure)
+                if policy == InvocationPolicy::Single || procedure.callees.contains(&session) =>
+            {
+                Err(PROCEDURE_ALREADY_EXISTS)
+            }
+            Some(procedure) => {
+                procedure.callees.push(session);
+                Ok(procedure.registration)
+            }
+            None => {
+                let registration = self.router_ids.next_id();
+                let procedure = Procedure {
+                    registration,
+                    policy,
+                    selector: CalleeSelector::new(policy, self.global_ids.next_id()),
+                    callees: vec![session],
+                };
+                self.procedures
+                    .insert(register.procedure.clone(), procedure);
+                self.registrations
+                    .insert(registration, register.procedure.clone());
+                Ok(registration)
+            }
+        };
+        let answer = match registration {
+            Ok(registration) => {
+                let endpoint = Endpoint {
+                    registration,
+                    callee: session,
+                };
+                self.tracker.register(endpoint, &register.options);
+                Message::Registered(Registered {
+                    request: register.request,
+                    registration,
+                })
+            }
+            Err(error) => {
+                Message::ErrorMessage(ErrorMessage::for_register(&register, error.into()))
+            }
+        };
+        self.deliver(session, answer);
+    }
+
+    fn unregister(&mut self, session: WampId, unregister: Unregister) {
+        let owned = self
+            .registrations
+            .get(&unregister.registration)
+            .and_then(|procedure| self.procedures.get(procedure))
+            .is_some_and(|procedure| procedure.callees.contains(&session));
+        if !owned {
+            let error = ErrorMessage::for_unregister(&unregister, NO_SUCH_REGISTRATION.into());
+            return self.deliver(session, Message::ErrorMessage(error));
+        }
+        self.remove_endpoint(Endpoint {
+            registration: unregister.registration,
+            callee: session,
+        });
+        let unregistered = Unregistered {
+            request: unregister.request,
+            details: None,
+        };
+        self.deliver(session, Message::Unregistered(unregistered));
+    }
+
+    /// Take a callee out of a registration, which goes away with its last callee. Calls
+    /// still queued for it then fail, invocations already running are answered as usual.
+    fn remove_endpoint(&mut self, endpoint: Endpoint) {
+        let Some(uri) = self.registrations.get(&endpoint.registration).cloned() else {
+            return;
+        };
+        let Some(procedure) = self.procedures.get_mut(&uri) else {
+            return;
+        };
+        procedure
+            .callees
+            .retain(|callee| *callee != endpoint.callee);
+        let last = procedure.callees.is_empty();
+        if last {
+            self.procedures.remove(&uri);
+            self.registrations.remove(&endpoint.registration);
+        }
+        for queued in self.tracker.unregister(endpoint, last) {
+            let error = queued.error(NO_SUCH_PROCEDURE);
+            self.deliver(queued.caller, Message::ErrorMessage(error));
+        }
+    }
+
+    /// Callees of `registration` in the order its invocation policy prefers them: the one
+    /// it selects, then the others in registration order. Paused callees are left out.
+    fn candidates(&mut self, registration: WampId) -> Vec<WampId> {
+        let Some(procedure) = self
+            .registrations
+            .get(&registration)
+            .and_then(|uri| self.procedures.get_mut(uri))
+        else {
+            return Vec::new();
+        };
+        let mut callees = procedure.callees.clone();
+        if let Some(selected) = procedure.selector.select(callees.len()) {
+            callees.rotate_left(selected);
+        }
+        callees.retain(|callee| !self.is_paused(*callee));
+        callees
+    }
+
+    fn call(&mut self, session: WampId, call: Call) {
+        let Some(registration) = self
+            .procedures
+            .get(&call.procedure)
+            .map(|procedure| procedure.registration)
+        else {
+            let error = ErrorMessage::for_call(&call, NO_SUCH_PROCEDURE.into());
+            return self.deliver(session, Message::ErrorMessage(error));
+        };
+        let callees = self.candidates(registration);
+        if callees.is_empty() {
+            // Every callee is paused.
+            let error = ErrorMessage::for_call(&call, UNAVAILABLE.into());
+            return self.deliver(session, Message::ErrorMessage(error));
+        }
+        let now = Instant::now();
+        match self
+            .tracker
+            .dispatch(registration, &callees, session, call.clone(), now)
+        {
+            Dispatch::Invoke(endpoint) => self.invoke(RoutedCall::new(endpoint, session, call)),
+            Dispatch::Queued => {}
+            Dispatch::Rejected { call, error } => {
+                self.deliver(session, Message::ErrorMessage(call.error(error)));
+            }
+        }
+    }
+
+    fn invoke(&mut self, routed: RoutedCall) {
+        let request = self.router_ids.next_id();
+        let callee = routed.endpoint.callee;
+        self.tracker.start(routed.endpoint, request);
+        self.calls
+            .insert((routed.caller, routed.call.request), request);
+        let invocation = routed.invocation(request);
+        self.rerouter.invoked(request, routed);
+        self.deliver(callee, Message::Invocation(invocation));
+    }
+
+    /// The invocation `request` is over, its callee takes the next queued call. The call
+    /// is dispatched anew when the callee is paused.
+    fn finished(&mut self, request: WampId) {
+        if let Some((endpoint, queued)) = self.tracker.finish(request) {
+            if self.is_paused(endpoint.callee) {
+                self.call(queued.caller, queued.call);
+            } else {
+                self.invoke(RoutedCall::new(endpoint, queued.caller, queued.call));
+            }
+        }
+    }
+
+    /// Answer CANCEL with `wamp.error.canceled` at once and INTERRUPT the callee, unless the
+    /// mode is `skip`. `kill` does not wait for the callee's ERROR either, it would find no
+    /// invocation to answer.
+    fn cancel(&mut self, session: WampId, cancel: Cancel) {
+        if let Some(request) = self.calls.remove(&(session, cancel.request)) {
+            let mode = cancel.options["mode"].as_str().unwrap_or("killnowait");
+            if let Some(routed) = self.rerouter.completed(request) {
+                if mode != "skip" {
+                    let interrupt = Interrupt {
+                        request,
+                        options: json::object! { mode: mode },
+                    };
+                    self.deliver(routed.endpoint.callee, Message::Interrupt(interrupt));
+                }
+            }
+            self.finished(request);
+        } else if self.tracker.cancel(session, cancel.request).is_none() {
+            // Answered already, or never called.
+            return;
+        }
+        let error = ErrorMessage::for_request(RequestType::Call, cancel.request, CANCELED.into());
+        self.deliver(session, Message::ErrorMessage(error));
+    }
+
+    fn answer(&mut self, session: WampId, answer: Yield) {
+        let Some(routed) = self
+            .rerouter
+            .get(answer.request)
+            .filter(|routed| routed.endpoint.callee == session)
+        else {
+            return;
+        };
+        let (caller, request) = (routed.caller, routed.call.request);
+        let receive_progress = routed.call.options["receive_progress"].as_bool() == Some(true);
+        let details = if answer.options["progress"].as_bool() == Some(true) {
+            // Progressive results only go to callers that asked for them.
+            if !receive_progress {
+                return;
+            }
+            json::object! { progress: true }
+        } else {
+            self.rerouter.completed(answer.request);
+            self.calls.remove(&(caller, request));
+            self.finished(answer.request);
+            json::object! {}
+        };
+        let result = WampResult {
+            request,
+            details,
+            args: answer.args,
+            kwargs: answer.kwargs,
+        };
+        self.deliver(caller, Message::MessageResult(result));
+    }
+
+    fn fail(&mut self, session: WampId, error: ErrorMessage) {
+        let Some(registration) = self
+            .rerouter
+            .get(error.request)
+            .filter(|routed| routed.endpoint.callee == session)
+            .map(|routed| routed.endpoint.registration)
+        else {
+            return;
+        };
+        let callees = self.candidates(registration);
+        let outcome = self.rerouter.on_error(&error, &callees);
+        self.finished(error.request);
+        match outcome {
+            Some(Reroute::Retry(routed)) => self.invoke(routed),
+            Some(Reroute::Forward { caller, error }) => {
+                self.calls.remove(&(caller, error.request));
+                self.deliver(caller, Message::ErrorMessage(error));
+            }
+            None => {}
+        }
+    }
+
+    /// Answer the queued calls that waited too long for a free callee.
+    fn expire(&mut self) {
+        for queued in self.tracker.expire(Instant::now()) {
+            let error = queued.error(UNAVAILABLE);
+            self.deliver(queued.caller, Message::ErrorMessage(error));
+        }
+    }
+
+    fn leave(&mut self, session: WampId) {
+        self.sessions.remove(&session);
+        self.subscriptions.remove_session(session);
+
+        // Nobody waits for the calls of the session any more.
+        let abandoned: Vec<WampId> = self
+            .calls
+            .iter()
+            .filter(|((caller, _), _)| *caller == session)
+            .map(|(_, request)| *request)
+            .collect();
+        self.calls.retain(|(caller, _), _| *caller != session);
+        for request in abandoned {
+            if let Some(routed) = self.rerouter.completed(request) {
+                let interrupt = Interrupt {
+                    request,
+                    options: json::object! { mode: "killnowait" },
+                };
+                self.deliver(routed.endpoint.callee, Message::Interrupt(interrupt));
+            }
+            self.finished(request);
+        }
+
+        let endpoints: Vec<Endpoint> = self
+            .procedures
+            .values()
+            .filter(|procedure| procedure.callees.contains(&session))
+            .map(|procedure| Endpoint {
+                registration: procedure.registration,
+                callee: session,
+            })
+            .collect();
+        for endpoint in endpoints {
+            self.remove_endpoint(endpoint);
+        }
+        // The calls the session was running move to the callees left, or are canceled.
+        let (procedures, registrations) = (&self.procedures, &self.registrations);
+        let sessions = &self.sessions;
+        let outcomes = self.rerouter.callee_lost(session, |registration| {
+            let callees = registrations
+                .get(&registration)
+                .and_then(|uri| procedures.get(uri))
+                .map_or(&[][..], |procedure| &procedure.callees);
+            callees
+                .iter()
+                .copied()
+                .filter(|callee| {
+                    sessions
+                        .get(callee)
+                        .is_some_and(|member| !member.session.is_paused())
+                })
+                .collect()
+        });
+        for outcome in outcomes {
+            match outcome {
+                Reroute::Retry(routed) => self.invoke(routed),
+                Reroute::Forward { caller, error } => {
+                    self.calls.remove(&(caller, error.request));
+                    self.deliver(caller, Message::ErrorMessage(error));
+                }
+            }
+        }
+    }
+}
+
+/// Wait for the next tick of `expiry`, forever without one.
+async fn tick(expiry: &mut Option<Interval>) {
+    match expiry {
+        Some(expiry) => {
+            expiry.tick().await;
+        }
+        None => std::future::pending().await,
+    }
+}
@@ -0,0 +1,208 @@
+use crate::arena::{Node, Tokenizer, Visitor};
+use crate::arity::{arity, Arity, Field, FieldKind};
+use crate::error::Error;
+use crate::messages::{
+    validate_args, validate_array_argument, validate_dict_argument, validate_optional_dict,
+    validate_str_argument, validate_u64_argument, validate_u8_argument, MAX_ID,
+};
+use json::JsonValue;
+use std::ops::Range;
+
+/// Most elements a standard message has, ERROR with both payload elements.
+const MAX_ELEMENTS: usize = 7;
+
+/// Layout of a frame checked by [`scan_frame`]: its message type and where each element sits
+/// in the frame.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrameShape {
+    arity: &'static Arity,
+    len: usize,
+    spans: [Range<usize>; MAX_ELEMENTS],
+}
+
+impl FrameShape {
+    pub fn message_type(&self) -> u8 {
+        self.arity.id
+    }
+
+    pub fn arity(&self) -> &'static Arity {
+        self.arity
+    }
+
+    /// Byte ranges of the elements in wire order, starting with the message code.
+    pub fn spans(&self) -> &[Range<usize>] {
+        self.spans.get(..self.len).unwrap_or(&self.spans)
+    }
+
+    /// Byte range of the element with the given [`Field`] name, e.g. `"Procedure"`.
+    pub fn span(&self, name: &str) -> Option<Range<usize>> {
+        self.arity
+            .fields
+            .iter()
+            .take(self.len)
+            .position(|field| field.name == name)
+            .and_then(|index| self.spans.get(index).cloned())
+    }
+
+    /// Raw JSON of the element at `index` in the frame that was scanned.
+    pub fn element<'a>(&self, frame: &'a str, index: usize) -> Option<&'a str> {
+        self.spans()
+            .get(index)
+            .and_then(|span| frame.get(span.clone()))
+    }
+}
+
+/// Check that `frame` is a well-formed standard message without building a [`JsonValue`]
+/// tree.
+///
+/// The frame is tokenized once: it has to be valid JSON, a top-level array with a known
+/// message code, no more elements than the message allows and the element types of its
+/// [`Arity`]. Nothing is allocated, so this is a cheap gate in front of the full parse, or
+/// all a firewall needs to drop malformed traffic. A frame accepted here is accepted by
+/// [`Message::parse_message_with`](crate::messages::Message::parse_message_with) with
+/// `reject_trailing_elements`, and a rejected one fails there with the same error. Duplicate
+/// keys and the `spec_strict` ID ranges are not checked.
+/// # Examples
+/// ```
+/// use wamp_helpers::error::Error;
+/// use wamp_helpers::scan::scan_frame;
+///
+/// let frame = r#"[48, 7814135, {}, "com.myapp.user.new", ["johnny"]]"#;
+/// let shape = scan_frame(frame).unwrap();
+/// assert_eq!(shape.message_type(), 48);
+/// assert_eq!(shape.spans().len(), 5);
+/// assert_eq!(&frame[shape.span("Procedure").unwrap()], r#""com.myapp.user.new""#);
+/// assert_eq!(shape.element(frame, 4), Some(r#"["johnny"]"#));
+///
+/// assert!(matches!(
+///     scan_frame(r#"[48, 7814135, {}, 42]"#),
+///     Err(Error::InvalidJsonStr { .. })
+/// ));
+/// assert!(matches!(
+///     scan_frame(r#"[48, 7814135, {}, "com.myapp.user.new", [], {}, 1]"#),
+///     Err(Error::TooManyElements { id: 48, len: 7 })
+/// ));
+/// assert!(matches!(scan_frame(r#"[48, 7814135, {}"#), Err(Error::JsonError(_))));
+/// ```
+pub fn scan_frame(frame: &str) -> Result<FrameShape, Error> {
+    let mut scanner = Tokenizer::new(frame.as_bytes(), Validate);
+    let mut spans: [Range<usize>; MAX_ELEMENTS] = Default::default();
+    let mut len = 0;
+
+    scanner.skip_whitespace();
+    let is_array = scanner.peek() == Some(b'[');
+    if is_array {
+        scanner.pos += 1;
+        scanner.skip_whitespace();
+        if scanner.peek() == Some(b']') {
+            scanner.pos += 1;
+        } else {
+            loop {
+                scanner.skip_whitespace();
+                let start = scanner.pos;
+                scanner.value(1)?;
+                if let Some(span) = spans.get_mut(len) {
+                    *span = start..scanner.pos;
+                }
+                len += 1;
+                scanner.skip_whitespace();
+                match scanner.peek() {
+                    Some(b',') => scanner.pos += 1,
+                    Some(b']') => {
+                        scanner.pos += 1;
+                        break;
+                    }
+                    _ => return Err(scanner.unexpected()),
+                }
+            }
+        }
+    } else {
+        scanner.value(0)?;
+    }
+    scanner.skip_whitespace();
+    if scanner.peek().is_some() {
+        return Err(scanner.unexpected());
+    }
+
+    if !is_array || len == 0 {
+        return Err(Error::InvalidId);
+    }
+    let code = match plain_integer(frame, &spans[0]) {
+        Some(code) => u8::try_from(code).ok(),
+        None => parse_element(frame, &spans[0])?.as_u8(),
+    };
+    let arity = arity(code.ok_or(Error::InvalidId)?).ok_or(Error::ExtensionMessage)?;
+    if len > arity.max {
+        return Err(Error::TooManyElements { id: arity.id, len });
+    }
+    for (index, field) in arity.fields.iter().enumerate().skip(1) {
+        match spans.get(index).filter(|_| index < len) {
+            Some(span) => check_element(frame, span, field)?,
+            None => validate(field, JsonValue::Null)?,
+        }
+    }
+
+    Ok(FrameShape { arity, len, spans })
+}
+
+/// The value of an element made of digits only, the form nearly every ID and code takes.
+fn plain_integer(frame: &str, span: &Range<usize>) -> Option<u64> {
+    let digits = frame.get(span.clone()).unwrap_or_default();
+    if digits.bytes().all(|byte| byte.is_ascii_digit()) {
+        digits.parse().ok()
+    } else {
+        None
+    }
+}
+
+fn parse_element(frame: &str, span: &Range<usize>) -> Result<JsonValue, Error> {
+    json::parse(frame.get(span.clone()).unwrap_or_default()).map_err(Error::JsonError)
+}
+
+fn check_element(frame: &str, span: &Range<usize>, field: &Field) -> Result<(), Error> {
+    let first = frame.as_bytes().get(span.start).copied().unwrap_or_default();
+    let valid = match field.kind {
+        FieldKind::Code => true,
+        FieldKind::Id => plain_integer(frame, span).is_some_and(|id| id <= MAX_ID),
+        FieldKind::U8 => plain_integer(frame, span).is_some_and(|code| code <= u8::MAX.into()),
+        FieldKind::Uri | FieldKind::Str => first == b'"',
+        FieldKind::Dict => first == b'{',
+        FieldKind::List => first == b'[',
+    };
+    if valid {
+        Ok(())
+    } else {
+        // Only odd or invalid elements get parsed, to reuse the exact checks of the parser.
+        validate(field, parse_element(frame, span)?)
+    }
+}
+
+fn validate(field: &Field, value: JsonValue) -> Result<(), Error> {
+    match (field.kind, field.optional) {
+        (FieldKind::Code, _) => Ok(()),
+        (FieldKind::Id, _) => validate_u64_argument(value).map(drop),
+        (FieldKind::U8, _) => validate_u8_argument(value).map(drop),
+        (FieldKind::Uri | FieldKind::Str, _) => validate_str_argument(value).map(drop),
+        (FieldKind::Dict, false) => validate_dict_argument(value).map(drop),
+        (FieldKind::Dict, true) => validate_optional_dict(value).map(drop),
+        (FieldKind::List, false) => validate_array_argument(value).map(drop),
+        (FieldKind::List, true) => validate_args(value).map(drop),
+    }
+}
+
+/// Only checks the tokens, the frame shape is all [`scan_frame`] keeps.
+struct Validate;
+
+impl Visitor for Validate {
+    fn scalar(&mut self, _: Node) {}
+
+    fn open(&mut self) -> usize {
+        0
+    }
+
+    fn close(&mut self, _: usize, _: Node) {}
+
+    fn text(&mut self) -> Option<&mut String> {
+        None
+    }
+}
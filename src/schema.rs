@@ -0,0 +1,224 @@
+use crate::arity::{Arity, Field, FieldKind, ARITY_TABLE};
+use crate::messages::MAX_ID;
+use json::JsonValue;
+
+/// A well-known key of a message's Details/Options dictionary.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OptionKey {
+    /// Message code the key belongs to.
+    pub id: u8,
+    pub key: &'static str,
+    /// JSON Schema type of the value.
+    pub kind: &'static str,
+    pub description: &'static str,
+}
+
+const fn option(
+    id: u8,
+    key: &'static str,
+    kind: &'static str,
+    description: &'static str,
+) -> OptionKey {
+    OptionKey {
+        id,
+        key,
+        kind,
+        description,
+    }
+}
+
+/// Details/Options keys the crate knows about. Dictionaries stay open, peers may send any
+/// other key as well.
+pub static OPTION_KEYS: &[OptionKey] = &[
+    option(1, "roles", "object", "Roles announced by the client."),
+    option(
+        1,
+        "authmethods",
+        "array",
+        "Authentication methods the client supports.",
+    ),
+    option(
+        1,
+        "authid",
+        "string",
+        "Identity the client wants to authenticate as.",
+    ),
+    option(2, "roles", "object", "Roles offered by the router."),
+    option(
+        2,
+        "authid",
+        "string",
+        "Identity the client was authenticated as.",
+    ),
+    option(2, "authrole", "string", "Role assigned to the client."),
+    option(
+        2,
+        "authmethod",
+        "string",
+        "Method used to authenticate the client.",
+    ),
+    option(3, "message", "string", "Human readable reason."),
+    option(
+        4,
+        "challenge",
+        "string",
+        "Challenge the client has to sign.",
+    ),
+    option(6, "message", "string", "Human readable reason."),
+    option(
+        16,
+        "acknowledge",
+        "boolean",
+        "Ask the broker for a PUBLISHED.",
+    ),
+    option(
+        16,
+        "exclude_me",
+        "boolean",
+        "Do not deliver the event to the publisher.",
+    ),
+    option(
+        16,
+        "exclude",
+        "array",
+        "Session ids the event is not delivered to.",
+    ),
+    option(
+        16,
+        "eligible",
+        "array",
+        "Session ids the event is only delivered to.",
+    ),
+    option(
+        32,
+        "match",
+        "string",
+        "Topic matching policy: exact, prefix or wildcard.",
+    ),
+    option(48, "timeout", "integer", "Call timeout in milliseconds."),
+    option(
+        48,
+        "receive_progress",
+        "boolean",
+        "Accept progressive results.",
+    ),
+    option(
+        48,
+        "disclose_me",
+        "boolean",
+        "Disclose the caller's session id to the callee.",
+    ),
+    option(
+        49,
+        "mode",
+        "string",
+        "Cancellation mode: skip, kill or killnowait.",
+    ),
+    option(50, "progress", "boolean", "More results follow."),
+    option(
+        64,
+        "match",
+        "string",
+        "Procedure matching policy: exact, prefix or wildcard.",
+    ),
+    option(64, "invoke", "string", "Shared registration policy."),
+    option(
+        64,
+        "concurrency",
+        "integer",
+        "Most invocations the callee runs at once.",
+    ),
+    option(
+        68,
+        "receive_progress",
+        "boolean",
+        "The caller accepts progressive results.",
+    ),
+    option(
+        68,
+        "caller",
+        "integer",
+        "Session id of the disclosed caller.",
+    ),
+    option(
+        69,
+        "mode",
+        "string",
+        "Cancellation mode: kill or killnowait.",
+    ),
+    option(70, "progress", "boolean", "More results follow."),
+];
+
+/// Well-known Details/Options keys of a message code.
+pub fn option_keys(id: u8) -> impl Iterator<Item = &'static OptionKey> {
+    OPTION_KEYS.iter().filter(move |option| option.id == id)
+}
+
+fn field_schema(arity: &Arity, field: &Field) -> JsonValue {
+    let mut schema = match field.kind {
+        FieldKind::Code => json::object! { "const": arity.id },
+        FieldKind::Id => json::object! { "type": "integer", "minimum": 0, "maximum": MAX_ID },
+        FieldKind::U8 => json::object! { "type": "integer", "minimum": 0, "maximum": 255 },
+        FieldKind::Uri => json::object! { "type": "string", "format": "uri-reference" },
+        FieldKind::Str => json::object! { "type": "string" },
+        FieldKind::List => json::object! { "type": "array" },
+        FieldKind::Dict => {
+            let mut properties = JsonValue::new_object();
+            if matches!(field.name, "Details" | "Options" | "Extra") {
+                for option in option_keys(arity.id) {
+                    properties[option.key] = json::object! {
+                        "type": option.kind,
+                        "description": option.description,
+                    };
+                }
+            }
+            json::object! { "type": "object", "properties": properties }
+        }
+    };
+    schema["title"] = field.name.into();
+    schema
+}
+
+/// JSON Schema (draft 2020-12) describing the positional structure of one message type.
+pub fn message_schema(arity: &Arity) -> JsonValue {
+    let items: Vec<JsonValue> = arity
+        .fields
+        .iter()
+        .map(|field| field_schema(arity, field))
+        .collect();
+    json::object! {
+        "title": arity.name,
+        "type": "array",
+        "prefixItems": items,
+        "minItems": arity.min,
+        "maxItems": arity.max,
+    }
+}
+
+/// JSON Schema (draft 2020-12) accepting any message defined by the spec, built from
+/// [`ARITY_TABLE`], the same table [`ParseOptions`](crate::parse::ParseOptions) validates
+/// against.
+/// # Examples
+/// ```
+/// use wamp_helpers::schema::json_schema;
+/// let schema = json_schema();
+/// let call = &schema["$defs"]["CALL"];
+/// assert_eq!(call["minItems"], 4);
+/// assert_eq!(call["prefixItems"][3]["title"], "Procedure");
+/// assert_eq!(call["prefixItems"][2]["properties"]["timeout"]["type"], "integer");
+/// assert_eq!(schema["oneOf"].len(), 24);
+/// ```
+pub fn json_schema() -> JsonValue {
+    let mut definitions = JsonValue::new_object();
+    let mut variants = Vec::new();
+    for arity in ARITY_TABLE.iter() {
+        definitions[arity.name] = message_schema(arity);
+        variants.push(json::object! { "$ref": format!("#/$defs/{}", arity.name) });
+    }
+    json::object! {
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "title": "WAMP message",
+        "oneOf": variants,
+        "$defs": definitions,
+    }
+}
@@ -0,0 +1,286 @@
+use crate::messages::{Args, Call, Cancel, Kwargs, Message, Options, Uri, WampId};
+use crate::rpc::{CallFuture, PendingCalls, RpcError};
+use crate::sim::{IdGenerator, SequentialIdGenerator};
+use crate::value::WampValue;
+use json::JsonValue;
+use std::collections::VecDeque;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::task::{Context, Poll, Waker};
+use tower_service::Service;
+
+/// A procedure call as handed to [`CallService`], the CALL without its request ID.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CallRequest {
+    pub procedure: Uri,
+    pub options: Options,
+    pub args: Option<Args>,
+    pub kwargs: Option<Kwargs>,
+}
+
+impl CallRequest {
+    pub fn new(procedure: impl Into<Uri>) -> Self {
+        CallRequest {
+            procedure: procedure.into(),
+            options: JsonValue::new_object(),
+            args: None,
+            kwargs: None,
+        }
+    }
+
+    pub fn options(mut self, options: Options) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub fn args(mut self, args: Args) -> Self {
+        self.args = Some(args);
+        self
+    }
+
+    pub fn kwargs(mut self, kwargs: Kwargs) -> Self {
+        self.kwargs = Some(kwargs);
+        self
+    }
+
+    fn into_call(self, request: WampId) -> Call {
+        Call {
+            request,
+            options: self.options,
+            procedure: self.procedure,
+            args: self.args,
+            kwargs: self.kwargs,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Shared {
+    ids: SequentialIdGenerator,
+    pending: PendingCalls,
+    outgoing: VecDeque<Call>,
+    cancels: VecDeque<Cancel>,
+    waiting: Vec<Waker>,
+}
+
+impl Shared {
+    fn lock(shared: &Mutex<Shared>) -> MutexGuard<'_, Shared> {
+        // Every update leaves the state consistent, a poisoned lock is still valid.
+        shared
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn wake_waiting(&mut self) {
+        for waker in self.waiting.drain(..) {
+            waker.wake();
+        }
+    }
+
+    /// The future of `request` is gone: drop the CALL if it is still queued, cancel it
+    /// otherwise.
+    fn abandon(&mut self, request: WampId) {
+        if !self.pending.forget(request) {
+            return;
+        }
+        let queued = self.outgoing.len();
+        self.outgoing.retain(|call| call.request != request);
+        if self.outgoing.len() == queued {
+            self.cancels.push_back(Cancel {
+                request,
+                options: JsonValue::new_object(),
+            });
+        }
+        self.wake_waiting();
+    }
+}
+
+/// Resolves to the outcome of a call made through [`CallService`].
+///
+/// Dropping it before the answer, e.g. when tower's `Timeout` gives up, frees its slot in
+/// the in-flight count and queues a CANCEL for [`take_cancels`](CallService::take_cancels).
+#[derive(Debug)]
+pub struct ServiceFuture {
+    call: CallFuture<WampValue>,
+    shared: Arc<Mutex<Shared>>,
+}
+
+impl Future for ServiceFuture {
+    type Output = Result<WampValue, RpcError>;
+
+    fn poll(mut self: Pin<&mut Self>, context: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.call).poll(context)
+    }
+}
+
+impl Drop for ServiceFuture {
+    fn drop(&mut self) {
+        Shared::lock(&self.shared).abandon(self.call.request());
+    }
+}
+
+/// The caller role of a session as a [`tower_service::Service`], so timeouts, retries,
+/// concurrency limits and load shedding from the tower ecosystem apply to WAMP calls.
+///
+/// Calling the service queues a CALL and returns a future resolving to its
+/// [`result_value`](crate::rpc::result_value). The session loop sends what
+/// [`take_calls`](CallService::take_calls) and [`take_cancels`](CallService::take_cancels)
+/// return and passes every received message to [`on_message`](CallService::on_message).
+/// With a
+/// [`max_in_flight`](CallService::max_in_flight) limit, `poll_ready` stays pending while
+/// that many calls are unanswered, which is what tower's load shedding reacts to.
+///
+/// Cloning the service gives another handle to the same session.
+/// # Examples
+/// ```
+/// use std::future::Future;
+/// use std::task::{Context, Poll, Waker};
+/// use tower_service::Service;
+/// use wamp_helpers::messages::Message;
+/// use wamp_helpers::service::{CallRequest, CallService};
+/// use wamp_helpers::value::WampValue;
+///
+/// fn now<F: Future>(future: F) -> F::Output {
+///     let mut future = std::pin::pin!(future);
+///     match future.as_mut().poll(&mut Context::from_waker(Waker::noop())) {
+///         Poll::Ready(output) => output,
+///         Poll::Pending => panic!("future is not ready"),
+///     }
+/// }
+///
+/// let mut service = CallService::new().max_in_flight(1);
+/// let mut context = Context::from_waker(Waker::noop());
+///
+/// assert!(service.poll_ready(&mut context).is_ready());
+/// let sum = service.call(CallRequest::new("com.example.add").args(vec![1.into(), 2.into()]));
+/// // The limit is reached until the dealer answers.
+/// assert!(service.poll_ready(&mut context).is_pending());
+///
+/// let calls = service.take_calls();
+/// assert_eq!(calls[0].procedure, "com.example.add");
+/// service.on_message(&Message::parse_message(&format!("[50, {}, {{}}, [3]]", calls[0].request)).unwrap());
+///
+/// assert_eq!(now(sum), Ok(WampValue::from(3)));
+/// assert!(service.poll_ready(&mut context).is_ready());
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct CallService {
+    shared: Arc<Mutex<Shared>>,
+    max_in_flight: Option<usize>,
+}
+
+impl CallService {
+    pub fn new() -> Self {
+        CallService::default()
+    }
+
+    /// Unanswered calls above which the service reports itself not ready.
+    pub fn max_in_flight(mut self, max_in_flight: usize) -> Self {
+        self.max_in_flight = Some(max_in_flight);
+        self
+    }
+
+    fn shared(&self) -> MutexGuard<'_, Shared> {
+        Shared::lock(&self.shared)
+    }
+
+    /// CALLs queued since the last call, in order, to be sent to the dealer.
+    pub fn take_calls(&self) -> Vec<Call> {
+        self.shared().outgoing.drain(..).collect()
+    }
+
+    /// CANCELs for sent calls whose future was dropped unanswered, to be sent after the
+    /// [`take_calls`](Self::take_calls) of the same round.
+    /// # Examples
+    /// ```
+    /// use tower_service::Service;
+    /// use wamp_helpers::service::{CallRequest, CallService};
+    ///
+    /// let mut service = CallService::new().max_in_flight(1);
+    /// let slow = service.call(CallRequest::new("com.example.slow"));
+    /// let request = service.take_calls()[0].request;
+    ///
+    /// // A timeout layer gives up on the call.
+    /// drop(slow);
+    /// assert_eq!(service.in_flight(), 0);
+    /// assert_eq!(service.take_cancels()[0].request, request);
+    ///
+    /// // A call dropped before it was sent is simply not sent.
+    /// drop(service.call(CallRequest::new("com.example.slow")));
+    /// assert!(service.take_calls().is_empty());
+    /// assert!(service.take_cancels().is_empty());
+    /// ```
+    pub fn take_cancels(&self) -> Vec<Cancel> {
+        self.shared().cancels.drain(..).collect()
+    }
+
+    /// Complete the call `message` answers, returns whether it answered one.
+    pub fn on_message(&self, message: &Message) -> bool {
+        let mut shared = self.shared();
+        let answered = shared.pending.on_message(message);
+        if answered {
+            shared.wake_waiting();
+        }
+        answered
+    }
+
+    /// Give up on `request` locally, it resolves to [`RpcError::Timeout`].
+    pub fn timeout(&self, request: WampId) -> bool {
+        let mut shared = self.shared();
+        let answered = shared.pending.timeout(request);
+        if answered {
+            shared.wake_waiting();
+        }
+        answered
+    }
+
+    /// The session ended, every unanswered call resolves to [`RpcError::TransportLost`].
+    pub fn transport_lost(&self) {
+        let mut shared = self.shared();
+        shared.outgoing.clear();
+        shared.cancels.clear();
+        shared.pending.transport_lost();
+        shared.wake_waiting();
+    }
+
+    /// Calls sent or queued and not answered yet.
+    pub fn in_flight(&self) -> usize {
+        self.shared().pending.len()
+    }
+}
+
+impl Service<CallRequest> for CallService {
+    type Response = WampValue;
+    type Error = RpcError;
+    type Future = ServiceFuture;
+
+    fn poll_ready(&mut self, context: &mut Context<'_>) -> Poll<Result<(), RpcError>> {
+        let mut shared = self.shared();
+        match self.max_in_flight {
+            Some(max) if shared.pending.len() >= max => {
+                let waker = context.waker();
+                if !shared
+                    .waiting
+                    .iter()
+                    .any(|waiting| waiting.will_wake(waker))
+                {
+                    shared.waiting.push(waker.clone());
+                }
+                Poll::Pending
+            }
+            _ => Poll::Ready(Ok(())),
+        }
+    }
+
+    fn call(&mut self, request: CallRequest) -> ServiceFuture {
+        let mut shared = self.shared();
+        let call = request.into_call(shared.ids.next_id());
+        let future = shared.pending.call(&call);
+        shared.outgoing.push_back(call);
+        ServiceFuture {
+            call: future,
+            shared: Arc::clone(&self.shared),
+        }
+    }
+}
@@ -0,0 +1,228 @@
+use crate::correlation::Direction;
+use crate::messages::{GoodbyeDetails, Message, Uri, WampId};
+
+/// Which end of the session the local peer is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    Client,
+    Router,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SessionState {
+    /// No session, only HELLO may be sent.
+    Closed,
+    /// HELLO was sent, waiting for WELCOME, CHALLENGE or ABORT.
+    Establishing,
+    /// CHALLENGE was sent, waiting for AUTHENTICATE or ABORT.
+    Challenged,
+    Established,
+    /// One side sent GOODBYE and waits for the reply.
+    Closing {
+        initiator: Direction,
+    },
+}
+
+/// How the peer ended the session, from its GOODBYE or ABORT.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PeerClose {
+    pub reason: Uri,
+    pub details: GoodbyeDetails,
+}
+
+/// Tracks the session lifecycle from the point of view of one peer.
+///
+/// Feed every message sent and received through [`Session::transition`], messages that are not
+/// allowed in the current state are refused and leave the state untouched.
+/// # Examples
+/// ```
+/// use wamp_helpers::correlation::Direction;
+/// use wamp_helpers::messages::Message;
+/// use wamp_helpers::session::{Session, SessionState, Side};
+///
+/// let mut session = Session::new(Side::Client);
+/// let hello = Message::parse_message(r#"[1, "realm1", {"roles": {"caller": {}}}]"#).unwrap();
+/// let welcome = Message::parse_message(r#"[2, 9129137332, {"roles": {"dealer": {}}}]"#).unwrap();
+///
+/// assert!(session.transition(Direction::Inbound, &welcome).is_err());
+/// session.transition(Direction::Outbound, &hello).unwrap();
+/// session.transition(Direction::Inbound, &welcome).unwrap();
+/// assert_eq!(session.state(), SessionState::Established);
+/// assert_eq!(session.session_id(), Some(9129137332));
+/// ```
+#[derive(Debug, Clone)]
+pub struct Session {
+    side: Side,
+    state: SessionState,
+    session_id: Option<WampId>,
+    peer_close: Option<PeerClose>,
+    paused: bool,
+}
+
+impl Session {
+    pub fn new(side: Side) -> Self {
+        Session {
+            side,
+            state: SessionState::Closed,
+            session_id: None,
+            peer_close: None,
+            paused: false,
+        }
+    }
+
+    pub fn side(&self) -> Side {
+        self.side
+    }
+
+    pub fn state(&self) -> SessionState {
+        self.state
+    }
+
+    /// The id assigned by the router in WELCOME, while the session is open.
+    pub fn session_id(&self) -> Option<WampId> {
+        self.session_id
+    }
+
+    /// Reason and details the peer gave when it sent GOODBYE or ABORT, kept until the next
+    /// HELLO.
+    /// ```
+    /// use wamp_helpers::correlation::Direction;
+    /// use wamp_helpers::messages::Message;
+    /// use wamp_helpers::session::{Session, Side};
+    ///
+    /// let mut session = Session::new(Side::Client);
+    /// let hello = Message::parse_message(r#"[1, "realm1", {"roles": {"caller": {}}}]"#).unwrap();
+    /// let abort = Message::parse_message(r#"[3, {"message": "no such realm"}, "wamp.error.no_such_realm"]"#).unwrap();
+    /// session.transition(Direction::Outbound, &hello).unwrap();
+    /// session.transition(Direction::Inbound, &abort).unwrap();
+    ///
+    /// let close = session.peer_close().unwrap();
+    /// assert_eq!(close.reason, "wamp.error.no_such_realm");
+    /// assert_eq!(close.details.message.as_deref(), Some("no such realm"));
+    /// ```
+    pub fn peer_close(&self) -> Option<&PeerClose> {
+        self.peer_close.as_ref()
+    }
+
+    /// Stop dispatching new INVOCATIONs and EVENTs to this client, e.g. while draining a callee
+    /// for a deploy. In-flight calls still complete. Returns whether the session was running.
+    /// ```
+    /// use wamp_helpers::messages::Message;
+    /// use wamp_helpers::session::{Session, Side};
+    ///
+    /// let mut session = Session::new(Side::Router);
+    /// let invocation = Message::parse_message(r#"[68, 1, 9823526, {}]"#).unwrap();
+    /// let result = Message::parse_message(r#"[50, 7, {}]"#).unwrap();
+    ///
+    /// assert!(session.pause());
+    /// assert!(!session.accepts_dispatch(&invocation));
+    /// assert!(session.accepts_dispatch(&result));
+    /// assert!(session.resume());
+    /// assert!(session.accepts_dispatch(&invocation));
+    /// ```
+    pub fn pause(&mut self) -> bool {
+        !std::mem::replace(&mut self.paused, true)
+    }
+
+    /// Resume dispatching after [`pause`](Session::pause), returns whether the session was
+    /// paused.
+    pub fn resume(&mut self) -> bool {
+        std::mem::replace(&mut self.paused, false)
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Whether the router may send `message` to this client now. Only new work, INVOCATION and
+    /// EVENT, is held back while paused.
+    pub fn accepts_dispatch(&self, message: &Message) -> bool {
+        !(self.paused && matches!(message, Message::Invocation(_) | Message::Event(_)))
+    }
+
+    /// Whether `direction` carries messages from the client to the router.
+    pub fn from_client(&self, direction: Direction) -> bool {
+        matches!(
+            (self.side, direction),
+            (Side::Client, Direction::Outbound) | (Side::Router, Direction::Inbound)
+        )
+    }
+
+    /// Apply a message to the state machine, returns the state it was refused in on error.
+    pub fn transition(
+        &mut self,
+        direction: Direction,
+        message: &Message,
+    ) -> Result<SessionState, SessionState> {
+        let from_client = self.from_client(direction);
+        let next = match (self.state, message) {
+            (SessionState::Closed, Message::Hello(_)) if from_client => SessionState::Establishing,
+
+            (SessionState::Establishing, Message::Challenge(_)) if !from_client => {
+                SessionState::Challenged
+            }
+            (SessionState::Challenged, Message::Authenticate(_)) if from_client => {
+                SessionState::Establishing
+            }
+            (SessionState::Establishing, Message::Welcome(welcome)) if !from_client => {
+                self.session_id = Some(welcome.session);
+                SessionState::Established
+            }
+            (SessionState::Establishing | SessionState::Challenged, Message::Abort(_)) => {
+                SessionState::Closed
+            }
+
+            (SessionState::Established, Message::Goodbye(_)) => SessionState::Closing {
+                initiator: direction,
+            },
+            (SessionState::Closing { initiator }, Message::Goodbye(_))
+                if initiator != direction =>
+            {
+                self.session_id = None;
+                SessionState::Closed
+            }
+            // The initiator must not send anything after its GOODBYE, the other side may still
+            // flush messages that were in flight.
+            (SessionState::Closing { initiator }, message)
+                if initiator != direction && is_session_message(message) =>
+            {
+                self.state
+            }
+
+            (SessionState::Established, message) if is_session_message(message) => self.state,
+
+            (state, _) => return Err(state),
+        };
+        match message {
+            Message::Hello(_) => self.peer_close = None,
+            Message::Goodbye(goodbye) if direction == Direction::Inbound => {
+                self.peer_close = Some(PeerClose {
+                    reason: goodbye.reason.clone(),
+                    details: goodbye.typed_details(),
+                });
+            }
+            Message::Abort(abort) if direction == Direction::Inbound => {
+                self.peer_close = Some(PeerClose {
+                    reason: abort.reason.clone(),
+                    details: GoodbyeDetails::from(&abort.details),
+                });
+            }
+            _ => {}
+        }
+        self.state = next;
+        Ok(next)
+    }
+}
+
+/// Messages exchanged inside an established session.
+fn is_session_message(message: &Message) -> bool {
+    !matches!(
+        message,
+        Message::Hello(_)
+            | Message::Welcome(_)
+            | Message::Abort(_)
+            | Message::Challenge(_)
+            | Message::Authenticate(_)
+            | Message::Goodbye(_)
+    )
+}
@@ -0,0 +1,141 @@
+use crate::messages::{Options, WampId, MAX_ID};
+use crate::rng::SeededRng;
+
+/// Source of WAMP IDs.
+pub trait IdGenerator {
+    fn next_id(&mut self) -> WampId;
+}
+
+/// Global scope IDs (sessions, publications), drawn uniformly from `[1, 2^53]`.
+#[derive(Debug, Clone)]
+pub struct RandomIdGenerator {
+    rng: SeededRng,
+}
+
+impl RandomIdGenerator {
+    pub fn new(seed: u64) -> Self {
+        RandomIdGenerator {
+            rng: SeededRng::new(seed),
+        }
+    }
+}
+
+impl IdGenerator for RandomIdGenerator {
+    fn next_id(&mut self) -> WampId {
+        self.rng.below(MAX_ID) + 1
+    }
+}
+
+/// Router and session scope IDs (subscriptions, registrations, requests), incrementing from
+/// 1 and wrapping after `2^53`.
+#[derive(Debug, Clone)]
+pub struct SequentialIdGenerator {
+    next: WampId,
+}
+
+impl Default for SequentialIdGenerator {
+    fn default() -> Self {
+        SequentialIdGenerator { next: 1 }
+    }
+}
+
+impl IdGenerator for SequentialIdGenerator {
+    fn next_id(&mut self) -> WampId {
+        let id = self.next;
+        self.next = if id >= MAX_ID { 1 } else { id + 1 };
+        id
+    }
+}
+
+/// The `invoke` policies of shared registrations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InvocationPolicy {
+    Single,
+    First,
+    Last,
+    RoundRobin,
+    Random,
+}
+
+impl InvocationPolicy {
+    /// Read the policy from REGISTER options, `single` when absent or unknown.
+    pub fn from_options(options: &Options) -> Self {
+        match options["invoke"].as_str() {
+            Some("first") => InvocationPolicy::First,
+            Some("last") => InvocationPolicy::Last,
+            Some("roundrobin") => InvocationPolicy::RoundRobin,
+            Some("random") => InvocationPolicy::Random,
+            _ => InvocationPolicy::Single,
+        }
+    }
+}
+
+/// Picks the callee of a shared registration, with randomness coming from a seed.
+#[derive(Debug, Clone)]
+pub struct CalleeSelector {
+    policy: InvocationPolicy,
+    rng: SeededRng,
+    cursor: usize,
+}
+
+impl CalleeSelector {
+    pub fn new(policy: InvocationPolicy, seed: u64) -> Self {
+        CalleeSelector {
+            policy,
+            rng: SeededRng::new(seed),
+            cursor: 0,
+        }
+    }
+
+    /// Index of the callee to invoke among `callees` registered callees, in registration
+    /// order. `None` when there are none.
+    pub fn select(&mut self, callees: usize) -> Option<usize> {
+        if callees == 0 {
+            return None;
+        }
+        Some(match self.policy {
+            InvocationPolicy::Single | InvocationPolicy::First => 0,
+            InvocationPolicy::Last => callees - 1,
+            InvocationPolicy::RoundRobin => {
+                let index = self.cursor % callees;
+                self.cursor = index + 1;
+                index
+            }
+            InvocationPolicy::Random => self.rng.below(callees as u64) as usize,
+        })
+    }
+}
+
+/// Derives every nondeterministic source a broker or dealer needs from one seed, so a run can
+/// be reproduced exactly.
+/// # Examples
+/// ```
+/// use wamp_helpers::sim::{IdGenerator, InvocationPolicy, Simulation};
+/// let mut a = Simulation::new(1234);
+/// let mut b = Simulation::new(1234);
+/// assert_eq!(a.session_ids().next_id(), b.session_ids().next_id());
+///
+/// let mut selector = a.callee_selector(InvocationPolicy::RoundRobin);
+/// assert_eq!((selector.select(2), selector.select(2), selector.select(2)), (Some(0), Some(1), Some(0)));
+/// ```
+#[derive(Debug, Clone)]
+pub struct Simulation {
+    rng: SeededRng,
+}
+
+impl Simulation {
+    pub fn new(seed: u64) -> Self {
+        Simulation {
+            rng: SeededRng::new(seed),
+        }
+    }
+
+    /// Generator for global scope IDs.
+    pub fn session_ids(&mut self) -> RandomIdGenerator {
+        RandomIdGenerator::new(self.rng.next_u64())
+    }
+
+    pub fn callee_selector(&mut self, policy: InvocationPolicy) -> CalleeSelector {
+        CalleeSelector::new(policy, self.rng.next_u64())
+    }
+}
@@ -0,0 +1,405 @@
+use crate::arity::arity;
+use crate::correlation::Direction;
+use crate::messages::{Message, RequestType, WampId};
+use json::JsonValue;
+use std::cmp::Reverse;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::time::{Duration, Instant};
+
+/// Message and byte counters of one direction.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TrafficStats {
+    /// Messages per message code.
+    pub messages: BTreeMap<u8, u64>,
+    pub bytes: u64,
+    /// ERROR and ABORT messages.
+    pub errors: u64,
+}
+
+impl TrafficStats {
+    pub fn total(&self) -> u64 {
+        self.messages.values().sum()
+    }
+}
+
+/// Counters of one session, fed with every message sent and received.
+/// # Examples
+/// ```
+/// use std::time::{Duration, Instant};
+/// use wamp_helpers::correlation::Direction;
+/// use wamp_helpers::messages::Message;
+/// use wamp_helpers::stats::SessionStats;
+///
+/// let start = Instant::now();
+/// let mut stats = SessionStats::new();
+/// for (direction, raw) in [
+///     (Direction::Inbound, r#"[2, 9129137332, {}]"#),
+///     (Direction::Outbound, r#"[32, 1, {}, "com.example.topic"]"#),
+///     (Direction::Inbound, r#"[33, 1, 5512315355]"#),
+///     (Direction::Outbound, r#"[48, 2, {}, "com.example.add", [1, 2]]"#),
+/// ] {
+///     stats.record(direction, &Message::parse_message(raw).unwrap(), raw.len(), start);
+/// }
+///
+/// assert_eq!(stats.outbound().total(), 2);
+/// assert_eq!(stats.active_subscriptions(), 1);
+/// assert_eq!(stats.pending_calls(), 1);
+/// assert_eq!(stats.uptime(start + Duration::from_secs(3)), Some(Duration::from_secs(3)));
+/// assert_eq!(stats.to_details(start)["subscriptions"], 1);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct SessionStats {
+    inbound: TrafficStats,
+    outbound: TrafficStats,
+    established_at: Option<Instant>,
+    subscriptions: HashSet<WampId>,
+    registrations: HashSet<WampId>,
+    /// UNSUBSCRIBE/UNREGISTER requests waiting for their answer, by request id.
+    unsubscribing: HashMap<WampId, WampId>,
+    unregistering: HashMap<WampId, WampId>,
+    pending_calls: HashSet<WampId>,
+}
+
+impl SessionStats {
+    pub fn new() -> Self {
+        SessionStats::default()
+    }
+
+    /// Count a message of `bytes` bytes seen at `at`.
+    pub fn record(&mut self, direction: Direction, message: &Message, bytes: usize, at: Instant) {
+        let traffic = match direction {
+            Direction::Inbound => &mut self.inbound,
+            Direction::Outbound => &mut self.outbound,
+        };
+        *traffic.messages.entry(message.message_id()).or_default() += 1;
+        traffic.bytes += bytes as u64;
+        if matches!(message, Message::ErrorMessage(_) | Message::Abort(_)) {
+            traffic.errors += 1;
+        }
+
+        match message {
+            Message::Welcome(_) => self.established_at = Some(at),
+            Message::Goodbye(_) | Message::Abort(_) => {
+                self.subscriptions.clear();
+                self.registrations.clear();
+                self.pending_calls.clear();
+            }
+            Message::Subscribed(subscribed) => {
+                self.subscriptions.insert(subscribed.subscription);
+            }
+            Message::Unsubscribe(unsubscribe) => {
+                self.unsubscribing
+                    .insert(unsubscribe.request, unsubscribe.subscription);
+            }
+            Message::Unsubscribed(unsubscribed) => {
+                if let Some(subscription) = self.unsubscribing.remove(&unsubscribed.request) {
+                    self.subscriptions.remove(&subscription);
+                }
+            }
+            Message::Registered(registered) => {
+                self.registrations.insert(registered.registration);
+            }
+            Message::Unregister(unregister) => {
+                self.unregistering
+                    .insert(unregister.request, unregister.registration);
+            }
+            Message::Unregistered(unregistered) => {
+                if let Some(registration) = self.unregistering.remove(&unregistered.request) {
+                    self.registrations.remove(&registration);
+                }
+            }
+            Message::Call(call) => {
+                self.pending_calls.insert(call.request);
+            }
+            Message::MessageResult(result)
+                if !result.details["progress"].as_bool().unwrap_or(false) =>
+            {
+                self.pending_calls.remove(&result.request);
+            }
+            Message::ErrorMessage(error) => match error.typed_request_type() {
+                Some(RequestType::Call) => {
+                    self.pending_calls.remove(&error.request);
+                }
+                Some(RequestType::Unsubscribe) => {
+                    self.unsubscribing.remove(&error.request);
+                }
+                Some(RequestType::Unregister) => {
+                    self.unregistering.remove(&error.request);
+                }
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+
+    pub fn inbound(&self) -> &TrafficStats {
+        &self.inbound
+    }
+
+    pub fn outbound(&self) -> &TrafficStats {
+        &self.outbound
+    }
+
+    pub fn active_subscriptions(&self) -> usize {
+        self.subscriptions.len()
+    }
+
+    pub fn active_registrations(&self) -> usize {
+        self.registrations.len()
+    }
+
+    pub fn pending_calls(&self) -> usize {
+        self.pending_calls.len()
+    }
+
+    /// Time since the WELCOME, `None` before the session was established.
+    pub fn uptime(&self, now: Instant) -> Option<Duration> {
+        self.established_at
+            .map(|established_at| now.saturating_duration_since(established_at))
+    }
+
+    /// The counters as a dictionary, e.g. to extend the result of `wamp.session.get`.
+    pub fn to_details(&self, now: Instant) -> JsonValue {
+        let traffic = |traffic: &TrafficStats| {
+            let mut messages = JsonValue::new_object();
+            for (id, count) in &traffic.messages {
+                let name = arity(*id).map_or_else(|| id.to_string(), |arity| arity.name.into());
+                messages[name.as_str()] = (*count).into();
+            }
+            json::object! {
+                messages: messages,
+                bytes: traffic.bytes,
+                errors: traffic.errors,
+            }
+        };
+        json::object! {
+            inbound: traffic(&self.inbound),
+            outbound: traffic(&self.outbound),
+            subscriptions: self.active_subscriptions(),
+            registrations: self.active_registrations(),
+            pending_calls: self.pending_calls(),
+            uptime_ms: self.uptime(now).map(|uptime| uptime.as_millis() as u64),
+        }
+    }
+}
+
+/// Approximate counters in fixed space: `depth` rows of `width` counters, an item counts in
+/// one counter per row and its estimate is the smallest of them.
+///
+/// Estimates never fall below the real count, and exceed it only through collisions.
+/// # Examples
+/// ```
+/// use wamp_helpers::stats::CountMinSketch;
+///
+/// let mut sketch = CountMinSketch::new(256, 4);
+/// sketch.add("com.example.add", 3);
+/// sketch.add("com.example.add", 2);
+/// assert_eq!(sketch.estimate("com.example.add"), 5);
+/// assert!(sketch.estimate("com.example.sub") < 5);
+/// ```
+#[derive(Debug, Clone)]
+pub struct CountMinSketch {
+    width: usize,
+    counters: Vec<Vec<u64>>,
+}
+
+impl CountMinSketch {
+    /// A sketch of `depth` rows of `width` counters, both at least one.
+    pub fn new(width: usize, depth: usize) -> Self {
+        let width = width.max(1);
+        CountMinSketch {
+            width,
+            counters: vec![vec![0; width]; depth.max(1)],
+        }
+    }
+
+    /// Count `item` `count` more times, returns its new estimate.
+    pub fn add<T: Hash + ?Sized>(&mut self, item: &T, count: u64) -> u64 {
+        let mut estimate = u64::MAX;
+        for row in 0..self.counters.len() {
+            let column = self.column(row, item);
+            let counter = &mut self.counters[row][column];
+            *counter = counter.saturating_add(count);
+            estimate = estimate.min(*counter);
+        }
+        estimate
+    }
+
+    pub fn estimate<T: Hash + ?Sized>(&self, item: &T) -> u64 {
+        (0..self.counters.len())
+            .map(|row| self.counters[row][self.column(row, item)])
+            .min()
+            .unwrap_or_default()
+    }
+
+    pub fn clear(&mut self) {
+        for row in &mut self.counters {
+            row.fill(0);
+        }
+    }
+
+    fn column<T: Hash + ?Sized>(&self, row: usize, item: &T) -> usize {
+        let mut hasher = DefaultHasher::new();
+        row.hash(&mut hasher);
+        item.hash(&mut hasher);
+        (hasher.finish() % self.width as u64) as usize
+    }
+}
+
+/// The `k` most frequent items of a stream, counted in a [`CountMinSketch`].
+///
+/// Only the `k` candidates are kept besides the sketch: an item replaces the least frequent
+/// candidate once its estimate is higher, so space does not grow with the number of items.
+#[derive(Debug, Clone)]
+pub struct TopK<T> {
+    k: usize,
+    sketch: CountMinSketch,
+    candidates: HashMap<T, u64>,
+}
+
+impl<T: Hash + Eq + Ord + Clone> TopK<T> {
+    /// Width of the sketch unless set with [`sketch`](Self::sketch).
+    pub const DEFAULT_WIDTH: usize = 2048;
+    pub const DEFAULT_DEPTH: usize = 4;
+
+    pub fn new(k: usize) -> Self {
+        TopK {
+            k,
+            sketch: CountMinSketch::new(Self::DEFAULT_WIDTH, Self::DEFAULT_DEPTH),
+            candidates: HashMap::with_capacity(k + 1),
+        }
+    }
+
+    /// Size of the sketch, wider is more accurate, deeper is less often wrong.
+    pub fn sketch(mut self, width: usize, depth: usize) -> Self {
+        self.sketch = CountMinSketch::new(width, depth);
+        self
+    }
+
+    /// Count `item` `count` more times.
+    pub fn record(&mut self, item: &T, count: u64) {
+        let estimate = self.sketch.add(item, count);
+        if let Some(candidate) = self.candidates.get_mut(item) {
+            *candidate = estimate;
+            return;
+        }
+        if self.candidates.len() >= self.k {
+            let Some((least, least_count)) = self
+                .candidates
+                .iter()
+                .min_by_key(|(candidate, count)| (**count, Reverse(*candidate)))
+                .map(|(candidate, count)| (candidate.clone(), *count))
+            else {
+                return;
+            };
+            if estimate <= least_count {
+                return;
+            }
+            self.candidates.remove(&least);
+        }
+        self.candidates.insert(item.clone(), estimate);
+    }
+
+    /// The candidates with their estimated counts, most frequent first.
+    pub fn top(&self) -> Vec<(T, u64)> {
+        let mut top: Vec<_> = self
+            .candidates
+            .iter()
+            .map(|(item, count)| (item.clone(), *count))
+            .collect();
+        top.sort_by(|(a, a_count), (b, b_count)| b_count.cmp(a_count).then_with(|| a.cmp(b)));
+        top
+    }
+
+    pub fn estimate(&self, item: &T) -> u64 {
+        self.sketch.estimate(item)
+    }
+
+    /// Forget every count, e.g. to start a new reporting window.
+    pub fn clear(&mut self) {
+        self.sketch.clear();
+        self.candidates.clear();
+    }
+}
+
+/// The hottest topics and procedures of a router and its noisiest sessions, in bounded space.
+///
+/// Fed with inbound messages: every message counts for its session, PUBLISHes and CALLs
+/// count for their URI. Counts are estimates that may only be too high.
+/// # Examples
+/// ```
+/// use wamp_helpers::messages::Message;
+/// use wamp_helpers::stats::TopTalkers;
+///
+/// let mut talkers = TopTalkers::new(2);
+/// for (session, frame) in [
+///     (1, r#"[16, 1, {}, "com.example.ticks"]"#),
+///     (1, r#"[16, 2, {}, "com.example.ticks"]"#),
+///     (2, r#"[48, 1, {}, "com.example.add"]"#),
+///     (1, r#"[16, 3, {}, "com.example.ticks"]"#),
+///     (3, r#"[32, 1, {}, "com.example.ticks"]"#),
+/// ] {
+///     talkers.record(session, &Message::parse_message(frame).unwrap());
+/// }
+///
+/// assert_eq!(talkers.hottest_uris()[0], ("com.example.ticks".to_string(), 3));
+/// assert_eq!(talkers.noisiest_sessions(), vec![(1, 3), (2, 1)]);
+/// assert_eq!(talkers.to_details()["sessions"][0]["session"], 1);
+/// ```
+#[derive(Debug, Clone)]
+pub struct TopTalkers {
+    uris: TopK<String>,
+    sessions: TopK<WampId>,
+}
+
+impl TopTalkers {
+    /// Track the `k` hottest URIs and the `k` noisiest sessions.
+    pub fn new(k: usize) -> Self {
+        TopTalkers {
+            uris: TopK::new(k),
+            sessions: TopK::new(k),
+        }
+    }
+
+    /// Count an inbound message of `session`.
+    pub fn record(&mut self, session: WampId, message: &Message) {
+        self.sessions.record(&session, 1);
+        match message {
+            Message::Publish(publish) => self.uris.record(&publish.topic.to_string(), 1),
+            Message::Call(call) => self.uris.record(&call.procedure.to_string(), 1),
+            _ => {}
+        }
+    }
+
+    /// Topics and procedures by publications and calls, hottest first.
+    pub fn hottest_uris(&self) -> Vec<(String, u64)> {
+        self.uris.top()
+    }
+
+    /// Sessions by messages sent, noisiest first.
+    pub fn noisiest_sessions(&self) -> Vec<(WampId, u64)> {
+        self.sessions.top()
+    }
+
+    pub fn clear(&mut self) {
+        self.uris.clear();
+        self.sessions.clear();
+    }
+
+    /// The report as a dictionary, e.g. for the result of an operator procedure.
+    pub fn to_details(&self) -> JsonValue {
+        let uris: Vec<_> = self
+            .hottest_uris()
+            .into_iter()
+            .map(|(uri, count)| json::object! { uri: uri, count: count })
+            .collect();
+        let sessions: Vec<_> = self
+            .noisiest_sessions()
+            .into_iter()
+            .map(|(session, count)| json::object! { session: session, count: count })
+            .collect();
+        json::object! { uris: uris, sessions: sessions }
+    }
+}
@@ -0,0 +1,195 @@
+use crate::error::Error;
+use crate::messages::{WampId, WampResult, Yield};
+use crate::value::WampValue;
+use json::JsonValue;
+use std::collections::VecDeque;
+use std::io::{ErrorKind, Read};
+
+/// Default size of the binary chunks sent in progressive YIELDs.
+pub const DEFAULT_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Turns a reader into progressive YIELDs for one invocation, each carrying a binary chunk as
+/// its only argument. All YIELDs but the last have `progress: true`, the last one may carry
+/// an empty chunk when the reader's length is a multiple of the chunk size.
+/// # Examples
+/// ```
+/// use std::io::{ErrorKind, Read};
+/// use wamp_helpers::messages::WampResult;
+/// use wamp_helpers::streaming::{ResultStream, YieldChunks};
+///
+/// let source: &[u8] = b"a large result";
+/// let chunks: Vec<_> = YieldChunks::new(42, source, 5).collect::<Result<_, _>>().unwrap();
+/// assert_eq!(chunks.len(), 3);
+/// assert_eq!(chunks[0].options["progress"], true);
+/// assert!(chunks[2].options["progress"].is_null());
+///
+/// // The dealer forwards the chunks to the caller as RESULTs.
+/// let mut stream = ResultStream::new(7);
+/// for chunk in chunks {
+///     let result = WampResult { request: 7, details: chunk.options, args: chunk.args, kwargs: None };
+///     assert!(stream.push(&result).unwrap());
+/// }
+/// assert!(stream.is_complete());
+/// let mut body = String::new();
+/// stream.read_to_string(&mut body).unwrap();
+/// assert_eq!(body, "a large result");
+/// ```
+#[derive(Debug)]
+pub struct YieldChunks<R> {
+    request: WampId,
+    reader: R,
+    chunk_size: usize,
+    pending: Option<Vec<u8>>,
+    done: bool,
+}
+
+impl<R: Read> YieldChunks<R> {
+    /// Stream `reader` as the result of INVOCATION `request`.
+    pub fn new(request: WampId, reader: R, chunk_size: usize) -> Self {
+        YieldChunks {
+            request,
+            reader,
+            chunk_size: chunk_size.max(1),
+            pending: None,
+            done: false,
+        }
+    }
+
+    fn read_chunk(&mut self) -> std::io::Result<Vec<u8>> {
+        let mut chunk = Vec::with_capacity(self.chunk_size);
+        (&mut self.reader)
+            .take(self.chunk_size as u64)
+            .read_to_end(&mut chunk)?;
+        Ok(chunk)
+    }
+
+    fn chunk(&self, chunk: Vec<u8>, progress: bool) -> Yield {
+        let mut options = JsonValue::new_object();
+        if progress {
+            options["progress"] = true.into();
+        }
+        Yield {
+            request: self.request,
+            options,
+            args: Some(vec![WampValue::Bytes(chunk)]),
+            kwargs: None,
+        }
+    }
+}
+
+impl<R: Read> Iterator for YieldChunks<R> {
+    type Item = std::io::Result<Yield>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let current = match self.pending.take() {
+            Some(chunk) => chunk,
+            None => match self.read_chunk() {
+                Ok(chunk) => chunk,
+                Err(err) => {
+                    self.done = true;
+                    return Some(Err(err));
+                }
+            },
+        };
+        if current.len() < self.chunk_size {
+            self.done = true;
+            return Some(Ok(self.chunk(current, false)));
+        }
+
+        // Read ahead to learn whether `current` is the final chunk.
+        match self.read_chunk() {
+            Ok(next) if next.is_empty() => {
+                self.done = true;
+                Some(Ok(self.chunk(current, false)))
+            }
+            Ok(next) => {
+                self.pending = Some(next);
+                Some(Ok(self.chunk(current, true)))
+            }
+            Err(err) => {
+                self.done = true;
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+/// Collects the binary chunks of progressive RESULTs for one call and reads them back in
+/// order, the counterpart of [`YieldChunks`].
+///
+/// Reading returns what arrived so far. Once that is read and the stream is not
+/// [complete](ResultStream::is_complete), reads fail with [`ErrorKind::WouldBlock`] until
+/// more RESULTs are pushed, 0 means the call's result ended.
+/// ```
+/// use std::io::{ErrorKind, Read};
+/// use wamp_helpers::messages::WampResult;
+/// use wamp_helpers::streaming::ResultStream;
+/// use wamp_helpers::value::WampValue;
+///
+/// let mut stream = ResultStream::new(7);
+/// let args = Some(vec![WampValue::Bytes(b"hi".to_vec())]);
+/// let details = json::object! { progress: true };
+/// stream.push(&WampResult { request: 7, details, args, kwargs: None }).unwrap();
+///
+/// let mut received = Vec::new();
+/// let error = stream.read_to_end(&mut received).unwrap_err();
+/// assert_eq!((error.kind(), received.as_slice()), (ErrorKind::WouldBlock, &b"hi"[..]));
+/// ```
+#[derive(Debug, Default)]
+pub struct ResultStream {
+    request: WampId,
+    buffer: VecDeque<u8>,
+    complete: bool,
+}
+
+impl ResultStream {
+    /// Collect the RESULTs of CALL `request`.
+    pub fn new(request: WampId) -> Self {
+        ResultStream {
+            request,
+            ..ResultStream::default()
+        }
+    }
+
+    /// Add a RESULT, returns `Ok(false)` when it belongs to another call and `Ok(true)` when
+    /// it was taken.
+    pub fn push(&mut self, result: &WampResult) -> Result<bool, Error> {
+        if result.request != self.request || self.complete {
+            return Ok(false);
+        }
+        for argument in result.args.iter().flatten() {
+            match argument {
+                WampValue::Bytes(chunk) => self.buffer.extend(chunk),
+                other => {
+                    return Err(Error::InvalidChunk {
+                        offense: other.clone().into(),
+                    })
+                }
+            }
+        }
+        self.complete = !result.details["progress"].as_bool().unwrap_or(false);
+        Ok(true)
+    }
+
+    /// `true` once the final, non-progressive RESULT arrived.
+    pub fn is_complete(&self) -> bool {
+        self.complete
+    }
+
+    /// Bytes received but not read yet.
+    pub fn buffered(&self) -> usize {
+        self.buffer.len()
+    }
+}
+
+impl Read for ResultStream {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.buffer.is_empty() && !self.complete && !buf.is_empty() {
+            return Err(ErrorKind::WouldBlock.into());
+        }
+        self.buffer.read(buf)
+    }
+}
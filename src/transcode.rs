@@ -0,0 +1,261 @@
+use crate::acceptor::Serializer;
+use crate::error::Error;
+use crate::value::WampValue;
+use json::JsonValue;
+
+/// Decode a frame of `serializer` into its generic form, binary payloads become
+/// [`WampValue::Bytes`] whatever their encoding on the wire.
+pub fn decode(frame: &[u8], serializer: Serializer) -> Result<WampValue, Error> {
+    match serializer {
+        Serializer::Json => {
+            let text = std::str::from_utf8(frame).map_err(|error| Error::Codec(Box::new(error)))?;
+            Ok(WampValue::from(
+                json::parse(text).map_err(Error::JsonError)?,
+            ))
+        }
+        #[cfg(feature = "msgpack")]
+        Serializer::MsgPack => {
+            let value = rmpv::decode::read_value(&mut &frame[..])
+                .map_err(|error| Error::Codec(Box::new(error)))?;
+            Ok(WampValue::from(value))
+        }
+        #[cfg(feature = "cbor")]
+        Serializer::Cbor => {
+            let value: ciborium::Value =
+                ciborium::de::from_reader(frame).map_err(|error| Error::Codec(Box::new(error)))?;
+            WampValue::try_from(value)
+        }
+        #[allow(unreachable_patterns)]
+        other => Err(Error::UnsupportedSerializer {
+            subprotocol: other.subprotocol(),
+        }),
+    }
+}
+
+/// Encode a generic value as a frame of `serializer`.
+pub fn encode(value: WampValue, serializer: Serializer) -> Result<Vec<u8>, Error> {
+    let mut frame = Vec::new();
+    encode_into(value, serializer, &mut frame)?;
+    Ok(frame)
+}
+
+/// Like [`encode`], appending to `frame`, e.g. a buffer from a
+/// [`BufferPool`](crate::pool::BufferPool).
+pub fn encode_into(
+    value: WampValue,
+    serializer: Serializer,
+    frame: &mut Vec<u8>,
+) -> Result<(), Error> {
+    match serializer {
+        Serializer::Json => JsonValue::from(value).write(frame).map_err(Error::Io),
+        #[cfg(feature = "msgpack")]
+        Serializer::MsgPack => rmpv::encode::write_value(frame, &rmpv::Value::from(value))
+            .map_err(|error| Error::Codec(Box::new(error))),
+        #[cfg(feature = "cbor")]
+        Serializer::Cbor => ciborium::ser::into_writer(&ciborium::Value::from(value), frame)
+            .map_err(|error| Error::Codec(Box::new(error))),
+        #[allow(unreachable_patterns)]
+        other => Err(Error::UnsupportedSerializer {
+            subprotocol: other.subprotocol(),
+        }),
+    }
+}
+
+/// Convert a raw frame between serializers, e.g. for a gateway linking peers that negotiated
+/// different ones.
+///
+/// Binary payloads keep their meaning: a `\0`-prefixed base64 string in JSON becomes a binary
+/// value in MsgPack and CBOR and back. Frames already in the target serializer are copied
+/// as is. MsgPack and CBOR need the `msgpack` and `cbor` features.
+/// # Examples
+/// ```
+/// use wamp_helpers::acceptor::Serializer;
+/// use wamp_helpers::transcode::transcode;
+///
+/// let frame = br#"[36, 5512315355, 4429313566, {}, ["\u0000AQID"]]"#;
+/// let json = transcode(frame, Serializer::Json, Serializer::Json).unwrap();
+/// assert_eq!(json, frame);
+/// # #[cfg(feature = "msgpack")]
+/// # {
+/// let packed = transcode(frame, Serializer::Json, Serializer::MsgPack).unwrap();
+/// let back = transcode(&packed, Serializer::MsgPack, Serializer::Json).unwrap();
+/// assert_eq!(back, br#"[36,5512315355,4429313566,{},["\u0000AQID"]]"#);
+/// # }
+/// # #[cfg(feature = "cbor")]
+/// # {
+/// # let cbor = transcode(frame, Serializer::Json, Serializer::Cbor).unwrap();
+/// # let back = transcode(&cbor, Serializer::Cbor, Serializer::Json).unwrap();
+/// # assert_eq!(back, br#"[36,5512315355,4429313566,{},["\u0000AQID"]]"#);
+/// # }
+/// ```
+pub fn transcode(frame: &[u8], from: Serializer, to: Serializer) -> Result<Vec<u8>, Error> {
+    if from == to {
+        return Ok(frame.to_vec());
+    }
+    encode(decode(frame, from)?, to)
+}
+
+/// Bytes a JSON number literal of at most this length takes, used for floats.
+const FLOAT_JSON_LEN: usize = 24;
+
+fn digits(mut magnitude: u64) -> usize {
+    let mut digits = 1;
+    while magnitude >= 10 {
+        magnitude /= 10;
+        digits += 1;
+    }
+    digits
+}
+
+/// Size of a MsgPack or CBOR length or integer header.
+fn header_size(serializer: Serializer, len: u64, inline: u64) -> usize {
+    match serializer {
+        Serializer::Json => 0,
+        _ if len < inline => 1,
+        _ if len <= u8::MAX as u64 => 2,
+        _ if len <= u16::MAX as u64 => 3,
+        _ if len <= u32::MAX as u64 => 5,
+        _ => 9,
+    }
+}
+
+fn integer_hint(magnitude: u64, negative: bool, serializer: Serializer) -> usize {
+    match serializer {
+        Serializer::Json => digits(magnitude) + usize::from(negative),
+        Serializer::MsgPack if negative => match magnitude {
+            0..=32 => 1,
+            33..=0x80 => 2,
+            0x81..=0x8000 => 3,
+            0x8001..=0x8000_0000 => 5,
+            _ => 9,
+        },
+        Serializer::MsgPack => header_size(serializer, magnitude, 128),
+        // Negative integers are stored as -1 - n.
+        Serializer::Cbor => header_size(serializer, magnitude - u64::from(negative), 24),
+    }
+}
+
+/// Encoded size of a string.
+pub fn str_size_hint(text: &str, serializer: Serializer) -> usize {
+    match serializer {
+        Serializer::Json => {
+            let escapes: usize = text
+                .bytes()
+                .map(|byte| match byte {
+                    b'"' | b'\\' | b'\x08' | b'\x0c' | b'\n' | b'\r' | b'\t' => 1,
+                    0..=0x1f => 5,
+                    _ => 0,
+                })
+                .sum();
+            text.len() + escapes + 2
+        }
+        Serializer::MsgPack => text.len() + header_size(serializer, text.len() as u64, 32),
+        Serializer::Cbor => text.len() + header_size(serializer, text.len() as u64, 24),
+    }
+}
+
+fn bytes_hint(len: usize, serializer: Serializer) -> usize {
+    match serializer {
+        // A `\0` escaped as `\u0000` followed by base64.
+        Serializer::Json => 2 + 6 + len.div_ceil(3) * 4,
+        // MsgPack bin has no inline length.
+        Serializer::MsgPack => len + header_size(serializer, len as u64, 0),
+        Serializer::Cbor => len + header_size(serializer, len as u64, 24),
+    }
+}
+
+/// Brackets, colons and commas of a JSON list or dictionary of `len` items, or the header of
+/// a MsgPack or CBOR one.
+pub fn container_size_hint(len: usize, map: bool, serializer: Serializer) -> usize {
+    match serializer {
+        Serializer::Json => 2 + len.saturating_sub(1) + if map { len } else { 0 },
+        // There are no 8 bit array and map headers.
+        Serializer::MsgPack => match len {
+            0..=15 => 1,
+            16..=0xffff => 3,
+            _ => 5,
+        },
+        Serializer::Cbor => header_size(serializer, len as u64, 24),
+    }
+}
+
+/// Encoded size of a non-negative integer such as an ID.
+pub fn integer_size_hint(value: u64, serializer: Serializer) -> usize {
+    integer_hint(value, false, serializer)
+}
+
+fn float_hint(serializer: Serializer) -> usize {
+    match serializer {
+        Serializer::Json => FLOAT_JSON_LEN,
+        _ => 9,
+    }
+}
+
+/// Estimated size of `value` encoded with `serializer`, without encoding it.
+///
+/// Never below the encoded size and exact for values without floats, which count as 24
+/// bytes in JSON and 9 in MsgPack and CBOR, their longest forms.
+pub fn size_hint(value: &WampValue, serializer: Serializer) -> usize {
+    match value {
+        WampValue::Null => match serializer {
+            Serializer::Json => 4,
+            _ => 1,
+        },
+        WampValue::Bool(value) => match serializer {
+            Serializer::Json if *value => 4,
+            Serializer::Json => 5,
+            _ => 1,
+        },
+        WampValue::Integer(value) => integer_hint(value.unsigned_abs(), *value < 0, serializer),
+        WampValue::Float(_) => float_hint(serializer),
+        WampValue::String(text) => str_size_hint(text, serializer),
+        WampValue::Bytes(bytes) => bytes_hint(bytes.len(), serializer),
+        WampValue::List(items) => {
+            container_size_hint(items.len(), false, serializer)
+                + items
+                    .iter()
+                    .map(|item| size_hint(item, serializer))
+                    .sum::<usize>()
+        }
+        WampValue::Dict(entries) => {
+            container_size_hint(entries.len(), true, serializer)
+                + entries
+                    .iter()
+                    .map(|(key, item)| str_size_hint(key, serializer) + size_hint(item, serializer))
+                    .sum::<usize>()
+        }
+    }
+}
+
+/// [`size_hint`] for a `JsonValue`, e.g. a Details dictionary, as it would be encoded after
+/// conversion to [`WampValue`].
+pub fn json_size_hint(value: &JsonValue, serializer: Serializer) -> usize {
+    match value {
+        JsonValue::Null => size_hint(&WampValue::Null, serializer),
+        JsonValue::Boolean(value) => size_hint(&WampValue::Bool(*value), serializer),
+        JsonValue::Number(_) => match (value.as_u64(), value.as_i64()) {
+            (Some(magnitude), _) => integer_hint(magnitude, false, serializer),
+            (None, Some(negative)) => integer_hint(negative.unsigned_abs(), true, serializer),
+            _ => float_hint(serializer),
+        },
+        JsonValue::Short(_) | JsonValue::String(_) => {
+            str_size_hint(value.as_str().unwrap_or_default(), serializer)
+        }
+        JsonValue::Array(items) => {
+            container_size_hint(items.len(), false, serializer)
+                + items
+                    .iter()
+                    .map(|item| json_size_hint(item, serializer))
+                    .sum::<usize>()
+        }
+        JsonValue::Object(object) => {
+            container_size_hint(object.len(), true, serializer)
+                + object
+                    .iter()
+                    .map(|(key, item)| {
+                        str_size_hint(key, serializer) + json_size_hint(item, serializer)
+                    })
+                    .sum::<usize>()
+        }
+    }
+}
@@ -0,0 +1,185 @@
+use crate::error::Error;
+use json::JsonValue;
+use std::future::Future;
+use std::net::{IpAddr, SocketAddr};
+
+/// A message-oriented connection carrying serialized WAMP frames.
+///
+/// Each call to [`Transport::send`] delivers exactly one frame, and [`Transport::next`] yields
+/// frames in the order they were received. Implementations exist per medium (WebSocket,
+/// RawSocket, QUIC, in-memory), nothing above this trait depends on which one is used.
+pub trait Transport {
+    /// Send one frame.
+    fn send(&mut self, frame: Vec<u8>) -> impl Future<Output = Result<(), Error>> + Send;
+
+    /// Wait for the next frame, `None` once the peer closed the transport.
+    fn next(&mut self) -> impl Future<Output = Option<Result<Vec<u8>, Error>>> + Send;
+
+    /// Close the transport, `reason` is passed on where the medium supports it.
+    fn close(&mut self, reason: &str) -> impl Future<Output = Result<(), Error>> + Send;
+
+    /// The remote peer as seen while the connection was opened, `None` when the medium has no
+    /// such notion.
+    fn peer(&self) -> Option<&PeerInfo> {
+        None
+    }
+}
+
+/// The HTTP request that opened a WebSocket connection.
+/// # Examples
+/// ```
+/// use wamp_helpers::transport::UpgradeRequest;
+///
+/// let request = UpgradeRequest::parse(
+///     "GET /ws HTTP/1.1\r\n\
+///      Host: router.example.com\r\n\
+///      Upgrade: websocket\r\n\
+///      Sec-WebSocket-Protocol: wamp.2.json\r\n\
+///      Cookie: cbtid=f1xH6hvC; theme=dark\r\n\
+///      X-Forwarded-For: 203.0.113.7, 10.0.0.2\r\n\r\n",
+/// )
+/// .unwrap();
+/// assert_eq!(request.path, "/ws");
+/// assert_eq!(request.header("sec-websocket-protocol"), Some("wamp.2.json"));
+/// assert_eq!(request.cookie("cbtid"), Some("f1xH6hvC"));
+/// assert_eq!(request.forwarded_for(), ["203.0.113.7".parse::<std::net::IpAddr>().unwrap(), "10.0.0.2".parse().unwrap()]);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UpgradeRequest {
+    pub method: String,
+    pub path: String,
+    /// Headers in the order received, names as sent.
+    pub headers: Vec<(String, String)>,
+}
+
+impl UpgradeRequest {
+    /// Parse the request line and headers, anything after the blank line is ignored.
+    pub fn parse(head: &str) -> Result<Self, Error> {
+        let mut lines = head.split("\r\n");
+        let request_line = lines.next().unwrap_or_default();
+        let mut parts = request_line.split(' ');
+        let (Some(method), Some(path), Some(_version)) = (parts.next(), parts.next(), parts.next())
+        else {
+            return Err(Error::InvalidHandshake {
+                line: request_line.to_string(),
+            });
+        };
+
+        let mut headers = Vec::new();
+        for line in lines.take_while(|line| !line.is_empty()) {
+            let Some((name, value)) = line.split_once(':') else {
+                return Err(Error::InvalidHandshake {
+                    line: line.to_string(),
+                });
+            };
+            headers.push((name.trim().to_string(), value.trim().to_string()));
+        }
+        Ok(UpgradeRequest {
+            method: method.to_string(),
+            path: path.to_string(),
+            headers,
+        })
+    }
+
+    /// First value of header `name`, compared case-insensitively.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(header, _)| header.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Cookies of every `Cookie` header as name/value pairs.
+    pub fn cookies(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.headers
+            .iter()
+            .filter(|(header, _)| header.eq_ignore_ascii_case("cookie"))
+            .flat_map(|(_, value)| value.split(';'))
+            .filter_map(|cookie| cookie.trim().split_once('='))
+    }
+
+    pub fn cookie(&self, name: &str) -> Option<&str> {
+        self.cookies()
+            .find(|(cookie, _)| *cookie == name)
+            .map(|(_, value)| value)
+    }
+
+    /// Addresses listed in `X-Forwarded-For`, client first, unparsable entries skipped.
+    pub fn forwarded_for(&self) -> Vec<IpAddr> {
+        self.header("x-forwarded-for")
+            .into_iter()
+            .flat_map(|value| value.split(','))
+            .filter_map(|address| address.trim().parse().ok())
+            .collect()
+    }
+}
+
+/// What a transport knows about the remote peer, for authenticators and session details.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PeerInfo {
+    /// Address of the socket peer, which is the proxy when behind one.
+    pub address: Option<SocketAddr>,
+    /// Client address reported by a load balancer through the PROXY protocol, see
+    /// [`parse_proxy_header`](crate::proxy::parse_proxy_header).
+    pub proxied: Option<SocketAddr>,
+    /// The WebSocket upgrade request, `None` for other transports.
+    pub upgrade: Option<UpgradeRequest>,
+}
+
+impl PeerInfo {
+    /// Best guess of the client's address: walk `X-Forwarded-For` from the right while the hop
+    /// is one of the `trusted_proxies`, starting at the PROXY protocol source or else the
+    /// socket peer.
+    pub fn client_address(&self, trusted_proxies: &[IpAddr]) -> Option<IpAddr> {
+        let mut client = self.proxied.or(self.address)?.ip();
+        if let Some(upgrade) = &self.upgrade {
+            for hop in upgrade.forwarded_for().into_iter().rev() {
+                if !trusted_proxies.contains(&client) {
+                    break;
+                }
+                client = hop;
+            }
+        }
+        Some(client)
+    }
+
+    /// Transport details in the style of Crossbar, e.g. for the `transport` key of
+    /// `wamp.session.get`.
+    /// ```
+    /// use wamp_helpers::transport::PeerInfo;
+    ///
+    /// let peer = PeerInfo {
+    ///     address: Some("10.0.0.2:41000".parse().unwrap()),
+    ///     proxied: Some("203.0.113.7:56324".parse().unwrap()),
+    ///     upgrade: None,
+    /// };
+    /// let details = peer.to_details();
+    /// assert_eq!(details["peer"], "tcp4:203.0.113.7:56324");
+    /// assert_eq!(details["proxy"], "tcp4:10.0.0.2:41000");
+    /// ```
+    pub fn to_details(&self) -> JsonValue {
+        let describe = |address: SocketAddr| match address {
+            SocketAddr::V4(address) => format!("tcp4:{}", address),
+            SocketAddr::V6(address) => format!("tcp6:{}", address),
+        };
+        let mut details = JsonValue::new_object();
+        match (self.proxied, self.address) {
+            (Some(proxied), proxy) => {
+                details["peer"] = describe(proxied).into();
+                if let Some(proxy) = proxy {
+                    details["proxy"] = describe(proxy).into();
+                }
+            }
+            (None, Some(address)) => details["peer"] = describe(address).into(),
+            (None, None) => {}
+        }
+        if let Some(upgrade) = &self.upgrade {
+            let mut headers = JsonValue::new_object();
+            for (name, value) in &upgrade.headers {
+                headers[name.to_ascii_lowercase().as_str()] = value.as_str().into();
+            }
+            details["http_headers_received"] = headers;
+        }
+        details
+    }
+}
@@ -0,0 +1,365 @@
+use crate::error::Error;
+use crate::messages::Uri;
+use std::collections::BTreeMap;
+
+/// Check a URI against the spec rules: components separated by `.`, loose URIs only forbid
+/// whitespace, `.` and `#` inside components, strict URIs only allow `[0-9a-z_]`.
+/// # Examples
+/// ```
+/// use wamp_helpers::uri::is_valid_uri;
+/// assert!(is_valid_uri("com.myapp.mytopic1", true));
+/// assert!(!is_valid_uri("com.MyApp.topic", true));
+/// assert!(is_valid_uri("com.MyApp.topic", false));
+/// assert!(!is_valid_uri("com..topic", false));
+/// ```
+pub fn is_valid_uri(uri: &str, strict: bool) -> bool {
+    !uri.is_empty()
+        && uri
+            .split('.')
+            .all(|component| is_valid_component(component, strict))
+}
+
+/// Like [`is_valid_uri`] but allows empty components, as used by wildcard subscriptions and
+/// registrations.
+pub fn is_valid_pattern(uri: &str, strict: bool) -> bool {
+    !uri.is_empty()
+        && uri
+            .split('.')
+            .all(|component| component.is_empty() || is_valid_component(component, strict))
+}
+
+/// Check a single URI component.
+pub fn is_valid_component(component: &str, strict: bool) -> bool {
+    !component.is_empty()
+        && component.chars().all(|character| {
+            if strict {
+                matches!(character, '0'..='9' | 'a'..='z' | '_')
+            } else {
+                !character.is_whitespace() && character != '.' && character != '#'
+            }
+        })
+}
+
+/// Splitting a URI into its `.` separated components.
+pub trait UriComponents {
+    /// # Examples
+    /// ```
+    /// use wamp_helpers::uri::UriComponents;
+    /// let components: Vec<&str> = "com.myapp.mytopic1".split_components().collect();
+    /// assert_eq!(components, ["com", "myapp", "mytopic1"]);
+    /// ```
+    fn split_components(&self) -> std::str::Split<'_, char>;
+}
+
+impl UriComponents for str {
+    fn split_components(&self) -> std::str::Split<'_, char> {
+        self.split('.')
+    }
+}
+
+/// Assembles a URI from components, checking each with [`is_valid_component`].
+///
+/// Useful when parts of the URI come from user input, a component containing `.` is rejected
+/// rather than silently adding a level.
+/// # Examples
+/// ```
+/// use wamp_helpers::error::Error;
+/// use wamp_helpers::uri::UriBuilder;
+///
+/// let uri = UriBuilder::new(true)
+///     .prefix("com.myapp")
+///     .component("user_42")
+///     .component("profile")
+///     .build()
+///     .unwrap();
+/// assert_eq!(uri, "com.myapp.user_42.profile");
+///
+/// let injected = UriBuilder::new(false).prefix("com.myapp").component("a.b").build();
+/// assert!(matches!(injected, Err(Error::InvalidUriComponent { .. })));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct UriBuilder {
+    components: Vec<String>,
+    strict: bool,
+}
+
+impl UriBuilder {
+    /// Start an empty URI, `strict` selects strict over loose components.
+    pub fn new(strict: bool) -> Self {
+        UriBuilder {
+            components: Vec::new(),
+            strict,
+        }
+    }
+
+    /// Append every component of an existing URI.
+    pub fn prefix(mut self, prefix: &str) -> Self {
+        self.components
+            .extend(prefix.split_components().map(str::to_string));
+        self
+    }
+
+    /// Append a single component.
+    pub fn component(mut self, component: impl Into<String>) -> Self {
+        self.components.push(component.into());
+        self
+    }
+
+    /// Join the components, failing on the first invalid one.
+    pub fn build(self) -> Result<Uri, Error> {
+        if self.components.is_empty() {
+            return Err(Error::InvalidUriComponent {
+                component: String::new(),
+            });
+        }
+        for component in &self.components {
+            if !is_valid_component(component, self.strict) {
+                return Err(Error::InvalidUriComponent {
+                    component: component.clone(),
+                });
+            }
+        }
+        Ok(self.components.join(".").into())
+    }
+}
+
+/// What a URI declared in a [`Namespace`] names.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum UriKind {
+    Procedure,
+    Topic,
+    Error,
+}
+
+/// The URI tree of an application, declared once under a common prefix.
+///
+/// Declaring the same URI twice fails with [`Error::UriCollision`], which catches copy-paste
+/// mistakes such as a topic reusing a procedure's name.
+/// # Examples
+/// ```
+/// use wamp_helpers::error::Error;
+/// use wamp_helpers::uri::{Namespace, UriKind};
+///
+/// let mut namespace = Namespace::new("com.myapp", true).unwrap();
+/// let add = namespace.procedure("math.add").unwrap();
+/// let updated = namespace.topic("users.updated").unwrap();
+/// assert_eq!(add, "com.myapp.math.add");
+/// assert_eq!(namespace.kind(&updated), Some(UriKind::Topic));
+///
+/// assert!(matches!(namespace.topic("math.add"), Err(Error::UriCollision { .. })));
+/// assert!(matches!(namespace.error("Bad Input"), Err(Error::InvalidUriComponent { .. })));
+/// ```
+#[derive(Debug, Clone)]
+pub struct Namespace {
+    prefix: Uri,
+    strict: bool,
+    declared: BTreeMap<Uri, UriKind>,
+}
+
+impl Namespace {
+    pub fn new(prefix: &str, strict: bool) -> Result<Self, Error> {
+        Ok(Namespace {
+            prefix: UriBuilder::new(strict).prefix(prefix).build()?,
+            strict,
+            declared: BTreeMap::new(),
+        })
+    }
+
+    pub fn prefix(&self) -> &str {
+        &self.prefix
+    }
+
+    /// Declare `path`, relative to the prefix, as a URI of `kind`.
+    pub fn declare(&mut self, kind: UriKind, path: &str) -> Result<Uri, Error> {
+        let uri = UriBuilder::new(self.strict)
+            .prefix(&self.prefix)
+            .prefix(path)
+            .build()?;
+        if self.declared.contains_key(&uri) {
+            return Err(Error::UriCollision {
+                uri: uri.into_string(),
+            });
+        }
+        self.declared.insert(uri.clone(), kind);
+        Ok(uri)
+    }
+
+    pub fn procedure(&mut self, path: &str) -> Result<Uri, Error> {
+        self.declare(UriKind::Procedure, path)
+    }
+
+    pub fn topic(&mut self, path: &str) -> Result<Uri, Error> {
+        self.declare(UriKind::Topic, path)
+    }
+
+    pub fn error(&mut self, path: &str) -> Result<Uri, Error> {
+        self.declare(UriKind::Error, path)
+    }
+
+    /// Kind of a declared URI, `None` when it is not part of the namespace.
+    pub fn kind(&self, uri: &str) -> Option<UriKind> {
+        self.declared.get(uri).copied()
+    }
+
+    /// Declared URIs in lexicographic order.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, UriKind)> {
+        self.declared
+            .iter()
+            .map(|(uri, kind)| (uri.as_str(), *kind))
+    }
+}
+
+/// How a subscription, registration or permission URI is matched, the `match` option.
+/// # Examples
+/// ```
+/// use wamp_helpers::uri::MatchPolicy;
+/// assert!(MatchPolicy::Prefix.matches("com.myapp", "com.myapp.add"));
+/// assert!(MatchPolicy::Wildcard.matches("com..add", "com.myapp.add"));
+/// assert!(!MatchPolicy::Wildcard.matches("com..add", "com.myapp.math.add"));
+/// assert!(!MatchPolicy::Exact.matches("com.myapp", "com.myapp.add"));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum MatchPolicy {
+    #[default]
+    Exact,
+    Prefix,
+    Wildcard,
+}
+
+impl MatchPolicy {
+    /// Read the `match` option, unknown policies are `None`.
+    pub fn from_options(options: &json::JsonValue) -> Option<Self> {
+        match options["match"].as_str() {
+            None | Some("exact") => Some(MatchPolicy::Exact),
+            Some("prefix") => Some(MatchPolicy::Prefix),
+            Some("wildcard") => Some(MatchPolicy::Wildcard),
+            Some(_) => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            MatchPolicy::Exact => "exact",
+            MatchPolicy::Prefix => "prefix",
+            MatchPolicy::Wildcard => "wildcard",
+        }
+    }
+
+    /// Whether `uri` matches `pattern` under this policy. Wildcard patterns match URIs with
+    /// the same number of components, empty pattern components match anything.
+    pub fn matches(self, pattern: &str, uri: &str) -> bool {
+        match self {
+            MatchPolicy::Exact => pattern == uri,
+            MatchPolicy::Prefix => uri.starts_with(pattern),
+            MatchPolicy::Wildcard => {
+                let pattern: Vec<&str> = pattern.split('.').collect();
+                let uri: Vec<&str> = uri.split('.').collect();
+                pattern.len() == uri.len()
+                    && pattern
+                        .iter()
+                        .zip(&uri)
+                        .all(|(expected, actual)| expected.is_empty() || expected == actual)
+            }
+        }
+    }
+}
+
+/// Error URIs defined by the spec, in the basic and advanced profiles.
+pub const STANDARD_ERRORS: &[&str] = &[
+    "wamp.error.authentication_denied",
+    "wamp.error.authentication_failed",
+    "wamp.error.authentication_required",
+    "wamp.error.authorization_denied",
+    "wamp.error.authorization_failed",
+    "wamp.error.authorization_required",
+    "wamp.error.canceled",
+    "wamp.error.feature_not_supported",
+    "wamp.error.invalid_argument",
+    "wamp.error.invalid_uri",
+    "wamp.error.network_failure",
+    "wamp.error.no_auth_method",
+    "wamp.error.no_available_callee",
+    "wamp.error.no_eligible_callee",
+    "wamp.error.no_such_authrole",
+    "wamp.error.no_such_principal",
+    "wamp.error.no_such_procedure",
+    "wamp.error.no_such_realm",
+    "wamp.error.no_such_registration",
+    "wamp.error.no_such_role",
+    "wamp.error.no_such_session",
+    "wamp.error.no_such_subscription",
+    "wamp.error.not_authorized",
+    "wamp.error.option_disallowed.disclose_me",
+    "wamp.error.option_not_allowed",
+    "wamp.error.payload_size_exceeded",
+    "wamp.error.procedure_already_exists",
+    "wamp.error.protocol_violation",
+    "wamp.error.timeout",
+    "wamp.error.type_check_error",
+    "wamp.error.unavailable",
+];
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, a) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        let mut left = i + 1;
+        for ((b, diagonal), above) in b.iter().zip(&previous).zip(previous.iter().skip(1)) {
+            let substitution = diagonal + usize::from(a != *b);
+            left = substitution.min(above + 1).min(left + 1);
+            current.push(left);
+        }
+        previous = current;
+    }
+    previous.last().copied().unwrap_or_default()
+}
+
+/// The standard error URI `uri` most likely was meant to be, `None` when it is standard
+/// already or nothing is close.
+/// ```
+/// use wamp_helpers::uri::suggest_error_uri;
+/// assert_eq!(suggest_error_uri("wamp.error.no_such_proceduer"), Some("wamp.error.no_such_procedure"));
+/// assert_eq!(suggest_error_uri("wamp.error.no_such_procedure"), None);
+/// assert_eq!(suggest_error_uri("com.myapp.error.overdrawn"), None);
+/// ```
+pub fn suggest_error_uri(uri: &str) -> Option<&'static str> {
+    if STANDARD_ERRORS.contains(&uri) {
+        return None;
+    }
+    STANDARD_ERRORS
+        .iter()
+        .map(|standard| (edit_distance(uri, standard), *standard))
+        .filter(|(distance, _)| *distance <= 3)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, standard)| standard)
+}
+
+/// Check the URI of an ERROR message. Strict checking also requires strict URI components
+/// and rejects `wamp.error.` URIs the spec does not define.
+/// # Examples
+/// ```
+/// use wamp_helpers::error::Error;
+/// use wamp_helpers::uri::check_error_uri;
+///
+/// assert!(check_error_uri("com.myapp.error.overdrawn", true).is_ok());
+/// assert!(check_error_uri("wamp.error.no_such_realm", true).is_ok());
+/// let Err(Error::InvalidErrorUri { suggestion, .. }) = check_error_uri("wamp.error.not_authorised", true) else {
+///     panic!()
+/// };
+/// assert_eq!(suggestion, Some("wamp.error.not_authorized"));
+/// assert!(check_error_uri("wamp.error.not_authorised", false).is_ok());
+/// ```
+pub fn check_error_uri(uri: &str, strict: bool) -> Result<(), Error> {
+    let unknown_standard =
+        strict && uri.starts_with("wamp.error.") && !STANDARD_ERRORS.contains(&uri);
+    if unknown_standard || !is_valid_uri(uri, strict) {
+        return Err(Error::InvalidErrorUri {
+            uri: uri.to_string(),
+            suggestion: suggest_error_uri(uri),
+        });
+    }
+    Ok(())
+}
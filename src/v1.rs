@@ -0,0 +1,412 @@
+use crate::error::Error;
+use crate::messages::{
+    validate_str_argument, Args, Call, ErrorMessage, Event, Message, Publish, Subscribe, Uri,
+    WampId, WampResult,
+};
+use crate::value::WampValue;
+use json::JsonValue;
+use std::collections::HashMap;
+
+/// Protocol version announced in a v1 WELCOME.
+pub const PROTOCOL_VERSION: u64 = 1;
+
+/// A WAMP v1 message.
+///
+/// v1 identifies sessions and calls by strings and uses URIs such as
+/// `http://example.com/calc#add`, which are usually not valid v2 URIs. The conversions to and
+/// from v2 keep URIs untouched, mapping them is up to the gateway.
+/// # Examples
+/// ```
+/// use wamp_helpers::messages::Message;
+/// use wamp_helpers::v1::{Prefixes, V1Message};
+///
+/// let mut prefixes = Prefixes::new();
+/// let prefix = V1Message::parse(r#"[1, "calc", "http://example.com/calc#"]"#).unwrap();
+/// prefixes.record(&prefix);
+///
+/// let call = V1Message::parse(r#"[2, "7DK6TdN4wLiUJgNM", "calc:add", 23, 99]"#).unwrap();
+/// let Some(Message::Call(call)) = prefixes.resolve(call).to_v2(1) else { panic!() };
+/// assert_eq!(call.procedure, "http://example.com/calc#add");
+/// assert_eq!(call.args.unwrap().len(), 2);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub enum V1Message {
+    Welcome {
+        session: String,
+        protocol_version: u64,
+        server_ident: String,
+    },
+    Prefix {
+        prefix: String,
+        uri: Uri,
+    },
+    Call {
+        call_id: String,
+        procedure: Uri,
+        args: Args,
+    },
+    CallResult {
+        call_id: String,
+        result: WampValue,
+    },
+    CallError {
+        call_id: String,
+        error: Uri,
+        description: String,
+        details: Option<WampValue>,
+    },
+    Subscribe {
+        topic: Uri,
+    },
+    Unsubscribe {
+        topic: Uri,
+    },
+    Publish {
+        topic: Uri,
+        event: WampValue,
+        exclude_me: Option<bool>,
+        /// Session ids the event is not delivered to.
+        exclude: Vec<String>,
+        /// Session ids the event is only delivered to, empty for everyone.
+        eligible: Vec<String>,
+    },
+    Event {
+        topic: Uri,
+        event: WampValue,
+    },
+}
+
+fn session_ids(value: &JsonValue) -> Vec<String> {
+    value
+        .members()
+        .filter_map(|id| id.as_str().map(str::to_string))
+        .collect()
+}
+
+impl V1Message {
+    pub const WELCOME: u8 = 0;
+    pub const PREFIX: u8 = 1;
+    pub const CALL: u8 = 2;
+    pub const CALL_RESULT: u8 = 3;
+    pub const CALL_ERROR: u8 = 4;
+    pub const SUBSCRIBE: u8 = 5;
+    pub const UNSUBSCRIBE: u8 = 6;
+    pub const PUBLISH: u8 = 7;
+    pub const EVENT: u8 = 8;
+
+    pub fn parse(raw: &str) -> Result<Self, Error> {
+        let mut data = json::parse(raw).map_err(Error::JsonError)?;
+        let Some(id) = data.array_remove(0).as_u8() else {
+            return Err(Error::InvalidId);
+        };
+        match id {
+            Self::WELCOME => {
+                let session = validate_str_argument(data.array_remove(0))?;
+                let version = data.array_remove(0);
+                let Some(protocol_version) = version.as_u64() else {
+                    return Err(Error::InvalidJsonU64 { offense: version });
+                };
+                let server_ident = validate_str_argument(data.array_remove(0))?;
+                Ok(V1Message::Welcome {
+                    session,
+                    protocol_version,
+                    server_ident,
+                })
+            }
+            Self::PREFIX => {
+                let prefix = validate_str_argument(data.array_remove(0))?;
+                let uri = validate_str_argument(data.array_remove(0))?;
+                Ok(V1Message::Prefix {
+                    prefix,
+                    uri: uri.into(),
+                })
+            }
+            Self::CALL => {
+                let call_id = validate_str_argument(data.array_remove(0))?;
+                let procedure = validate_str_argument(data.array_remove(0))?;
+                let args = data.members().map(WampValue::from).collect();
+                Ok(V1Message::Call {
+                    call_id,
+                    procedure: procedure.into(),
+                    args,
+                })
+            }
+            Self::CALL_RESULT => {
+                let call_id = validate_str_argument(data.array_remove(0))?;
+                let result = WampValue::from(data.array_remove(0));
+                Ok(V1Message::CallResult { call_id, result })
+            }
+            Self::CALL_ERROR => {
+                let call_id = validate_str_argument(data.array_remove(0))?;
+                let error = validate_str_argument(data.array_remove(0))?;
+                let description = validate_str_argument(data.array_remove(0))?;
+                let details = data.array_remove(0);
+                Ok(V1Message::CallError {
+                    call_id,
+                    error: error.into(),
+                    description,
+                    details: (!details.is_null()).then(|| WampValue::from(details)),
+                })
+            }
+            Self::SUBSCRIBE => Ok(V1Message::Subscribe {
+                topic: validate_str_argument(data.array_remove(0))?.into(),
+            }),
+            Self::UNSUBSCRIBE => Ok(V1Message::Unsubscribe {
+                topic: validate_str_argument(data.array_remove(0))?.into(),
+            }),
+            Self::PUBLISH => {
+                let topic = validate_str_argument(data.array_remove(0))?;
+                let event = WampValue::from(data.array_remove(0));
+                // The fourth element is either excludeMe or the exclude list.
+                let exclusion = data.array_remove(0);
+                Ok(V1Message::Publish {
+                    topic: topic.into(),
+                    event,
+                    exclude_me: exclusion.as_bool(),
+                    exclude: session_ids(&exclusion),
+                    eligible: session_ids(&data.array_remove(0)),
+                })
+            }
+            Self::EVENT => {
+                let topic = validate_str_argument(data.array_remove(0))?;
+                let event = WampValue::from(data.array_remove(0));
+                Ok(V1Message::Event {
+                    topic: topic.into(),
+                    event,
+                })
+            }
+            _ => Err(Error::ExtensionMessage),
+        }
+    }
+
+    pub fn message_id(&self) -> u8 {
+        match self {
+            V1Message::Welcome { .. } => Self::WELCOME,
+            V1Message::Prefix { .. } => Self::PREFIX,
+            V1Message::Call { .. } => Self::CALL,
+            V1Message::CallResult { .. } => Self::CALL_RESULT,
+            V1Message::CallError { .. } => Self::CALL_ERROR,
+            V1Message::Subscribe { .. } => Self::SUBSCRIBE,
+            V1Message::Unsubscribe { .. } => Self::UNSUBSCRIBE,
+            V1Message::Publish { .. } => Self::PUBLISH,
+            V1Message::Event { .. } => Self::EVENT,
+        }
+    }
+
+    pub fn to_json(&self) -> JsonValue {
+        let mut frame = json::array![self.message_id()];
+        let mut push = |value: JsonValue| {
+            // Pushing onto an array cannot fail.
+            let _ = frame.push(value);
+        };
+        match self {
+            V1Message::Welcome {
+                session,
+                protocol_version,
+                server_ident,
+            } => {
+                push(session.as_str().into());
+                push((*protocol_version).into());
+                push(server_ident.as_str().into());
+            }
+            V1Message::Prefix { prefix, uri } => {
+                push(prefix.as_str().into());
+                push(uri.as_str().into());
+            }
+            V1Message::Call {
+                call_id,
+                procedure,
+                args,
+            } => {
+                push(call_id.as_str().into());
+                push(procedure.as_str().into());
+                for arg in args {
+                    push(arg.clone().into());
+                }
+            }
+            V1Message::CallResult { call_id, result } => {
+                push(call_id.as_str().into());
+                push(result.clone().into());
+            }
+            V1Message::CallError {
+                call_id,
+                error,
+                description,
+                details,
+            } => {
+                push(call_id.as_str().into());
+                push(error.as_str().into());
+                push(description.as_str().into());
+                if let Some(details) = details {
+                    push(details.clone().into());
+                }
+            }
+            V1Message::Subscribe { topic } | V1Message::Unsubscribe { topic } => {
+                push(topic.as_str().into());
+            }
+            V1Message::Publish {
+                topic,
+                event,
+                exclude_me,
+                exclude,
+                eligible,
+            } => {
+                push(topic.as_str().into());
+                push(event.clone().into());
+                if !exclude.is_empty() || !eligible.is_empty() {
+                    push(exclude.clone().into());
+                    push(eligible.clone().into());
+                } else if let Some(exclude_me) = exclude_me {
+                    push((*exclude_me).into());
+                }
+            }
+            V1Message::Event { topic, event } => {
+                push(topic.as_str().into());
+                push(event.clone().into());
+            }
+        }
+        frame
+    }
+
+    /// The closest v2 message a client sent this as, with `request` as request id.
+    ///
+    /// Only CALL, SUBSCRIBE and PUBLISH have a v2 counterpart, UNSUBSCRIBE needs the
+    /// subscription id the gateway tracked for the topic. Session id lists of PUBLISH cannot
+    /// be carried over and are dropped.
+    pub fn to_v2(self, request: WampId) -> Option<Message> {
+        match self {
+            V1Message::Call {
+                procedure, args, ..
+            } => Some(Message::Call(Call {
+                request,
+                options: json::object! {},
+                procedure,
+                args: (!args.is_empty()).then_some(args),
+                kwargs: None,
+            })),
+            V1Message::Subscribe { topic } => Some(Message::Subscribe(Subscribe {
+                request,
+                options: json::object! {},
+                topic,
+            })),
+            V1Message::Publish {
+                topic,
+                event,
+                exclude_me,
+                ..
+            } => {
+                let mut options = json::object! {};
+                if let Some(exclude_me) = exclude_me {
+                    options["exclude_me"] = exclude_me.into();
+                }
+                Some(Message::Publish(Publish {
+                    request,
+                    options,
+                    topic,
+                    args: Some(vec![event]),
+                    kwargs: None,
+                }))
+            }
+            _ => None,
+        }
+    }
+
+    /// CALLRESULT answering `call_id`, the first positional result or null.
+    pub fn call_result(call_id: &str, result: &WampResult) -> Self {
+        V1Message::CallResult {
+            call_id: call_id.to_string(),
+            result: first_arg(&result.args),
+        }
+    }
+
+    /// CALLERROR answering `call_id`.
+    pub fn call_error(call_id: &str, error: &ErrorMessage) -> Self {
+        V1Message::CallError {
+            call_id: call_id.to_string(),
+            error: error.error.clone(),
+            description: error.details["message"]
+                .as_str()
+                .unwrap_or_default()
+                .to_string(),
+            details: error.args.as_ref().and_then(|args| args.first().cloned()),
+        }
+    }
+
+    /// EVENT on `topic`, the v2 EVENT only carries the subscription id.
+    pub fn event(topic: &str, event: &Event) -> Self {
+        V1Message::Event {
+            topic: topic.to_string().into(),
+            event: first_arg(&event.args),
+        }
+    }
+}
+
+fn first_arg(args: &Option<Args>) -> WampValue {
+    args.as_ref()
+        .and_then(|args| args.first().cloned())
+        .unwrap_or(WampValue::Null)
+}
+
+/// CURIE prefixes a v1 peer declared with PREFIX messages.
+#[derive(Debug, Clone, Default)]
+pub struct Prefixes {
+    prefixes: HashMap<String, Uri>,
+}
+
+impl Prefixes {
+    pub fn new() -> Self {
+        Prefixes::default()
+    }
+
+    /// Remember the prefix declared by a PREFIX message, other messages are ignored.
+    pub fn record(&mut self, message: &V1Message) {
+        if let V1Message::Prefix { prefix, uri } = message {
+            self.prefixes.insert(prefix.clone(), uri.clone());
+        }
+    }
+
+    /// Expand `prefix:suffix` when the prefix was declared, otherwise return `uri` as is.
+    pub fn expand(&self, uri: &str) -> Uri {
+        if let Some((prefix, suffix)) = uri.split_once(':') {
+            if let Some(expanded) = self.prefixes.get(prefix) {
+                return format!("{expanded}{suffix}").into();
+            }
+        }
+        uri.to_string().into()
+    }
+
+    /// Expand the CURIEs of a message sent by the peer.
+    pub fn resolve(&self, message: V1Message) -> V1Message {
+        match message {
+            V1Message::Call {
+                call_id,
+                procedure,
+                args,
+            } => V1Message::Call {
+                call_id,
+                procedure: self.expand(&procedure),
+                args,
+            },
+            V1Message::Subscribe { topic } => V1Message::Subscribe {
+                topic: self.expand(&topic),
+            },
+            V1Message::Unsubscribe { topic } => V1Message::Unsubscribe {
+                topic: self.expand(&topic),
+            },
+            V1Message::Publish {
+                topic,
+                event,
+                exclude_me,
+                exclude,
+                eligible,
+            } => V1Message::Publish {
+                topic: self.expand(&topic),
+                event,
+                exclude_me,
+                exclude,
+                eligible,
+            },
+            other => other,
+        }
+    }
+}
@@ -0,0 +1,471 @@
+use crate::arity::arity;
+use crate::correlation::{request_key, response_key, Direction};
+use crate::messages::{Abort, ErrorMessage, Message, RequestType, Roles, WampId};
+use crate::session::{Session, SessionState, Side};
+use crate::uri::{is_valid_pattern, is_valid_uri, suggest_error_uri};
+use std::collections::{HashMap, HashSet};
+
+/// Sections of the [WAMP specification](https://wamp-proto.org/wamp_latest_ietf.html), as
+/// published from [wamp-proto/wamp-proto](https://github.com/wamp-proto/wamp-proto), cited
+/// by [`Violation::spec`].
+pub const SPEC_MESSAGES: &str = "wamp_latest_ietf.html#name-messages";
+pub const SPEC_SESSION_ESTABLISHMENT: &str = "wamp_latest_ietf.html#name-session-establishment";
+pub const SPEC_SESSION_CLOSING: &str = "wamp_latest_ietf.html#name-session-closing";
+pub const SPEC_IDS: &str = "wamp_latest_ietf.html#name-ids";
+pub const SPEC_URIS: &str = "wamp_latest_ietf.html#name-uris";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ViolationKind {
+    /// The sender's roles never send this message.
+    WrongDirection,
+    /// The message is not allowed in the current session state.
+    BadSequencing,
+    InvalidId,
+    MalformedUri,
+    /// A request reuses the id of a request of the same type still waiting for its answer.
+    DuplicateRequestId,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Violation {
+    pub kind: ViolationKind,
+    pub direction: Direction,
+    pub message_id: u8,
+    pub detail: String,
+    /// Spec section the violated rule comes from.
+    pub spec: &'static str,
+}
+
+impl Violation {
+    /// ABORT message reporting the violation to the peer.
+    pub fn to_abort(&self) -> Abort {
+        Abort {
+            details: json::object! {
+                message: self.detail.clone(),
+                spec: self.spec,
+            },
+            reason: "wamp.error.protocol_violation".into(),
+        }
+    }
+}
+
+/// What to do about a [`Violation`], in increasing severity.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum ViolationAction {
+    /// Process the message as if nothing happened.
+    Ignore,
+    /// Process the message and report the violation.
+    Log,
+    /// Drop the message and answer it with an ERROR. Only requests can be answered, other
+    /// messages are handled as [`Abort`](ViolationAction::Abort).
+    Error,
+    /// Send ABORT and close the transport.
+    Abort,
+}
+
+/// The [`ViolationAction`] for each [`ViolationKind`], e.g. per realm to tolerate a buggy
+/// client on one realm only.
+///
+/// The default is strict: malformed URIs in requests are answered with
+/// `wamp.error.invalid_uri` and reused request ids with `wamp.error.protocol_violation`,
+/// every other violation aborts the session.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct ViolationPolicy {
+    pub wrong_direction: ViolationAction,
+    pub bad_sequencing: ViolationAction,
+    pub invalid_id: ViolationAction,
+    pub malformed_uri: ViolationAction,
+    pub duplicate_request_id: ViolationAction,
+}
+
+impl Default for ViolationPolicy {
+    fn default() -> Self {
+        ViolationPolicy {
+            wrong_direction: ViolationAction::Abort,
+            bad_sequencing: ViolationAction::Abort,
+            invalid_id: ViolationAction::Abort,
+            malformed_uri: ViolationAction::Error,
+            duplicate_request_id: ViolationAction::Error,
+        }
+    }
+}
+
+/// Outcome of [`Validator::enforce`]. Each variant lists the violations to report, those
+/// the policy ignores are left out.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Verdict {
+    Accept(Vec<Violation>),
+    /// Drop the message and send the ERROR to its sender.
+    Reject(ErrorMessage, Vec<Violation>),
+    /// Send the ABORT and close the transport.
+    Abort(Abort, Vec<Violation>),
+}
+
+impl ViolationPolicy {
+    /// Report every violation and never interfere.
+    pub fn lenient() -> Self {
+        ViolationPolicy {
+            wrong_direction: ViolationAction::Log,
+            bad_sequencing: ViolationAction::Log,
+            invalid_id: ViolationAction::Log,
+            malformed_uri: ViolationAction::Log,
+            duplicate_request_id: ViolationAction::Log,
+        }
+    }
+
+    pub fn action(&self, kind: ViolationKind) -> ViolationAction {
+        match kind {
+            ViolationKind::WrongDirection => self.wrong_direction,
+            ViolationKind::BadSequencing => self.bad_sequencing,
+            ViolationKind::InvalidId => self.invalid_id,
+            ViolationKind::MalformedUri => self.malformed_uri,
+            ViolationKind::DuplicateRequestId => self.duplicate_request_id,
+        }
+    }
+
+    pub fn with(mut self, kind: ViolationKind, action: ViolationAction) -> Self {
+        let slot = match kind {
+            ViolationKind::WrongDirection => &mut self.wrong_direction,
+            ViolationKind::BadSequencing => &mut self.bad_sequencing,
+            ViolationKind::InvalidId => &mut self.invalid_id,
+            ViolationKind::MalformedUri => &mut self.malformed_uri,
+            ViolationKind::DuplicateRequestId => &mut self.duplicate_request_id,
+        };
+        *slot = action;
+        self
+    }
+
+    /// Decide about `message` given the violations found in it, the most severe action
+    /// wins.
+    pub fn judge(&self, message: &Message, violations: Vec<Violation>) -> Verdict {
+        let reported: Vec<_> = violations
+            .into_iter()
+            .filter(|violation| self.action(violation.kind) != ViolationAction::Ignore)
+            .collect();
+        // The first of the most severe violations decides.
+        let Some(worst) = reported
+            .iter()
+            .enumerate()
+            .rev()
+            .max_by_key(|(_, violation)| self.action(violation.kind))
+            .map(|(index, _)| index)
+        else {
+            return Verdict::Accept(reported);
+        };
+        let action = self.action(reported[worst].kind);
+        if action == ViolationAction::Log {
+            return Verdict::Accept(reported);
+        }
+
+        let request_type = RequestType::try_from(message.message_id()).ok();
+        match (action, request_type, message.request_id()) {
+            (ViolationAction::Error, Some(request_type), Some(request)) => {
+                let error = match reported[worst].kind {
+                    ViolationKind::MalformedUri => "wamp.error.invalid_uri",
+                    _ => "wamp.error.protocol_violation",
+                };
+                let mut error = ErrorMessage::for_request(request_type, request, error.into());
+                error.details = json::object! { message: reported[worst].detail.clone() };
+                Verdict::Reject(error, reported)
+            }
+            _ => Verdict::Abort(reported[worst].to_abort(), reported),
+        }
+    }
+}
+
+/// Checks traffic of one session against the spec: message directions, session sequencing,
+/// IDs and URIs.
+/// # Examples
+/// ```
+/// use wamp_helpers::correlation::Direction;
+/// use wamp_helpers::messages::Message;
+/// use wamp_helpers::session::Side;
+/// use wamp_helpers::validator::{Validator, ViolationKind};
+///
+/// let mut validator = Validator::new(Side::Router);
+/// let call = Message::parse_message(r#"[48, 1, {}, "com.example..add"]"#).unwrap();
+///
+/// let violations = validator.observe(Direction::Inbound, &call);
+/// assert_eq!(violations[0].kind, ViolationKind::BadSequencing);
+/// assert_eq!(violations[1].kind, ViolationKind::MalformedUri);
+/// assert_eq!(violations[0].to_abort().reason, "wamp.error.protocol_violation");
+/// ```
+#[derive(Debug, Clone)]
+pub struct Validator {
+    session: Session,
+    strict_uris: bool,
+    policy: ViolationPolicy,
+    /// Requests waiting for their answer, by message type and request id.
+    pending: HashSet<(u8, WampId)>,
+    /// ERRORs of rejected requests, which must not settle a pending request with the same id
+    /// if they are observed on their way out.
+    rejected: HashMap<(u8, WampId), Vec<ErrorMessage>>,
+}
+
+impl Validator {
+    pub fn new(side: Side) -> Self {
+        Validator {
+            session: Session::new(side),
+            strict_uris: false,
+            policy: ViolationPolicy::default(),
+            pending: HashSet::new(),
+            rejected: HashMap::new(),
+        }
+    }
+
+    /// Require strict URIs (`[0-9a-z_]` components) instead of loose ones.
+    pub fn strict_uris(mut self, strict_uris: bool) -> Self {
+        self.strict_uris = strict_uris;
+        self
+    }
+
+    /// How [`enforce`](Self::enforce) handles violations.
+    pub fn policy(mut self, policy: ViolationPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    pub fn session(&self) -> &Session {
+        &self.session
+    }
+
+    /// [`observe`](Self::observe) a message and decide what to do with it according to the
+    /// [`ViolationPolicy`], for the receive loop of a router session.
+    /// # Examples
+    /// ```
+    /// use wamp_helpers::correlation::Direction;
+    /// use wamp_helpers::messages::Message;
+    /// use wamp_helpers::session::Side;
+    /// use wamp_helpers::validator::{
+    ///     Validator, Verdict, ViolationAction, ViolationKind, ViolationPolicy,
+    /// };
+    ///
+    /// let mut validator = Validator::new(Side::Router);
+    /// let hello = Message::parse_message(r#"[1, "realm1", {"roles": {"caller": {}}}]"#).unwrap();
+    /// let welcome = Message::parse_message(r#"[2, 1, {"roles": {"dealer": {}}}]"#).unwrap();
+    /// assert_eq!(validator.enforce(Direction::Inbound, &hello), Verdict::Accept(Vec::new()));
+    /// validator.enforce(Direction::Outbound, &welcome);
+    ///
+    /// let call = Message::parse_message(r#"[48, 7, {}, "com.example..add"]"#).unwrap();
+    /// let Verdict::Reject(error, _) = validator.enforce(Direction::Inbound, &call) else {
+    ///     panic!()
+    /// };
+    /// assert_eq!((error.request, error.error.as_str()), (7, "wamp.error.invalid_uri"));
+    ///
+    /// // A second HELLO aborts the session, unless the realm tolerates it.
+    /// let Verdict::Abort(abort, _) = validator.enforce(Direction::Inbound, &hello) else {
+    ///     panic!()
+    /// };
+    /// assert_eq!(abort.reason, "wamp.error.protocol_violation");
+    ///
+    /// let policy = ViolationPolicy::default().with(ViolationKind::BadSequencing, ViolationAction::Log);
+    /// let mut tolerant = Validator::new(Side::Router).policy(policy);
+    /// tolerant.enforce(Direction::Inbound, &hello);
+    /// tolerant.enforce(Direction::Outbound, &welcome);
+    /// let Verdict::Accept(logged) = tolerant.enforce(Direction::Inbound, &hello) else {
+    ///     panic!()
+    /// };
+    /// assert_eq!(logged[0].kind, ViolationKind::BadSequencing);
+    /// ```
+    pub fn enforce(&mut self, direction: Direction, message: &Message) -> Verdict {
+        let violations = self.observe(direction, message);
+        let duplicate = violations
+            .iter()
+            .any(|violation| violation.kind == ViolationKind::DuplicateRequestId);
+        let verdict = self.policy.judge(message, violations);
+        if let Some(key) = request_key(message) {
+            // A refused request gets no answer of its own, it is not pending. Its id stays
+            // pending for the original request when it was a duplicate.
+            if !duplicate && !matches!(verdict, Verdict::Accept(_)) {
+                self.pending.remove(&key);
+            }
+            if let Verdict::Reject(error, _) = &verdict {
+                self.rejected.entry(key).or_default().push(error.clone());
+            }
+        }
+        verdict
+    }
+
+    /// Number of requests waiting for their answer.
+    ///
+    /// A request that [`enforce`](Self::enforce) rejects or aborts on is not pending, its id
+    /// may be used again. A request reusing the id of a pending one is a
+    /// [`ViolationKind::DuplicateRequestId`]. When it is rejected, the original request stays
+    /// pending until its own answer. The ERROR of a [`Verdict::Reject`] may be observed on
+    /// its way out or not, it does not settle a pending request either way.
+    /// # Examples
+    /// ```
+    /// use wamp_helpers::correlation::Direction;
+    /// use wamp_helpers::messages::Message;
+    /// use wamp_helpers::session::Side;
+    /// use wamp_helpers::validator::{Validator, Verdict};
+    ///
+    /// let mut validator = Validator::new(Side::Router);
+    /// for (direction, frame) in [
+    ///     (Direction::Inbound, r#"[1, "realm1", {"roles": {"caller": {}}}]"#),
+    ///     (Direction::Outbound, r#"[2, 1, {"roles": {"dealer": {}}}]"#),
+    /// ] {
+    ///     validator.enforce(direction, &Message::parse_message(frame).unwrap());
+    /// }
+    /// let call = Message::parse_message(r#"[48, 7, {}, "com.example.add"]"#).unwrap();
+    /// validator.enforce(Direction::Inbound, &call);
+    /// let Verdict::Reject(error, _) = validator.enforce(Direction::Inbound, &call) else {
+    ///     panic!()
+    /// };
+    /// assert_eq!(error.error.as_str(), "wamp.error.protocol_violation");
+    ///
+    /// validator.enforce(Direction::Outbound, &Message::ErrorMessage(error));
+    /// assert_eq!(validator.pending_requests(), 1);
+    /// let result = Message::parse_message("[50, 7, {}]").unwrap();
+    /// validator.enforce(Direction::Outbound, &result);
+    /// assert_eq!(validator.pending_requests(), 0);
+    ///
+    /// // The ERROR rejecting a duplicate need not be observed.
+    /// validator.enforce(Direction::Inbound, &call);
+    /// assert!(matches!(validator.enforce(Direction::Inbound, &call), Verdict::Reject(..)));
+    /// validator.enforce(Direction::Outbound, &result);
+    /// assert_eq!(validator.pending_requests(), 0);
+    /// assert_eq!(validator.enforce(Direction::Inbound, &call), Verdict::Accept(Vec::new()));
+    /// ```
+    pub fn pending_requests(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Check a message travelling in `direction` and advance the session state.
+    pub fn observe(&mut self, direction: Direction, message: &Message) -> Vec<Violation> {
+        let mut violations = Vec::new();
+        let message_id = message.message_id();
+        let mut violation = |kind, detail: String, spec| {
+            violations.push(Violation {
+                kind,
+                direction,
+                message_id,
+                detail,
+                spec,
+            })
+        };
+
+        let from_client = self.session.from_client(direction);
+        let sender_roles: &[Roles] = if from_client {
+            &[
+                Roles::Callee,
+                Roles::Caller,
+                Roles::Publisher,
+                Roles::Subscriber,
+            ]
+        } else {
+            &[Roles::Dealer, Roles::Broker]
+        };
+        let sendable = sender_roles
+            .iter()
+            .any(|role| *message.get_message_direction(*role).sends);
+        if !sendable {
+            let sender = if from_client { "client" } else { "router" };
+            violation(
+                ViolationKind::WrongDirection,
+                format!("a {} never sends message {}", sender, message_id),
+                SPEC_MESSAGES,
+            );
+        }
+
+        if let Err(state) = self.session.transition(direction, message) {
+            let spec = match state {
+                SessionState::Closing { .. } => SPEC_SESSION_CLOSING,
+                _ => SPEC_SESSION_ESTABLISHMENT,
+            };
+            violation(
+                ViolationKind::BadSequencing,
+                format!("message {} is not allowed in state {:?}", message_id, state),
+                spec,
+            );
+        }
+
+        // Revocations are the one message carrying request id 0.
+        let revocation = match message {
+            Message::Unsubscribed(unsubscribed) => unsubscribed.details.is_some(),
+            Message::Unregistered(unregistered) => unregistered.details.is_some(),
+            _ => false,
+        };
+        if message.request_id() == Some(0) && !revocation {
+            violation(
+                ViolationKind::InvalidId,
+                "request ids start at 1".to_string(),
+                SPEC_IDS,
+            );
+        }
+        if let Message::Welcome(welcome) = message {
+            if welcome.session == 0 {
+                violation(
+                    ViolationKind::InvalidId,
+                    "session ids start at 1".to_string(),
+                    SPEC_IDS,
+                );
+            }
+        }
+
+        if let Some(key) = request_key(message) {
+            if !self.pending.insert(key) {
+                let name = arity(key.0).map_or("request", |arity| arity.name);
+                violation(
+                    ViolationKind::DuplicateRequestId,
+                    format!("{} {} reuses the id of a pending request", name, key.1),
+                    SPEC_IDS,
+                );
+            }
+        } else if let Some((key, false)) = response_key(message) {
+            let rejection = match (message, self.rejected.get_mut(&key)) {
+                (Message::ErrorMessage(error), Some(rejected)) => rejected
+                    .iter()
+                    .position(|rejection| rejection == error)
+                    .map(|index| rejected.swap_remove(index)),
+                _ => None,
+            };
+            if rejection.is_none() {
+                self.pending.remove(&key);
+                self.rejected.remove(&key);
+            }
+        }
+        if self.session.state() == SessionState::Closed {
+            self.pending.clear();
+            self.rejected.clear();
+        }
+
+        if let Some(uri) = message.uri() {
+            let is_pattern = match message {
+                Message::Subscribe(_) | Message::Register(_) => message
+                    .details()
+                    .is_some_and(|options| !options["match"].is_null()),
+                _ => false,
+            };
+            let valid = if is_pattern {
+                is_valid_pattern(uri, self.strict_uris)
+            } else {
+                is_valid_uri(uri, self.strict_uris)
+            };
+            if !valid {
+                violation(
+                    ViolationKind::MalformedUri,
+                    format!("{:?} is not a valid URI", uri),
+                    SPEC_URIS,
+                );
+            } else if let Message::ErrorMessage(_) = message {
+                if let Some(suggestion) = suggest_error_uri(uri) {
+                    violation(
+                        ViolationKind::MalformedUri,
+                        format!(
+                            "{:?} is not a standard error, did you mean {}?",
+                            uri, suggestion
+                        ),
+                        SPEC_URIS,
+                    );
+                }
+            }
+        }
+
+        violations
+    }
+}
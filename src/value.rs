@@ -0,0 +1,571 @@
+#[cfg(feature = "cbor")]
+use crate::error::Error;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use json::JsonValue;
+use std::collections::BTreeMap;
+use std::hash::{Hash, Hasher};
+use std::mem;
+
+/// Prefix marking a JSON string as base64 encoded binary data, per the WAMP JSON serializer.
+pub const BINARY_PREFIX: char = '\0';
+
+/// A payload value as WAMP sees it, independent of the serializer.
+///
+/// Unlike `JsonValue` this keeps text and binary data apart: on the JSON wire `Bytes` are
+/// written as a base64 string starting with `\0`, and such strings are decoded back to `Bytes`.
+/// # Examples
+/// ```
+/// use wamp_helpers::value::WampValue;
+/// let value = WampValue::Bytes(vec![0x10, 0x11, 0x12]);
+/// let json = json::JsonValue::from(value.clone());
+/// assert_eq!(json.as_str(), Some("\0EBES"));
+/// assert_eq!(WampValue::from(&json), value);
+/// ```
+#[derive(Debug, Clone)]
+pub enum WampValue {
+    Null,
+    Bool(bool),
+    Integer(i64),
+    Float(f64),
+    String(String),
+    Bytes(Vec<u8>),
+    List(Vec<WampValue>),
+    Dict(BTreeMap<String, WampValue>),
+}
+
+impl WampValue {
+    pub fn is_text(&self) -> bool {
+        matches!(self, Self::String(_))
+    }
+
+    pub fn is_binary(&self) -> bool {
+        matches!(self, Self::Bytes(_))
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            Self::Bytes(value) => Some(value),
+            _ => None,
+        }
+    }
+}
+
+/// Floats compare like JSON numbers in the `json` crate: NaN equals NaN, which makes the
+/// equality total.
+impl PartialEq for WampValue {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Null, Self::Null) => true,
+            (Self::Bool(a), Self::Bool(b)) => a == b,
+            (Self::Integer(a), Self::Integer(b)) => a == b,
+            (Self::Float(a), Self::Float(b)) => a == b || a.is_nan() && b.is_nan(),
+            (Self::String(a), Self::String(b)) => a == b,
+            (Self::Bytes(a), Self::Bytes(b)) => a == b,
+            (Self::List(a), Self::List(b)) => a == b,
+            (Self::Dict(a), Self::Dict(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+impl Eq for WampValue {}
+
+impl Hash for WampValue {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        mem::discriminant(self).hash(state);
+        match self {
+            Self::Null => {}
+            Self::Bool(value) => value.hash(state),
+            Self::Integer(value) => value.hash(state),
+            Self::Float(value) => hash_float(*value, state),
+            Self::String(value) => value.hash(state),
+            Self::Bytes(value) => value.hash(state),
+            Self::List(items) => items.hash(state),
+            Self::Dict(entries) => entries.hash(state),
+        }
+    }
+}
+
+/// Hash a float so that values equal under `==` hash alike, `-0.0` and `0.0` included.
+fn hash_float<H: Hasher>(value: f64, state: &mut H) {
+    let bits = if value == 0.0 {
+        0
+    } else if value.is_nan() {
+        f64::NAN.to_bits()
+    } else {
+        value.to_bits()
+    };
+    bits.hash(state);
+}
+
+/// Encode bytes using the `\0`-prefixed base64 convention.
+pub fn encode_binary(bytes: &[u8]) -> String {
+    let mut encoded = String::from(BINARY_PREFIX);
+    STANDARD.encode_string(bytes, &mut encoded);
+    encoded
+}
+
+/// Decode a `\0`-prefixed base64 string, returns `None` when the string is not binary data.
+pub fn decode_binary(value: &str) -> Option<Vec<u8>> {
+    let encoded = value.strip_prefix(BINARY_PREFIX)?;
+    STANDARD.decode(encoded).ok()
+}
+
+impl From<&JsonValue> for WampValue {
+    fn from(value: &JsonValue) -> Self {
+        match value {
+            JsonValue::Null => Self::Null,
+            JsonValue::Boolean(value) => Self::Bool(*value),
+            JsonValue::Number(_) => match value.as_i64() {
+                Some(integer) => Self::Integer(integer),
+                None => Self::Float(value.as_f64().unwrap_or(f64::NAN)),
+            },
+            JsonValue::Short(_) | JsonValue::String(_) => {
+                let text = value.as_str().unwrap_or_default();
+                match decode_binary(text) {
+                    Some(bytes) => Self::Bytes(bytes),
+                    None => Self::String(text.to_string()),
+                }
+            }
+            JsonValue::Array(items) => Self::List(items.iter().map(Self::from).collect()),
+            JsonValue::Object(object) => Self::Dict(
+                object
+                    .iter()
+                    .map(|(key, item)| (key.to_string(), Self::from(item)))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+impl From<JsonValue> for WampValue {
+    fn from(value: JsonValue) -> Self {
+        Self::from(&value)
+    }
+}
+
+impl From<WampValue> for JsonValue {
+    fn from(value: WampValue) -> Self {
+        match value {
+            WampValue::Null => JsonValue::Null,
+            WampValue::Bool(value) => JsonValue::Boolean(value),
+            WampValue::Integer(value) => JsonValue::from(value),
+            WampValue::Float(value) => JsonValue::from(value),
+            WampValue::String(value) => JsonValue::from(value),
+            WampValue::Bytes(value) => JsonValue::from(encode_binary(&value)),
+            WampValue::List(items) => {
+                JsonValue::Array(items.into_iter().map(JsonValue::from).collect())
+            }
+            WampValue::Dict(entries) => {
+                let mut object = JsonValue::new_object();
+                for (key, item) in entries {
+                    object[key.as_str()] = JsonValue::from(item);
+                }
+                object
+            }
+        }
+    }
+}
+
+impl From<bool> for WampValue {
+    fn from(value: bool) -> Self {
+        Self::Bool(value)
+    }
+}
+
+impl From<i64> for WampValue {
+    fn from(value: i64) -> Self {
+        Self::Integer(value)
+    }
+}
+
+impl From<f64> for WampValue {
+    fn from(value: f64) -> Self {
+        Self::Float(value)
+    }
+}
+
+impl From<&str> for WampValue {
+    fn from(value: &str) -> Self {
+        Self::String(value.to_string())
+    }
+}
+
+impl From<String> for WampValue {
+    fn from(value: String) -> Self {
+        Self::String(value)
+    }
+}
+
+impl From<Vec<u8>> for WampValue {
+    fn from(value: Vec<u8>) -> Self {
+        Self::Bytes(value)
+    }
+}
+
+impl From<Vec<WampValue>> for WampValue {
+    fn from(value: Vec<WampValue>) -> Self {
+        Self::List(value)
+    }
+}
+
+impl From<BTreeMap<String, WampValue>> for WampValue {
+    fn from(value: BTreeMap<String, WampValue>) -> Self {
+        Self::Dict(value)
+    }
+}
+
+#[cfg(feature = "serde")]
+mod serde_impl {
+    use super::WampValue;
+    use serde::de::value::{
+        Error as DeError, MapAccessDeserializer, MapDeserializer, SeqDeserializer,
+    };
+    use serde::de::{Deserialize, Deserializer, IntoDeserializer, MapAccess, SeqAccess, Visitor};
+    use serde::ser::{Serialize, SerializeMap, SerializeSeq, Serializer};
+    use std::collections::BTreeMap;
+    use std::fmt;
+
+    impl Serialize for WampValue {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            match self {
+                WampValue::Null => serializer.serialize_unit(),
+                WampValue::Bool(value) => serializer.serialize_bool(*value),
+                WampValue::Integer(value) => serializer.serialize_i64(*value),
+                WampValue::Float(value) => serializer.serialize_f64(*value),
+                WampValue::String(value) => serializer.serialize_str(value),
+                WampValue::Bytes(value) => serializer.serialize_bytes(value),
+                WampValue::List(items) => {
+                    let mut seq = serializer.serialize_seq(Some(items.len()))?;
+                    for item in items {
+                        seq.serialize_element(item)?;
+                    }
+                    seq.end()
+                }
+                WampValue::Dict(entries) => {
+                    let mut map = serializer.serialize_map(Some(entries.len()))?;
+                    for (key, item) in entries {
+                        map.serialize_entry(key, item)?;
+                    }
+                    map.end()
+                }
+            }
+        }
+    }
+
+    struct WampValueVisitor;
+
+    impl<'de> Visitor<'de> for WampValueVisitor {
+        type Value = WampValue;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("a WAMP payload value")
+        }
+
+        fn visit_unit<E>(self) -> Result<WampValue, E> {
+            Ok(WampValue::Null)
+        }
+
+        fn visit_none<E>(self) -> Result<WampValue, E> {
+            Ok(WampValue::Null)
+        }
+
+        fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<WampValue, D::Error> {
+            WampValue::deserialize(deserializer)
+        }
+
+        fn visit_bool<E>(self, value: bool) -> Result<WampValue, E> {
+            Ok(WampValue::Bool(value))
+        }
+
+        fn visit_i64<E>(self, value: i64) -> Result<WampValue, E> {
+            Ok(WampValue::Integer(value))
+        }
+
+        fn visit_u64<E>(self, value: u64) -> Result<WampValue, E> {
+            Ok(match i64::try_from(value) {
+                Ok(value) => WampValue::Integer(value),
+                Err(_) => WampValue::Float(value as f64),
+            })
+        }
+
+        fn visit_f64<E>(self, value: f64) -> Result<WampValue, E> {
+            Ok(WampValue::Float(value))
+        }
+
+        fn visit_str<E>(self, value: &str) -> Result<WampValue, E> {
+            Ok(WampValue::String(value.to_string()))
+        }
+
+        fn visit_string<E>(self, value: String) -> Result<WampValue, E> {
+            Ok(WampValue::String(value))
+        }
+
+        fn visit_bytes<E>(self, value: &[u8]) -> Result<WampValue, E> {
+            Ok(WampValue::Bytes(value.to_vec()))
+        }
+
+        fn visit_byte_buf<E>(self, value: Vec<u8>) -> Result<WampValue, E> {
+            Ok(WampValue::Bytes(value))
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<WampValue, A::Error> {
+            let mut items = Vec::new();
+            while let Some(item) = seq.next_element()? {
+                items.push(item);
+            }
+            Ok(WampValue::List(items))
+        }
+
+        fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<WampValue, A::Error> {
+            let mut entries = BTreeMap::new();
+            while let Some((key, item)) = map.next_entry()? {
+                entries.insert(key, item);
+            }
+            Ok(WampValue::Dict(entries))
+        }
+    }
+
+    impl<'de> Deserialize<'de> for WampValue {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            deserializer.deserialize_any(WampValueVisitor)
+        }
+    }
+
+    /// Deserialize typed values out of a payload, e.g. `u64::deserialize(value)`.
+    impl<'de> Deserializer<'de> for WampValue {
+        type Error = DeError;
+
+        fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+            match self {
+                WampValue::Null => visitor.visit_unit(),
+                WampValue::Bool(value) => visitor.visit_bool(value),
+                WampValue::Integer(value) => visitor.visit_i64(value),
+                WampValue::Float(value) => visitor.visit_f64(value),
+                WampValue::String(value) => visitor.visit_string(value),
+                WampValue::Bytes(value) => visitor.visit_byte_buf(value),
+                WampValue::List(items) => {
+                    let mut seq = SeqDeserializer::new(items.into_iter());
+                    let value = visitor.visit_seq(&mut seq)?;
+                    seq.end()?;
+                    Ok(value)
+                }
+                WampValue::Dict(entries) => {
+                    let mut map = MapDeserializer::new(entries.into_iter());
+                    let value = visitor.visit_map(&mut map)?;
+                    map.end()?;
+                    Ok(value)
+                }
+            }
+        }
+
+        fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+            match self {
+                WampValue::Null => visitor.visit_none(),
+                value => visitor.visit_some(value),
+            }
+        }
+
+        fn deserialize_newtype_struct<V: Visitor<'de>>(
+            self,
+            _name: &'static str,
+            visitor: V,
+        ) -> Result<V::Value, DeError> {
+            visitor.visit_newtype_struct(self)
+        }
+
+        /// Unit variants are strings, other variants a dictionary with a single entry.
+        fn deserialize_enum<V: Visitor<'de>>(
+            self,
+            _name: &'static str,
+            _variants: &'static [&'static str],
+            visitor: V,
+        ) -> Result<V::Value, DeError> {
+            match self {
+                WampValue::String(variant) => visitor.visit_enum(variant.into_deserializer()),
+                WampValue::Dict(entries) => visitor.visit_enum(MapAccessDeserializer::new(
+                    MapDeserializer::new(entries.into_iter()),
+                )),
+                other => other.deserialize_any(visitor),
+            }
+        }
+
+        serde::forward_to_deserialize_any! {
+            bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes
+            byte_buf unit unit_struct seq tuple tuple_struct map struct identifier ignored_any
+        }
+    }
+
+    impl<'de> IntoDeserializer<'de, DeError> for WampValue {
+        type Deserializer = WampValue;
+
+        fn into_deserializer(self) -> WampValue {
+            self
+        }
+    }
+}
+
+#[cfg(feature = "msgpack")]
+impl From<WampValue> for rmpv::Value {
+    fn from(value: WampValue) -> Self {
+        match value {
+            WampValue::Null => rmpv::Value::Nil,
+            WampValue::Bool(value) => rmpv::Value::Boolean(value),
+            WampValue::Integer(value) => rmpv::Value::from(value),
+            WampValue::Float(value) => rmpv::Value::F64(value),
+            WampValue::String(value) => rmpv::Value::from(value),
+            WampValue::Bytes(value) => rmpv::Value::Binary(value),
+            WampValue::List(items) => {
+                rmpv::Value::Array(items.into_iter().map(rmpv::Value::from).collect())
+            }
+            WampValue::Dict(entries) => rmpv::Value::Map(
+                entries
+                    .into_iter()
+                    .map(|(key, item)| (rmpv::Value::from(key), rmpv::Value::from(item)))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+/// Binary map keys and string keys that are not UTF-8 become their [`encode_binary`] form,
+/// other keys that are not strings are rendered with their msgpack display form. Extension
+/// types are carried as their raw bytes, like strings that are not UTF-8.
+/// ```
+/// use wamp_helpers::value::{encode_binary, WampValue};
+///
+/// // A three byte msgpack str ending in 0xff.
+/// let raw = rmpv::decode::read_value(&mut &[0xa3, 0x66, 0x6f, 0xff][..]).unwrap();
+/// assert_eq!(WampValue::from(raw), WampValue::Bytes(vec![0x66, 0x6f, 0xff]));
+///
+/// // The same str as the key of a one entry map.
+/// let raw = rmpv::decode::read_value(&mut &[0x81, 0xa3, 0x66, 0x6f, 0xff, 0xc0][..]).unwrap();
+/// let WampValue::Dict(entries) = WampValue::from(raw) else { panic!() };
+/// assert_eq!(entries[&encode_binary(&[0x66, 0x6f, 0xff])], WampValue::Null);
+/// ```
+#[cfg(feature = "msgpack")]
+impl From<rmpv::Value> for WampValue {
+    fn from(value: rmpv::Value) -> Self {
+        match value {
+            rmpv::Value::Nil => WampValue::Null,
+            rmpv::Value::Boolean(value) => WampValue::Bool(value),
+            rmpv::Value::Integer(value) => match value.as_i64() {
+                Some(value) => WampValue::Integer(value),
+                None => WampValue::Float(value.as_f64().unwrap_or(f64::NAN)),
+            },
+            rmpv::Value::F32(value) => WampValue::Float(value as f64),
+            rmpv::Value::F64(value) => WampValue::Float(value),
+            rmpv::Value::String(value) => match String::from_utf8(value.into_bytes()) {
+                Ok(value) => WampValue::String(value),
+                Err(error) => WampValue::Bytes(error.into_bytes()),
+            },
+            rmpv::Value::Binary(value) => WampValue::Bytes(value),
+            rmpv::Value::Array(items) => {
+                WampValue::List(items.into_iter().map(WampValue::from).collect())
+            }
+            rmpv::Value::Map(entries) => WampValue::Dict(
+                entries
+                    .into_iter()
+                    .map(|(key, item)| {
+                        let key = match key {
+                            rmpv::Value::String(key) => match String::from_utf8(key.into_bytes()) {
+                                Ok(key) => key,
+                                Err(error) => encode_binary(error.as_bytes()),
+                            },
+                            rmpv::Value::Binary(key) => encode_binary(&key),
+                            other => other.to_string(),
+                        };
+                        (key, WampValue::from(item))
+                    })
+                    .collect(),
+            ),
+            rmpv::Value::Ext(_, value) => WampValue::Bytes(value),
+        }
+    }
+}
+
+#[cfg(feature = "cbor")]
+impl From<WampValue> for ciborium::Value {
+    fn from(value: WampValue) -> Self {
+        match value {
+            WampValue::Null => ciborium::Value::Null,
+            WampValue::Bool(value) => ciborium::Value::Bool(value),
+            WampValue::Integer(value) => ciborium::Value::Integer(value.into()),
+            WampValue::Float(value) => ciborium::Value::Float(value),
+            WampValue::String(value) => ciborium::Value::Text(value),
+            WampValue::Bytes(value) => ciborium::Value::Bytes(value),
+            WampValue::List(items) => {
+                ciborium::Value::Array(items.into_iter().map(ciborium::Value::from).collect())
+            }
+            WampValue::Dict(entries) => ciborium::Value::Map(
+                entries
+                    .into_iter()
+                    .map(|(key, item)| (ciborium::Value::Text(key), ciborium::Value::from(item)))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+/// Integers outside the `i64` range become floats and tags are dropped. Byte string map keys
+/// become their [`encode_binary`] form, other keys that are not text are rendered with their
+/// debug form. Values of a kind this crate does not know fail with [`Error::Codec`].
+/// ```
+/// use wamp_helpers::value::{encode_binary, WampValue};
+///
+/// let key = ciborium::Value::Bytes(vec![0xff]);
+/// let raw = ciborium::Value::Map(vec![(key, ciborium::Value::Bool(true))]);
+/// let WampValue::Dict(entries) = WampValue::try_from(raw).unwrap() else { panic!() };
+/// assert_eq!(entries[&encode_binary(&[0xff])], WampValue::Bool(true));
+/// ```
+#[cfg(feature = "cbor")]
+impl TryFrom<ciborium::Value> for WampValue {
+    type Error = Error;
+
+    fn try_from(value: ciborium::Value) -> Result<Self, Error> {
+        Ok(match value {
+            ciborium::Value::Null => WampValue::Null,
+            ciborium::Value::Bool(value) => WampValue::Bool(value),
+            ciborium::Value::Integer(value) => match i64::try_from(value) {
+                Ok(value) => WampValue::Integer(value),
+                Err(_) => WampValue::Float(i128::from(value) as f64),
+            },
+            ciborium::Value::Float(value) => WampValue::Float(value),
+            ciborium::Value::Text(value) => WampValue::String(value),
+            ciborium::Value::Bytes(value) => WampValue::Bytes(value),
+            ciborium::Value::Array(items) => WampValue::List(
+                items
+                    .into_iter()
+                    .map(WampValue::try_from)
+                    .collect::<Result<_, _>>()?,
+            ),
+            ciborium::Value::Map(entries) => WampValue::Dict(
+                entries
+                    .into_iter()
+                    .map(|(key, item)| {
+                        let key = match key {
+                            ciborium::Value::Text(key) => key,
+                            ciborium::Value::Bytes(key) => encode_binary(&key),
+                            other => format!("{other:?}"),
+                        };
+                        Ok((key, WampValue::try_from(item)?))
+                    })
+                    .collect::<Result<_, Error>>()?,
+            ),
+            ciborium::Value::Tag(_, value) => WampValue::try_from(*value)?,
+            other => {
+                return Err(Error::Codec(
+                    format!("unsupported CBOR value {other:?}").into(),
+                ))
+            }
+        })
+    }
+}
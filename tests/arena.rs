@@ -0,0 +1,89 @@
+use wamp_helpers::arena::ParseArena;
+
+const FRAMES: &[&str] = &[
+    r#"[1, "com.example.realm", {"roles": {"caller": {}, "subscriber": {}}}]"#,
+    r#"[8, 48, 7814135, {}, "com.myapp.error.object_write_protected", ["Object is write protected."], {"severity": 3}]"#,
+    r#"[16, 239714735, {"acknowledge": true}, "com.myapp.mytopic1", ["Hello, world!"], {"color": "orange", "sizes": [23, 42, 7]}]"#,
+    r#"[48, 7814135, {"timeout": 1000}, "com.myapp.echo", ["\u0000AAEC"], {"b": 2, "a": 1}]"#,
+    r#"[50, 1, {}, [-1, 0, 1.5, -2.25e-3, 1E+2, 18446744073709551615, -9223372036854775807]]"#,
+    r#"[50, 1, {}, ["tab\there", "quote\"", "slash\/", "\\", "😀", "snowman ☃", "grüße"]]"#,
+    r#"[50, 1, {}, [null, true, false, [], {}, [[[]]], {"a": {"b": {"c": null}}}]]"#,
+    "  [ 36 ,\n 1 ,\t2 , { } ]  ",
+];
+
+const MALFORMED: &[&str] = &[
+    "",
+    "[",
+    "[1,]",
+    "[1 2]",
+    "{\"a\" 1}",
+    "{1: 2}",
+    "[01]",
+    "[1.]",
+    "[-]",
+    "[1e]",
+    "[\"unterminated]",
+    "[\"bad \\x escape\"]",
+    "[\"raw\ncontrol\"]",
+    "[tru]",
+    "[1] 2",
+    "[\"lone \\ud800A\"]",
+    "[\"lone \\udc00\"]",
+    "[\"unpaired \\ud800\\u0041\"]",
+    "[\"signed \\u+abc\"]",
+    "[\"signed \\u-abc\"]",
+];
+
+#[test]
+fn trees_match_the_json_crate() {
+    let mut arena = ParseArena::new();
+    for frame in FRAMES {
+        let expected = json::parse(frame).unwrap();
+        let tree = arena.parse(frame).unwrap();
+        assert_eq!(tree.to_json(), expected, "{frame}");
+
+        let mut written = String::new();
+        tree.write_to(&mut written);
+        assert_eq!(json::parse(&written).unwrap(), expected, "{written}");
+    }
+}
+
+#[test]
+fn malformed_frames_are_rejected() {
+    let mut arena = ParseArena::new();
+    for frame in MALFORMED {
+        assert!(json::parse(frame).is_err(), "{frame}");
+        assert!(arena.parse(frame).is_err(), "{frame}");
+    }
+    let deep = format!("{}{}", "[".repeat(600), "]".repeat(600));
+    assert!(arena.parse(&deep).is_err());
+}
+
+#[test]
+fn arena_is_reused_between_messages() {
+    let mut arena = ParseArena::new();
+    let long = arena.parse(FRAMES[2]).unwrap();
+    assert_eq!(long.len(), 6);
+    let nodes = arena.len();
+
+    let short = arena.parse("[36, 1, 2, {}]").unwrap();
+    assert_eq!(short.get(3).map(|details| details.is_object()), Some(true));
+    assert!(arena.len() < nodes);
+}
+
+#[test]
+fn lookups() {
+    let mut arena = ParseArena::new();
+    let call = arena
+        .parse(r#"[48, 7, {"timeout": 1000, "timeout": 2000}, "com.myapp.echo"]"#)
+        .unwrap();
+    let options = call.get(2).unwrap();
+    assert_eq!(
+        options.get_key("timeout").and_then(|t| t.as_u64()),
+        Some(2000)
+    );
+    assert!(options.get_key("receive_progress").is_none());
+    assert_eq!(options.entries().count(), 2);
+    assert!(call.get(4).is_none());
+    assert!(call.get(3).unwrap().as_u64().is_none());
+}
@@ -0,0 +1,30 @@
+use std::fs;
+use wamp_helpers::autobahn::{dump_cases, load_cases, Report};
+use wamp_helpers::parse::ParseOptions;
+
+fn cases() -> String {
+    fs::read_to_string(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/autobahn/cases.json"
+    ))
+    .unwrap()
+}
+
+#[test]
+fn parser_passes_every_case() {
+    let mut report = Report::new(env!("CARGO_PKG_NAME"));
+    for case in load_cases(&cases()).unwrap() {
+        report.push(case.run(&ParseOptions::default()));
+    }
+    let failed: Vec<_> = report.failed().collect();
+    assert!(failed.is_empty(), "{failed:#?}");
+
+    let index = report.to_json();
+    assert_eq!(index[env!("CARGO_PKG_NAME")].len(), report.results().len());
+}
+
+#[test]
+fn cases_survive_export() {
+    let cases = load_cases(&cases()).unwrap();
+    assert_eq!(load_cases(&dump_cases(&cases)).unwrap(), cases);
+}
@@ -0,0 +1,157 @@
+#![cfg(feature = "serde")]
+
+use std::time::{Duration, Instant};
+use wamp_helpers::config::{Action, AuthorizationCache, RouterConfig};
+use wamp_helpers::error::Error;
+use wamp_helpers::messages::Message;
+use wamp_helpers::uri::MatchPolicy;
+use wamp_helpers::validator::{ViolationAction, ViolationPolicy};
+
+const CONFIG: &str = r#"{
+    "realms": [{
+        "name": "realm1",
+        "roles": [{
+            "name": "backend",
+            "permissions": [
+                {"uri": "", "match": "prefix", "allow": {"call": true, "subscribe": true}},
+                {"uri": "com.example.", "match": "prefix", "allow": {"register": true, "publish": true}},
+                {"uri": "com.example.admin.reset", "allow": {}},
+                {"uri": "com..status", "match": "wildcard", "allow": {"publish": true}}
+            ]
+        }],
+        "principals": [
+            {"authid": "worker", "role": "backend", "secret": "prq7+YkJ1/KlW1X0YczMHw==", "salt": "salt123", "iterations": 100, "keylen": 16}
+        ]
+    }]
+}"#;
+
+#[test]
+fn loads_crossbar_style_json() {
+    let config: RouterConfig = serde_json::from_str(CONFIG).unwrap();
+    config.validate().unwrap();
+
+    let realm = config.realm("realm1").unwrap();
+    let role = realm.role("backend").unwrap();
+    assert_eq!(role.permissions[0].match_policy, MatchPolicy::Prefix);
+    assert_eq!(role.permissions[2].match_policy, MatchPolicy::Exact);
+    assert_eq!(realm.principal("worker").unwrap().iterations, Some(100));
+}
+
+#[test]
+fn most_specific_permission_wins() {
+    let config: RouterConfig = serde_json::from_str(CONFIG).unwrap();
+    let realm = config.realm("realm1").unwrap();
+
+    // Catch-all prefix.
+    assert!(realm.authorize("backend", "org.other.proc", Action::Call));
+    assert!(!realm.authorize("backend", "org.other.proc", Action::Register));
+    // Longer prefix replaces the catch-all entirely.
+    assert!(realm.authorize("backend", "com.example.add", Action::Register));
+    assert!(!realm.authorize("backend", "com.example.add", Action::Call));
+    // Exact match denies everything.
+    assert!(!realm.authorize("backend", "com.example.admin.reset", Action::Register));
+    // Any prefix match, even the catch-all, takes precedence over wildcards.
+    assert!(!realm.authorize("backend", "com.other.status", Action::Publish));
+    let mut role = realm.role("backend").unwrap().clone();
+    role.permissions.remove(0);
+    assert!(role
+        .permission_for("com.other.status")
+        .is_some_and(|permission| permission.allow.publish));
+}
+
+#[test]
+fn rejects_principal_with_unknown_role() {
+    let mut config: RouterConfig = serde_json::from_str(CONFIG).unwrap();
+    config.realms[0].principals[0].role = "frontend".to_string();
+    assert!(matches!(
+        config.validate(),
+        Err(Error::InvalidConfig { .. })
+    ));
+}
+
+#[test]
+fn message_allowlists_are_checked() {
+    let mut config: RouterConfig = serde_json::from_str(CONFIG).unwrap();
+    config.realms[0].roles[0].allow_messages = Some(vec!["CALL".to_string(), "YIELD".to_string()]);
+    config.validate().unwrap();
+
+    let realm = config.realm("realm1").unwrap();
+    let call = Message::parse_message(r#"[48, 1, {}, "com.example.add"]"#).unwrap();
+    let publish = Message::parse_message(r#"[16, 2, {}, "com.example.status"]"#).unwrap();
+    assert!(realm.check_message("backend", &call).is_ok());
+    assert!(matches!(
+        realm.check_message("backend", &publish),
+        Err(Error::MessageNotAllowed {
+            message_type: 16,
+            ..
+        })
+    ));
+    assert!(realm.check_message("nobody", &call).is_err());
+
+    config.realms[0].roles[0].allow_messages = Some(vec!["call".to_string()]);
+    assert!(matches!(
+        config.validate(),
+        Err(Error::InvalidConfig { .. })
+    ));
+}
+
+#[test]
+fn roundtrips_through_serde() {
+    let config: RouterConfig = serde_json::from_str(CONFIG).unwrap();
+    let serialized = serde_json::to_string(&config).unwrap();
+    assert_eq!(
+        serde_json::from_str::<RouterConfig>(&serialized).unwrap(),
+        config
+    );
+}
+
+#[test]
+fn violation_policy_defaults_per_key() {
+    let config: RouterConfig = serde_json::from_str(
+        r#"{"realms": [
+            {"name": "realm1"},
+            {"name": "legacy", "violations": {"bad_sequencing": "log", "malformed_uri": "ignore"}}
+        ]}"#,
+    )
+    .unwrap();
+    assert_eq!(
+        config.realm("realm1").unwrap().violations,
+        ViolationPolicy::default()
+    );
+    let legacy = config.realm("legacy").unwrap().violations;
+    assert_eq!(legacy.bad_sequencing, ViolationAction::Log);
+    assert_eq!(legacy.malformed_uri, ViolationAction::Ignore);
+    assert_eq!(legacy.wrong_direction, ViolationAction::Abort);
+}
+
+#[test]
+fn cached_decisions_follow_invalidation() {
+    let config: RouterConfig = serde_json::from_str(CONFIG).unwrap();
+    let realm = config.realm("realm1").unwrap();
+    let start = Instant::now();
+    let mut cache = AuthorizationCache::new(Duration::from_secs(10));
+    let register = Message::parse_message(r#"[64, 1, {}, "com.example.add"]"#).unwrap();
+    let call = Message::parse_message(r#"[48, 2, {}, "com.example.add"]"#).unwrap();
+
+    assert!(cache.authorize_message(realm, "backend", &register, start));
+    assert!(!cache.authorize_message(realm, "backend", &call, start));
+    assert!(!cache.authorize_message(realm, "frontend", &call, start));
+    assert_eq!(cache.len(), 2);
+
+    // A decision pushed by hand wins until it is invalidated.
+    cache.insert(
+        "realm1",
+        "backend",
+        "com.example.add",
+        Action::Call,
+        true,
+        start,
+    );
+    assert!(cache.authorize_message(realm, "backend", &call, start));
+    cache.invalidate("realm1", "backend", "com.example.add", Action::Call);
+    assert!(!cache.authorize_message(realm, "backend", &call, start));
+
+    assert_eq!(cache.expire(start + Duration::from_secs(9)), 0);
+    assert_eq!(cache.expire(start + Duration::from_secs(10)), 2);
+    assert!(cache.is_empty());
+}
@@ -0,0 +1,257 @@
+use std::time::{Duration, Instant};
+use wamp_helpers::dealer::{
+    BusyPolicy, ConcurrencyTracker, Dispatch, Endpoint, Reroute, Rerouter, RoutedCall,
+};
+use wamp_helpers::messages::{Call, ErrorMessage, Options};
+use wamp_helpers::options::RegisterOptions;
+
+fn call(request: u64) -> Call {
+    format!(r#"[48, {request}, {{}}, "com.example.work"]"#)
+        .parse()
+        .unwrap()
+}
+
+const SHARED: u64 = 9;
+
+fn routed(callee: u64, caller: u64, request: u64) -> RoutedCall {
+    RoutedCall::new(
+        Endpoint {
+            registration: SHARED,
+            callee,
+        },
+        caller,
+        call(request),
+    )
+}
+
+#[test]
+fn yield_racing_the_disconnect_is_dropped() {
+    let mut rerouter = Rerouter::new(3);
+    rerouter.invoked(1, routed(100, 200, 7));
+
+    let outcomes = rerouter.callee_lost(100, |_| vec![]);
+    assert_eq!(outcomes.len(), 1);
+    assert!(matches!(
+        &outcomes[0],
+        Reroute::Forward { caller: 200, error } if error.error == "wamp.error.canceled" && error.request == 7
+    ));
+
+    // The YIELD or ERROR was already on the wire when the session ended.
+    assert!(rerouter.completed(1).is_none());
+    let late: ErrorMessage = r#"[8, 68, 1, {}, "wamp.error.unavailable"]"#.parse().unwrap();
+    assert!(rerouter.on_error(&late, &[101]).is_none());
+}
+
+#[test]
+fn calls_move_to_the_remaining_callees() {
+    let mut rerouter = Rerouter::new(3);
+    rerouter.invoked(1, routed(100, 200, 7));
+    rerouter.invoked(2, routed(100, 201, 8));
+    rerouter.invoked(3, routed(101, 202, 9));
+
+    let outcomes = rerouter.callee_lost(100, |_| vec![101, 102]);
+    assert_eq!(outcomes.len(), 2);
+    for outcome in &outcomes {
+        let Reroute::Retry(retry) = outcome else {
+            panic!("{outcome:?}")
+        };
+        assert_eq!(retry.endpoint.callee, 101);
+        assert_eq!(retry.tried(), &[100]);
+    }
+    assert_eq!(rerouter.stats().rerouted, 2);
+    // The call already running at the remaining callee is untouched.
+    assert!(rerouter.completed(3).is_some());
+}
+
+#[test]
+fn callee_dropping_while_a_retry_targeted_it() {
+    let mut rerouter = Rerouter::new(3);
+    rerouter.invoked(1, routed(100, 200, 7));
+    let Reroute::Retry(retry) = rerouter.callee_lost(100, |_| vec![101, 102]).remove(0) else {
+        panic!()
+    };
+    // The retry was sent to 101, which left before answering.
+    rerouter.invoked(2, retry);
+    let Reroute::Retry(retry) = rerouter.callee_lost(101, |_| vec![101, 102]).remove(0) else {
+        panic!()
+    };
+    assert_eq!(retry.endpoint.callee, 102);
+    assert_eq!(retry.tried(), &[100, 101]);
+
+    // The third attempt is the last one.
+    rerouter.invoked(3, retry);
+    let outcome = rerouter.callee_lost(102, |_| vec![103]).remove(0);
+    assert!(matches!(outcome, Reroute::Forward { caller: 200, .. }));
+    assert_eq!(rerouter.stats().rerouted, 2);
+}
+
+#[test]
+fn lost_callee_frees_its_concurrency_slots() {
+    let mut tracker = ConcurrencyTracker::new();
+    let lost = Endpoint {
+        registration: SHARED,
+        callee: 100,
+    };
+    let options = Options::from(RegisterOptions {
+        concurrency: Some(1),
+        ..RegisterOptions::default()
+    });
+    tracker.register(lost, &options);
+    tracker.start(lost, 1);
+    assert!(!tracker.is_available(lost));
+
+    assert!(tracker.remove_callee(100, |_| vec![]).is_empty());
+    assert_eq!(tracker.running(lost), 0);
+    // A late YIELD does not free a slot of anybody else.
+    assert!(tracker.finish(1).is_none());
+}
+
+/// A tracker under `policy` with callees 100 and 101 sharing [`SHARED`], each running at
+/// most one invocation, 100 busy with invocation 1.
+fn busy_tracker(policy: BusyPolicy) -> ConcurrencyTracker {
+    let mut tracker = ConcurrencyTracker::new().policy(policy);
+    let options = Options::from(RegisterOptions {
+        concurrency: Some(1),
+        ..RegisterOptions::default()
+    });
+    for callee in [100, 101] {
+        let endpoint = Endpoint {
+            registration: SHARED,
+            callee,
+        };
+        tracker.register(endpoint, &options);
+    }
+    tracker.start(
+        Endpoint {
+            registration: SHARED,
+            callee: 100,
+        },
+        1,
+    );
+    tracker
+}
+
+#[test]
+fn reject_only_tries_the_selected_callee() {
+    let mut tracker = busy_tracker(BusyPolicy::Reject);
+    let now = Instant::now();
+    assert!(matches!(
+        tracker.dispatch(SHARED, &[100, 101], 200, call(7), now),
+        Dispatch::Rejected {
+            error: "wamp.error.unavailable",
+            ..
+        }
+    ));
+    assert!(matches!(
+        tracker.dispatch(SHARED, &[101, 100], 200, call(8), now),
+        Dispatch::Invoke(Endpoint { callee: 101, .. })
+    ));
+}
+
+#[test]
+fn spillover_moves_on_to_a_free_callee() {
+    let mut tracker = busy_tracker(BusyPolicy::Spillover);
+    let now = Instant::now();
+    let Dispatch::Invoke(endpoint) = tracker.dispatch(SHARED, &[100, 101], 200, call(7), now)
+    else {
+        panic!()
+    };
+    assert_eq!(endpoint.callee, 101);
+    tracker.start(endpoint, 2);
+
+    // Without a queue, a call finding every callee busy is refused.
+    assert!(matches!(
+        tracker.dispatch(SHARED, &[100, 101], 200, call(8), now),
+        Dispatch::Rejected {
+            error: "wamp.error.unavailable",
+            ..
+        }
+    ));
+    assert_eq!(tracker.queued(SHARED), 0);
+}
+
+#[test]
+fn queued_calls_time_out() {
+    let timeout = Duration::from_secs(5);
+    let mut tracker = busy_tracker(BusyPolicy::Queue { max: 2, timeout });
+    let start = Instant::now();
+    let endpoint = Endpoint {
+        registration: SHARED,
+        callee: 101,
+    };
+    assert!(matches!(
+        tracker.dispatch(SHARED, &[100, 101], 200, call(7), start),
+        Dispatch::Invoke(invoked) if invoked == endpoint
+    ));
+    tracker.start(endpoint, 2);
+
+    let later = start + Duration::from_secs(2);
+    assert!(matches!(
+        tracker.dispatch(SHARED, &[100, 101], 200, call(8), start),
+        Dispatch::Queued
+    ));
+    assert!(matches!(
+        tracker.dispatch(SHARED, &[100, 101], 201, call(9), later),
+        Dispatch::Queued
+    ));
+    assert!(tracker
+        .expire(start + timeout - Duration::from_millis(1))
+        .is_empty());
+    let expired = tracker.expire(start + timeout);
+    assert_eq!(expired.len(), 1);
+    assert_eq!((expired[0].caller, expired[0].call.request), (200, 8));
+    assert_eq!(tracker.queued(SHARED), 1);
+
+    let (next, queued) = tracker.finish(2).unwrap();
+    assert_eq!((next, queued.caller), (endpoint, 201));
+    assert!(tracker.expire(later + timeout).is_empty());
+}
+
+#[test]
+fn calls_queued_for_the_last_callee_are_returned_when_it_leaves() {
+    let timeout = Duration::from_secs(5);
+    let mut tracker = busy_tracker(BusyPolicy::Queue { max: 2, timeout });
+    let now = Instant::now();
+    let alone = Endpoint {
+        registration: 10,
+        callee: 100,
+    };
+    tracker.register(
+        alone,
+        &Options::from(RegisterOptions {
+            concurrency: Some(1),
+            ..RegisterOptions::default()
+        }),
+    );
+    tracker.start(alone, 2);
+    tracker.start(
+        Endpoint {
+            registration: SHARED,
+            callee: 101,
+        },
+        3,
+    );
+    assert!(matches!(
+        tracker.dispatch(10, &[100], 200, call(7), now),
+        Dispatch::Queued
+    ));
+    assert!(matches!(
+        tracker.dispatch(SHARED, &[100, 101], 201, call(8), now),
+        Dispatch::Queued
+    ));
+
+    // Registration 10 is gone with callee 100, 101 is left for the shared one.
+    let callees = |registration| {
+        if registration == SHARED {
+            vec![101]
+        } else {
+            vec![]
+        }
+    };
+    let orphaned = tracker.remove_callee(100, callees);
+    assert_eq!(orphaned.len(), 1);
+    assert_eq!(orphaned[0].error("wamp.error.no_such_procedure").request, 7);
+    assert_eq!((tracker.queued(10), tracker.queued(SHARED)), (0, 1));
+    let (next, queued) = tracker.finish(3).unwrap();
+    assert_eq!((next.callee, queued.caller), (101, 201));
+}
@@ -0,0 +1,69 @@
+use wamp_helpers::error::Error;
+use wamp_helpers::messages::Message;
+use wamp_helpers::parse::{DictBudget, ParseOptions};
+
+fn options(truncate: bool) -> ParseOptions {
+    ParseOptions {
+        dict_budget: Some(DictBudget {
+            truncate,
+            ..DictBudget::default()
+        }),
+        ..ParseOptions::default()
+    }
+}
+
+fn hello_with_details(details: &str) -> String {
+    format!(r#"[1, "realm1", {details}]"#)
+}
+
+#[test]
+fn many_keys_are_rejected() {
+    let entries: Vec<String> = (0..10_000).map(|key| format!(r#""k{key}": 0"#)).collect();
+    let frame = hello_with_details(&format!("{{{}}}", entries.join(",")));
+    assert!(matches!(
+        Message::parse_message_with(&frame, &options(false)),
+        Err(Error::DictionaryTooLarge {
+            keys: 10_000,
+            limit: 64
+        })
+    ));
+}
+
+#[test]
+fn many_keys_are_truncated_in_order() {
+    let entries: Vec<String> = (0..10_000).map(|key| format!(r#""k{key}": 0"#)).collect();
+    let frame = hello_with_details(&format!("{{{}}}", entries.join(",")));
+    let hello = Message::parse_message_with(&frame, &options(true)).unwrap();
+    let details = hello.details().unwrap();
+    assert_eq!(details.len(), 64);
+    assert!(details.has_key("k0") && details.has_key("k63") && !details.has_key("k64"));
+}
+
+#[test]
+fn long_strings_are_rejected() {
+    let frame = hello_with_details(&format!(r#"{{"authid": "{}"}}"#, "x".repeat(1 << 20)));
+    assert!(matches!(
+        Message::parse_message_with(&frame, &options(false)),
+        Err(Error::ValueTooLarge { key, .. }) if key == "authid"
+    ));
+}
+
+#[test]
+fn large_values_are_dropped_when_truncating() {
+    // The JSON parser itself refuses much deeper nesting.
+    let nested = format!("{}{}", "[".repeat(100), "]".repeat(100));
+    let inflated = format!("[{}]", vec!["{}"; 10_000].join(","));
+    let frame = hello_with_details(&format!(
+        r#"{{"roles": {{"caller": {{}}}}, "nested": {nested}, "inflated": {inflated}}}"#
+    ));
+    let hello = Message::parse_message_with(&frame, &options(true)).unwrap();
+    let details = hello.details().unwrap();
+    assert!(details.has_key("roles") && details.has_key("nested"));
+    assert!(!details.has_key("inflated"));
+}
+
+#[test]
+fn the_default_budget_accepts_ordinary_frames() {
+    let frame = r#"[48, 7814135, {"receive_progress": true, "timeout": 1000}, "com.myapp.echo", ["Hello"]]"#;
+    assert!(Message::parse_message_with(frame, &ParseOptions::strict()).is_ok());
+}
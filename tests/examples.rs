@@ -0,0 +1,29 @@
+//! Run the programs in `examples/` as tests, they assert on what they print.
+
+#[path = "../examples/chat.rs"]
+mod chat;
+
+#[cfg(feature = "serde")]
+#[path = "../examples/calculator.rs"]
+mod calculator;
+
+#[cfg(feature = "cra")]
+#[path = "../examples/auth.rs"]
+mod auth;
+
+#[test]
+fn chat() {
+    chat::main();
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn calculator() {
+    calculator::main();
+}
+
+#[cfg(feature = "cra")]
+#[test]
+fn auth() {
+    auth::main();
+}
@@ -0,0 +1,129 @@
+//! Wire format snapshots: every message in `CORPUS` is serialized as canonical JSON and compared
+//! byte for byte with `tests/golden/<name>.json`.
+//!
+//! After an intended wire format change, regenerate the files with
+//! `UPDATE_GOLDEN=1 cargo test --test golden` and review the diff.
+
+use std::fs;
+use std::path::PathBuf;
+use wamp_helpers::messages::Message;
+
+const CORPUS: &[(&str, &str)] = &[
+    (
+        "hello",
+        r#"[1, "com.example.realm", {"roles": {"caller": {}, "subscriber": {}}}]"#,
+    ),
+    (
+        "welcome",
+        r#"[2, 9129137332, {"roles": {"broker": {}, "dealer": {}}}]"#,
+    ),
+    (
+        "abort",
+        r#"[3, {"message": "no such realm"}, "wamp.error.no_such_realm"]"#,
+    ),
+    (
+        "challenge",
+        r#"[4, "wampcra", {"challenge": "{\"nonce\": \"LHRTC9zeOIrt_9U3\"}"}]"#,
+    ),
+    (
+        "authenticate",
+        r#"[5, "gir1mSx+deCDUV7wRM5SGIn/+R/ClqLZuH4m7FJeBVI=", {}]"#,
+    ),
+    (
+        "goodbye",
+        r#"[6, {"message": "The host is shutting down now."}, "wamp.close.system_shutdown"]"#,
+    ),
+    (
+        "error",
+        r#"[8, 48, 7814135, {}, "com.myapp.error.object_write_protected", ["Object is write protected."], {"severity": 3}]"#,
+    ),
+    (
+        "publish",
+        r#"[16, 239714735, {"acknowledge": true}, "com.myapp.mytopic1", ["Hello, world!"], {"color": "orange", "sizes": [23, 42, 7]}]"#,
+    ),
+    ("published", r#"[17, 239714735, 4429313566]"#),
+    (
+        "subscribe",
+        r#"[32, 713845233, {"match": "prefix"}, "com.myapp.mytopic1"]"#,
+    ),
+    ("subscribed", r#"[33, 713845233, 5512315355]"#),
+    ("unsubscribe", r#"[34, 85346237, 5512315355]"#),
+    ("unsubscribed", r#"[35, 85346237]"#),
+    (
+        "event",
+        r#"[36, 5512315355, 4429313566, {}, ["Hello, world!"], {"color": "orange"}]"#,
+    ),
+    (
+        "call",
+        r#"[48, 7814135, {"timeout": 1000}, "com.myapp.echo", ["\u0000AAEC"], {"b": 2, "a": 1}]"#,
+    ),
+    ("cancel", r#"[49, 7814135, {"mode": "kill"}]"#),
+    ("result", r#"[50, 7814135, {"progress": true}, [30]]"#),
+    (
+        "register",
+        r#"[64, 25349185, {"invoke": "roundrobin"}, "com.myapp.myprocedure1"]"#,
+    ),
+    ("registered", r#"[65, 25349185, 2103333224]"#),
+    ("unregister", r#"[66, 788923562, 2103333224]"#),
+    ("unregistered", r#"[67, 788923562]"#),
+    (
+        "invocation",
+        r#"[68, 6131533, 9823526, {}, ["johnny"], {"firstname": "John"}]"#,
+    ),
+    ("interrupt", r#"[69, 6131533, {"mode": "killnowait"}]"#),
+    ("yield", r#"[70, 6131533, {}, [], {"userid": 123}]"#),
+];
+
+fn golden_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("golden")
+        .join(format!("{}.json", name))
+}
+
+#[test]
+fn corpus_covers_every_message_type() {
+    let mut ids: Vec<u8> = CORPUS
+        .iter()
+        .map(|(_, frame)| Message::parse_message(frame).unwrap().message_id())
+        .collect();
+    ids.sort_unstable();
+    ids.dedup();
+    assert_eq!(ids.len(), 24);
+}
+
+#[test]
+fn wire_format_matches_golden_files() {
+    let update = std::env::var_os("UPDATE_GOLDEN").is_some();
+    let mut mismatches = Vec::new();
+
+    for (name, frame) in CORPUS {
+        let message = Message::parse_message(frame).unwrap();
+        let actual = wamp_helpers::canonical::to_canonical_string(&message.to_json().unwrap());
+        let path = golden_path(name);
+
+        if update {
+            fs::write(&path, format!("{}\n", actual)).unwrap();
+            continue;
+        }
+
+        let expected = fs::read_to_string(&path)
+            .unwrap_or_else(|err| panic!("missing golden file {}: {}", path.display(), err));
+        let expected = expected.trim_end();
+        if actual != expected {
+            mismatches.push(format!(
+                "{}:\n  expected {}\n  actual   {}",
+                name, expected, actual
+            ));
+        }
+
+        // The snapshot itself must stay parseable.
+        Message::parse_message(expected).unwrap();
+    }
+
+    assert!(
+        mismatches.is_empty(),
+        "wire format changed, rerun with UPDATE_GOLDEN=1 if intended:\n{}",
+        mismatches.join("\n")
+    );
+}
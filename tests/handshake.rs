@@ -0,0 +1,48 @@
+use wamp_helpers::handshake::{FeatureGate, FeaturePolicy, HelloAnalysis};
+use wamp_helpers::messages::{Hello, Message};
+
+fn gate_for(roles: &str) -> FeatureGate {
+    let hello: Hello = format!(r#"[1, "realm1", {{"roles": {roles}}}]"#)
+        .parse()
+        .unwrap();
+    FeatureGate::new(&HelloAnalysis::from(&hello))
+}
+
+#[test]
+fn strip_removes_unannounced_black_and_white_listing() {
+    let gate = gate_for(r#"{"publisher": {}}"#).policy(FeaturePolicy::Strip);
+    let mut publish = Message::parse_message(
+        r#"[16, 1, {"exclude": [7], "eligible": [8, 9], "acknowledge": true}, "com.example.topic"]"#,
+    )
+    .unwrap();
+    assert_eq!(gate.check(&mut publish).unwrap(), ["exclude", "eligible"]);
+    assert_eq!(publish.details().unwrap().dump(), r#"{"acknowledge":true}"#);
+
+    // Announced, the lists stay.
+    let announced =
+        gate_for(r#"{"publisher": {"features": {"subscriber_blackwhite_listing": true}}}"#)
+            .policy(FeaturePolicy::Strip);
+    let mut publish =
+        Message::parse_message(r#"[16, 2, {"exclude": [7]}, "com.example.topic"]"#).unwrap();
+    assert!(announced.check(&mut publish).unwrap().is_empty());
+    assert_eq!(publish.details().unwrap()["exclude"][0], 7);
+}
+
+#[test]
+fn default_options_need_no_feature() {
+    let gate = gate_for(r#"{"callee": {}, "publisher": {}}"#);
+    for frame in [
+        r#"[64, 1, {"invoke": "single", "match": "exact"}, "com.example.add"]"#,
+        r#"[16, 2, {"exclude_me": true, "exclude": []}, "com.example.topic"]"#,
+    ] {
+        let mut message = Message::parse_message(frame).unwrap();
+        assert!(gate.check(&mut message).unwrap().is_empty(), "{frame}");
+    }
+
+    let mut register =
+        Message::parse_message(r#"[64, 3, {"invoke": "roundrobin"}, "com.example.add"]"#).unwrap();
+    assert!(gate.check(&mut register).is_err());
+    let mut publish =
+        Message::parse_message(r#"[16, 4, {"exclude_me": false}, "com.example.topic"]"#).unwrap();
+    assert!(gate.check(&mut publish).is_err());
+}
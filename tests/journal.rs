@@ -0,0 +1,89 @@
+use std::io::Cursor;
+use std::time::{Duration, UNIX_EPOCH};
+use wamp_helpers::correlation::Direction;
+use wamp_helpers::error::Error;
+use wamp_helpers::journal::{Envelope, Journal};
+use wamp_helpers::messages::Message;
+use wamp_helpers::session::{Session, Side};
+
+fn envelope() -> Envelope {
+    Envelope {
+        received_at: UNIX_EPOCH + Duration::from_micros(1_700_000_000_123_456),
+        direction: Direction::Inbound,
+        session_id: None,
+        message: Message::parse_message(r#"[1, "realm1", {"roles": {"caller": {}}}]"#).unwrap(),
+    }
+}
+
+#[test]
+fn journal_lines_round_trip() {
+    let mut journal = Journal::new(Vec::new());
+    journal.append(&envelope()).unwrap();
+    let written = String::from_utf8(journal.into_inner()).unwrap();
+    assert_eq!(
+        written,
+        concat!(
+            r#"{"received_at":1700000000123456,"direction":"inbound","session_id":null,"#,
+            r#""message":[1,"realm1",{"roles":{"caller":{}}}]}"#,
+            "\n"
+        )
+    );
+
+    let read: Vec<_> = Journal::new(Cursor::new(format!("{written}\n{written}")))
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(read, vec![envelope(), envelope()]);
+}
+
+#[test]
+fn malformed_journal_lines_are_errors() {
+    for line in [
+        "[1, 2]",
+        r#"{"direction": "inbound", "message": [6, {}, "wamp.close.normal"]}"#,
+        r#"{"received_at": 1, "direction": "sideways", "message": [6, {}, "wamp.close.normal"]}"#,
+        r#"{"received_at": 1, "direction": "inbound", "message": "[6, {}, \"x\"]"}"#,
+    ] {
+        let mut journal = Journal::new(Cursor::new(line));
+        assert!(
+            matches!(journal.read(), Err(Error::InvalidFrame { .. })),
+            "{line}"
+        );
+    }
+    let mut truncated = Journal::new(Cursor::new(r#"{"received_at": 1, "dire"#));
+    assert!(matches!(truncated.read(), Err(Error::JsonError(_))));
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn envelopes_serialize_like_journal_lines() {
+    let serialized = serde_json::to_value(envelope()).unwrap();
+    let line: serde_json::Value =
+        serde_json::from_str(&envelope().to_json().unwrap().dump()).unwrap();
+    assert_eq!(serialized, line);
+    assert_eq!(
+        serialized["message"],
+        serde_json::json!([1, "realm1", {"roles": {"caller": {}}}])
+    );
+    let back: Envelope = serde_json::from_value(serialized).unwrap();
+    assert_eq!(back, envelope());
+}
+
+#[test]
+fn replay_catches_a_different_session_id() {
+    let capture = concat!(
+        r#"{"received_at": 1, "direction": "outbound", "session_id": null, "message": [1, "realm1", {"roles": {"caller": {}}}]}"#,
+        "\n",
+        r#"{"received_at": 2, "direction": "inbound", "session_id": 8, "message": [2, 7, {"roles": {"dealer": {}}}]}"#,
+        "\n",
+    );
+    let mut session = Session::new(Side::Client);
+    let replayed = Journal::new(Cursor::new(capture)).replay_into(&mut session);
+    let Err(Error::ReplayDiverged { entry, reason }) = replayed else {
+        panic!("replay did not diverge: {replayed:?}");
+    };
+    assert_eq!(entry, 1);
+    assert_eq!(
+        reason,
+        "WELCOME recorded in session 8, replayed in session 7"
+    );
+}
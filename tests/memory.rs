@@ -0,0 +1,28 @@
+use std::future::Future;
+use std::task::{Context, Poll, Waker};
+use wamp_helpers::memory::{Fault, MemoryTransport};
+use wamp_helpers::transport::Transport;
+
+fn now<F: Future>(future: F) -> F::Output {
+    let mut future = std::pin::pin!(future);
+    match future
+        .as_mut()
+        .poll(&mut Context::from_waker(Waker::noop()))
+    {
+        Poll::Ready(output) => output,
+        Poll::Pending => panic!("future is not ready"),
+    }
+}
+
+#[test]
+fn delayed_frames_are_delivered_before_the_close() {
+    let (mut client, mut router) = MemoryTransport::pair();
+    client.inject(Fault::Delay(5));
+    now(client.send(b"late".to_vec())).unwrap();
+    now(client.send(b"on time".to_vec())).unwrap();
+    now(client.close("wamp.close.normal")).unwrap();
+
+    assert_eq!(now(router.next()).unwrap().unwrap(), b"on time");
+    assert_eq!(now(router.next()).unwrap().unwrap(), b"late");
+    assert!(now(router.next()).is_none());
+}
@@ -0,0 +1,82 @@
+use wamp_helpers::bridge::mqtt::{MqttBridge, MqttPublish, PayloadFormat, QoS};
+use wamp_helpers::messages::Event;
+use wamp_helpers::uri::MatchPolicy;
+use wamp_helpers::value::WampValue;
+
+fn bridge() -> MqttBridge {
+    MqttBridge::new("com.example")
+}
+
+#[test]
+fn filters_map_to_match_policies() {
+    let pattern = |filter: &str| bridge().filter_to_pattern(filter).ok();
+    assert_eq!(
+        pattern("a/b"),
+        Some(("com.example.a.b".into(), MatchPolicy::Exact))
+    );
+    assert_eq!(
+        pattern("a/#"),
+        Some(("com.example.a.".into(), MatchPolicy::Prefix))
+    );
+    assert_eq!(
+        pattern("#"),
+        Some(("com.example.".into(), MatchPolicy::Prefix))
+    );
+    assert_eq!(
+        pattern("+/b/+"),
+        Some(("com.example..b.".into(), MatchPolicy::Wildcard))
+    );
+    assert!(pattern("+/b/#").is_none());
+    assert!(pattern("a#").is_none());
+    assert!(pattern("a//b").is_none());
+    assert!(pattern("$SYS/broker").is_none());
+}
+
+#[test]
+fn topics_round_trip() {
+    let bridge = bridge();
+    let uri = bridge.topic_to_uri("plant/line-3/press").unwrap();
+    assert_eq!(uri, "com.example.plant.line-3.press");
+    assert_eq!(
+        bridge.uri_to_topic(&uri).as_deref(),
+        Some("plant/line-3/press")
+    );
+    assert!(bridge.uri_to_topic("org.other.topic").is_none());
+    assert!(bridge.topic_to_uri("plant/line.3").is_err());
+    assert!(bridge.topic_to_uri("plant/+").is_err());
+}
+
+#[test]
+fn payloads_are_wrapped_per_format() {
+    let publish = |payload: &[u8]| MqttPublish {
+        topic: "t".to_string(),
+        payload: payload.to_vec(),
+        qos: QoS::AtMostOnce,
+        retain: true,
+    };
+    let first = |bridge: MqttBridge, payload: &[u8]| {
+        let publish = bridge.to_wamp_publish(&publish(payload), 1).unwrap();
+        assert_eq!(publish.options["retain"], true);
+        assert!(publish.options["acknowledge"].is_null());
+        publish.args.unwrap().remove(0)
+    };
+    assert_eq!(
+        first(bridge(), b"[1, 2]"),
+        WampValue::List(vec![1.into(), 2.into()])
+    );
+    assert_eq!(first(bridge(), b"on"), WampValue::Bytes(b"on".to_vec()));
+    let text = bridge().payload(PayloadFormat::Text);
+    assert_eq!(first(text, b"on"), WampValue::String("on".to_string()));
+    let binary = bridge().payload(PayloadFormat::Binary);
+    assert_eq!(first(binary, b"[1]"), WampValue::Bytes(b"[1]".to_vec()));
+
+    let event: Event = r#"[36, 5, 6, {"retained": true}, ["on"]]"#.parse().unwrap();
+    let text = bridge().payload(PayloadFormat::Text);
+    let delivered = text
+        .to_mqtt_publish("com.example.switch", &event, QoS::AtLeastOnce)
+        .unwrap();
+    assert_eq!(delivered.topic, "switch");
+    assert_eq!(delivered.payload, b"on");
+    assert!(delivered.retain);
+    assert_eq!(delivered.qos, QoS::AtLeastOnce);
+}
@@ -0,0 +1,95 @@
+//! Untrusted input reaches these functions straight from the network, whatever it is they
+//! have to return an error rather than panic and take the router down.
+
+use proptest::prelude::*;
+use wamp_helpers::acceptor::Serializer;
+use wamp_helpers::arena::ParseArena;
+use wamp_helpers::batch::split_batch;
+use wamp_helpers::framed::{decode_message, RawSocketCodec};
+use wamp_helpers::messages::Message;
+use wamp_helpers::parse::{check_duplicate_keys, ParseOptions};
+use wamp_helpers::proxy::{parse_proxy_header, V2_SIGNATURE};
+use wamp_helpers::scan::scan_frame;
+use wamp_helpers::transcode::transcode;
+use wamp_helpers::uri::{check_error_uri, suggest_error_uri};
+
+const SERIALIZERS: [Serializer; 3] = [Serializer::Json, Serializer::MsgPack, Serializer::Cbor];
+
+/// Text close enough to a WAMP frame to get past the first checks.
+fn frame_like() -> impl Strategy<Value = String> {
+    prop_oneof![
+        any::<String>(),
+        r#"\[[0-9]{1,3}(, ?([0-9-]{1,17}|\{\}|\[\]|"[a-z.\\"u]{0,10}"|\{"[a-z]{0,4}": ?[0-9\[\]{}"]{0,4}\}|null|1e999)){0,8}\]?"#,
+        r#"[\[\]{}",:\\u0-9a-z .-]{0,48}"#,
+    ]
+}
+
+fn exercise_text(frame: &str) {
+    let _ = Message::parse_message(frame);
+    let _ = Message::parse_message_with(frame, &ParseOptions::strict());
+    let _ = scan_frame(frame);
+    let _ = ParseArena::new().parse(frame).map(|tree| {
+        let mut out = String::new();
+        tree.write_to(&mut out);
+        tree.to_json()
+    });
+    let _ = check_duplicate_keys(frame);
+    let _ = check_error_uri(frame, true);
+    let _ = suggest_error_uri(frame);
+}
+
+fn exercise_bytes(bytes: &[u8]) {
+    for serializer in SERIALIZERS {
+        let _ = decode_message(bytes, serializer);
+        let _ = split_batch(bytes, serializer);
+        let _ = transcode(bytes, serializer, Serializer::Json);
+    }
+    let mut codec = RawSocketCodec::new(1 << 16);
+    codec.extend(bytes);
+    while let Ok(Some(_)) = codec.decode() {}
+    let _ = parse_proxy_header(bytes);
+    let mut v2 = V2_SIGNATURE.to_vec();
+    v2.extend_from_slice(bytes);
+    let _ = parse_proxy_header(&v2);
+}
+
+proptest! {
+    #[test]
+    fn text_never_panics(frame in frame_like()) {
+        exercise_text(&frame);
+        exercise_bytes(frame.as_bytes());
+    }
+
+    #[test]
+    fn bytes_never_panic(bytes in prop::collection::vec(any::<u8>(), 0..96)) {
+        exercise_bytes(&bytes);
+    }
+}
+
+#[test]
+fn known_edge_cases_do_not_panic() {
+    for frame in [
+        "",
+        "[",
+        "[]",
+        "[\"",
+        "[\"\\",
+        "[\"\\u",
+        "[\"\\ud800\"]",
+        "[\"\\ud800\\u0041\"]",
+        "[1, {\"a\": 1, \"a\": 2}]",
+        "[48, 1, {}, \"\\\"\"]",
+        "[8, 48, 1, {}, \"wamp.error.\"]",
+        "[70, 1, {}, [], {}, 1, 2, 3, 4]",
+        "[1e999, 1]",
+        "[-0]",
+        "[99999999999999999999999]",
+        "[\"é\"",
+    ] {
+        exercise_text(frame);
+        exercise_bytes(frame.as_bytes());
+    }
+    let mut deep = "[".repeat(10_000);
+    deep.push_str(&"]".repeat(10_000));
+    exercise_text(&deep);
+}
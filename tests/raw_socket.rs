@@ -0,0 +1,137 @@
+#![cfg(feature = "futures")]
+
+use futures_core::Stream;
+use futures_io::{AsyncRead, AsyncWrite};
+use std::collections::VecDeque;
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+use wamp_helpers::acceptor::Serializer;
+use wamp_helpers::framed::{FrameKind, Framed, RawSocket, RawSocketCodec};
+use wamp_helpers::messages::Message;
+
+fn now<F: Future>(future: F) -> F::Output {
+    let mut future = std::pin::pin!(future);
+    match future
+        .as_mut()
+        .poll(&mut Context::from_waker(Waker::noop()))
+    {
+        Poll::Ready(output) => output,
+        Poll::Pending => panic!("future is not ready"),
+    }
+}
+
+/// One end of an in-memory connection: reads what the other end wrote, at most `chunk`
+/// bytes at a time.
+struct Pipe {
+    incoming: Arc<Mutex<VecDeque<u8>>>,
+    outgoing: Arc<Mutex<VecDeque<u8>>>,
+    chunk: usize,
+    closed: Arc<Mutex<bool>>,
+}
+
+fn pipe(chunk: usize) -> (Pipe, Pipe) {
+    let (a, b) = (Arc::default(), Arc::default());
+    let closed = Arc::default();
+    let left = Pipe {
+        incoming: Arc::clone(&a),
+        outgoing: Arc::clone(&b),
+        chunk,
+        closed: Arc::clone(&closed),
+    };
+    let right = Pipe {
+        incoming: b,
+        outgoing: a,
+        chunk,
+        closed,
+    };
+    (left, right)
+}
+
+impl AsyncRead for Pipe {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        _: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let mut incoming = self.incoming.lock().unwrap();
+        if incoming.is_empty() && !*self.closed.lock().unwrap() {
+            return Poll::Pending;
+        }
+        let n = buf.len().min(incoming.len()).min(self.chunk);
+        for (slot, byte) in buf.iter_mut().zip(incoming.drain(..n)) {
+            *slot = byte;
+        }
+        Poll::Ready(Ok(n))
+    }
+}
+
+impl AsyncWrite for Pipe {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        _: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let n = buf.len().min(self.chunk);
+        self.outgoing.lock().unwrap().extend(&buf[..n]);
+        Poll::Ready(Ok(n))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+        *self.closed.lock().unwrap() = true;
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[test]
+fn messages_cross_in_small_pieces_and_close() {
+    let (client, router) = pipe(3);
+    let mut client = Framed::new(RawSocket::new(client, 1 << 16), Serializer::Json);
+    let mut router = Framed::new(RawSocket::new(router, 1 << 16), Serializer::Json);
+    let hello = Message::parse_message(r#"[1, "realm1", {"roles": {"caller": {}}}]"#).unwrap();
+
+    now(client.send(hello.clone())).unwrap();
+    let mut cx = Context::from_waker(Waker::noop());
+    assert!(matches!(
+        Pin::new(&mut router).poll_next(&mut cx),
+        Poll::Ready(Some(Ok(received))) if received == hello
+    ));
+    assert!(Pin::new(&mut router).poll_next(&mut cx).is_pending());
+
+    now(client.close("wamp.close.normal")).unwrap();
+    assert!(now(router.next()).is_none());
+}
+
+#[test]
+fn pings_are_answered_on_the_next_flush() {
+    let (client, router) = pipe(1 << 16);
+    let mut wire = Vec::new();
+    RawSocketCodec::encode(FrameKind::Ping, b"1234", &mut wire).unwrap();
+    client.outgoing.lock().unwrap().extend(&wire);
+    let client_incoming = Arc::clone(&client.incoming);
+
+    let mut router = RawSocket::new(router, 1 << 16);
+    let mut cx = Context::from_waker(Waker::noop());
+    assert!(router.poll_frame(&mut cx).is_pending());
+    assert!(client_incoming.lock().unwrap().is_empty());
+    assert!(router.poll_flush(&mut cx).is_ready());
+
+    let mut codec = RawSocketCodec::new(1 << 16);
+    codec.extend(
+        &client_incoming
+            .lock()
+            .unwrap()
+            .drain(..)
+            .collect::<Vec<u8>>(),
+    );
+    assert_eq!(
+        codec.decode().unwrap(),
+        Some((FrameKind::Pong, b"1234".to_vec()))
+    );
+}
@@ -0,0 +1,381 @@
+use json::JsonValue;
+use proptest::prelude::*;
+use std::collections::BTreeMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::str::FromStr;
+use wamp_helpers::acceptor::Serializer;
+use wamp_helpers::error::Error;
+use wamp_helpers::messages::*;
+use wamp_helpers::options::{PublishOptions, RegisterOptions};
+use wamp_helpers::transcode::encode;
+use wamp_helpers::value::WampValue;
+
+fn id() -> impl Strategy<Value = WampId> {
+    1..=MAX_ID
+}
+
+fn uri() -> impl Strategy<Value = Uri> {
+    "[a-z][a-z0-9_]{0,8}(\\.[a-z][a-z0-9_]{0,8}){0,3}".prop_map(Uri::from)
+}
+
+fn dict() -> impl Strategy<Value = JsonValue> {
+    let leaf = prop_oneof![
+        Just(JsonValue::Null),
+        any::<bool>().prop_map(JsonValue::from),
+        any::<i64>().prop_map(JsonValue::from),
+        "[a-zA-Z0-9 ]{0,12}".prop_map(JsonValue::from),
+    ];
+    prop::collection::btree_map("[a-z_]{1,10}", leaf, 0..4).prop_map(|entries| {
+        let mut object = JsonValue::new_object();
+        for (key, value) in entries {
+            object[key.as_str()] = value;
+        }
+        object
+    })
+}
+
+/// Floats are left out: integral floats come back as integers, which is a property of JSON
+/// rather than of the parser.
+fn value() -> impl Strategy<Value = WampValue> {
+    let leaf = prop_oneof![
+        Just(WampValue::Null),
+        any::<bool>().prop_map(WampValue::Bool),
+        any::<i64>().prop_map(WampValue::Integer),
+        "[a-zA-Z0-9 .]{0,12}".prop_map(WampValue::String),
+        prop::collection::vec(any::<u8>(), 0..16).prop_map(WampValue::Bytes),
+    ];
+    leaf.prop_recursive(2, 16, 4, |inner| {
+        prop_oneof![
+            prop::collection::vec(inner.clone(), 0..4).prop_map(WampValue::List),
+            prop::collection::btree_map("[a-z_]{1,8}", inner, 0..4).prop_map(WampValue::Dict),
+        ]
+    })
+}
+
+fn args() -> impl Strategy<Value = Option<Args>> {
+    prop::option::of(prop::collection::vec(value(), 0..4))
+}
+
+fn kwargs() -> impl Strategy<Value = Option<BTreeMap<String, WampValue>>> {
+    prop::option::of(prop::collection::btree_map("[a-z_]{1,8}", value(), 0..4))
+}
+
+fn message() -> impl Strategy<Value = Message> {
+    prop_oneof![
+        (uri(), dict()).prop_map(|(realm, details)| Message::Hello(Hello { realm, details })),
+        (id(), dict())
+            .prop_map(|(session, details)| Message::Welcome(Welcome { session, details })),
+        (dict(), uri()).prop_map(|(details, reason)| Message::Abort(Abort { details, reason })),
+        ("[a-z]{1,10}", dict()).prop_map(|(authmethod, details)| {
+            Message::Challenge(Challenge {
+                authmethod,
+                details,
+            })
+        }),
+        ("[a-zA-Z0-9]{0,20}", dict()).prop_map(|(signature, details)| {
+            Message::Authenticate(Authenticate { signature, details })
+        }),
+        (dict(), uri()).prop_map(|(details, reason)| Message::Goodbye(Goodbye { details, reason })),
+        (
+            prop::sample::select(vec![16u8, 32, 34, 48, 64, 66, 68]),
+            id(),
+            dict(),
+            uri(),
+            args(),
+            kwargs()
+        )
+            .prop_map(|(request_type, request, details, error, args, kwargs)| {
+                Message::ErrorMessage(ErrorMessage {
+                    request_type,
+                    request,
+                    details,
+                    error,
+                    args,
+                    kwargs,
+                })
+            }),
+        (id(), dict(), uri(), args(), kwargs()).prop_map(
+            |(request, options, topic, args, kwargs)| {
+                Message::Publish(Publish {
+                    request,
+                    options,
+                    topic,
+                    args,
+                    kwargs,
+                })
+            }
+        ),
+        (id(), id()).prop_map(|(request, publication)| {
+            Message::Published(Published {
+                request,
+                publication,
+            })
+        }),
+        (id(), dict(), uri()).prop_map(|(request, options, topic)| {
+            Message::Subscribe(Subscribe {
+                request,
+                options,
+                topic,
+            })
+        }),
+        (id(), id()).prop_map(|(request, subscription)| {
+            Message::Subscribed(Subscribed {
+                request,
+                subscription,
+            })
+        }),
+        (id(), id()).prop_map(|(request, subscription)| {
+            Message::Unsubscribe(Unsubscribe {
+                request,
+                subscription,
+            })
+        }),
+        id().prop_map(|request| Message::Unsubscribed(Unsubscribed {
+            request,
+            details: None
+        })),
+        (id(), id(), dict(), args(), kwargs()).prop_map(
+            |(subscription, publication, details, args, kwargs)| {
+                Message::Event(Event {
+                    subscription,
+                    publication,
+                    details,
+                    args,
+                    kwargs,
+                })
+            }
+        ),
+        (id(), dict(), uri(), args(), kwargs()).prop_map(
+            |(request, options, procedure, args, kwargs)| {
+                Message::Call(Call {
+                    request,
+                    options,
+                    procedure,
+                    args,
+                    kwargs,
+                })
+            }
+        ),
+        (id(), dict()).prop_map(|(request, options)| Message::Cancel(Cancel { request, options })),
+        (id(), dict(), args(), kwargs()).prop_map(|(request, details, args, kwargs)| {
+            Message::MessageResult(WampResult {
+                request,
+                details,
+                args,
+                kwargs,
+            })
+        }),
+        (id(), dict(), uri()).prop_map(|(request, options, procedure)| {
+            Message::Register(Register {
+                request,
+                options,
+                procedure,
+            })
+        }),
+        (id(), id()).prop_map(|(request, registration)| {
+            Message::Registered(Registered {
+                request,
+                registration,
+            })
+        }),
+        (id(), id()).prop_map(|(request, registration)| {
+            Message::Unregister(Unregister {
+                request,
+                registration,
+            })
+        }),
+        id().prop_map(|request| Message::Unregistered(Unregistered {
+            request,
+            details: None
+        })),
+        (id(), id(), dict(), args(), kwargs()).prop_map(
+            |(request, registration, details, args, kwargs)| {
+                Message::Invocation(Invocation {
+                    request,
+                    registration,
+                    details,
+                    args,
+                    kwargs,
+                })
+            }
+        ),
+        (id(), dict())
+            .prop_map(|(request, options)| { Message::Interrupt(Interrupt { request, options }) }),
+        (id(), dict(), args(), kwargs()).prop_map(|(request, options, args, kwargs)| {
+            Message::Yield(Yield {
+                request,
+                options,
+                args,
+                kwargs,
+            })
+        }),
+    ]
+}
+
+fn hash(message: &Message) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    message.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Parse through the message type's own `FromStr` rather than `Message::parse_message`.
+fn parse_typed(id: u8, raw: &str) -> Result<Message, Error> {
+    Ok(match id {
+        Hello::ID => Message::Hello(Hello::from_str(raw)?),
+        Welcome::ID => Message::Welcome(Welcome::from_str(raw)?),
+        Abort::ID => Message::Abort(Abort::from_str(raw)?),
+        Challenge::ID => Message::Challenge(Challenge::from_str(raw)?),
+        Authenticate::ID => Message::Authenticate(Authenticate::from_str(raw)?),
+        Goodbye::ID => Message::Goodbye(Goodbye::from_str(raw)?),
+        ErrorMessage::ID => Message::ErrorMessage(ErrorMessage::from_str(raw)?),
+        Publish::ID => Message::Publish(Publish::from_str(raw)?),
+        Published::ID => Message::Published(Published::from_str(raw)?),
+        Subscribe::ID => Message::Subscribe(Subscribe::from_str(raw)?),
+        Subscribed::ID => Message::Subscribed(Subscribed::from_str(raw)?),
+        Unsubscribe::ID => Message::Unsubscribe(Unsubscribe::from_str(raw)?),
+        Unsubscribed::ID => Message::Unsubscribed(Unsubscribed::from_str(raw)?),
+        Event::ID => Message::Event(Event::from_str(raw)?),
+        Call::ID => Message::Call(Call::from_str(raw)?),
+        Cancel::ID => Message::Cancel(Cancel::from_str(raw)?),
+        WampResult::ID => Message::MessageResult(WampResult::from_str(raw)?),
+        Register::ID => Message::Register(Register::from_str(raw)?),
+        Registered::ID => Message::Registered(Registered::from_str(raw)?),
+        Unregister::ID => Message::Unregister(Unregister::from_str(raw)?),
+        Unregistered::ID => Message::Unregistered(Unregistered::from_str(raw)?),
+        Invocation::ID => Message::Invocation(Invocation::from_str(raw)?),
+        Interrupt::ID => Message::Interrupt(Interrupt::from_str(raw)?),
+        Yield::ID => Message::Yield(Yield::from_str(raw)?),
+        _ => return Err(Error::ExtensionMessage),
+    })
+}
+
+/// Keys of a decoded `frame`, with the binary ones decoded.
+#[cfg(any(feature = "msgpack", feature = "cbor"))]
+fn binary_keys(frame: &[u8], serializer: Serializer) -> Vec<Vec<u8>> {
+    use wamp_helpers::transcode::decode;
+    use wamp_helpers::value::decode_binary;
+
+    let Ok(WampValue::Dict(entries)) = decode(frame, serializer) else {
+        panic!()
+    };
+    entries
+        .keys()
+        .map(|key| decode_binary(key).unwrap())
+        .collect()
+}
+
+#[cfg(feature = "msgpack")]
+#[test]
+fn msgpack_keys_that_are_not_utf8_stay_distinct() {
+    // {"\xff": 1, bin "\xfe": 2}
+    let frame = [0x82, 0xa1, 0xff, 0x01, 0xc4, 0x01, 0xfe, 0x02];
+    assert_eq!(
+        binary_keys(&frame, Serializer::MsgPack),
+        [vec![0xfe], vec![0xff]]
+    );
+    let json =
+        wamp_helpers::transcode::transcode(&frame, Serializer::MsgPack, Serializer::Json).unwrap();
+    assert_eq!(
+        binary_keys(&json, Serializer::Json),
+        [vec![0xfe], vec![0xff]]
+    );
+}
+
+#[cfg(feature = "cbor")]
+#[test]
+fn cbor_byte_string_keys_stay_distinct() {
+    // {h'ff': 1, h'fe': 2}
+    let frame = [0xa2, 0x41, 0xff, 0x01, 0x41, 0xfe, 0x02];
+    assert_eq!(
+        binary_keys(&frame, Serializer::Cbor),
+        [vec![0xfe], vec![0xff]]
+    );
+}
+
+/// Encode `message` with a binary serializer and decode it again, it has to come back as
+/// the JSON round trip brings it back.
+#[cfg(any(feature = "msgpack", feature = "cbor"))]
+fn binary_round_trip(message: Message, serializer: Serializer) -> Result<(), TestCaseError> {
+    use wamp_helpers::framed::{decode_message, encode_message};
+
+    let expected = Message::parse_message(&message.clone().to_json().unwrap().dump()).unwrap();
+    let frame = encode_message(message, serializer).unwrap();
+    let decoded = decode_message(&frame, serializer).unwrap();
+    prop_assert_eq!(&decoded, &expected);
+    prop_assert_eq!(encode_message(decoded, serializer).unwrap(), frame);
+    Ok(())
+}
+
+proptest! {
+    #[cfg(feature = "msgpack")]
+    #[test]
+    fn msgpack_round_trip(message in message()) {
+        binary_round_trip(message, Serializer::MsgPack)?;
+    }
+
+    #[cfg(feature = "cbor")]
+    #[test]
+    fn cbor_round_trip(message in message()) {
+        binary_round_trip(message, Serializer::Cbor)?;
+    }
+
+    #[test]
+    fn serialize_parse_serialize_is_stable(message in message()) {
+        let id = message.message_id();
+        let first = message.to_json().unwrap().dump();
+
+        let reparsed = Message::parse_message(&first).unwrap();
+        prop_assert_eq!(reparsed.message_id(), id);
+        prop_assert_eq!(&reparsed.to_json().unwrap().dump(), &first);
+
+        let typed = parse_typed(id, &first).unwrap();
+        prop_assert_eq!(&typed.to_json().unwrap().dump(), &first);
+    }
+
+    #[test]
+    fn reparsed_messages_are_equal(message in message()) {
+        // Absent arguments before keyword arguments come back as an empty list, which
+        // makes no difference to the canonical frame.
+        let frame = message.clone().to_json().unwrap().dump();
+        let first = Message::parse_message(&frame).unwrap();
+        let second = Message::parse_message(&frame).unwrap();
+        prop_assert_eq!(&first, &second);
+        prop_assert_eq!(hash(&first), hash(&second));
+        prop_assert_eq!(&message, &first);
+        prop_assert_eq!(hash(&message), hash(&first));
+        prop_assert_eq!(Message::parse_message(&first.to_json().unwrap().dump()).unwrap(), second);
+    }
+
+    #[test]
+    fn canonical_output_is_idempotent(message in message()) {
+        let canonical = wamp_helpers::canonical::to_canonical_string(&message.to_json().unwrap());
+        let reparsed = Message::parse_message(&canonical).unwrap();
+        prop_assert_eq!(
+            wamp_helpers::canonical::to_canonical_string(&reparsed.to_json().unwrap()),
+            canonical
+        );
+    }
+
+    #[test]
+    fn size_hint_matches_encoded_size(message in message()) {
+        let hint = |serializer| message.encoded_size_hint(serializer);
+        let json = message.clone().to_json().unwrap();
+        prop_assert_eq!(hint(Serializer::Json), json.dump().len());
+        for serializer in [Serializer::MsgPack, Serializer::Cbor] {
+            if let Ok(frame) = encode(WampValue::from(&json), serializer) {
+                prop_assert_eq!(hint(serializer), frame.len());
+            }
+        }
+    }
+
+    #[test]
+    fn typed_options_preserve_every_key(options in dict()) {
+        let canonical = wamp_helpers::canonical::to_canonical_string(&options);
+        let publish = Options::from(PublishOptions::from(&options));
+        prop_assert_eq!(wamp_helpers::canonical::to_canonical_string(&publish), canonical.clone());
+        let register = Options::from(RegisterOptions::from(&options));
+        prop_assert_eq!(wamp_helpers::canonical::to_canonical_string(&register), canonical.clone());
+        let goodbye = Details::from(GoodbyeDetails::from(&options));
+        prop_assert_eq!(wamp_helpers::canonical::to_canonical_string(&goodbye), canonical);
+    }
+}
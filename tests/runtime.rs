@@ -0,0 +1,525 @@
+#![cfg(feature = "runtime")]
+
+use std::future::Future;
+use std::time::Duration;
+use wamp_helpers::acceptor::Serializer;
+use wamp_helpers::dealer::BusyPolicy;
+use wamp_helpers::memory::MemoryTransport;
+use wamp_helpers::messages::Message;
+use wamp_helpers::runtime::{Router, RouterHandle};
+use wamp_helpers::transport::Transport;
+use wamp_helpers::value::WampValue;
+
+/// The roles and features of every test client, for the gated options to pass.
+const ROLES: &str = r#"{
+    "caller": {"features": {"progressive_call_results": true, "call_canceling": true}},
+    "callee": {"features": {"progressive_call_results": true, "shared_registration": true}},
+    "publisher": {"features": {"publisher_exclusion": true, "subscriber_blackwhite_listing": true}},
+    "subscriber": {"features": {"pattern_based_subscription": true}}
+}"#;
+
+fn run<F: Future>(test: F) -> F::Output {
+    tokio::runtime::Builder::new_current_thread()
+        .enable_time()
+        .build()
+        .unwrap()
+        .block_on(test)
+}
+
+async fn send(client: &mut MemoryTransport, raw: &str) {
+    client.send(raw.as_bytes().to_vec()).await.unwrap();
+}
+
+async fn receive(client: &mut MemoryTransport) -> Message {
+    let frame = client.next().await.unwrap().unwrap();
+    Message::parse_message(std::str::from_utf8(&frame).unwrap()).unwrap()
+}
+
+async fn connect(router: &RouterHandle, realm: &str) -> (MemoryTransport, Message) {
+    connect_as(router, realm, ROLES).await
+}
+
+async fn connect_as(router: &RouterHandle, realm: &str, roles: &str) -> (MemoryTransport, Message) {
+    let (mut client, server) = MemoryTransport::pair();
+    router.attach(server, Serializer::Json);
+    let hello = format!(r#"[1, "{realm}", {{"roles": {roles}}}]"#);
+    send(&mut client, &hello).await;
+    let answer = receive(&mut client).await;
+    (client, answer)
+}
+
+async fn join(router: &RouterHandle) -> MemoryTransport {
+    join_as(router).await.0
+}
+
+/// Join `realm1`, with the session id of the WELCOME.
+async fn join_as(router: &RouterHandle) -> (MemoryTransport, u64) {
+    match connect(router, "realm1").await {
+        (client, Message::Welcome(welcome)) => (client, welcome.session),
+        (_, answer) => panic!("not welcomed: {answer:?}"),
+    }
+}
+
+async fn error(client: &mut MemoryTransport) -> (u8, u64, String) {
+    match receive(client).await {
+        Message::ErrorMessage(error) => {
+            (error.request_type, error.request, error.error.to_string())
+        }
+        answer => panic!("not an ERROR: {answer:?}"),
+    }
+}
+
+async fn invocation(callee: &mut MemoryTransport) -> u64 {
+    match receive(callee).await {
+        Message::Invocation(invocation) => invocation.request,
+        answer => panic!("not an INVOCATION: {answer:?}"),
+    }
+}
+
+async fn register(callee: &mut MemoryTransport, options: &str) {
+    send(
+        callee,
+        &format!(r#"[64, 1, {options}, "com.example.work"]"#),
+    )
+    .await;
+    let answer = receive(callee).await;
+    assert!(matches!(answer, Message::Registered(_)), "{answer:?}");
+}
+
+#[test]
+fn events_reach_subscribers_but_not_the_publisher() {
+    run(async {
+        let router = Router::new().realm("realm1").start();
+        let mut publisher = join(&router).await;
+        let mut subscriber = join(&router).await;
+
+        send(&mut publisher, r#"[32, 1, {}, "com.example.created"]"#).await;
+        send(&mut subscriber, r#"[32, 1, {}, "com.example.created"]"#).await;
+        assert!(matches!(
+            receive(&mut publisher).await,
+            Message::Subscribed(_)
+        ));
+        let Message::Subscribed(subscribed) = receive(&mut subscriber).await else {
+            panic!()
+        };
+
+        send(
+            &mut publisher,
+            r#"[16, 2, {"acknowledge": true}, "com.example.created", ["hi"]]"#,
+        )
+        .await;
+        let Message::Published(published) = receive(&mut publisher).await else {
+            panic!()
+        };
+        let Message::Event(event) = receive(&mut subscriber).await else {
+            panic!()
+        };
+        assert_eq!(event.subscription, subscribed.subscription);
+        assert_eq!(event.publication, published.publication);
+        assert_eq!(event.args, Some(vec!["hi".into()]));
+        assert!(publisher.try_next().is_none());
+    });
+}
+
+#[test]
+fn pattern_subscriptions_receive_the_topic() {
+    run(async {
+        let router = Router::new().realm("realm1").start();
+        let mut publisher = join(&router).await;
+        let mut subscriber = join(&router).await;
+
+        send(
+            &mut subscriber,
+            r#"[32, 1, {"match": "prefix"}, "com.example"]"#,
+        )
+        .await;
+        assert!(matches!(
+            receive(&mut subscriber).await,
+            Message::Subscribed(_)
+        ));
+        send(
+            &mut subscriber,
+            r#"[32, 2, {"match": "regex"}, "com.example"]"#,
+        )
+        .await;
+        assert_eq!(
+            error(&mut subscriber).await,
+            (32, 2, "wamp.error.invalid_argument".to_string())
+        );
+
+        send(&mut publisher, r#"[16, 1, {}, "com.example.created"]"#).await;
+        let Message::Event(event) = receive(&mut subscriber).await else {
+            panic!()
+        };
+        assert_eq!(event.details["topic"], "com.example.created");
+    });
+}
+
+#[test]
+fn invalid_requests_are_answered_with_errors() {
+    run(async {
+        let router = Router::new().realm("realm1").start();
+        let mut client = join(&router).await;
+        send(&mut client, r#"[48, 7, {}, "com.example..add"]"#).await;
+        assert_eq!(
+            error(&mut client).await,
+            (48, 7, "wamp.error.invalid_uri".to_string())
+        );
+
+        // Progressive results were not announced by this caller.
+        let roles = r#"{"caller": {}}"#;
+        let (mut client, _) = connect_as(&router, "realm1", roles).await;
+        send(
+            &mut client,
+            r#"[48, 8, {"receive_progress": true}, "com.example.add"]"#,
+        )
+        .await;
+        assert_eq!(
+            error(&mut client).await,
+            (48, 8, "wamp.error.option_not_allowed".to_string())
+        );
+    });
+}
+
+#[test]
+fn calls_are_canceled_when_the_callee_leaves() {
+    run(async {
+        let router = Router::new().realm("realm1").start();
+        let mut callee = join(&router).await;
+        let mut caller = join(&router).await;
+
+        send(&mut callee, r#"[64, 1, {}, "com.example.slow"]"#).await;
+        assert!(matches!(receive(&mut callee).await, Message::Registered(_)));
+        send(&mut caller, r#"[48, 7, {}, "com.example.slow"]"#).await;
+        assert!(matches!(receive(&mut callee).await, Message::Invocation(_)));
+
+        send(&mut callee, r#"[6, {}, "wamp.close.close_realm"]"#).await;
+        assert!(matches!(receive(&mut callee).await, Message::Goodbye(_)));
+        let Message::ErrorMessage(error) = receive(&mut caller).await else {
+            panic!()
+        };
+        assert_eq!((error.request_type, error.request), (48, 7));
+        assert_eq!(error.error, "wamp.error.canceled");
+
+        send(&mut caller, r#"[48, 8, {}, "com.example.slow"]"#).await;
+        let Message::ErrorMessage(error) = receive(&mut caller).await else {
+            panic!()
+        };
+        assert_eq!(error.error, "wamp.error.no_such_procedure");
+    });
+}
+
+#[test]
+fn spoofed_identities_do_not_reach_the_callee() {
+    run(async {
+        let router = Router::new().realm("realm1").start();
+        let mut callee = join(&router).await;
+        let mut caller = join(&router).await;
+
+        send(&mut callee, r#"[64, 1, {}, "com.example.reset"]"#).await;
+        assert!(matches!(receive(&mut callee).await, Message::Registered(_)));
+        let call = r#"[48, 7, {
+            "caller": 1,
+            "caller_authrole": "admin",
+            "forward_for": [{"session": 1, "authid": "router-a", "authrole": "rlink"}],
+            "receive_progress": true
+        }, "com.example.reset"]"#;
+        send(&mut caller, call).await;
+        let Message::Invocation(invocation) = receive(&mut callee).await else {
+            panic!()
+        };
+        assert_eq!(invocation.details.dump(), r#"{"receive_progress":true}"#);
+    });
+}
+
+#[test]
+fn canceled_calls_interrupt_the_callee() {
+    run(async {
+        let router = Router::new().realm("realm1").start();
+        let mut callee = join(&router).await;
+        let mut caller = join(&router).await;
+        register(&mut callee, "{}").await;
+
+        send(&mut caller, r#"[48, 7, {}, "com.example.work"]"#).await;
+        let request = invocation(&mut callee).await;
+        send(&mut caller, r#"[49, 7, {"mode": "kill"}]"#).await;
+        assert_eq!(
+            error(&mut caller).await,
+            (48, 7, "wamp.error.canceled".to_string())
+        );
+        let Message::Interrupt(interrupt) = receive(&mut callee).await else {
+            panic!()
+        };
+        assert_eq!(interrupt.request, request);
+        assert_eq!(interrupt.options["mode"], "kill");
+
+        // The callee's late answer goes nowhere.
+        send(&mut callee, &format!("[70, {request}, {{}}]")).await;
+        send(&mut caller, r#"[48, 8, {}, "com.example.work"]"#).await;
+        let request = invocation(&mut callee).await;
+        send(&mut callee, &format!("[70, {request}, {{}}]")).await;
+        let Message::MessageResult(result) = receive(&mut caller).await else {
+            panic!()
+        };
+        assert_eq!(result.request, 8);
+    });
+}
+
+#[test]
+fn progress_only_reaches_callers_that_asked_for_it() {
+    run(async {
+        let router = Router::new().realm("realm1").start();
+        let mut callee = join(&router).await;
+        let mut caller = join(&router).await;
+        register(&mut callee, "{}").await;
+
+        for (request, options) in [(7, "{}"), (8, r#"{"receive_progress": true}"#)] {
+            send(
+                &mut caller,
+                &format!(r#"[48, {request}, {options}, "com.example.work"]"#),
+            )
+            .await;
+            let request = invocation(&mut callee).await;
+            send(
+                &mut callee,
+                &format!(r#"[70, {request}, {{"progress": true}}, [1]]"#),
+            )
+            .await;
+            send(&mut callee, &format!("[70, {request}, {{}}, [2]]")).await;
+        }
+
+        let Message::MessageResult(result) = receive(&mut caller).await else {
+            panic!()
+        };
+        assert_eq!(
+            (result.request, result.args, result.details.dump()),
+            (7, Some(vec![2.into()]), "{}".into())
+        );
+        let Message::MessageResult(progress) = receive(&mut caller).await else {
+            panic!()
+        };
+        assert_eq!(progress.details["progress"], true);
+        assert_eq!(progress.args, Some(vec![1.into()]));
+        let Message::MessageResult(result) = receive(&mut caller).await else {
+            panic!()
+        };
+        assert_eq!(result.args, Some(vec![2.into()]));
+    });
+}
+
+#[test]
+fn shared_registrations_follow_their_invocation_policy() {
+    run(async {
+        let router = Router::new().realm("realm1").start();
+        let mut first = join(&router).await;
+        let mut second = join(&router).await;
+        let mut caller = join(&router).await;
+        register(&mut first, r#"{"invoke": "roundrobin"}"#).await;
+        register(&mut second, r#"{"invoke": "roundrobin"}"#).await;
+
+        send(
+            &mut caller,
+            r#"[64, 1, {"invoke": "first"}, "com.example.work"]"#,
+        )
+        .await;
+        assert_eq!(
+            error(&mut caller).await,
+            (
+                64,
+                1,
+                "wamp.error.procedure_exists_invocation_policy_conflict".to_string()
+            )
+        );
+        send(&mut caller, r#"[64, 2, {}, "com.example.work"]"#).await;
+        assert_eq!(
+            error(&mut caller).await.2,
+            "wamp.error.procedure_exists_invocation_policy_conflict"
+        );
+
+        for request in 1..=4 {
+            send(
+                &mut caller,
+                &format!(r#"[48, {request}, {{}}, "com.example.work"]"#),
+            )
+            .await;
+        }
+        for callee in [&mut first, &mut second] {
+            for _ in 0..2 {
+                let request = invocation(callee).await;
+                send(callee, &format!("[70, {request}, {{}}]")).await;
+            }
+        }
+        for _ in 1..=4 {
+            assert!(matches!(
+                receive(&mut caller).await,
+                Message::MessageResult(_)
+            ));
+        }
+    });
+}
+
+#[test]
+fn unavailable_callees_pass_the_call_on() {
+    run(async {
+        let router = Router::new().realm("realm1").start();
+        let mut first = join(&router).await;
+        let mut second = join(&router).await;
+        let mut caller = join(&router).await;
+        register(&mut first, r#"{"invoke": "first"}"#).await;
+        register(&mut second, r#"{"invoke": "first"}"#).await;
+
+        send(&mut caller, r#"[48, 7, {}, "com.example.work"]"#).await;
+        let request = invocation(&mut first).await;
+        send(
+            &mut first,
+            &format!(r#"[8, 68, {request}, {{}}, "wamp.error.unavailable"]"#),
+        )
+        .await;
+        let request = invocation(&mut second).await;
+        send(&mut second, &format!("[70, {request}, {{}}]")).await;
+        let Message::MessageResult(result) = receive(&mut caller).await else {
+            panic!()
+        };
+        assert_eq!(result.request, 7);
+    });
+}
+
+#[test]
+fn busy_callees_queue_calls_until_they_time_out() {
+    run(async {
+        let busy = BusyPolicy::Queue {
+            max: 1,
+            timeout: Duration::from_millis(100),
+        };
+        let router = Router::new().realm("realm1").busy(busy).start();
+        let mut callee = join(&router).await;
+        let mut caller = join(&router).await;
+        register(&mut callee, r#"{"concurrency": 1}"#).await;
+
+        for request in 1..=3 {
+            send(
+                &mut caller,
+                &format!(r#"[48, {request}, {{}}, "com.example.work"]"#),
+            )
+            .await;
+        }
+        let running = invocation(&mut callee).await;
+        // 2 waits for the callee, the queue has no room for 3.
+        assert_eq!(
+            error(&mut caller).await,
+            (48, 3, "wamp.error.unavailable".to_string())
+        );
+        send(&mut callee, &format!("[70, {running}, {{}}]")).await;
+        let Message::MessageResult(result) = receive(&mut caller).await else {
+            panic!()
+        };
+        assert_eq!(result.request, 1);
+        let _ = invocation(&mut callee).await;
+
+        // Nobody frees the callee for 4.
+        send(&mut caller, r#"[48, 4, {}, "com.example.work"]"#).await;
+        assert_eq!(
+            error(&mut caller).await,
+            (48, 4, "wamp.error.unavailable".to_string())
+        );
+    });
+}
+
+#[test]
+fn unknown_realms_and_early_messages_are_aborted() {
+    run(async {
+        let router = Router::new().realm("realm1").start();
+        let (mut client, answer) = connect(&router, "realm2").await;
+        let Message::Abort(abort) = answer else {
+            panic!()
+        };
+        assert_eq!(abort.reason, "wamp.error.no_such_realm");
+        assert!(client.next().await.is_none());
+
+        let (mut client, server) = MemoryTransport::pair();
+        router.attach(server, Serializer::Json);
+        send(&mut client, r#"[16, 1, {}, "com.example.topic"]"#).await;
+        let Message::Abort(abort) = receive(&mut client).await else {
+            panic!()
+        };
+        assert_eq!(abort.reason, "wamp.error.protocol_violation");
+    });
+}
+
+#[test]
+fn shutdown_says_goodbye_to_every_session() {
+    run(async {
+        let router = Router::new().realm("realm1").start();
+        let mut client = join(&router).await;
+        router.shutdown().await;
+        let Message::Goodbye(goodbye) = receive(&mut client).await else {
+            panic!()
+        };
+        assert_eq!(goodbye.reason, "wamp.close.system_shutdown");
+        send(&mut client, r#"[6, {}, "wamp.close.goodbye_and_out"]"#).await;
+        assert!(client.next().await.is_none());
+    });
+}
+
+#[test]
+fn paused_callees_finish_their_calls_but_get_no_new_ones() {
+    run(async {
+        let router = Router::new().realm("realm1").start();
+        let (mut first, session) = join_as(&router).await;
+        let mut second = join(&router).await;
+        let mut caller = join(&router).await;
+        register(&mut first, r#"{"invoke": "first"}"#).await;
+        register(&mut second, r#"{"invoke": "first"}"#).await;
+        send(&mut first, r#"[32, 2, {}, "com.example.news"]"#).await;
+        assert!(matches!(receive(&mut first).await, Message::Subscribed(_)));
+        send(
+            &mut caller,
+            r#"[32, 1, {}, "wamp.registration.on_unregister"]"#,
+        )
+        .await;
+        assert!(matches!(receive(&mut caller).await, Message::Subscribed(_)));
+
+        send(&mut caller, r#"[48, 1, {}, "com.example.work"]"#).await;
+        let running = invocation(&mut first).await;
+        router.pause("realm1", session).await;
+        let Message::Event(event) = receive(&mut caller).await else {
+            panic!()
+        };
+        let args = event.args.unwrap();
+        assert_eq!(args[0], WampValue::Integer(session as i64));
+        assert_eq!(event.kwargs.unwrap()["paused"], WampValue::Bool(true));
+
+        // New calls go to the other callee, the paused one still answers its own.
+        send(&mut caller, r#"[48, 2, {}, "com.example.work"]"#).await;
+        let request = invocation(&mut second).await;
+        send(&mut second, &format!("[70, {request}, {{}}]")).await;
+        send(&mut first, &format!("[70, {running}, {{}}]")).await;
+        for request in [2, 1] {
+            let Message::MessageResult(result) = receive(&mut caller).await else {
+                panic!()
+            };
+            assert_eq!(result.request, request);
+        }
+
+        // Events published while paused are skipped.
+        send(
+            &mut caller,
+            r#"[16, 3, {"acknowledge": true}, "com.example.news"]"#,
+        )
+        .await;
+        assert!(matches!(receive(&mut caller).await, Message::Published(_)));
+        router.resume("realm1", session).await;
+        send(
+            &mut caller,
+            r#"[16, 4, {"acknowledge": true}, "com.example.news"]"#,
+        )
+        .await;
+        let Message::Published(published) = receive(&mut caller).await else {
+            panic!()
+        };
+        let Message::Event(event) = receive(&mut first).await else {
+            panic!()
+        };
+        assert_eq!(event.publication, published.publication);
+    });
+}
@@ -0,0 +1,102 @@
+use wamp_helpers::messages::Message;
+use wamp_helpers::parse::ParseOptions;
+use wamp_helpers::scan::scan_frame;
+
+const FRAMES: &[&str] = &[
+    r#"[1, "com.example.realm", {"roles": {"caller": {}, "subscriber": {}}}]"#,
+    r#"[2, 9129137332, {"roles": {"broker": {}}}]"#,
+    r#"[3, {"message": "The realm does not exist."}, "wamp.error.no_such_realm"]"#,
+    r#"[8, 48, 7814135, {}, "com.myapp.error.object_write_protected", ["Object is write protected."], {"severity": 3}]"#,
+    r#"[16, 239714735, {"acknowledge": true}, "com.myapp.mytopic1", ["Hello, world!"], {"color": "orange", "sizes": [23, 42, 7]}]"#,
+    r#"[36, 5512315355, 4429313566, {}, null, {"a": 1}]"#,
+    r#"[48, 7814135, {"timeout": 1000}, "com.myapp.echo", ["\u0000AAEC", "😀"], {"b": 2, "a": 1}]"#,
+    r#"[50, 1, {}, [-1, 0, 1.5, -2.25e-3, 1E+2, 18446744073709551615]]"#,
+    r#"[50, 1e0, {}]"#,
+    "  [ 50 ,\n 1 ,\t{ } ]  ",
+    // Rejected frames.
+    "",
+    "[",
+    "{}",
+    "[]",
+    "\"hello\"",
+    "[48, 1, {},]",
+    "[48, 1, {} \"x\"]",
+    r#"[48, 1, {}, "bad \x escape"]"#,
+    r#"[48, 1, {}, "lone \ud800A"]"#,
+    r#"[48, 1, {}, "lone \udc00"]"#,
+    r#"[48, 1, {}, "unpaired \ud800\u0041"]"#,
+    r#"[48, 1, {}, "paired \ud83d\ude00"]"#,
+    r#"[48, 1, {}, "signed \u+abc"]"#,
+    "[\"1\", 1, {}]",
+    "[1.5, 1, {}]",
+    "[300, 1, {}]",
+    "[99, 1, {}]",
+    "[50, -1, {}]",
+    "[50, 1.5, {}]",
+    "[50, 9007199254740993, {}]",
+    "[50, 1, []]",
+    "[50, 1, {}, {}]",
+    "[50, 1, {}, [], []]",
+    "[50, 1, {}, [], {}, 1]",
+    "[50, 1]",
+    "[48, 1, {}]",
+    "[8, 256, 1, {}, \"wamp.error.x\"]",
+    r#"[48, 1, {}, "com.myapp.echo"] 2"#,
+    r#"[65, 1, 2, "surplus"]"#,
+];
+
+#[test]
+fn agrees_with_the_parser() {
+    let options = ParseOptions {
+        reject_trailing_elements: true,
+        ..ParseOptions::default()
+    };
+    for frame in FRAMES {
+        let parsed = Message::parse_message_with(frame, &options);
+        match scan_frame(frame) {
+            Ok(shape) => {
+                let message = parsed.unwrap_or_else(|error| panic!("{frame}: {error:?}"));
+                assert_eq!(shape.message_type(), message.message_id(), "{frame}");
+            }
+            Err(error) => {
+                let expected = parsed.err().unwrap_or_else(|| panic!("{frame} parsed"));
+                assert_eq!(format!("{error:?}"), format!("{expected:?}"), "{frame}");
+            }
+        }
+    }
+}
+
+#[test]
+fn spans_cover_each_element() {
+    let frame = r#" [8, 48, 7814135, {"a": [1, {"b": "]"}]}, "com.myapp.error", ["x,y"], {}] "#;
+    let shape = scan_frame(frame).unwrap();
+    let elements: Vec<_> = (0..shape.spans().len())
+        .map(|index| shape.element(frame, index).unwrap())
+        .collect();
+    assert_eq!(
+        elements,
+        [
+            "8",
+            "48",
+            "7814135",
+            r#"{"a": [1, {"b": "]"}]}"#,
+            r#""com.myapp.error""#,
+            r#"["x,y"]"#,
+            "{}",
+        ]
+    );
+    let error = frame.find(r#""com.myapp.error""#).unwrap();
+    assert_eq!(shape.span("Error"), Some(error..error + 17));
+    assert_eq!(shape.span("Unknown"), None);
+
+    let short = scan_frame("[50, 1, {}]").unwrap();
+    assert_eq!(short.spans().len(), 3);
+    assert_eq!(short.span("Arguments"), None);
+}
+
+#[test]
+fn deep_nesting_is_bounded() {
+    let depth = 600;
+    let frame = format!("[50, 1, {{}}, {}{}]", "[".repeat(depth), "]".repeat(depth));
+    assert!(scan_frame(&frame).is_err());
+}
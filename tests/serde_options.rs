@@ -0,0 +1,99 @@
+#![cfg(feature = "serde")]
+
+use wamp_helpers::messages::{GoodbyeDetails, Options};
+use wamp_helpers::options::{PublishOptions, RegisterOptions};
+
+#[test]
+fn default_publish_options_serialize_to_empty_dict() {
+    let serialized = serde_json::to_string(&PublishOptions::default()).unwrap();
+    assert_eq!(serialized, "{}");
+}
+
+#[test]
+fn publish_options_match_wire_form() {
+    let options = PublishOptions {
+        acknowledge: true,
+        exclude_me: Some(false),
+        exclude: vec![7, 8],
+        dedup_key: Some("order-42".to_string()),
+        ..PublishOptions::default()
+    };
+    let serialized = serde_json::to_string(&options).unwrap();
+    assert_eq!(serialized, Options::from(options.clone()).dump());
+    assert_eq!(
+        serialized,
+        r#"{"acknowledge":true,"exclude_me":false,"exclude":[7,8],"x_dedup_key":"order-42"}"#
+    );
+}
+
+#[test]
+fn publish_options_absent_keys_deserialize_to_defaults() {
+    let options: PublishOptions = serde_json::from_str(r#"{"exclude":[3]}"#).unwrap();
+    assert_eq!(
+        options,
+        PublishOptions {
+            exclude: vec![3],
+            ..PublishOptions::default()
+        }
+    );
+}
+
+#[test]
+fn goodbye_details_omit_absent_keys() {
+    assert_eq!(
+        serde_json::to_string(&GoodbyeDetails::default()).unwrap(),
+        "{}"
+    );
+    let details = GoodbyeDetails {
+        message: Some("shutting down".to_string()),
+        ..GoodbyeDetails::default()
+    };
+    let serialized = serde_json::to_string(&details).unwrap();
+    assert_eq!(serialized, r#"{"message":"shutting down"}"#);
+    assert_eq!(
+        serde_json::from_str::<GoodbyeDetails>(&serialized).unwrap(),
+        details
+    );
+}
+
+#[test]
+fn register_options_match_wire_form() {
+    let raw = json::object! { "match": "wildcard", "concurrency": 2 };
+    let options: RegisterOptions = serde_json::from_str(&raw.dump()).unwrap();
+    assert_eq!(options, RegisterOptions::from(&raw));
+    assert_eq!(serde_json::to_string(&options).unwrap(), raw.dump());
+}
+
+#[test]
+fn custom_options_are_flattened_into_extra() {
+    let raw = r#"{"acknowledge":true,"x_priority":3,"x_tags":["a","b"]}"#;
+    let options: PublishOptions = serde_json::from_str(raw).unwrap();
+    assert_eq!(options.extra.len(), 2);
+    assert_eq!(options, PublishOptions::from(&json::parse(raw).unwrap()));
+    assert_eq!(serde_json::to_string(&options).unwrap(), raw);
+}
+
+#[test]
+fn forward_for_matches_wire_form() {
+    let raw = json::object! {
+        "forward_for": [{ "session": 11, "authid": "router-a", "authrole": "rlink" }],
+    };
+    let options: PublishOptions = serde_json::from_str(&raw.dump()).unwrap();
+    assert_eq!(options, PublishOptions::from(&raw));
+    assert_eq!(options.forward_for[0].authid, "router-a");
+    assert_eq!(serde_json::to_string(&options).unwrap(), raw.dump());
+}
+
+#[test]
+fn known_keys_of_an_unexpected_type_stay_in_extra() {
+    let raw = json::object! { "acknowledge": "yes", "exclude": [1, "x"], "eligible": [2] };
+    let options: PublishOptions = serde_json::from_str(&raw.dump()).unwrap();
+    assert_eq!(options, PublishOptions::from(&raw));
+    assert!(!options.acknowledge && options.exclude.is_empty());
+    assert_eq!(options.eligible, [2]);
+    assert_eq!(options.extra.len(), 2);
+
+    let serialized = serde_json::to_string(&options).unwrap();
+    assert_eq!(serialized.matches("\"exclude\"").count(), 1);
+    assert_eq!(json::parse(&serialized).unwrap(), Options::from(options));
+}
@@ -0,0 +1,57 @@
+use wamp_helpers::correlation::Direction;
+use wamp_helpers::messages::Message;
+use wamp_helpers::session::Side;
+use wamp_helpers::validator::{
+    Validator, Verdict, ViolationAction, ViolationKind, ViolationPolicy,
+};
+
+fn established(policy: ViolationPolicy) -> Validator {
+    let mut validator = Validator::new(Side::Router).policy(policy);
+    for (direction, frame) in [
+        (
+            Direction::Inbound,
+            r#"[1, "realm1", {"roles": {"caller": {}}}]"#,
+        ),
+        (Direction::Outbound, r#"[2, 1, {"roles": {"dealer": {}}}]"#),
+    ] {
+        validator.enforce(direction, &Message::parse_message(frame).unwrap());
+    }
+    validator
+}
+
+#[test]
+fn rejected_requests_do_not_stay_pending() {
+    let mut validator = established(ViolationPolicy::default());
+    let malformed = Message::parse_message(r#"[48, 7, {}, "com.example..add"]"#).unwrap();
+    let Verdict::Reject(error, _) = validator.enforce(Direction::Inbound, &malformed) else {
+        panic!()
+    };
+    assert_eq!(error.error, "wamp.error.invalid_uri");
+    assert_eq!(validator.pending_requests(), 0);
+
+    // The id is free for the corrected request, whether the ERROR is observed or not.
+    let call = Message::parse_message(r#"[48, 7, {}, "com.example.add"]"#).unwrap();
+    assert_eq!(
+        validator.enforce(Direction::Inbound, &call),
+        Verdict::Accept(Vec::new())
+    );
+    validator.enforce(Direction::Outbound, &Message::ErrorMessage(error));
+    assert_eq!(validator.pending_requests(), 1);
+}
+
+#[test]
+fn tolerated_violations_keep_the_request_pending() {
+    let policy = ViolationPolicy::default().with(ViolationKind::MalformedUri, ViolationAction::Log);
+    let mut validator = established(policy);
+    let malformed = Message::parse_message(r#"[48, 7, {}, "com.example..add"]"#).unwrap();
+    let Verdict::Accept(logged) = validator.enforce(Direction::Inbound, &malformed) else {
+        panic!()
+    };
+    assert_eq!(logged[0].kind, ViolationKind::MalformedUri);
+    assert_eq!(validator.pending_requests(), 1);
+    assert!(matches!(
+        validator.enforce(Direction::Inbound, &malformed),
+        Verdict::Reject(..)
+    ));
+    assert_eq!(validator.pending_requests(), 1);
+}